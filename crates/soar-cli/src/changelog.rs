@@ -0,0 +1,51 @@
+use nu_ansi_term::Color::{Blue, Cyan, Green, Red, Yellow};
+use soar_operations::PackageChangelog;
+use tracing::info;
+
+use crate::utils::Colored;
+
+/// Print the release notes gathered for each package, newest version first.
+///
+/// Notes are the project's markdown as written; they are indented under
+/// their version rather than rendered, which keeps them readable in a pager
+/// and in a log alike.
+pub fn display_changelogs(changelogs: &[PackageChangelog]) {
+    for changelog in changelogs {
+        let range = match &changelog.from_version {
+            Some(from) => {
+                format!(
+                    "{} -> {}",
+                    Colored(Red, from),
+                    Colored(Green, &changelog.to_version)
+                )
+            }
+            None => Colored(Green, &changelog.to_version).to_string(),
+        };
+        info!("\n{} {}", Colored(Blue, &changelog.pkg_name), range);
+
+        if changelog.notes.is_empty() {
+            info!("  No release notes found.");
+            continue;
+        }
+
+        for notes in &changelog.notes {
+            let published = notes
+                .published_at
+                .as_deref()
+                .map(|date| format!(" ({})", date.get(..10).unwrap_or(date)))
+                .unwrap_or_default();
+            info!("  {}{}", Colored(Cyan, &notes.version), published);
+            match &notes.body {
+                Some(body) => {
+                    for line in body.lines() {
+                        info!("    {}", line);
+                    }
+                }
+                None => info!("    No notes published."),
+            }
+            if let Some(url) = &notes.url {
+                info!("    {}", Colored(Yellow, url));
+            }
+        }
+    }
+}
@@ -1,7 +1,10 @@
-use clap::{ArgAction, Parser, Subcommand, ValueHint};
-use clap_complete::Shell;
+use std::path::PathBuf;
 
-use crate::utils::parse_default_repos_arg;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum, ValueHint};
+use clap_complete::{ArgValueCompleter, Shell};
+use soar_utils::bytes::parse_rate;
+
+use crate::{completion, utils::parse_default_repos_arg};
 
 #[derive(Parser)]
 #[command(
@@ -37,7 +40,7 @@ pub struct Args {
     pub no_progress: bool,
 
     /// Set current profile
-    #[arg(short, long, global = true)]
+    #[arg(short, long, global = true, add = ArgValueCompleter::new(completion::profiles))]
     pub profile: Option<String>,
 
     /// Provide custom config file
@@ -64,10 +67,18 @@ pub struct Args {
     #[arg(long, short = '6', global = true)]
     pub ipv6: bool,
 
+    /// Limit download speed, shared by all downloads (e.g. 5M, 500K)
+    #[arg(long, global = true, value_name = "RATE", value_parser = parse_rate)]
+    pub limit_rate: Option<u64>,
+
     /// Manage system-wide packages (requires root)
     #[arg(long, short = 'S', global = true)]
     pub system: bool,
 
+    /// Install into the system mounted at DIR instead of the running one
+    #[arg(long, global = true, value_name = "DIR")]
+    pub root: Option<String>,
+
     #[clap(subcommand)]
     pub command: Commands,
 }
@@ -95,10 +106,14 @@ pub enum RepoAction {
         /// Sync interval (e.g., "1h", "12h", "1d")
         #[arg(long)]
         sync_interval: Option<String>,
+        /// How long a version must have been out before updates take it (e.g., "3d")
+        #[arg(long)]
+        min_release_age: Option<String>,
     },
     /// Update an existing repository
     Update {
         /// Repository name
+        #[arg(add = ArgValueCompleter::new(completion::repositories))]
         name: String,
         /// Repository metadata URL
         #[arg(long)]
@@ -118,16 +133,124 @@ pub enum RepoAction {
         /// Sync interval (e.g., "1h", "12h", "1d")
         #[arg(long)]
         sync_interval: Option<String>,
+        /// How long a version must have been out before updates take it (e.g., "3d")
+        #[arg(long)]
+        min_release_age: Option<String>,
     },
     /// Remove a repository
     #[clap(visible_alias = "del")]
     Remove {
         /// Repository name
+        #[arg(add = ArgValueCompleter::new(completion::repositories))]
         name: String,
     },
     /// List configured repositories
     #[clap(visible_alias = "ls")]
     List,
+    /// Build a repository from a directory of artifacts or a manifest
+    Build {
+        /// Directory of <name>-<version> artifacts, or a manifest.toml listing them
+        #[arg(value_hint = ValueHint::AnyPath)]
+        source: PathBuf,
+        /// Directory to write the repository to (default: beside the source)
+        #[arg(long, short, value_hint = ValueHint::DirPath)]
+        output: Option<PathBuf>,
+        /// URL the repository will be served from (default: URLs relative to the index)
+        #[arg(long)]
+        base_url: Option<String>,
+        /// Repository name recorded in the metadata database
+        #[arg(long)]
+        name: Option<String>,
+        /// Minisign secret key to sign the artifacts and metadata with
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        key: Option<PathBuf>,
+    },
+    /// Sign files with a minisign secret key, writing <file>.sig beside each
+    Sign {
+        /// Files to sign
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        files: Vec<PathBuf>,
+        /// Minisign secret key to sign with
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        key: PathBuf,
+    },
+    /// Generate an unencrypted minisign key pair for signing a repository
+    Keygen {
+        /// Where to write the secret key; the public key goes to <path>.pub
+        #[arg(default_value = "soar.key", value_hint = ValueHint::FilePath)]
+        path: PathBuf,
+        /// Replace an existing key
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProfileAction {
+    /// Add a new profile
+    Add {
+        /// Profile name
+        name: String,
+        /// Root directory for the profile's data and packages
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        root_path: String,
+        /// Where packages are stored (default: <root-path>/packages)
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        packages_path: Option<String>,
+    },
+    /// Remove a profile that holds no packages
+    #[clap(visible_alias = "rm")]
+    Remove {
+        /// Profile name
+        #[arg(add = ArgValueCompleter::new(completion::profiles))]
+        name: String,
+    },
+    /// List profiles
+    #[clap(visible_alias = "ls")]
+    List,
+    /// Show the paths a profile uses
+    Show {
+        /// Profile name (default: the current profile)
+        #[arg(add = ArgValueCompleter::new(completion::profiles))]
+        name: Option<String>,
+    },
+    /// Make a profile the default
+    SetDefault {
+        /// Profile name
+        #[arg(add = ArgValueCompleter::new(completion::profiles))]
+        name: String,
+    },
+    /// Move an installed package to another profile
+    Migrate {
+        /// Package to move
+        #[arg(add = ArgValueCompleter::new(completion::installed))]
+        package: String,
+        /// Profile to move it to
+        #[arg(long, add = ArgValueCompleter::new(completion::profiles))]
+        to: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ImageAction {
+    /// Install declared packages into a fresh root and write it as an OCI image
+    Build {
+        /// Path to packages.toml (default: ~/.config/soar/packages.toml)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        from: Option<String>,
+        /// Where to write the OCI image layout tarball
+        #[arg(long, short, value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+        /// Where the packages are placed inside the image
+        #[arg(long, default_value = soar_operations::image::DEFAULT_PREFIX)]
+        prefix: PathBuf,
+        /// Name recorded for the image in the layout
+        #[arg(long, default_value = "latest")]
+        tag: String,
+        /// Skip checksum verification
+        #[arg(long)]
+        no_verify: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -142,6 +265,14 @@ pub enum SelfAction {
     Uninstall,
 }
 
+/// A shell `soar shell-hook` can write a command-not-found handler for.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Print the configuration file to stdout
@@ -157,7 +288,7 @@ pub enum Commands {
     #[clap(name = "install", visible_alias = "i", visible_alias = "add")]
     Install {
         /// Packages to install
-        #[arg(required = true)]
+        #[arg(required = true, add = ArgValueCompleter::new(completion::available))]
         packages: Vec<String>,
 
         /// Whether to force install the package
@@ -222,6 +353,10 @@ pub enum Commands {
         #[arg(required = false, long)]
         binary_only: bool,
 
+        /// Unpack AppImages to run without FUSE, even where FUSE works
+        #[arg(required = false, long)]
+        extract: bool,
+
         /// Ask for confirmation before installation
         #[arg(required = false, long, short)]
         ask: bool,
@@ -273,8 +408,12 @@ pub enum Commands {
     #[clap(name = "query", visible_alias = "Q")]
     Query {
         /// Package to query
-        #[arg(required = true)]
+        #[arg(required = true, add = ArgValueCompleter::new(completion::available))]
         query: String,
+
+        /// Show release notes, from the installed version if one is older
+        #[arg(required = false, long)]
+        changelog: bool,
     },
 
     /// Remove packages
@@ -282,7 +421,7 @@ pub enum Commands {
     #[clap(name = "remove", visible_alias = "r", visible_alias = "del")]
     Remove {
         /// Packages to remove
-        #[arg(required = true)]
+        #[arg(required = true, add = ArgValueCompleter::new(completion::installed))]
         packages: Vec<String>,
 
         /// Skip prompts and use first match
@@ -302,7 +441,7 @@ pub enum Commands {
     #[clap(name = "update", visible_alias = "u", visible_alias = "upgrade")]
     Update {
         /// Packages to update
-        #[arg(required = false)]
+        #[arg(required = false, add = ArgValueCompleter::new(completion::installed))]
         packages: Option<Vec<String>>,
 
         /// Keep old version
@@ -317,6 +456,10 @@ pub enum Commands {
         #[arg(required = false, long, conflicts_with_all = ["ask", "keep", "no_verify"])]
         check: bool,
 
+        /// Show the release notes of every version an update moves through
+        #[arg(required = false, long)]
+        changelog: bool,
+
         /// Skip checksum verification
         #[arg(required = false, long)]
         no_verify: bool,
@@ -326,7 +469,7 @@ pub enum Commands {
     #[clap(name = "info", visible_alias = "list-installed")]
     ListInstalledPackages {
         /// Repository to get installed packages for
-        #[arg(required = false, long, short)]
+        #[arg(required = false, long, short, add = ArgValueCompleter::new(completion::repositories))]
         repo_name: Option<String>,
 
         /// Only show the unique package install count
@@ -334,11 +477,29 @@ pub enum Commands {
         count: bool,
     },
 
+    /// List the files an installed package placed
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "files")]
+    Files {
+        /// Installed package to list the files of
+        #[arg(required = true, add = ArgValueCompleter::new(completion::installed))]
+        package: String,
+    },
+
+    /// Find the installed package a file belongs to
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "owns")]
+    Owns {
+        /// File to look up
+        #[arg(required = true, value_hint = ValueHint::AnyPath)]
+        path: PathBuf,
+    },
+
     /// List all available packages
     #[clap(name = "list", visible_alias = "ls")]
     ListPackages {
         /// Which repository to get the packages from
-        #[arg(required = false)]
+        #[arg(required = false, add = ArgValueCompleter::new(completion::repositories))]
         repo_name: Option<String>,
     },
 
@@ -347,7 +508,7 @@ pub enum Commands {
     #[clap(name = "log")]
     Log {
         /// Package to view log for
-        #[arg(required = true)]
+        #[arg(required = true, add = ArgValueCompleter::new(completion::available))]
         package: String,
     },
 
@@ -356,7 +517,7 @@ pub enum Commands {
     #[clap(name = "inspect")]
     Inspect {
         /// Package to view build script for
-        #[arg(required = true)]
+        #[arg(required = true, add = ArgValueCompleter::new(completion::available))]
         package: String,
     },
 
@@ -372,8 +533,17 @@ pub enum Commands {
         #[arg(required = false, long)]
         no_verify: bool,
 
+        /// Keep the program's home, config, data and cache in a portable dir
+        #[arg(
+            required = false,
+            long, num_args = 0..=1,
+            require_equals = true,
+            value_hint = ValueHint::AnyPath
+        )]
+        portable: Option<Option<String>>,
+
         /// Command to execute
-        #[arg(required = true)]
+        #[arg(required = true, add = ArgValueCompleter::new(completion::available))]
         command: Vec<String>,
 
         /// Package id
@@ -381,16 +551,43 @@ pub enum Commands {
         pkg_id: Option<String>,
 
         /// Repo name
-        #[arg(required = false, short, long)]
+        #[arg(required = false, short, long, add = ArgValueCompleter::new(completion::repositories))]
         repo_name: Option<String>,
     },
 
+    /// Find the packages that provide a command
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "which-package")]
+    WhichPackage {
+        /// Command to look for
+        #[arg(required = true)]
+        command: String,
+
+        /// Answer as a shell's command-not-found handler: explain on stderr,
+        /// offer to run the command, and exit with its status or 127
+        #[arg(long, hide = true)]
+        not_found: bool,
+
+        /// Arguments the command was typed with, for --not-found
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, hide = true)]
+        args: Vec<String>,
+    },
+
+    /// Print a command-not-found handler that suggests soar packages
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "shell-hook")]
+    ShellHook {
+        /// Shell to write the handler for
+        #[arg(value_enum)]
+        shell: HookShell,
+    },
+
     /// Use package from different family
     #[command(arg_required_else_help = true)]
     #[clap(name = "use")]
     Use {
         /// The package name to use alternative package for
-        #[arg(required = true)]
+        #[arg(required = true, add = ArgValueCompleter::new(completion::installed))]
         package_name: String,
     },
 
@@ -464,6 +661,18 @@ pub enum Commands {
     #[clap(name = "health")]
     Health,
 
+    /// Finish, or roll back, packages an interrupted operation left behind
+    #[clap(name = "resume")]
+    Resume {
+        /// Undo them instead, going back to the version each was replacing
+        #[arg(long)]
+        rollback: bool,
+
+        /// Skip confirmation prompt
+        #[arg(required = false, short, long)]
+        yes: bool,
+    },
+
     /// Generate default config
     #[clap(name = "defconfig")]
     DefConfig {
@@ -487,6 +696,14 @@ pub enum Commands {
         action: RepoAction,
     },
 
+    /// Manage profiles
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "profile")]
+    Profile {
+        #[clap(subcommand)]
+        action: ProfileAction,
+    },
+
     /// View env
     #[clap(name = "env")]
     Env,
@@ -495,6 +712,18 @@ pub enum Commands {
     #[clap(name = "plugin-manifest")]
     PluginManifest,
 
+    /// Answer JSON-RPC requests from a frontend until the input closes
+    #[clap(name = "serve")]
+    Serve {
+        /// Listen on this Unix socket instead of stdin and stdout
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        socket: Option<String>,
+    },
+
+    /// Search, inspect and queue package changes on a full-screen interface
+    #[clap(name = "tui")]
+    Tui,
+
     /// Act on a soar:// link, or register soar as its handler
     #[command(arg_required_else_help = true)]
     #[clap(name = "url")]
@@ -557,6 +786,14 @@ pub enum Commands {
         no_verify: bool,
     },
 
+    /// Build container images from declared packages
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "image")]
+    Image {
+        #[clap(subcommand)]
+        action: ImageAction,
+    },
+
     /// Generate default packages configuration
     #[clap(name = "defpackages")]
     DefPackages,
@@ -0,0 +1,183 @@
+//! Shell completion that knows what can be installed and what already is.
+//!
+//! The script `soar completions <shell>` prints calls back into soar with
+//! `COMPLETE=<shell>` set at every tab, and the completers below answer from
+//! the name indexes sync writes and the installed database.
+
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    io::{self, Write},
+    path::{self, PathBuf},
+    sync::Arc,
+};
+
+use clap::CommandFactory;
+use clap_complete::{env::Shells, CompletionCandidate, Shell};
+use soar_config::config::{self, enable_system_mode, get_config, set_current_profile};
+use soar_core::{
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+use soar_events::NullSink;
+use soar_operations::{completion, SoarContext};
+
+use crate::cli::Args;
+
+/// The variable the registration script sets when it calls back into soar.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+/// The options already on the command line that decide which installation
+/// the command will act on.
+#[derive(Debug, Default, PartialEq)]
+struct Scope {
+    system: bool,
+    profile: Option<String>,
+}
+
+impl Scope {
+    /// Read from `words`, the command line as the shell passes it to soar
+    /// after `--`.
+    ///
+    /// Completers only see the word being completed, so the rest is parsed
+    /// again here, leniently, since the line is unfinished.
+    fn typed(words: impl IntoIterator<Item = OsString>) -> Self {
+        let Ok(matches) = Args::command()
+            .ignore_errors(true)
+            .try_get_matches_from(words)
+        else {
+            return Self::default();
+        };
+        Self {
+            system: matches.get_flag("system"),
+            profile: matches.get_one::<String>("profile").cloned(),
+        }
+    }
+
+    /// The scope of the command line this process was asked to complete.
+    fn current() -> Self {
+        Self::typed(env::args_os().skip_while(|arg| arg != "--").skip(1))
+    }
+}
+
+/// Answer with `complete`, or with nothing when it cannot: a completer has no
+/// way to report an error, and the shell is better off offering no names than
+/// printing one into the command line.
+fn candidates(
+    current: &OsStr,
+    complete: impl Fn(&SoarContext, &str) -> SoarResult<Vec<String>>,
+) -> Vec<CompletionCandidate> {
+    let Some(typed) = current.to_str() else {
+        return Vec::new();
+    };
+    let scope = Scope::current();
+    if scope.system {
+        enable_system_mode();
+    }
+    if config::init().is_err() {
+        return Vec::new();
+    }
+    if let Some(profile) = &scope.profile {
+        if set_current_profile(profile).is_err() {
+            return Vec::new();
+        }
+    }
+    let ctx = SoarContext::new(get_config(), Arc::new(NullSink));
+    complete(&ctx, typed)
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Packages the synced repositories publish.
+pub fn available(current: &OsStr) -> Vec<CompletionCandidate> {
+    candidates(current, completion::available_packages)
+}
+
+/// Packages already installed, under the profile named on the command line
+/// if one is.
+pub fn installed(current: &OsStr) -> Vec<CompletionCandidate> {
+    let profile = Scope::current().profile;
+    candidates(current, |ctx, typed| {
+        completion::installed_packages(ctx, typed, profile.as_deref())
+    })
+}
+
+/// Configured repositories.
+pub fn repositories(current: &OsStr) -> Vec<CompletionCandidate> {
+    candidates(current, |ctx, typed| {
+        Ok(completion::repository_names(ctx, typed))
+    })
+}
+
+/// Configured profiles.
+pub fn profiles(current: &OsStr) -> Vec<CompletionCandidate> {
+    candidates(current, |ctx, typed| {
+        Ok(completion::profile_names(ctx, typed))
+    })
+}
+
+/// Print the script that has `shell` complete soar's arguments by asking soar.
+///
+/// The script names soar as it was invoked, so one written to a file keeps
+/// working for as long as that path does.
+pub fn write_registration(shell: Shell) -> SoarResult<()> {
+    let name = shell.to_string();
+    let shells = Shells::builtins();
+    let completer = shells.completer(&name).ok_or_else(|| {
+        SoarError::Custom(format!("Dynamic completion is not available for {name}"))
+    })?;
+
+    let invoked = env::args_os()
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| "soar".into());
+    // A bare name is found on PATH at completion time; a relative path would
+    // be resolved against whichever directory the shell is in by then.
+    let invoked = if invoked.components().count() > 1 {
+        path::absolute(&invoked).unwrap_or(invoked)
+    } else {
+        invoked
+    };
+
+    let mut script = Vec::new();
+    completer
+        .write_registration(
+            COMPLETE_VAR,
+            "soar",
+            "soar",
+            &invoked.to_string_lossy(),
+            &mut script,
+        )
+        .and_then(|_| io::stdout().write_all(&script))
+        .with_context(|| format!("writing the {name} completion script"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(line: &str) -> Scope {
+        Scope::typed(line.split(' ').map(OsString::from))
+    }
+
+    #[test]
+    fn the_installation_named_on_the_line_is_the_one_completed() {
+        assert_eq!(scope("soar remove fo"), Scope::default());
+        assert_eq!(
+            scope("soar -S remove --profile work fo"),
+            Scope {
+                system: true,
+                profile: Some("work".into()),
+            }
+        );
+        assert_eq!(
+            scope("soar remove -qSpwork fo"),
+            Scope {
+                system: true,
+                profile: Some("work".into()),
+            }
+        );
+    }
+}
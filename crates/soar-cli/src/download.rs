@@ -8,11 +8,13 @@ use soar_dl::{
     download::Download,
     error::DownloadError,
     filter::Filter,
+    gitea::Gitea,
     github::Github,
     gitlab::GitLab,
     oci::OciDownload,
     platform::PlatformUrl,
-    traits::{Asset, Platform as _, Release as _},
+    sourcehut::SourceHut,
+    traits::{Asset, Platform, Release as _},
     types::{OverwriteMode, Progress},
 };
 use soar_utils::bytes::format_bytes;
@@ -120,7 +122,7 @@ pub async fn handle_direct_downloads(
                 tag,
             }) => {
                 info!("Detected GitHub URL, processing as GitHub release");
-                if let Err(err) = handle_github_release(ctx, &project, tag.as_deref()) {
+                if let Err(err) = handle_release::<Github>(ctx, &project, tag.as_deref()) {
                     error!("{}", err);
                 }
             }
@@ -129,7 +131,25 @@ pub async fn handle_direct_downloads(
                 tag,
             }) => {
                 info!("Detected GitLab URL, processing as GitLab release");
-                if let Err(err) = handle_gitlab_release(ctx, &project, tag.as_deref()) {
+                if let Err(err) = handle_release::<GitLab>(ctx, &project, tag.as_deref()) {
+                    error!("{}", err);
+                }
+            }
+            Some(PlatformUrl::Gitea {
+                project,
+                tag,
+            }) => {
+                info!("Detected Gitea URL, processing as Gitea release");
+                if let Err(err) = handle_release::<Gitea>(ctx, &project, tag.as_deref()) {
+                    error!("{}", err);
+                }
+            }
+            Some(PlatformUrl::SourceHut {
+                project,
+                tag,
+            }) => {
+                info!("Detected SourceHut URL, processing as SourceHut release");
+                if let Err(err) = handle_release::<SourceHut>(ctx, &project, tag.as_deref()) {
                     error!("{}", err);
                 }
             }
@@ -311,81 +331,14 @@ pub async fn handle_oci_downloads(
     Ok(())
 }
 
-fn handle_github_release(
-    ctx: &DownloadContext,
-    project: &str,
-    tag: Option<&str>,
-) -> SoarResult<()> {
-    let releases = Github::fetch_releases(project, tag)?;
-
-    let release = if let Some(tag) = tag {
-        releases.iter().find(|r| r.tag() == tag)
-    } else {
-        releases
-            .iter()
-            .find(|r| !r.is_prerelease())
-            .or_else(|| releases.first())
-    };
-
-    let release = release.ok_or_else(|| DownloadError::InvalidResponse)?;
-
-    info!("Found release: {}", release.tag());
-    let filter = ctx.create_filter();
-
-    let assets: Vec<_> = release
-        .assets()
-        .iter()
-        .filter(|a| filter.matches(a.name()))
-        .collect();
-
-    if assets.is_empty() {
-        let available = release
-            .assets()
-            .iter()
-            .map(|a| a.name().to_string())
-            .collect::<Vec<String>>();
-
-        Err(DownloadError::NoMatch {
-            available,
-        })?
-    }
-
-    let selected_asset = if assets.len() == 1 || ctx.yes {
-        assets[0]
-    } else {
-        &select_asset_interactively(assets)?
-    };
-
-    info!("Downloading asset: {}", selected_asset.name());
-
-    let mut dl = Download::new(selected_asset.url())
-        .overwrite(ctx.get_overwrite_mode())
-        .extract(ctx.extract);
-
-    if let Some(ref out) = ctx.output {
-        dl = dl.output(out);
-    }
-
-    if let Some(ref extract_dir) = ctx.extract_dir {
-        dl = dl.extract_to(extract_dir);
-    }
-
-    let cb = ctx.progress_callback.clone();
-    dl = dl.progress(move |p| {
-        cb(p);
-    });
-
-    dl.execute()?;
-
-    Ok(())
-}
-
-fn handle_gitlab_release(
+/// Download an asset from one release of a forge project, picking it
+/// interactively when the filters leave more than one.
+fn handle_release<P: Platform>(
     ctx: &DownloadContext,
     project: &str,
     tag: Option<&str>,
 ) -> SoarResult<()> {
-    let releases = GitLab::fetch_releases(project, tag)?;
+    let releases = P::fetch_releases(project, tag)?;
 
     let release = if let Some(tag) = tag {
         releases.iter().find(|r| r.tag() == tag)
@@ -473,7 +426,7 @@ pub async fn handle_github_downloads(
             _ => (project.trim_end_matches('@'), None),
         };
 
-        if let Err(err) = handle_github_release(ctx, project, tag) {
+        if let Err(err) = handle_release::<Github>(ctx, project, tag) {
             error!("{}", err);
         }
     }
@@ -492,7 +445,7 @@ pub async fn handle_gitlab_downloads(
             _ => (project.trim_end_matches('@'), None),
         };
 
-        if let Err(err) = handle_gitlab_release(ctx, project, tag) {
+        if let Err(err) = handle_release::<GitLab>(ctx, project, tag) {
             error!("{}", err);
         }
     }
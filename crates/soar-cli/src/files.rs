@@ -0,0 +1,84 @@
+//! Listing the files an installed package placed, and finding a file's owner.
+
+use std::path::Path;
+
+use nu_ansi_term::Color::{Blue, Cyan, Green, LightRed, Magenta};
+use soar_core::SoarResult;
+use soar_operations::{files, SoarContext};
+use tracing::{info, warn};
+
+use crate::{
+    json_output::{self, FileOwnerJson, PackageFilesJson},
+    utils::{json_enabled, Colored},
+};
+
+/// Print the files every installed package matching `package` placed,
+/// returning 1 when nothing matches.
+pub fn list_files(ctx: &SoarContext, package: &str) -> SoarResult<i32> {
+    let listed = files::list_files(ctx, package)?;
+
+    if json_enabled() {
+        let listed: Vec<PackageFilesJson> = listed.iter().map(Into::into).collect();
+        json_output::emit(&listed);
+        return Ok(if listed.is_empty() { 1 } else { 0 });
+    }
+
+    if listed.is_empty() {
+        warn!("Package {} is not installed.", package);
+        return Ok(1);
+    }
+
+    for entry in &listed {
+        let pkg = &entry.package;
+        info!(
+            "{}:{} ({})",
+            Colored(Blue, &pkg.pkg_name),
+            Colored(Cyan, &pkg.repo_name),
+            Colored(Magenta, &pkg.version)
+        );
+        info!(
+            "  {:<9} {}",
+            "directory",
+            Colored(Green, &pkg.installed_path)
+        );
+        for file in &entry.files {
+            info!("  {:<9} {}", file.kind, file.path);
+        }
+        if entry.files.is_empty() {
+            info!(
+                "  {}",
+                Colored(LightRed, "no files recorded outside this directory")
+            );
+        }
+    }
+    Ok(0)
+}
+
+/// Print the installed packages `path` belongs to, returning 1 when it
+/// belongs to none.
+pub fn find_owners(ctx: &SoarContext, path: &Path) -> SoarResult<i32> {
+    let owners = files::find_owners(ctx, path)?;
+
+    if json_enabled() {
+        let owners: Vec<FileOwnerJson> = owners.iter().map(Into::into).collect();
+        json_output::emit(&owners);
+    } else if owners.is_empty() {
+        warn!("{} is not owned by any installed package.", path.display());
+    } else {
+        for owner in &owners {
+            let pkg = &owner.package;
+            info!(
+                "{} is owned by {}:{} ({}){}",
+                path.display(),
+                Colored(Blue, &pkg.pkg_name),
+                Colored(Cyan, &pkg.repo_name),
+                Colored(Magenta, &pkg.version),
+                owner
+                    .kind
+                    .map(|kind| format!(" as {kind}"))
+                    .unwrap_or_default()
+            );
+        }
+    }
+    Ok(if owners.is_empty() { 1 } else { 0 })
+}
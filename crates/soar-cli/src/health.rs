@@ -62,6 +62,16 @@ pub async fn display_health(ctx: &SoarContext) -> SoarResult<()> {
     };
     builder.push_record(["Broken Symlinks".to_string(), sym_status]);
 
+    // Nothing to say on a host where every image mounts, or that has none.
+    if !report.needs_fuse.is_empty() {
+        let fuse_status = format!(
+            "{} {} cannot mount",
+            Colored(Yellow, icon_or(Icons::WARNING, "!")),
+            Colored(Yellow, report.needs_fuse.len())
+        );
+        builder.push_record(["FUSE".to_string(), fuse_status]);
+    }
+
     let table = builder
         .build()
         .with(Panel::header("System Health Check"))
@@ -96,6 +106,17 @@ pub async fn display_health(ctx: &SoarContext) -> SoarResult<()> {
         );
     }
 
+    if !report.needs_fuse.is_empty() {
+        info!("\nImages that need FUSE, which is missing or unusable here:");
+        for path in &report.needs_fuse {
+            info!("  {} {}", Icons::ARROW, Colored(Yellow, path.display()));
+        }
+        info!(
+            "Run {} to unpack one and run it without FUSE",
+            Colored(Green, "soar install --force --extract <package>")
+        );
+    }
+
     Ok(())
 }
 
@@ -0,0 +1,129 @@
+//! `soar image build`: the packages a `packages.toml` declares, as an OCI
+//! image.
+
+use std::{fs, path::PathBuf};
+
+use nu_ansi_term::Color::{Blue, Cyan, Green};
+use soar_config::{config::CONFIG, packages::PackagesConfig};
+use soar_core::{
+    error::{ErrorContext, SoarError},
+    package::remove::make_tree_writable,
+    utils::setup_required_paths,
+    SoarResult,
+};
+use soar_operations::{
+    image::{build_image, image_config, ImageOptions},
+    SoarContext,
+};
+use soar_utils::{bytes::format_bytes, path::install_root};
+use tracing::info;
+
+use crate::{cli::ImageAction, progress::create_wait_job, utils::Colored};
+
+/// Overrides that would send the image's installs back to the host's paths.
+const PATH_OVERRIDES: [&str; 6] = [
+    "SOAR_BIN",
+    "SOAR_DB",
+    "SOAR_PACKAGES",
+    "SOAR_CACHE",
+    "SOAR_PORTABLE_DIRS",
+    "SOAR_DESKTOP",
+];
+
+pub async fn handle_image_action(ctx: &SoarContext, action: ImageAction) -> SoarResult<()> {
+    match action {
+        ImageAction::Build {
+            from,
+            output,
+            prefix,
+            tag,
+            no_verify,
+        } => {
+            let options = ImageOptions {
+                prefix,
+                tag,
+                no_verify,
+            };
+            build(ctx, from, output, options).await
+        }
+    }
+}
+
+async fn build(
+    ctx: &SoarContext,
+    from: Option<String>,
+    output: PathBuf,
+    options: ImageOptions,
+) -> SoarResult<()> {
+    if install_root().is_some() {
+        return Err(SoarError::Custom(
+            "An image is built in a scratch root of its own; drop --root to build one".into(),
+        ));
+    }
+    if let Some(var) = PATH_OVERRIDES
+        .iter()
+        .find(|var| std::env::var_os(var).is_some())
+    {
+        return Err(SoarError::Custom(format!(
+            "{var} is set, and would install the image's packages outside it; unset it to build an image"
+        )));
+    }
+
+    let packages = PackagesConfig::load(from.as_deref())?.resolved_packages();
+    if packages.is_empty() {
+        return Err(SoarError::Custom(
+            "No packages declared; an image needs at least one".into(),
+        ));
+    }
+
+    // The root is built beside the cache rather than in /tmp, which is often
+    // a small tmpfs and would have to hold every package twice.
+    let root = ctx
+        .config()
+        .get_cache_path()?
+        .join(format!("image-{}", std::process::id()));
+    if root.exists() {
+        make_tree_writable(&root);
+        fs::remove_dir_all(&root).with_context(|| format!("removing {}", root.display()))?;
+    }
+    fs::create_dir_all(&root).with_context(|| format!("creating {}", root.display()))?;
+
+    let config = image_config(ctx.config(), &root)?;
+    // Hooks and integration read the global configuration, so it has to point
+    // at the same root the context installs to.
+    *CONFIG.write()? = Some(config.clone());
+    setup_required_paths()?;
+    let image_ctx = SoarContext::new(config, ctx.events().clone());
+
+    info!(
+        "Building image from {} package declaration(s)",
+        packages.len()
+    );
+    let spinner = create_wait_job("installing into image root");
+    let result = build_image(&image_ctx, &root, &packages, &output, &options).await;
+    spinner.finish_and_clear();
+
+    make_tree_writable(&root);
+    fs::remove_dir_all(&root).ok();
+    let report = result?;
+
+    for pkg in &report.installed {
+        info!(
+            "  {} {}",
+            Colored(Blue, &pkg.pkg_name),
+            Colored(Cyan, &pkg.version)
+        );
+    }
+    info!(
+        "\nWrote {} ({}, layer {})",
+        Colored(Green, output.display()),
+        report.digest,
+        format_bytes(report.layer_size, 2)
+    );
+    info!(
+        "Load it with `podman load -i {}` or `docker load -i {}`",
+        output.display(),
+        output.display()
+    );
+    Ok(())
+}
@@ -28,6 +28,7 @@ pub async fn install_packages(
     portable_cache: Option<String>,
     no_notes: bool,
     binary_only: bool,
+    extract: bool,
     ask: bool,
     no_verify: bool,
     name_override: Option<String>,
@@ -51,6 +52,7 @@ pub async fn install_packages(
         portable_cache: portable_cache.clone(),
         binary_only,
         no_verify,
+        extract,
         name_override,
         version_override,
         pkg_type_override,
@@ -8,9 +8,13 @@ use serde::Serialize;
 use soar_config::repository::Repository;
 use soar_core::{
     database::models::{InstalledPackage, Package},
-    package::install::InstallTarget,
+    package::{changelog::ReleaseNotes, install::InstallTarget},
+};
+use soar_operations::{
+    ApplyDiff, ApplyReport, CommandProvider, DeferredUpdate, FailedInfo, FileOwner, HealthReport,
+    InstalledEntry, InstalledInfo, PackageChangelog, PackageFiles, PackageListEntry, RemovedInfo,
+    SearchEntry, UpdateCheck, UpdateInfo,
 };
-use soar_operations::{ApplyDiff, InstalledEntry, PackageListEntry, SearchEntry, UpdateInfo};
 
 /// A package as published by a repository.
 #[derive(Serialize)]
@@ -119,6 +123,9 @@ pub struct PackageDetailJson {
     pub build_date: Option<String>,
     /// Formatted for display.
     pub maintainers: Vec<String>,
+    /// Present only when `--changelog` asked for it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<Vec<ReleaseNotesJson>>,
 }
 
 impl From<&Package> for PackageDetailJson {
@@ -144,10 +151,37 @@ impl From<&Package> for PackageDetailJson {
                 .as_ref()
                 .map(|all| all.iter().map(ToString::to_string).collect())
                 .unwrap_or_default(),
+            changelog: None,
+        }
+    }
+}
+
+/// What a project published about one version.
+#[derive(Serialize)]
+pub struct ReleaseNotesJson {
+    pub version: String,
+    pub published_at: Option<String>,
+    /// Markdown, as the project wrote it.
+    pub body: Option<String>,
+    pub url: Option<String>,
+}
+
+impl From<&ReleaseNotes> for ReleaseNotesJson {
+    fn from(notes: &ReleaseNotes) -> Self {
+        Self {
+            version: notes.version.clone(),
+            published_at: notes.published_at.clone(),
+            body: notes.body.clone(),
+            url: notes.url.clone(),
         }
     }
 }
 
+/// The notes gathered for one package, newest first.
+pub fn changelog_json(changelog: &PackageChangelog) -> Vec<ReleaseNotesJson> {
+    changelog.notes.iter().map(Into::into).collect()
+}
+
 /// A package with a newer version waiting for it.
 #[derive(Serialize)]
 pub struct UpdateJson {
@@ -157,6 +191,9 @@ pub struct UpdateJson {
     pub current_version: String,
     pub new_version: String,
     pub size: Option<u64>,
+    /// Present only when `--changelog` asked for it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<Vec<ReleaseNotesJson>>,
 }
 
 impl From<&UpdateInfo> for UpdateJson {
@@ -169,6 +206,49 @@ impl From<&UpdateInfo> for UpdateJson {
             current_version: update.current_version.clone(),
             new_version: update.new_version.clone(),
             size: package.ghcr_size.or(package.size),
+            changelog: None,
+        }
+    }
+}
+
+/// A newer version held back by a minimum release age.
+#[derive(Serialize)]
+pub struct DeferredUpdateJson {
+    pub name: String,
+    pub repo: String,
+    pub current_version: String,
+    pub new_version: String,
+    /// When it becomes old enough, in RFC 3339.
+    pub eligible_at: String,
+}
+
+impl From<&DeferredUpdate> for DeferredUpdateJson {
+    fn from(update: &DeferredUpdate) -> Self {
+        Self {
+            name: update.pkg_name.clone(),
+            repo: update.repo_name.clone(),
+            current_version: update.current_version.clone(),
+            new_version: update.new_version.clone(),
+            eligible_at: update.eligible_at.to_rfc3339(),
+        }
+    }
+}
+
+/// What `update --check` found: the listing of ready updates, with those
+/// still deferred beside it.
+#[derive(Serialize)]
+pub struct UpdateCheckJson {
+    pub items: Vec<UpdateJson>,
+    pub total: usize,
+    pub deferred: Vec<DeferredUpdateJson>,
+}
+
+impl From<&UpdateCheck> for UpdateCheckJson {
+    fn from(check: &UpdateCheck) -> Self {
+        Self {
+            items: check.updates.iter().map(Into::into).collect(),
+            total: check.updates.len(),
+            deferred: check.deferred.iter().map(Into::into).collect(),
         }
     }
 }
@@ -195,6 +275,103 @@ impl From<&Repository> for RepositoryJson {
     }
 }
 
+/// A configured profile and the directories it resolves to.
+#[derive(Serialize)]
+pub struct ProfileJson {
+    pub name: String,
+    pub root: String,
+    pub packages: String,
+    pub cache: String,
+    pub default: bool,
+    pub current: bool,
+}
+
+/// A package that would put a command on the PATH.
+#[derive(Serialize)]
+pub struct CommandProviderJson {
+    pub name: String,
+    pub family: Option<String>,
+    pub repo: String,
+}
+
+impl From<&CommandProvider> for CommandProviderJson {
+    fn from(provider: &CommandProvider) -> Self {
+        Self {
+            name: provider.pkg_name.clone(),
+            family: provider.pkg_family.clone(),
+            repo: provider.repo_name.clone(),
+        }
+    }
+}
+
+/// A file an installed package placed outside its install directory.
+#[derive(Serialize)]
+pub struct InstalledFileJson {
+    pub path: String,
+    /// binary, shared, desktop, icon, metainfo or portable.
+    pub kind: &'static str,
+}
+
+/// An installed package and the files it placed, as `files` reports them.
+#[derive(Serialize)]
+pub struct PackageFilesJson {
+    pub name: String,
+    pub family: Option<String>,
+    pub repo: String,
+    pub version: String,
+    pub installed_path: String,
+    /// Empty for a package installed before soar recorded its files.
+    pub files: Vec<InstalledFileJson>,
+}
+
+impl From<&PackageFiles> for PackageFilesJson {
+    fn from(entry: &PackageFiles) -> Self {
+        let package = &entry.package;
+        Self {
+            name: package.pkg_name.clone(),
+            family: package.pkg_family.clone(),
+            repo: package.repo_name.clone(),
+            version: package.version.clone(),
+            installed_path: package.installed_path.clone(),
+            files: entry
+                .files
+                .iter()
+                .map(|file| {
+                    InstalledFileJson {
+                        path: file.path.clone(),
+                        kind: file.kind.as_str(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// An installed package a path belongs to, as `owns` reports it.
+#[derive(Serialize)]
+pub struct FileOwnerJson {
+    pub name: String,
+    pub family: Option<String>,
+    pub repo: String,
+    pub version: String,
+    /// How the package placed the path, or null when the path lies inside
+    /// its install directory.
+    pub kind: Option<&'static str>,
+}
+
+impl From<&FileOwner> for FileOwnerJson {
+    fn from(owner: &FileOwner) -> Self {
+        let package = &owner.package;
+        Self {
+            name: package.pkg_name.clone(),
+            family: package.pkg_family.clone(),
+            repo: package.repo_name.clone(),
+            version: package.version.clone(),
+            kind: owner.kind.map(|kind| kind.as_str()),
+        }
+    }
+}
+
 /// Where soar keeps its files, so a frontend can read and write the same ones.
 #[derive(Serialize)]
 pub struct EnvJson {
@@ -263,6 +440,174 @@ impl ApplyDiffJson {
     }
 }
 
+/// How many packages applying the declarative configuration changed.
+#[derive(Serialize)]
+pub struct ApplyReportJson {
+    pub installed: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub failed: usize,
+}
+
+impl From<&ApplyReport> for ApplyReportJson {
+    fn from(report: &ApplyReport) -> Self {
+        Self {
+            installed: report.installed_count,
+            updated: report.updated_count,
+            removed: report.removed_count,
+            failed: report.failed_count,
+        }
+    }
+}
+
+/// One of several packages a query could have meant.
+#[derive(Serialize)]
+pub struct CandidateJson {
+    pub name: String,
+    pub family: Option<String>,
+    pub repo: String,
+    pub version: String,
+}
+
+impl From<&Package> for CandidateJson {
+    fn from(package: &Package) -> Self {
+        Self {
+            name: package.pkg_name.clone(),
+            family: package.pkg_family.clone(),
+            repo: package.repo_name.clone(),
+            version: package.version.clone(),
+        }
+    }
+}
+
+impl From<&InstalledPackage> for CandidateJson {
+    fn from(package: &InstalledPackage) -> Self {
+        Self {
+            name: package.pkg_name.clone(),
+            family: package.pkg_family.clone(),
+            repo: package.repo_name.clone(),
+            version: package.version.clone(),
+        }
+    }
+}
+
+/// A query that matched more than one package, left for the caller to narrow.
+#[derive(Serialize)]
+pub struct AmbiguousJson {
+    pub query: String,
+    pub candidates: Vec<CandidateJson>,
+}
+
+/// A package an operation put in place.
+#[derive(Serialize)]
+pub struct InstalledInfoJson {
+    pub name: String,
+    pub family: Option<String>,
+    pub repo: String,
+    pub version: String,
+    pub install_dir: String,
+    pub notes: Vec<String>,
+}
+
+impl From<&InstalledInfo> for InstalledInfoJson {
+    fn from(info: &InstalledInfo) -> Self {
+        Self {
+            name: info.pkg_name.clone(),
+            family: info.pkg_family.clone(),
+            repo: info.repo_name.clone(),
+            version: info.version.clone(),
+            install_dir: info.install_dir.display().to_string(),
+            notes: info.notes.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// A package an operation could not finish.
+#[derive(Serialize)]
+pub struct FailedJson {
+    pub name: String,
+    pub error: String,
+}
+
+impl From<&FailedInfo> for FailedJson {
+    fn from(info: &FailedInfo) -> Self {
+        Self {
+            name: info.pkg_name.clone(),
+            error: info.error.clone(),
+        }
+    }
+}
+
+/// A package an operation took away.
+#[derive(Serialize)]
+pub struct RemovedJson {
+    pub name: String,
+    pub repo: String,
+    pub version: String,
+}
+
+impl From<&RemovedInfo> for RemovedJson {
+    fn from(info: &RemovedInfo) -> Self {
+        Self {
+            name: info.pkg_name.clone(),
+            repo: info.repo_name.clone(),
+            version: info.version.clone(),
+        }
+    }
+}
+
+/// A package whose install directory is gone or never finished.
+#[derive(Serialize)]
+pub struct BrokenPackageJson {
+    pub name: String,
+    pub installed_path: String,
+}
+
+/// What `health` found wrong with this installation.
+#[derive(Serialize)]
+pub struct HealthJson {
+    /// Whether the bin directory is on `PATH`.
+    pub path_configured: bool,
+    pub bin_path: String,
+    pub man_path: Option<String>,
+    pub man_path_configured: bool,
+    pub broken_packages: Vec<BrokenPackageJson>,
+    pub broken_symlinks: Vec<String>,
+    /// Links to images that cannot mount themselves on this host.
+    pub needs_fuse: Vec<String>,
+}
+
+impl From<&HealthReport> for HealthJson {
+    fn from(report: &HealthReport) -> Self {
+        Self {
+            path_configured: report.path_configured,
+            bin_path: report.bin_path.display().to_string(),
+            man_path: report.man_path.as_ref().map(|p| p.display().to_string()),
+            man_path_configured: report.man_path_configured,
+            broken_packages: report
+                .broken_packages
+                .iter()
+                .map(|p| {
+                    BrokenPackageJson {
+                        name: p.pkg_name.clone(),
+                        installed_path: p.installed_path.clone(),
+                    }
+                })
+                .collect(),
+            broken_symlinks: report
+                .broken_symlinks
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect(),
+            needs_fuse: report
+                .needs_fuse
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect(),
+        }
+    }
+}
+
 /// Wraps a listing so fields can be added without changing the shape.
 #[derive(Serialize)]
 pub struct Listing<T: Serialize> {
@@ -2,7 +2,7 @@ use std::collections::HashSet;
 
 use nu_ansi_term::Color::{Blue, Cyan, Green, LightRed, Magenta, Red, Yellow};
 use soar_core::SoarResult;
-use soar_operations::{list, search, SoarContext};
+use soar_operations::{changelog::package_changelog, list, search, SoarContext};
 use soar_utils::bytes::format_bytes;
 use tabled::{
     builder::Builder,
@@ -11,7 +11,9 @@ use tabled::{
 use tracing::{debug, info};
 
 use crate::{
-    json_output::{self, InstalledJson, Listing, PackageDetailJson, PackageJson},
+    changelog::display_changelogs,
+    json_output::{self, changelog_json, InstalledJson, Listing, PackageDetailJson, PackageJson},
+    progress::create_wait_job,
     utils::{
         display_settings, icon_or, json_enabled, pretty_package_size, term_width, vec_string,
         Colored, Icons,
@@ -135,13 +137,31 @@ pub async fn search_packages(
     Ok(())
 }
 
-pub async fn query_package(ctx: &SoarContext, query_str: String) -> SoarResult<()> {
+pub async fn query_package(
+    ctx: &SoarContext,
+    query_str: String,
+    changelog: bool,
+) -> SoarResult<()> {
     debug!(query = query_str, "querying package info");
 
     let packages = search::query_package(ctx, &query_str).await?;
+    let changelogs = if changelog {
+        let spinner = create_wait_job("fetching release notes");
+        let changelogs = packages
+            .iter()
+            .map(|package| package_changelog(ctx, package))
+            .collect::<SoarResult<Vec<_>>>();
+        spinner.finish_and_clear();
+        changelogs?
+    } else {
+        Vec::new()
+    };
 
     if json_enabled() {
-        let items: Vec<PackageDetailJson> = packages.iter().map(Into::into).collect();
+        let mut items: Vec<PackageDetailJson> = packages.iter().map(Into::into).collect();
+        for (item, changelog) in items.iter_mut().zip(&changelogs) {
+            item.changelog = Some(changelog_json(changelog));
+        }
         let total = items.len();
         json_output::emit(&Listing::new(items, total));
         return Ok(());
@@ -297,6 +317,7 @@ pub async fn query_package(ctx: &SoarContext, query_str: String) -> SoarResult<(
             "\n{table}"
         );
     }
+    display_changelogs(&changelogs);
 
     Ok(())
 }
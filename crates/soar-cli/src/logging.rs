@@ -1,3 +1,5 @@
+use std::sync::{mpsc::Sender, Mutex};
+
 use nu_ansi_term::Color::{Blue, Magenta, Red, Yellow};
 use tracing::{Event, Level, Subscriber};
 use tracing_subscriber::{
@@ -9,7 +11,10 @@ use tracing_subscriber::{
     registry::LookupSpan,
 };
 
-use crate::{cli::Args, utils::Colored};
+use crate::{
+    cli::{Args, Commands},
+    utils::Colored,
+};
 
 /// Collects an event's message and the fields recorded alongside it.
 ///
@@ -82,6 +87,15 @@ where
     }
 }
 
+/// Where log records go instead of the terminal while something else, the
+/// TUI, is drawing on it.
+static CAPTURE: Mutex<Option<Sender<String>>> = Mutex::new(None);
+
+/// Send log records, a line at a time, to `sender` until called with `None`.
+pub fn capture(sender: Option<Sender<String>>) {
+    *CAPTURE.lock().unwrap() = sender;
+}
+
 /// Chooses which stream log records go to.
 ///
 /// Info goes to stdout and the rest to stderr, unless soar is emitting an
@@ -135,6 +149,13 @@ impl Drop for SuspendingWriter {
         // Remove trailing newline since println adds one
         let output = output.trim_end_matches('\n');
 
+        if let Some(sender) = CAPTURE.lock().unwrap().as_ref() {
+            for line in output.lines() {
+                sender.send(line.to_string()).ok();
+            }
+            return;
+        }
+
         let use_stderr = self.use_stderr;
         let output = output.to_string();
         crate::progress::suspend(|| {
@@ -170,6 +191,9 @@ pub fn setup_logging(args: &Args) {
         Level::INFO
     };
 
+    // The daemon answers on stdout, so nothing else may be written there.
+    let logs_to_stderr = args.json || matches!(args.command, Commands::Serve { .. });
+
     let builder = fmt::Subscriber::builder()
         .with_env_filter(format!("soar={filter_level}"))
         .with_target(false)
@@ -178,7 +202,7 @@ pub fn setup_logging(args: &Args) {
         .with_file(false)
         .with_line_number(false)
         .with_span_events(FmtSpan::NONE)
-        .with_writer(WriterBuilder::new(args.json))
+        .with_writer(WriterBuilder::new(logs_to_stderr))
         .compact()
         .without_time();
 
@@ -7,6 +7,7 @@ use std::{
 
 use apply::apply_packages;
 use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
 use cli::Args;
 use download::{create_regex_patterns, download, DownloadContext};
 use health::{display_health, remove_broken_packages};
@@ -28,10 +29,10 @@ use soar_core::{
     utils::{cleanup_cache, remove_broken_symlinks, setup_required_paths},
     SoarResult,
 };
-use soar_dl::http_client::configure_http_client;
+use soar_dl::{http_client::configure_http_client, throttle::configure_throttle};
 use soar_events::EventSinkHandle;
 use soar_operations::SoarContext;
-use soar_utils::path::resolve_path;
+use soar_utils::path::{resolve_path, set_install_root};
 use tracing::{debug, info, warn};
 use update::update_packages;
 use ureq::{config::IpFamily, Proxy};
@@ -39,9 +40,13 @@ use use_package::use_alternate_package;
 use utils::{progress_enabled, COLOR};
 
 mod apply;
+mod changelog;
 mod cli;
+mod completion;
 mod download;
+mod files;
 mod health;
+mod image;
 mod inspect;
 mod install;
 mod json2db;
@@ -49,10 +54,16 @@ mod json_output;
 mod list;
 mod logging;
 mod plugin_manifest;
+mod profile;
 mod progress;
 mod remove;
 mod repo;
+mod repo_build;
+mod resume;
 mod run;
+mod serve;
+mod shell_hook;
+mod tui;
 mod update;
 mod url_handler;
 #[path = "use.rs"]
@@ -106,6 +117,9 @@ fn answers_with_document(command: &cli::Commands) -> bool {
             | cli::Commands::Search { .. }
             | cli::Commands::Query { .. }
             | cli::Commands::Env
+            | cli::Commands::WhichPackage { .. }
+            | cli::Commands::Files { .. }
+            | cli::Commands::Owns { .. }
             | cli::Commands::Update {
                 check: true,
                 ..
@@ -117,6 +131,9 @@ fn answers_with_document(command: &cli::Commands) -> bool {
             | cli::Commands::Repo {
                 action: cli::RepoAction::List,
             }
+            | cli::Commands::Profile {
+                action: cli::ProfileAction::List | cli::ProfileAction::Show { .. },
+            }
     )
 }
 
@@ -156,6 +173,12 @@ fn handle_system_mode() -> SoarResult<()> {
 }
 
 async fn handle_cli() -> SoarResult<()> {
+    // A shell asking for completions gets them and nothing else, before
+    // anything can write to stdout.
+    CompleteEnv::with_factory(Args::command)
+        .var(completion::COMPLETE_VAR)
+        .complete();
+
     let mut args = env::args().collect::<Vec<_>>();
 
     let mut i = 0;
@@ -199,6 +222,20 @@ async fn handle_cli() -> SoarResult<()> {
         handle_system_mode()?;
     }
 
+    if let Some(ref root) = args.root {
+        // Canonical, so paths found under it through a symlink still match.
+        let root = resolve_path(root)?;
+        let root = fs::canonicalize(&root)
+            .with_context(|| format!("resolving install root {}", root.display()))?;
+        if !root.is_dir() {
+            return Err(SoarError::Custom(format!(
+                "Install root {} is not a directory",
+                root.display()
+            )));
+        }
+        set_install_root(root);
+    }
+
     if let Some(ref c) = args.config {
         {
             let mut config_path = CONFIG_PATH.write().unwrap();
@@ -259,11 +296,45 @@ async fn handle_cli() -> SoarResult<()> {
                 set_current_profile(profile)?;
             }
 
+            let download_settings = get_config().download();
+            let rate_limit = match args.limit_rate {
+                Some(rate) => Some(rate),
+                None => download_settings.rate_limit()?,
+            };
+            configure_throttle(|throttle| {
+                throttle.rate_limit = rate_limit;
+                throttle.per_host = Some(download_settings.per_host_connections());
+            });
+
             setup_required_paths().unwrap();
 
+            // The daemon makes a context per connection, each streaming its
+            // events to whoever asked.
+            if let cli::Commands::Serve {
+                socket,
+            } = command
+            {
+                return serve::serve(socket).await;
+            }
+
+            // The screen draws its own progress from the events, so it takes
+            // them before anything else can.
+            if let cli::Commands::Tui = command {
+                return tui::run().await;
+            }
+
             let (ctx, progress_guard) = create_context_for(answers_with_document(&command));
             let mut run_exit_code = None;
 
+            // The command-not-found hook runs which-package behind every
+            // mistyped command, where a warning about an earlier run is noise.
+            if !matches!(
+                command,
+                cli::Commands::Resume { .. } | cli::Commands::WhichPackage { .. }
+            ) {
+                resume::warn_if_interrupted(&ctx);
+            }
+
             match command {
                 cli::Commands::Install {
                     packages,
@@ -276,6 +347,7 @@ async fn handle_cli() -> SoarResult<()> {
                     portable_cache,
                     no_notes,
                     binary_only,
+                    extract,
                     ask,
                     no_verify,
                     name,
@@ -302,6 +374,7 @@ async fn handle_cli() -> SoarResult<()> {
                         portable_cache,
                         no_notes,
                         binary_only,
+                        extract,
                         ask,
                         no_verify,
                         name,
@@ -321,8 +394,9 @@ async fn handle_cli() -> SoarResult<()> {
                 }
                 cli::Commands::Query {
                     query,
+                    changelog,
                 } => {
-                    query_package(&ctx, query).await?;
+                    query_package(&ctx, query, changelog).await?;
                 }
                 cli::Commands::Remove {
                     packages,
@@ -339,9 +413,10 @@ async fn handle_cli() -> SoarResult<()> {
                     keep,
                     ask,
                     check,
+                    changelog,
                     no_verify,
                 } => {
-                    update_packages(&ctx, packages, keep, ask, check, no_verify).await?;
+                    update_packages(&ctx, packages, keep, ask, check, changelog, no_verify).await?;
                 }
                 cli::Commands::ListInstalledPackages {
                     repo_name,
@@ -349,6 +424,22 @@ async fn handle_cli() -> SoarResult<()> {
                 } => {
                     list_installed_packages(&ctx, repo_name, count).await?;
                 }
+                cli::Commands::Files {
+                    package,
+                } => {
+                    let code = files::list_files(&ctx, &package)?;
+                    if code != 0 {
+                        run_exit_code = Some(code);
+                    }
+                }
+                cli::Commands::Owns {
+                    path,
+                } => {
+                    let code = files::find_owners(&ctx, &path)?;
+                    if code != 0 {
+                        run_exit_code = Some(code);
+                    }
+                }
                 cli::Commands::ListPackages {
                     repo_name,
                 } => {
@@ -363,15 +454,18 @@ async fn handle_cli() -> SoarResult<()> {
                 cli::Commands::Run {
                     yes,
                     no_verify,
+                    portable,
                     command,
                     pkg_id,
                     repo_name,
                 } => {
+                    let portable = portable.map(|p| p.unwrap_or_default());
                     let code = run_package(
                         &ctx,
                         command.as_ref(),
                         yes,
                         no_verify,
+                        portable.as_deref(),
                         repo_name.as_deref(),
                         pkg_id.as_deref(),
                     )
@@ -380,6 +474,19 @@ async fn handle_cli() -> SoarResult<()> {
                         run_exit_code = Some(code);
                     }
                 }
+                cli::Commands::WhichPackage {
+                    command,
+                    not_found,
+                    args,
+                } => {
+                    let code = shell_hook::which_package(&ctx, &command, not_found, args).await?;
+                    if code != 0 {
+                        run_exit_code = Some(code);
+                    }
+                }
+                cli::Commands::ShellHook {
+                    shell,
+                } => shell_hook::print_hook(shell)?,
                 cli::Commands::Use {
                     package_name,
                 } => {
@@ -433,11 +540,20 @@ async fn handle_cli() -> SoarResult<()> {
                     download(context, links, github, gitlab, ghcr).await?;
                 }
                 cli::Commands::Health => display_health(&ctx).await?,
+                cli::Commands::Resume {
+                    rollback,
+                    yes,
+                } => resume::resume_operations(&ctx, rollback, yes).await?,
                 cli::Commands::Repo {
                     action,
                 } => {
                     repo::handle_repo_action(&ctx, action)?;
                 }
+                cli::Commands::Profile {
+                    action,
+                } => {
+                    profile::handle_profile_action(&ctx, action)?;
+                }
                 cli::Commands::PluginManifest => {
                     let profiles: Vec<String> = get_config().profile.keys().cloned().collect();
                     print!("{}", plugin_manifest::manifest(&profiles));
@@ -546,6 +662,11 @@ async fn handle_cli() -> SoarResult<()> {
                 } => {
                     apply_packages(&ctx, prune, dry_run, yes, packages_config, no_verify).await?;
                 }
+                cli::Commands::Image {
+                    action,
+                } => {
+                    image::handle_image_action(&ctx, action).await?;
+                }
                 cli::Commands::DefPackages => {
                     soar_config::packages::generate_default_packages_config()?;
                 }
@@ -559,8 +680,7 @@ async fn handle_cli() -> SoarResult<()> {
                 cli::Commands::Completions {
                     shell,
                 } => {
-                    let mut cmd = cli::Args::command();
-                    clap_complete::generate(shell, &mut cmd, "soar", &mut std::io::stdout());
+                    completion::write_registration(shell)?;
                 }
                 _ => unreachable!(),
             }
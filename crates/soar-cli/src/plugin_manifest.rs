@@ -27,6 +27,15 @@ selector = ["{family}/{name}", "{name}"]
 args = ["--system"]
 elevate = true
 
+# A frontend that would rather keep soar running than start it per action
+# talks JSON-RPC 2.0 to this, one message per line, and hears every event as a
+# notification named `event` carrying the same object the streams below print.
+[daemon]
+args = ["serve"]
+protocol = "jsonrpc"
+notification = "event"
+methods = ["search", "query", "install", "remove", "update", "apply", "health", "sync", "cancel"]
+
 [detect]
 command = "soar"
 version = ["--version"]
@@ -262,6 +271,17 @@ mod tests {
         assert_eq!(parsed["id"].as_str(), Some("soar"));
     }
 
+    #[test]
+    fn the_daemon_runs_a_real_subcommand() {
+        use clap::CommandFactory;
+
+        let parsed: toml::Value = toml::from_str(&manifest(&["default".into()])).unwrap();
+        let subcommand = parsed["daemon"]["args"][0].as_str().unwrap();
+        assert!(crate::cli::Args::command()
+            .get_subcommands()
+            .any(|sub| sub.get_name() == subcommand));
+    }
+
     #[test]
     fn every_operation_names_a_real_subcommand() {
         use clap::CommandFactory;
@@ -0,0 +1,115 @@
+use soar_config::{
+    config::{get_config, get_current_profile, Config},
+    profile::Profile,
+};
+use soar_core::SoarResult;
+use soar_operations::{profile::migrate_package, SoarContext};
+use tracing::{info, warn};
+
+use crate::{
+    cli::ProfileAction,
+    json_output::{self, Listing, ProfileJson},
+    utils::json_enabled,
+};
+
+fn describe(config: &Config, name: &str, profile: &Profile) -> SoarResult<ProfileJson> {
+    Ok(ProfileJson {
+        name: name.to_string(),
+        root: profile.get_root_path()?.display().to_string(),
+        packages: profile.get_packages_path()?.display().to_string(),
+        cache: profile.get_cache_path()?.display().to_string(),
+        default: config.default_profile == name,
+        current: get_current_profile() == name,
+    })
+}
+
+pub fn handle_profile_action(ctx: &SoarContext, action: ProfileAction) -> SoarResult<()> {
+    match action {
+        ProfileAction::Add {
+            name,
+            root_path,
+            packages_path,
+        } => {
+            ctx.add_profile(
+                &name,
+                Profile {
+                    root_path,
+                    packages_path,
+                },
+            )?;
+            info!("Profile '{}' added successfully.", name);
+        }
+        ProfileAction::Remove {
+            name,
+        } => {
+            ctx.remove_profile(&name)?;
+            info!("Profile '{}' removed successfully.", name);
+        }
+        ProfileAction::List => {
+            let config = get_config();
+            let mut names: Vec<&String> = config.profile.keys().collect();
+            names.sort();
+
+            if json_enabled() {
+                let items = names
+                    .iter()
+                    .map(|name| describe(&config, name, &config.profile[*name]))
+                    .collect::<SoarResult<Vec<_>>>()?;
+                let total = items.len();
+                json_output::emit(&Listing::new(items, total));
+                return Ok(());
+            }
+
+            for name in names {
+                let profile = describe(&config, name, &config.profile[name])?;
+                let marker = if profile.default { " (default)" } else { "" };
+                info!("{}{} - {}", profile.name, marker, profile.root);
+            }
+        }
+        ProfileAction::Show {
+            name,
+        } => {
+            let config = get_config();
+            let name = name.unwrap_or_else(get_current_profile);
+            let profile = describe(&config, &name, config.get_profile(&name)?)?;
+
+            if json_enabled() {
+                json_output::emit(&profile);
+                return Ok(());
+            }
+
+            info!("Profile: {}", profile.name);
+            info!("Default: {}", profile.default);
+            info!("Root: {}", profile.root);
+            info!("Packages: {}", profile.packages);
+            info!("Cache: {}", profile.cache);
+        }
+        ProfileAction::SetDefault {
+            name,
+        } => {
+            ctx.set_default_profile(&name)?;
+            info!("Profile '{}' is now the default.", name);
+            // The bin directory and databases live under the default profile,
+            // so packages installed before are no longer on PATH from here.
+            warn!(
+                "The bin directory and databases now come from '{}'; \
+                 run `soar env` to see the new paths.",
+                name
+            );
+        }
+        ProfileAction::Migrate {
+            package,
+            to,
+        } => {
+            let migrated = migrate_package(ctx, &package, &to)?;
+            info!(
+                "Moved {} to profile '{}': {} -> {}",
+                migrated.package.pkg_name,
+                to,
+                migrated.from.display(),
+                migrated.to.display()
+            );
+        }
+    }
+    Ok(())
+}
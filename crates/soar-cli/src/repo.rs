@@ -6,6 +6,7 @@ use tracing::info;
 use crate::{
     cli::RepoAction,
     json_output::{self, Listing, RepositoryJson},
+    repo_build::{build_repo, generate_key, sign_files, BuildOptions},
     utils::json_enabled,
 };
 
@@ -19,6 +20,7 @@ pub fn handle_repo_action(ctx: &SoarContext, action: RepoAction) -> SoarResult<(
             desktop_integration,
             signature_verification,
             sync_interval,
+            min_release_age,
         } => {
             ctx.add_repository(Repository {
                 name: name.clone(),
@@ -28,6 +30,7 @@ pub fn handle_repo_action(ctx: &SoarContext, action: RepoAction) -> SoarResult<(
                 desktop_integration,
                 signature_verification,
                 sync_interval,
+                min_release_age,
             })?;
             info!("Repository '{}' added successfully.", name);
         }
@@ -39,6 +42,7 @@ pub fn handle_repo_action(ctx: &SoarContext, action: RepoAction) -> SoarResult<(
             desktop_integration,
             signature_verification,
             sync_interval,
+            min_release_age,
         } => {
             ctx.update_repository(
                 &name,
@@ -49,6 +53,7 @@ pub fn handle_repo_action(ctx: &SoarContext, action: RepoAction) -> SoarResult<(
                     desktop_integration,
                     signature_verification,
                     sync_interval,
+                    min_release_age,
                 },
             )?;
             info!("Repository '{}' updated successfully.", name);
@@ -82,6 +87,29 @@ pub fn handle_repo_action(ctx: &SoarContext, action: RepoAction) -> SoarResult<(
                 }
             }
         }
+        RepoAction::Build {
+            source,
+            output,
+            base_url,
+            name,
+            key,
+        } => {
+            build_repo(BuildOptions {
+                source,
+                output,
+                base_url,
+                name,
+                key,
+            })?;
+        }
+        RepoAction::Sign {
+            files,
+            key,
+        } => sign_files(&files, &key)?,
+        RepoAction::Keygen {
+            path,
+            force,
+        } => generate_key(&path, force)?,
     }
     Ok(())
 }
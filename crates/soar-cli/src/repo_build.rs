@@ -0,0 +1,521 @@
+//! The publishing side of a repository: `soar repo build`, `soar repo sign`
+//! and `soar repo keygen`.
+//!
+//! A build turns a directory of artifacts, or a manifest describing them, into
+//! everything a client syncs and installs from: a versioned `index.json`, the
+//! same index as a zstd-compressed `metadata.db.zst`, a zsync control file and
+//! the extracted icon and desktop file beside each artifact, and minisign
+//! signatures over all of it when given a key.
+
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    os::unix::fs::OpenOptionsExt as _,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use soar_core::{
+    error::{ErrorContext, SoarError},
+    package::url::parse_filename,
+    SoarResult,
+};
+use soar_package::{extract_assets, get_file_type, PackageFormat};
+use soar_registry::{RemotePackage, SigningKey, SUPPORTED_FORMAT};
+use tracing::{info, warn};
+
+use crate::json2db::json_to_db;
+
+const INDEX_FILE: &str = "index.json";
+const METADATA_FILE: &str = "metadata.db.zst";
+
+/// What `soar repo build` was asked to do.
+pub struct BuildOptions {
+    pub source: PathBuf,
+    pub output: Option<PathBuf>,
+    pub base_url: Option<String>,
+    pub name: Option<String>,
+    pub key: Option<PathBuf>,
+}
+
+/// A repository described by hand, for artifacts whose file names do not say
+/// what they are or that need more than a name and version.
+///
+/// Each package names its artifact with `file`, relative to the manifest;
+/// every other key is an index field and is published as written.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    name: Option<String>,
+    base_url: Option<String>,
+    #[serde(default)]
+    packages: Vec<ManifestEntry>,
+}
+
+#[derive(Deserialize)]
+struct ManifestEntry {
+    file: PathBuf,
+    #[serde(flatten)]
+    fields: Map<String, Value>,
+}
+
+/// An artifact to publish, with whatever is known about it up front.
+struct Artifact {
+    path: PathBuf,
+    fields: Map<String, Value>,
+}
+
+fn registry_error(err: soar_registry::RegistryError) -> SoarError {
+    SoarError::Custom(err.to_string())
+}
+
+pub fn build_repo(options: BuildOptions) -> SoarResult<()> {
+    let key = options
+        .key
+        .as_deref()
+        .map(SigningKey::from_file)
+        .transpose()
+        .map_err(registry_error)?;
+
+    let (artifacts, manifest, source_dir) = if options.source.is_dir() {
+        let artifacts = scan_dir(&options.source, options.key.as_deref())?;
+        (artifacts, None, options.source.clone())
+    } else {
+        let (artifacts, manifest) = read_manifest(&options.source)?;
+        (artifacts, Some(manifest), parent_dir(&options.source))
+    };
+
+    let output = options.output.unwrap_or(source_dir);
+    fs::create_dir_all(&output)
+        .with_context(|| format!("creating output directory {}", output.display()))?;
+    let output = output
+        .canonicalize()
+        .with_context(|| format!("resolving output directory {}", output.display()))?;
+
+    let (manifest_name, manifest_base_url) =
+        manifest.map(|m| (m.name, m.base_url)).unwrap_or_default();
+    // Without a URL to serve from, artifacts are named relative to the index,
+    // which a client resolves against wherever it finds the repository.
+    let base_url = options.base_url.or(manifest_base_url);
+    let base_url = base_url.as_deref().map(|url| url.trim_end_matches('/'));
+    let name = options
+        .name
+        .or(manifest_name)
+        .unwrap_or_else(|| "local".to_string());
+
+    if artifacts.is_empty() {
+        return Err(SoarError::Custom(
+            "no artifacts to publish; name them <name>-<version>.<ext> or list them in a \
+             manifest"
+                .into(),
+        ));
+    }
+
+    let mut packages = Vec::with_capacity(artifacts.len());
+    for artifact in artifacts {
+        packages.push(publish_artifact(artifact, &output, base_url, key.as_ref())?);
+    }
+
+    let index = serde_json::json!({
+        "format": SUPPORTED_FORMAT,
+        "packages": packages,
+    });
+    let index_path = output.join(INDEX_FILE);
+    let index = serde_json::to_vec_pretty(&index)
+        .map_err(|err| SoarError::Custom(format!("serializing the index: {err}")))?;
+    fs::write(&index_path, index).with_context(|| format!("writing {}", index_path.display()))?;
+
+    let metadata_path = output.join(METADATA_FILE);
+    write_compressed_db(&index_path, &metadata_path, &name)?;
+
+    if let Some(ref key) = key {
+        for path in [&index_path, &metadata_path] {
+            key.sign_file(path).map_err(registry_error)?;
+        }
+    }
+
+    info!(
+        count = packages.len(),
+        output = %output.display(),
+        "Built repository '{name}' with {} packages in {}",
+        packages.len(),
+        output.display()
+    );
+    if key.is_none() {
+        warn!("the repository is unsigned; pass --key to sign it");
+    }
+    Ok(())
+}
+
+/// Whether `file_name` in `dir` is something an earlier build wrote there:
+/// the index and its database, a signature or zsync file, or an icon or
+/// desktop file extracted beside the artifact it was named after.
+///
+/// Only those are passed over. An artifact may well be a `.json` or a
+/// `.tar.zst` itself.
+fn is_generated(dir: &Path, file_name: &str) -> bool {
+    if file_name == INDEX_FILE || file_name == METADATA_FILE {
+        return true;
+    }
+    match file_name.rsplit_once('.') {
+        Some((_, "sig" | "zsync")) => true,
+        Some((artifact, "png" | "svg" | "xpm" | "desktop")) => dir.join(artifact).is_file(),
+        _ => false,
+    }
+}
+
+/// Every artifact in `dir` whose file name gives its name and version.
+///
+/// The signing key, and its public half, are never published, wherever they
+/// are kept.
+fn scan_dir(dir: &Path, key: Option<&Path>) -> SoarResult<Vec<Artifact>> {
+    let key_files: Vec<PathBuf> = key
+        .into_iter()
+        .flat_map(|key| {
+            let mut public = key.as_os_str().to_os_string();
+            public.push(".pub");
+            [key.to_path_buf(), PathBuf::from(public)]
+        })
+        .filter_map(|path| path.canonicalize().ok())
+        .collect();
+
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("reading directory {}", dir.display()))?;
+
+    let mut artifacts = Vec::new();
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("reading entry from directory {}", dir.display()))?
+            .path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if file_name.starts_with('.') || is_generated(dir, file_name) || !path.is_file() {
+            continue;
+        }
+        if path
+            .canonicalize()
+            .is_ok_and(|path| key_files.contains(&path))
+        {
+            continue;
+        }
+
+        let (pkg_name, version) = parse_filename(file_name);
+        if version == "unknown" {
+            warn!(
+                "skipping {file_name}: its name gives no version; name it \
+                 <name>-<version>.<ext> or list it in a manifest"
+            );
+            continue;
+        }
+
+        let mut fields = Map::new();
+        fields.insert("pkg_name".into(), pkg_name.into());
+        fields.insert("version".into(), version.into());
+        artifacts.push(Artifact {
+            path,
+            fields,
+        });
+    }
+
+    // Directory order is arbitrary; an index that reorders itself between
+    // builds makes every rebuild look like a change.
+    artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(artifacts)
+}
+
+fn read_manifest(path: &Path) -> SoarResult<(Vec<Artifact>, Manifest)> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("reading manifest {}", path.display()))?;
+    let mut manifest: Manifest = toml::from_str(&content)
+        .map_err(|err| SoarError::Custom(format!("parsing {}: {err}", path.display())))?;
+    let dir = parent_dir(path);
+
+    let artifacts = std::mem::take(&mut manifest.packages)
+        .into_iter()
+        .map(|entry| {
+            Artifact {
+                path: dir.join(entry.file),
+                fields: entry.fields,
+            }
+        })
+        .collect();
+    Ok((artifacts, manifest))
+}
+
+/// The directory holding `path`, which for a bare file name is the current one.
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Put `artifact` and everything published beside it into `output`, and
+/// return its index entry.
+fn publish_artifact(
+    artifact: Artifact,
+    output: &Path,
+    base_url: Option<&str>,
+    key: Option<&SigningKey>,
+) -> SoarResult<Value> {
+    let source = &artifact.path;
+    let file_name = source
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| SoarError::Custom(format!("{} has no file name", source.display())))?
+        .to_string();
+
+    let published = output.join(&file_name);
+    let source = source
+        .canonicalize()
+        .with_context(|| format!("reading artifact {}", source.display()))?;
+    if source != published {
+        fs::copy(&source, &published)
+            .with_context(|| format!("copying {} to {}", source.display(), published.display()))?;
+    }
+
+    let format = {
+        let file =
+            File::open(&published).with_context(|| format!("opening {}", published.display()))?;
+        get_file_type(&mut BufReader::new(file))?
+    };
+    let assets = extract_assets(&published, format, output, &file_name)?;
+
+    soar_dl::zsync::write_control_file(&published, &output.join(format!("{file_name}.zsync")))?;
+    if let Some(key) = key {
+        key.sign_file(&published).map_err(registry_error)?;
+    }
+
+    let url_of = |path: &Path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        match base_url {
+            Some(base_url) => format!("{base_url}/{name}"),
+            None => name.into_owned(),
+        }
+    };
+
+    let mut fields = artifact.fields;
+    // The artifact's own checksum and size are facts of the build, whatever
+    // a manifest says.
+    fields.insert(
+        "bsum".into(),
+        soar_utils::hash::calculate_checksum(&published)?.into(),
+    );
+    fields.insert(
+        "size".into(),
+        fs::metadata(&published)
+            .with_context(|| format!("reading metadata of {}", published.display()))?
+            .len()
+            .into(),
+    );
+    let mut fill = |key: &str, value: Option<String>| {
+        if let Some(value) = value {
+            fields.entry(key).or_insert(value.into());
+        }
+    };
+    fill("download_url", Some(url_of(&published)));
+    fill("pkg_type", pkg_type(format).map(String::from));
+    fill("icon", assets.icon.as_deref().map(url_of));
+    fill("desktop", assets.desktop.as_deref().map(url_of));
+    fill(
+        "description",
+        Some(
+            assets
+                .desktop
+                .as_deref()
+                .and_then(|path| fs::read_to_string(path).ok())
+                .and_then(|content| desktop_comment(&content))
+                .unwrap_or_default(),
+        ),
+    );
+
+    // Read back as a package, so an entry the client would reject is caught
+    // here rather than when someone syncs.
+    let package: RemotePackage = serde_json::from_value(Value::Object(fields))
+        .map_err(|err| SoarError::Custom(format!("index entry for {file_name}: {err}")))?;
+    info!(
+        "Published {} {} ({file_name})",
+        package.pkg_name, package.version
+    );
+
+    let mut entry = serde_json::to_value(&package)
+        .map_err(|err| SoarError::Custom(format!("index entry for {file_name}: {err}")))?;
+    if let Value::Object(ref mut map) = entry {
+        map.retain(|_, value| !value.is_null());
+    }
+    Ok(entry)
+}
+
+/// The `pkg_type` the index records for a detected format.
+fn pkg_type(format: PackageFormat) -> Option<&'static str> {
+    match format {
+        PackageFormat::AppImage => Some("appimage"),
+        PackageFormat::FlatImage => Some("flatimage"),
+        PackageFormat::RunImage => Some("runimage"),
+        PackageFormat::Wrappe => Some("wrappe"),
+        PackageFormat::Onelf => Some("onelf"),
+        PackageFormat::ELF => Some("static"),
+        PackageFormat::Unknown => None,
+    }
+}
+
+/// The `Comment` of a desktop file's main entry, which makes a serviceable
+/// description when nothing better is given.
+fn desktop_comment(content: &str) -> Option<String> {
+    content
+        .lines()
+        .skip_while(|line| line.trim() != "[Desktop Entry]")
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .find_map(|line| line.strip_prefix("Comment="))
+        .map(|comment| comment.trim().to_string())
+        .filter(|comment| !comment.is_empty())
+}
+
+/// Import the index into a metadata database and compress it, the form a
+/// client syncs fastest.
+fn write_compressed_db(index_path: &Path, output: &Path, repo_name: &str) -> SoarResult<()> {
+    let mut db_name = output.file_name().unwrap_or_default().to_os_string();
+    db_name.push(format!(".{}.db", std::process::id()));
+    let db_path = output.with_file_name(db_name);
+
+    json_to_db(
+        &index_path.to_string_lossy(),
+        &db_path.to_string_lossy(),
+        Some(repo_name),
+    )?;
+    let result = (|| {
+        let db = File::open(&db_path)
+            .with_context(|| format!("opening database {}", db_path.display()))?;
+        let out = File::create(output).with_context(|| format!("creating {}", output.display()))?;
+        zstd::stream::copy_encode(db, out, 19)
+            .with_context(|| format!("compressing database into {}", output.display()))
+    })();
+    fs::remove_file(&db_path).ok();
+    result
+}
+
+/// Sign each of `files`, writing `<file>.sig` beside it.
+pub fn sign_files(files: &[PathBuf], key: &Path) -> SoarResult<()> {
+    let key = SigningKey::from_file(key).map_err(registry_error)?;
+    for file in files {
+        let sig = key.sign_file(file).map_err(registry_error)?;
+        info!("Signed {} ({})", file.display(), sig.display());
+    }
+    Ok(())
+}
+
+/// Write a new key pair to `path` and `<path>.pub`.
+pub fn generate_key(path: &Path, force: bool) -> SoarResult<()> {
+    let mut pub_path = path.as_os_str().to_os_string();
+    pub_path.push(".pub");
+    let pub_path = PathBuf::from(pub_path);
+    if !force {
+        if let Some(existing) = [path, &pub_path].into_iter().find(|p| p.exists()) {
+            return Err(SoarError::Custom(format!(
+                "{} already exists; pass --force to replace it",
+                existing.display()
+            )));
+        }
+    }
+
+    let key = SigningKey::generate().map_err(registry_error)?;
+    // The secret key is readable by its owner alone from the moment it exists.
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("creating {}", path.display()))?;
+    std::io::Write::write_all(&mut file, key.encode().as_bytes())
+        .with_context(|| format!("writing {}", path.display()))?;
+    fs::write(&pub_path, key.public_key_file())
+        .with_context(|| format!("writing {}", pub_path.display()))?;
+
+    info!(
+        "Wrote secret key to {} and public key to {}",
+        path.display(),
+        pub_path.display()
+    );
+    info!("Public key: {}", key.public_key());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desktop_comment_is_read_from_the_main_entry() {
+        let desktop = "[Desktop Entry]\nName=App\nComment=Does things\n\n\
+                       [Desktop Action new]\nComment=Other\n";
+        assert_eq!(desktop_comment(desktop).as_deref(), Some("Does things"));
+
+        let desktop = "[Desktop Entry]\nName=App\n[Desktop Action new]\nComment=Other\n";
+        assert_eq!(desktop_comment(desktop), None);
+    }
+
+    #[test]
+    fn manifest_fields_pass_through_beside_the_file() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            base_url = "https://example.com/repo"
+
+            [[packages]]
+            file = "bin/tool"
+            pkg_name = "tool"
+            version = "1.2.0"
+            homepages = ["https://example.com"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.base_url.as_deref(),
+            Some("https://example.com/repo")
+        );
+        let entry = &manifest.packages[0];
+        assert_eq!(entry.file, PathBuf::from("bin/tool"));
+        assert_eq!(entry.fields["version"], "1.2.0");
+        assert!(!entry.fields.contains_key("file"));
+    }
+
+    #[test]
+    fn only_what_a_build_writes_is_passed_over() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "tool-1.0.tar.zst",
+            "config-2.1.json",
+            "logo-3.0.png",
+            "app-1.2.AppImage",
+            "app-1.2.AppImage.png",
+            "app-1.2.AppImage.desktop",
+            "app-1.2.AppImage.zsync",
+            "app-1.2.AppImage.sig",
+            "index.json",
+            "index.json.sig",
+            "metadata.db.zst",
+            "repo-1.0.key",
+            "repo-1.0.key.pub",
+        ] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+
+        let artifacts = scan_dir(dir.path(), Some(&dir.path().join("repo-1.0.key"))).unwrap();
+        let names: Vec<_> = artifacts
+            .iter()
+            .map(|a| a.path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "app-1.2.AppImage",
+                "config-2.1.json",
+                "logo-3.0.png",
+                "tool-1.0.tar.zst"
+            ]
+        );
+    }
+}
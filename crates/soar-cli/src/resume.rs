@@ -0,0 +1,76 @@
+use nu_ansi_term::Color::{Blue, Cyan, Green, Yellow};
+use soar_core::SoarResult;
+use soar_operations::{journal, SoarContext};
+use tracing::{error, info, warn};
+
+use crate::utils::{confirm_action, Colored};
+
+/// Finish or roll back what an interrupted operation left half done.
+pub async fn resume_operations(ctx: &SoarContext, rollback: bool, yes: bool) -> SoarResult<()> {
+    let entries = journal::unfinished(ctx)?;
+    if entries.is_empty() {
+        info!("Nothing was interrupted.");
+        return Ok(());
+    }
+
+    info!(
+        "{} package(s) were interrupted:",
+        Colored(Cyan, entries.len())
+    );
+    for entry in &entries {
+        info!(
+            "  - {}:{} ({}) {} at {}, started {}",
+            Colored(Blue, &entry.pkg_name),
+            Colored(Green, &entry.repo_name),
+            entry.version,
+            entry.operation,
+            Colored(Yellow, &entry.step),
+            entry.started_at,
+        );
+    }
+
+    let question = if rollback {
+        "Roll them back?"
+    } else {
+        "Finish them?"
+    };
+    if !yes && !confirm_action(question)? {
+        info!("Left as they were");
+        return Ok(());
+    }
+
+    let report = journal::resume(ctx, rollback).await?;
+
+    for installed in &report.finished {
+        info!(
+            "Finished {}:{} ({})",
+            installed.pkg_name, installed.repo_name, installed.version
+        );
+    }
+    for name in &report.already_complete {
+        info!("{name} had already finished");
+    }
+    for rolled_back in &report.rolled_back {
+        info!(
+            "Rolled back {}:{} ({}), interrupted at {}",
+            rolled_back.pkg_name, rolled_back.repo_name, rolled_back.version, rolled_back.step
+        );
+    }
+    for failed in &report.failed {
+        error!("Could not finish {}: {}", failed.pkg_name, failed.error);
+    }
+
+    Ok(())
+}
+
+/// Say so when a previous run died part-way, since nothing else will.
+pub fn warn_if_interrupted(ctx: &SoarContext) {
+    match journal::unfinished_count(ctx) {
+        Ok(0) | Err(_) => {}
+        Ok(count) => {
+            warn!(
+                "{count} package(s) were left unfinished by an interrupted operation. Run `soar resume` to finish them, or `soar resume --rollback` to undo them."
+            );
+        }
+    }
+}
@@ -8,6 +8,7 @@ pub async fn run_package(
     command: &[String],
     yes: bool,
     no_verify: bool,
+    portable: Option<&str>,
     repo_name: Option<&str>,
     pkg_id: Option<&str>,
 ) -> SoarResult<i32> {
@@ -18,15 +19,19 @@ pub async fn run_package(
         &[]
     };
 
-    let result = run::prepare_run(ctx, package_name, repo_name, pkg_id, no_verify).await?;
+    let result =
+        run::prepare_run(ctx, package_name, repo_name, pkg_id, no_verify, portable).await?;
 
     let downloaded;
+    let dirs;
     let output_path = match result {
         PrepareRunResult::Ready {
             path,
             downloaded: d,
+            portable,
         } => {
             downloaded = d;
+            dirs = portable;
             path
         }
         PrepareRunResult::Ambiguous(amb) => {
@@ -57,6 +62,7 @@ pub async fn run_package(
                 Some(&pkg.repo_name),
                 pkg.pkg_id.as_deref(),
                 no_verify,
+                portable,
             )
             .await?;
 
@@ -64,8 +70,10 @@ pub async fn run_package(
                 PrepareRunResult::Ready {
                     path,
                     downloaded: d,
+                    portable,
                 } => {
                     downloaded = d;
+                    dirs = portable;
                     path
                 }
                 _ => return Ok(0),
@@ -79,7 +87,7 @@ pub async fn run_package(
         eprintln!();
     }
 
-    let run_result = run::execute_binary(&output_path, args)?;
+    let run_result = run::execute_binary(&output_path, args, &dirs)?;
 
     Ok(run_result.exit_code)
 }
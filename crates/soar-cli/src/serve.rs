@@ -0,0 +1,686 @@
+//! `soar serve`: the operations a frontend drives, answered over JSON-RPC.
+//!
+//! Shelling out costs a process, and a reload of every metadata database, per
+//! action. A frontend that keeps this running asks over one connection instead
+//! and hears every event as a notification while the work happens.
+//!
+//! Requests and responses are JSON-RPC 2.0, one message per line, on stdio or
+//! a Unix socket. Each request runs as its own task, so a long install does not
+//! hold up a search, and `cancel` names the request to abandon.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use soar_config::{config::get_config, packages::PackagesConfig};
+use soar_core::{
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+use soar_events::{EventSinkHandle, JsonRpcSink};
+use soar_operations::{
+    apply, health, install, remove, search, update, InstallOptions, RemoveResolveResult,
+    ResolveResult, SoarContext,
+};
+use tokio::{
+    sync::mpsc,
+    task::{AbortHandle, JoinSet},
+};
+use tracing::{debug, info, warn};
+
+use crate::json_output::{
+    AmbiguousJson, ApplyDiffJson, ApplyReportJson, CandidateJson, FailedJson, HealthJson,
+    InstalledInfoJson, Listing, PackageDetailJson, PackageJson, RemovedJson, UpdateCheckJson,
+};
+
+/// The stream responses and notifications share.
+type SharedWriter = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Requests still running, by id, so `cancel` can find them.
+type InFlight = Arc<Mutex<HashMap<String, AbortHandle>>>;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const OPERATION_FAILED: i64 = -32000;
+/// The code LSP uses for the same thing, which a frontend may already know.
+const REQUEST_CANCELLED: i64 = -32800;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    jsonrpc: Option<String>,
+    /// Absent for a notification, which is run but never answered.
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<SoarError> for RpcError {
+    fn from(err: SoarError) -> Self {
+        Self::new(OPERATION_FAILED, err.to_string())
+    }
+}
+
+type RpcResult = Result<Value, RpcError>;
+
+/// Serve until the input closes: stdio when `socket` is `None`, otherwise
+/// every connection made to the socket.
+pub async fn serve(socket: Option<String>) -> SoarResult<()> {
+    match socket {
+        Some(path) => serve_socket(Path::new(&path)).await,
+        None => {
+            let writer: SharedWriter = Arc::new(Mutex::new(Box::new(std::io::stdout())));
+            serve_connection(std::io::stdin(), writer).await;
+            Ok(())
+        }
+    }
+}
+
+async fn serve_socket(path: &Path) -> SoarResult<()> {
+    // A socket file left by a daemon that died is in the way of binding. One
+    // that still answers belongs to a daemon that is running, and taking it
+    // over would strand that daemon's clients.
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(SoarError::Custom(format!(
+                "another soar is already serving on {}",
+                path.display()
+            )));
+        }
+        fs::remove_file(path)
+            .with_context(|| format!("removing stale socket {}", path.display()))?;
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
+    }
+
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("binding {}", path.display()))?;
+    // Whoever can connect can install and remove packages as this user.
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("restricting {}", path.display()))?;
+    info!("Listening on {}", path.display());
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if tx.send(stream).is_err() {
+                        break;
+                    }
+                }
+                Err(err) => warn!("accepting connection: {err}"),
+            }
+        }
+    });
+
+    while let Some(stream) = rx.recv().await {
+        let writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(err) => {
+                warn!("cloning connection: {err}");
+                continue;
+            }
+        };
+        let writer: SharedWriter = Arc::new(Mutex::new(Box::new(writer)));
+        tokio::spawn(serve_connection(stream, writer));
+    }
+
+    Ok(())
+}
+
+/// Answer one client until it closes its end.
+///
+/// A connection gets its own context, so its events go to it alone, and keeps
+/// it for as long as it stays open, so the metadata is loaded once.
+async fn serve_connection<R: Read + Send + 'static>(reader: R, writer: SharedWriter) {
+    let events: EventSinkHandle = Arc::new(JsonRpcSink::new(writer.clone()));
+    let ctx = SoarContext::new(get_config(), events);
+    let in_flight: InFlight = Arc::default();
+    let mut tasks = JoinSet::new();
+
+    let mut lines = read_lines(reader);
+    while let Some(line) = lines.recv().await {
+        // Finished tasks are only collected when asked for.
+        while tasks.try_join_next().is_some() {}
+
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                respond(
+                    &writer,
+                    Value::Null,
+                    Err(RpcError::new(PARSE_ERROR, err.to_string())),
+                );
+                continue;
+            }
+        };
+        let id = request.id.clone().unwrap_or(Value::Null);
+        if request.jsonrpc.as_deref() != Some("2.0") {
+            respond(
+                &writer,
+                id,
+                Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"")),
+            );
+            continue;
+        }
+        debug!(method = request.method, id = %id, "received request");
+
+        if request.method == "cancel" {
+            let result = cancel(&in_flight, &writer, request.params);
+            if request.id.is_some() {
+                respond(&writer, id, result);
+            }
+            continue;
+        }
+
+        let key = id.to_string();
+        let task_key = key.clone();
+        let answers = request.id.is_some();
+        let ctx = ctx.clone();
+        let task_writer = writer.clone();
+        let task_in_flight = in_flight.clone();
+
+        // Registered under the lock the task takes to unregister itself, so a
+        // request that finishes at once still finds its own entry.
+        let mut running = in_flight.lock().unwrap();
+        if answers && running.contains_key(&key) {
+            drop(running);
+            respond(
+                &writer,
+                id,
+                Err(RpcError::new(
+                    INVALID_REQUEST,
+                    "a request with this id is still running",
+                )),
+            );
+            continue;
+        }
+        let handle = tasks.spawn(async move {
+            let result = dispatch(&ctx, &request.method, request.params).await;
+            // Only a request still registered answers: one that was cancelled
+            // has been answered by the cancellation.
+            let registered = task_in_flight.lock().unwrap().remove(&task_key).is_some();
+            if answers && registered {
+                respond(&task_writer, id, result);
+            }
+        });
+        if answers {
+            running.insert(key, handle);
+        }
+    }
+
+    // The client stopped asking, but what it already asked for is finished
+    // rather than left half done.
+    while tasks.join_next().await.is_some() {}
+}
+
+/// Read lines on a thread of their own, since stdin and a Unix stream both
+/// block.
+fn read_lines<R: Read + Send + 'static>(reader: R) -> mpsc::UnboundedReceiver<String> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+fn respond(writer: &SharedWriter, id: Value, result: RpcResult) {
+    let message = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    };
+    let Ok(mut writer) = writer.lock() else {
+        return;
+    };
+    let _ = writeln!(writer, "{message}");
+    let _ = writer.flush();
+}
+
+#[derive(Deserialize)]
+struct CancelParams {
+    id: Value,
+}
+
+/// Abandon a running request, answering it as cancelled.
+///
+/// The task stops at its next await. A download already under way is dropped
+/// with it, and the install it belonged to is left pending, which the next
+/// install of the same version resumes.
+fn cancel(in_flight: &InFlight, writer: &SharedWriter, params: Value) -> RpcResult {
+    let params: CancelParams = parse(params)?;
+    let Some(handle) = in_flight.lock().unwrap().remove(&params.id.to_string()) else {
+        return Ok(Value::Bool(false));
+    };
+    handle.abort();
+    respond(
+        writer,
+        params.id,
+        Err(RpcError::new(REQUEST_CANCELLED, "request cancelled")),
+    );
+    Ok(Value::Bool(true))
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    // Omitted params mean every option at its default.
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+fn to_value<T: Serialize>(value: T) -> RpcResult {
+    serde_json::to_value(value).map_err(|err| RpcError::new(OPERATION_FAILED, err.to_string()))
+}
+
+async fn dispatch(ctx: &SoarContext, method: &str, params: Value) -> RpcResult {
+    match method {
+        "search" => search_method(ctx, parse(params)?).await,
+        "query" => query_method(ctx, parse(params)?).await,
+        "install" => install_method(ctx, parse(params)?).await,
+        "remove" => remove_method(ctx, parse(params)?).await,
+        "update" => update_method(ctx, parse(params)?).await,
+        "apply" => apply_method(ctx, parse(params)?).await,
+        "health" => to_value(HealthJson::from(&health::check_health(ctx)?)),
+        "sync" => {
+            ctx.sync().await?;
+            Ok(Value::Null)
+        }
+        other => {
+            Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("no method named {other}"),
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    query: String,
+    #[serde(default)]
+    case_sensitive: bool,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+async fn search_method(ctx: &SoarContext, params: SearchParams) -> RpcResult {
+    let result =
+        search::search_packages(ctx, &params.query, params.case_sensitive, params.limit).await?;
+    let items: Vec<PackageJson> = result.packages.iter().map(Into::into).collect();
+    to_value(Listing::new(items, result.total_count))
+}
+
+#[derive(Deserialize)]
+struct QueryParams {
+    query: String,
+}
+
+async fn query_method(ctx: &SoarContext, params: QueryParams) -> RpcResult {
+    let packages = search::query_package(ctx, &params.query).await?;
+    let items: Vec<PackageDetailJson> = packages.iter().map(Into::into).collect();
+    let total = items.len();
+    to_value(Listing::new(items, total))
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct InstallParams {
+    packages: Vec<String>,
+    force: bool,
+    binary_only: bool,
+    no_verify: bool,
+    extract: bool,
+    portable: Option<String>,
+    portable_home: Option<String>,
+    portable_config: Option<String>,
+    portable_share: Option<String>,
+    portable_cache: Option<String>,
+}
+
+#[derive(Serialize)]
+struct InstallResultJson {
+    installed: Vec<InstalledInfoJson>,
+    failed: Vec<FailedJson>,
+    warnings: Vec<String>,
+    not_found: Vec<String>,
+    already_installed: Vec<String>,
+    /// Queries that were not installed because they could mean several
+    /// packages. Nobody is there to choose, so the caller asks again.
+    ambiguous: Vec<AmbiguousJson>,
+}
+
+async fn install_method(ctx: &SoarContext, params: InstallParams) -> RpcResult {
+    let options = InstallOptions {
+        force: params.force,
+        portable: params.portable,
+        portable_home: params.portable_home,
+        portable_config: params.portable_config,
+        portable_share: params.portable_share,
+        portable_cache: params.portable_cache,
+        binary_only: params.binary_only,
+        no_verify: params.no_verify,
+        extract: params.extract,
+        ..Default::default()
+    };
+
+    let mut targets = Vec::new();
+    let mut not_found = Vec::new();
+    let mut already_installed = Vec::new();
+    let mut ambiguous = Vec::new();
+    for result in install::resolve_packages(ctx, &params.packages, &options).await? {
+        match result {
+            ResolveResult::Resolved(resolved) => targets.extend(resolved),
+            ResolveResult::Ambiguous(amb) => {
+                ambiguous.push(AmbiguousJson {
+                    query: amb.query,
+                    candidates: amb.candidates.iter().map(CandidateJson::from).collect(),
+                })
+            }
+            ResolveResult::NotFound(name) => not_found.push(name),
+            ResolveResult::AlreadyInstalled {
+                pkg_name, ..
+            } => already_installed.push(pkg_name),
+        }
+    }
+
+    let report = if targets.is_empty() {
+        None
+    } else {
+        Some(install::perform_installation(ctx, targets, &options).await?)
+    };
+
+    to_value(InstallResultJson {
+        installed: report
+            .as_ref()
+            .map(|r| r.installed.iter().map(Into::into).collect())
+            .unwrap_or_default(),
+        failed: report
+            .as_ref()
+            .map(|r| r.failed.iter().map(Into::into).collect())
+            .unwrap_or_default(),
+        warnings: report.map(|r| r.warnings).unwrap_or_default(),
+        not_found,
+        already_installed,
+        ambiguous,
+    })
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RemoveParams {
+    packages: Vec<String>,
+    all: bool,
+}
+
+#[derive(Serialize)]
+struct RemoveResultJson {
+    removed: Vec<RemovedJson>,
+    failed: Vec<FailedJson>,
+    not_installed: Vec<String>,
+    ambiguous: Vec<AmbiguousJson>,
+}
+
+async fn remove_method(ctx: &SoarContext, params: RemoveParams) -> RpcResult {
+    let mut targets = Vec::new();
+    let mut not_installed = Vec::new();
+    let mut ambiguous = Vec::new();
+    for result in remove::resolve_removals(ctx, &params.packages, params.all)? {
+        match result {
+            RemoveResolveResult::Resolved(resolved) => targets.extend(resolved),
+            RemoveResolveResult::Ambiguous {
+                query,
+                candidates,
+            } => {
+                ambiguous.push(AmbiguousJson {
+                    query,
+                    candidates: candidates.iter().map(CandidateJson::from).collect(),
+                })
+            }
+            RemoveResolveResult::NotInstalled(name) => not_installed.push(name),
+        }
+    }
+
+    let report = remove::perform_removal(ctx, targets).await?;
+    to_value(RemoveResultJson {
+        removed: report.removed.iter().map(Into::into).collect(),
+        failed: report.failed.iter().map(Into::into).collect(),
+        not_installed,
+        ambiguous,
+    })
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct UpdateParams {
+    /// Every updatable package when omitted.
+    packages: Option<Vec<String>>,
+    /// Report what would be updated and stop there.
+    check: bool,
+    keep: bool,
+    no_verify: bool,
+}
+
+#[derive(Serialize)]
+struct UpdateResultJson {
+    updated: Vec<InstalledInfoJson>,
+    failed: Vec<FailedJson>,
+}
+
+async fn update_method(ctx: &SoarContext, params: UpdateParams) -> RpcResult {
+    let checked = update::check_updates(ctx, params.packages.as_deref()).await?;
+    if params.check {
+        return to_value(UpdateCheckJson::from(&checked));
+    }
+
+    let report =
+        update::perform_update(ctx, checked.updates, params.keep, params.no_verify).await?;
+    to_value(UpdateResultJson {
+        updated: report.updated.iter().map(Into::into).collect(),
+        failed: report.failed.iter().map(Into::into).collect(),
+    })
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ApplyParams {
+    /// Answer with the diff instead of applying it.
+    dry_run: bool,
+    prune: bool,
+    packages_config: Option<String>,
+    no_verify: bool,
+}
+
+async fn apply_method(ctx: &SoarContext, params: ApplyParams) -> RpcResult {
+    let config =
+        PackagesConfig::load(params.packages_config.as_deref()).map_err(SoarError::from)?;
+    let resolved = config.resolved_packages();
+    let diff = apply::compute_diff(ctx, &resolved, params.prune).await?;
+    if params.dry_run {
+        return to_value(ApplyDiffJson::new(&diff));
+    }
+
+    let report = apply::execute_apply(ctx, diff, params.no_verify).await?;
+    to_value(ApplyReportJson::from(&report))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Lines,
+        net::{Shutdown, TcpListener},
+        sync::mpsc as std_mpsc,
+        time::Duration,
+    };
+
+    use soar_config::{
+        config::{Config, CONFIG},
+        repository::Repository,
+    };
+
+    use super::*;
+
+    const WAIT: Duration = Duration::from_secs(30);
+
+    fn send(client: &UnixStream, message: Value) {
+        writeln!(&*client, "{message}").unwrap();
+    }
+
+    /// Read messages until one satisfies `done`, returning all of them.
+    fn read_until(
+        lines: &mut Lines<BufReader<UnixStream>>,
+        done: impl Fn(&Value) -> bool,
+    ) -> Vec<Value> {
+        let mut seen = Vec::new();
+        loop {
+            let line = lines.next().expect("the connection closed early").unwrap();
+            let message: Value = serde_json::from_str(&line).unwrap();
+            let finished = done(&message);
+            seen.push(message);
+            if finished {
+                return seen;
+            }
+        }
+    }
+
+    #[test]
+    fn omitted_params_take_every_default() {
+        let params: UpdateParams = parse(Value::Null).unwrap();
+        assert!(params.packages.is_none());
+        assert!(!params.check);
+    }
+
+    #[test]
+    fn a_missing_required_param_is_invalid_params() {
+        let Err(err) = parse::<SearchParams>(json!({ "limit": 3 })) else {
+            panic!("a search without a query must be refused");
+        };
+        assert_eq!(err.code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn cancelling_an_unknown_request_reports_nothing_was_running() {
+        let in_flight: InFlight = Arc::default();
+        let buffer: SharedWriter = Arc::new(Mutex::new(Box::new(Vec::new())));
+        let result = cancel(&in_flight, &buffer, json!({ "id": 9 })).unwrap();
+        assert_eq!(result, Value::Bool(false));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn a_connection_answers_streams_events_and_abandons_what_is_cancelled() {
+        // A repository whose metadata request is accepted and never answered,
+        // so a sync of it stays in flight until cancelled.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/index.json", listener.local_addr().unwrap());
+        let (held_tx, held) = std_mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if held_tx.send(stream).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let root = tempfile::tempdir().unwrap();
+        let mut config = Config::default_config::<&str>(&[]);
+        config.relocate(root.path()).unwrap();
+        config.repositories_path = Some(root.path().join("repos").display().to_string());
+        config.repositories = vec![Repository {
+            name: "held".into(),
+            url,
+            desktop_integration: None,
+            // http is only fetched from when the result is verified
+            pubkey: Some("unused".into()),
+            enabled: None,
+            signature_verification: None,
+            sync_interval: None,
+            min_release_age: None,
+        }];
+        // Repository paths are resolved through the global configuration.
+        *CONFIG.write().unwrap() = Some(config);
+
+        let (server, client) = UnixStream::pair().unwrap();
+        let writer: SharedWriter = Arc::new(Mutex::new(Box::new(server.try_clone().unwrap())));
+        let serving = tokio::spawn(serve_connection(server, writer));
+        client.set_read_timeout(Some(WAIT)).unwrap();
+        let mut lines = BufReader::new(client.try_clone().unwrap()).lines();
+
+        send(
+            &client,
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "health" }),
+        );
+        let answer = read_until(&mut lines, |m| m["id"] == 1).pop().unwrap();
+        assert!(answer["result"].is_object(), "{answer}");
+
+        send(
+            &client,
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "sync" }),
+        );
+        let events = read_until(&mut lines, |m| m["method"] == "event");
+        let event = &events.last().unwrap()["params"];
+        assert_eq!(event["type"], "sync_progress");
+        assert_eq!(event["repo_name"], "held");
+        let fetch = held.recv_timeout(WAIT).unwrap();
+
+        send(
+            &client,
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "cancel", "params": { "id": 2 } }),
+        );
+        let answers = read_until(&mut lines, |m| m["id"] == 3);
+        let cancelled = answers.iter().find(|m| m["id"] == 2).unwrap();
+        assert_eq!(cancelled["error"]["code"], REQUEST_CANCELLED);
+        assert_eq!(answers.last().unwrap()["result"], true);
+
+        // Let the abandoned fetch fail, and hang up.
+        drop(fetch);
+        client.shutdown(Shutdown::Write).unwrap();
+        tokio::time::timeout(WAIT, serving).await.unwrap().unwrap();
+
+        // The sync never answers on its own once cancelled.
+        for line in lines {
+            let message: Value = serde_json::from_str(&line.unwrap()).unwrap();
+            assert_ne!(message["id"], 2, "{message}");
+        }
+    }
+}
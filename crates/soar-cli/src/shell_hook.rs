@@ -0,0 +1,163 @@
+//! Pointing at soar packages when the shell cannot find a command.
+
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
+
+use soar_core::SoarResult;
+use soar_operations::{commands, CommandProvider, SoarContext};
+use tracing::info;
+
+use crate::{
+    cli::HookShell,
+    json_output::{self, CommandProviderJson},
+    run::run_package,
+    utils::{confirm_action, json_enabled},
+};
+
+/// Quote `path` as one word for `shell`.
+///
+/// Inside single quotes nothing is special to bash or zsh, so a quote is
+/// closed, escaped and reopened. Fish also honours a backslash there.
+fn quote(path: &Path, shell: HookShell) -> String {
+    let path = path.to_string_lossy();
+    match shell {
+        HookShell::Bash | HookShell::Zsh => format!("'{}'", path.replace('\'', r"'\''")),
+        HookShell::Fish => {
+            format!("'{}'", path.replace('\\', r"\\").replace('\'', r"\'"))
+        }
+    }
+}
+
+/// The command-not-found handler for `shell`, calling the soar at `soar`.
+///
+/// The handler names soar by its full path. Were soar itself to go missing
+/// from the PATH, a bare `soar` would be the very command not found, and
+/// bash would call the handler for it again.
+pub fn hook(shell: HookShell, soar: &Path) -> String {
+    let soar = quote(soar, shell);
+    match shell {
+        HookShell::Bash | HookShell::Zsh => {
+            let name = match shell {
+                HookShell::Bash => "command_not_found_handle",
+                _ => "command_not_found_handler",
+            };
+            format!(
+                "{name}() {{
+    if [ -x {soar} ]; then
+        {soar} which-package --not-found -- \"$@\"
+    else
+        printf '%s: command not found\\n' \"$1\" >&2
+        return 127
+    fi
+}}
+"
+            )
+        }
+        HookShell::Fish => {
+            format!(
+                "function fish_command_not_found
+    if test -x {soar}
+        {soar} which-package --not-found -- $argv
+    else
+        __fish_default_command_not_found_handler $argv
+    end
+end
+"
+            )
+        }
+    }
+}
+
+/// Print the command-not-found handler for `shell`.
+pub fn print_hook(shell: HookShell) -> SoarResult<()> {
+    let soar = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("soar"));
+    print!("{}", hook(shell, &soar));
+    Ok(())
+}
+
+/// Answer a shell that could not find `command`.
+///
+/// The suggestions go to stderr, where the shell's own message would have.
+/// `soar run` starts a package's own binary, so running is only offered for
+/// a package named after the command, and only when someone is there to say
+/// yes. The status is the command's when it ran, and 127 otherwise, as the
+/// shell would have returned.
+async fn not_found(
+    ctx: &SoarContext,
+    command: &str,
+    providers: &[CommandProvider],
+    args: Vec<String>,
+) -> SoarResult<i32> {
+    if providers.is_empty() {
+        eprintln!("{command}: command not found");
+        return Ok(127);
+    }
+
+    eprintln!("{command}: command not found, but soar can install it:");
+    for provider in providers {
+        eprintln!("  soar install {}", provider.query());
+    }
+
+    let Some(runnable) = providers.iter().find(|p| p.pkg_name == command) else {
+        return Ok(127);
+    };
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return Ok(127);
+    }
+    let query = runnable.query();
+    if !confirm_action(&format!("Run it once with `soar run {query}`?"))? {
+        return Ok(127);
+    }
+
+    let mut command = vec![query];
+    command.extend(args);
+    run_package(ctx, &command, false, false, None, None, None).await
+}
+
+/// Report which packages provide `command`, returning the exit status.
+///
+/// One package query per line, so a script can install the answer as is.
+/// Nothing found is a failure, like `which` finding nothing.
+pub async fn which_package(
+    ctx: &SoarContext,
+    command: &str,
+    not_found_handler: bool,
+    args: Vec<String>,
+) -> SoarResult<i32> {
+    let providers = commands::which_package(ctx, command)?;
+    if not_found_handler {
+        return not_found(ctx, command, &providers, args).await;
+    }
+
+    if json_enabled() {
+        let providers: Vec<CommandProviderJson> = providers.iter().map(Into::into).collect();
+        json_output::emit(&providers);
+    } else {
+        for provider in &providers {
+            info!("{}", provider.query());
+        }
+    }
+    Ok(if providers.is_empty() { 1 } else { 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_handler_names_soar_by_a_quoted_path() {
+        let soar = Path::new("/home/o'neil/bin/soar");
+        let bash = hook(HookShell::Bash, soar);
+        assert!(bash.starts_with("command_not_found_handle() {"));
+        assert!(bash.contains(r"'/home/o'\''neil/bin/soar' which-package --not-found -- "));
+
+        let zsh = hook(HookShell::Zsh, soar);
+        assert!(zsh.starts_with("command_not_found_handler() {"));
+
+        let fish = hook(HookShell::Fish, soar);
+        assert!(fish.starts_with("function fish_command_not_found"));
+        assert!(fish.contains(r"'/home/o\'neil/bin/soar' which-package --not-found -- $argv"));
+    }
+}
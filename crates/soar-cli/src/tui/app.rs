@@ -0,0 +1,741 @@
+//! What the TUI shows and what a key press does to it.
+//!
+//! Nothing here touches the terminal or runs an operation: a key press returns
+//! the [`Effect`] the event loop should carry out, which keeps every decision
+//! testable without a screen.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use soar_events::{
+    InstallStage, OperationId, RemoveStage, SoarEvent, UpdateCleanupStage, VerifyStage,
+};
+use soar_operations::{InstalledEntry, SearchEntry};
+
+/// How many log lines are kept; older ones scroll away.
+const LOG_LIMIT: usize = 200;
+
+/// Rows a page key moves by.
+const PAGE: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Browse,
+    Search,
+    /// Waiting for a yes or no before the queue runs.
+    Confirm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    Install,
+    Remove,
+    Update,
+}
+
+impl Action {
+    pub fn marker(self) -> char {
+        match self {
+            Action::Install => '+',
+            Action::Remove => '-',
+            Action::Update => '^',
+        }
+    }
+
+    pub fn verb(self) -> &'static str {
+        match self {
+            Action::Install => "install",
+            Action::Remove => "remove",
+            Action::Update => "update",
+        }
+    }
+}
+
+/// Everything the detail pane can say about a row.
+///
+/// A search result carries what the repository publishes, an installed package
+/// what was recorded when it went in, so each side fills in its own part.
+#[derive(Debug, Clone, Default)]
+pub struct Detail {
+    pub description: Option<String>,
+    pub pkg_type: Option<String>,
+    pub homepages: Vec<String>,
+    pub licenses: Vec<String>,
+    pub maintainers: Vec<String>,
+    pub notes: Vec<String>,
+    pub size: Option<u64>,
+    pub other_versions: Vec<String>,
+    pub installed_path: Option<String>,
+    pub installed_date: Option<String>,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub name: String,
+    pub family: Option<String>,
+    pub repo: String,
+    pub version: String,
+    pub installed: bool,
+    pub detail: Detail,
+}
+
+impl Row {
+    /// The query that names this package and no other.
+    ///
+    /// A family derived from a URL can hold the characters queries are split
+    /// on, so such a family is left out and the repository narrows it instead.
+    pub fn query(&self) -> String {
+        match self.family.as_deref() {
+            Some(family) if !family.contains(['/', '#', '@', ':']) => {
+                format!("{family}/{}:{}", self.name, self.repo)
+            }
+            _ => format!("{}:{}", self.name, self.repo),
+        }
+    }
+}
+
+impl From<&SearchEntry> for Row {
+    fn from(entry: &SearchEntry) -> Self {
+        let package = &entry.package;
+        Self {
+            name: package.pkg_name.clone(),
+            family: package.pkg_family.clone(),
+            repo: package.repo_name.clone(),
+            version: package.version.clone(),
+            installed: entry.installed,
+            detail: Detail {
+                description: Some(package.description.clone()).filter(|d| !d.is_empty()),
+                pkg_type: package.pkg_type.clone(),
+                homepages: package.homepages.clone().unwrap_or_default(),
+                licenses: package.licenses.clone().unwrap_or_default(),
+                maintainers: package
+                    .maintainers
+                    .as_ref()
+                    .map(|all| all.iter().map(ToString::to_string).collect())
+                    .unwrap_or_default(),
+                notes: package.notes.clone().unwrap_or_default(),
+                size: package.ghcr_size.or(package.size),
+                other_versions: entry.other_versions.clone(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl From<&InstalledEntry> for Row {
+    fn from(entry: &InstalledEntry) -> Self {
+        let package = &entry.package;
+        Self {
+            name: package.pkg_name.clone(),
+            family: package.pkg_family.clone(),
+            repo: package.repo_name.clone(),
+            version: package.version.clone(),
+            installed: package.is_installed,
+            detail: Detail {
+                pkg_type: package.pkg_type.clone(),
+                size: Some(entry.disk_size),
+                installed_path: Some(package.installed_path.clone()),
+                installed_date: Some(package.installed_date.clone()),
+                pinned: package.pinned,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Queued {
+    pub action: Action,
+    pub query: String,
+}
+
+/// The queue split by what is to be done, in the order it runs: removals
+/// first, so a package being swapped for another frees its names in time.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub remove: Vec<String>,
+    pub install: Vec<String>,
+    pub update: Vec<String>,
+}
+
+/// One operation the event stream is reporting on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activity {
+    pub pkg_name: String,
+    pub stage: String,
+    pub current: u64,
+    pub total: u64,
+}
+
+impl Activity {
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.current as f64 / self.total as f64).clamp(0.0, 1.0)
+        }
+    }
+}
+
+/// What the event loop should do after a key press.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Effect {
+    None,
+    Quit,
+    /// The query changed; search again once typing settles.
+    Search,
+    Apply(Plan),
+}
+
+pub struct App {
+    pub mode: Mode,
+    pub query: String,
+    pub rows: Vec<Row>,
+    pub selected: usize,
+    pub queue: Vec<Queued>,
+    pub activity: BTreeMap<OperationId, Activity>,
+    pub log: VecDeque<String>,
+    /// Set while the queue runs; nothing else may be applied meanwhile.
+    pub busy: bool,
+    /// Set while a search is out, so the list can say it is stale.
+    pub searching: bool,
+    pub status: String,
+    /// Bumped per search, so a slow answer to an older query is dropped.
+    pub generation: u64,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Browse,
+            query: String::new(),
+            rows: Vec::new(),
+            selected: 0,
+            queue: Vec::new(),
+            activity: BTreeMap::new(),
+            log: VecDeque::new(),
+            busy: false,
+            searching: false,
+            status: String::new(),
+            generation: 0,
+        }
+    }
+}
+
+impl App {
+    pub fn selected_row(&self) -> Option<&Row> {
+        self.rows.get(self.selected)
+    }
+
+    /// The action queued for a row, if any.
+    pub fn queued(&self, row: &Row) -> Option<Action> {
+        let query = row.query();
+        self.queue
+            .iter()
+            .find(|q| q.query == query)
+            .map(|q| q.action)
+    }
+
+    pub fn set_rows(&mut self, generation: u64, rows: Vec<Row>) {
+        if generation != self.generation {
+            return;
+        }
+        self.searching = false;
+        self.rows = rows;
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+    }
+
+    pub fn push_log(&mut self, line: impl Into<String>) {
+        if self.log.len() == LOG_LIMIT {
+            self.log.pop_front();
+        }
+        self.log.push_back(line.into());
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        if self.rows.is_empty() {
+            self.selected = 0;
+            return;
+        }
+        let last = self.rows.len() - 1;
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Queue `action` for the selected row, or take it off the queue if that
+    /// is what is already queued for it.
+    fn toggle(&mut self, action: Action) {
+        let Some(row) = self.selected_row() else {
+            return;
+        };
+        if action != Action::Install && !row.installed {
+            self.status = format!("{} is not installed", row.name);
+            return;
+        }
+        let query = row.query();
+        match self.queue.iter().position(|q| q.query == query) {
+            Some(at) if self.queue[at].action == action => {
+                self.queue.remove(at);
+            }
+            Some(at) => self.queue[at].action = action,
+            None => {
+                self.queue.push(Queued {
+                    action,
+                    query,
+                })
+            }
+        }
+        self.status.clear();
+    }
+
+    /// The action space takes: removing what is installed, installing the rest.
+    fn toggle_default(&mut self) {
+        let action = match self.selected_row() {
+            Some(row) if row.installed => Action::Remove,
+            Some(_) => Action::Install,
+            None => return,
+        };
+        self.toggle(action);
+    }
+
+    pub fn plan(&self) -> Plan {
+        let mut plan = Plan::default();
+        for queued in &self.queue {
+            let list = match queued.action {
+                Action::Install => &mut plan.install,
+                Action::Remove => &mut plan.remove,
+                Action::Update => &mut plan.update,
+            };
+            list.push(queued.query.clone());
+        }
+        plan
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Effect {
+        if key.kind != KeyEventKind::Press {
+            return Effect::None;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Effect::Quit;
+        }
+
+        match self.mode {
+            Mode::Search => self.search_key(key),
+            Mode::Confirm => self.confirm_key(key),
+            Mode::Browse => self.browse_key(key),
+        }
+    }
+
+    fn search_key(&mut self, key: KeyEvent) -> Effect {
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter => self.mode = Mode::Browse,
+            KeyCode::Up => self.move_by(-1),
+            KeyCode::Down => self.move_by(1),
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.query.clear();
+                return self.requery();
+            }
+            KeyCode::Backspace if !self.query.is_empty() => {
+                self.query.pop();
+                return self.requery();
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                return self.requery();
+            }
+            _ => {}
+        }
+        Effect::None
+    }
+
+    fn requery(&mut self) -> Effect {
+        self.generation += 1;
+        self.searching = true;
+        self.selected = 0;
+        Effect::Search
+    }
+
+    /// Only an explicit yes applies the queue; Enter takes the `[y/N]`
+    /// default like any other key.
+    fn confirm_key(&mut self, key: KeyEvent) -> Effect {
+        self.mode = Mode::Browse;
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                let plan = self.plan();
+                self.queue.clear();
+                self.busy = true;
+                self.status = "Applying the queue...".into();
+                Effect::Apply(plan)
+            }
+            _ => {
+                self.status = "Kept the queue as it was".into();
+                Effect::None
+            }
+        }
+    }
+
+    fn browse_key(&mut self, key: KeyEvent) -> Effect {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if self.busy {
+                    self.status = "Still applying the queue; Ctrl-C leaves anyway".into();
+                } else {
+                    return Effect::Quit;
+                }
+            }
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Char('j') | KeyCode::Down => self.move_by(1),
+            KeyCode::Char('k') | KeyCode::Up => self.move_by(-1),
+            KeyCode::PageDown => self.move_by(PAGE as isize),
+            KeyCode::PageUp => self.move_by(-(PAGE as isize)),
+            KeyCode::Char('g') | KeyCode::Home => self.selected = 0,
+            KeyCode::Char('G') | KeyCode::End => self.move_by(isize::MAX),
+            KeyCode::Char(' ') => self.toggle_default(),
+            KeyCode::Char('i') => self.toggle(Action::Install),
+            KeyCode::Char('r') => self.toggle(Action::Remove),
+            KeyCode::Char('u') => self.toggle(Action::Update),
+            KeyCode::Char('c') => {
+                self.queue.clear();
+                self.status.clear();
+            }
+            KeyCode::Char('a') => {
+                if self.busy {
+                    self.status = "The queue is already being applied".into();
+                } else if self.queue.is_empty() {
+                    self.status = "Nothing is queued".into();
+                } else {
+                    self.mode = Mode::Confirm;
+                    self.status = format!("Apply {} queued change(s)? [y/N]", self.queue.len());
+                }
+            }
+            _ => {}
+        }
+        Effect::None
+    }
+
+    /// Fold one event from the operation stream into what the screen shows.
+    pub fn observe(&mut self, event: SoarEvent) {
+        match event {
+            SoarEvent::DownloadPreparing {
+                op_id,
+                pkg_name,
+            } => self.set_stage(op_id, pkg_name, "connecting"),
+            SoarEvent::DownloadStarting {
+                op_id,
+                pkg_name,
+                total,
+            } => {
+                let activity = self.stage(op_id, pkg_name, "downloading");
+                activity.current = 0;
+                activity.total = total;
+            }
+            SoarEvent::DownloadResuming {
+                op_id,
+                pkg_name,
+                current,
+                total,
+            }
+            | SoarEvent::DownloadProgress {
+                op_id,
+                pkg_name,
+                current,
+                total,
+                ..
+            } => {
+                let activity = self.stage(op_id, pkg_name, "downloading");
+                activity.current = current;
+                activity.total = total;
+            }
+            SoarEvent::DownloadComplete {
+                op_id,
+                pkg_name,
+                total,
+            } => {
+                let activity = self.stage(op_id, pkg_name, "downloaded");
+                activity.current = total;
+                activity.total = total;
+            }
+            SoarEvent::DownloadRetry {
+                op_id,
+                pkg_name,
+            } => self.set_stage(op_id, pkg_name, "retrying"),
+            SoarEvent::DownloadRecovered {
+                op_id,
+                pkg_name,
+            } => self.set_stage(op_id, pkg_name, "downloading"),
+            SoarEvent::DownloadAborted {
+                op_id,
+                pkg_name,
+            } => {
+                self.activity.remove(&op_id);
+                self.push_log(format!("{pkg_name}: download aborted"));
+            }
+            SoarEvent::Verifying {
+                op_id,
+                pkg_name,
+                stage,
+            } => {
+                let text = match stage {
+                    VerifyStage::Checksum => "checking checksum",
+                    VerifyStage::Signature => "checking signature",
+                    VerifyStage::Passed => "verified",
+                    VerifyStage::Failed(ref reason) => {
+                        self.push_log(format!("{pkg_name}: verification failed: {reason}"));
+                        "verification failed"
+                    }
+                };
+                self.set_stage(op_id, pkg_name, text)
+            }
+            SoarEvent::Installing {
+                op_id,
+                pkg_name,
+                stage,
+            } => {
+                let text = match stage {
+                    InstallStage::Extracting | InstallStage::ExtractingNested => "extracting",
+                    InstallStage::LinkingBinaries => "linking binaries",
+                    InstallStage::DesktopIntegration => "integrating",
+                    InstallStage::SetupPortable => "setting up portable dirs",
+                    InstallStage::RecordingDatabase => "recording",
+                    InstallStage::RunningHook(_) => "running hook",
+                    InstallStage::Complete => "installed",
+                };
+                self.set_stage(op_id, pkg_name, text)
+            }
+            SoarEvent::Removing {
+                op_id,
+                pkg_name,
+                stage,
+            } => {
+                let text = match stage {
+                    RemoveStage::RunningHook(_) => "running hook",
+                    RemoveStage::UnlinkingBinaries
+                    | RemoveStage::UnlinkingDesktop
+                    | RemoveStage::UnlinkingIcons => "unlinking",
+                    RemoveStage::RemovingDirectory => "deleting files",
+                    RemoveStage::CleaningDatabase => "forgetting",
+                    RemoveStage::Complete {
+                        ..
+                    } => "removed",
+                };
+                self.set_stage(op_id, pkg_name, text)
+            }
+            SoarEvent::UpdateCleanup {
+                op_id,
+                pkg_name,
+                old_version,
+                stage,
+            } => {
+                if let UpdateCleanupStage::Complete {
+                    ..
+                } = stage
+                {
+                    self.push_log(format!("{pkg_name}: removed {old_version}"));
+                }
+                self.stage(op_id, pkg_name, "cleaning up");
+            }
+            SoarEvent::Hook {
+                op_id,
+                pkg_name,
+                hook_name,
+                ..
+            } => self.set_stage(op_id, pkg_name, &format!("hook {hook_name}")),
+            SoarEvent::Building {
+                op_id,
+                pkg_name,
+                ..
+            } => self.set_stage(op_id, pkg_name, "building"),
+            SoarEvent::OperationComplete {
+                op_id, ..
+            } => {
+                self.activity.remove(&op_id);
+            }
+            SoarEvent::OperationFailed {
+                op_id,
+                pkg_name,
+                error,
+            } => {
+                self.activity.remove(&op_id);
+                self.push_log(format!("{pkg_name}: {error}"));
+            }
+            SoarEvent::BatchProgress {
+                completed,
+                total,
+                failed,
+            } => {
+                self.status = format!("{completed}/{total} done, {failed} failed");
+            }
+            SoarEvent::Log {
+                message, ..
+            } => self.push_log(message),
+            SoarEvent::Running {
+                ..
+            }
+            | SoarEvent::UpdateCheck {
+                ..
+            }
+            | SoarEvent::SyncProgress {
+                ..
+            }
+            | SoarEvent::ApplyComplete {
+                ..
+            } => {}
+        }
+    }
+
+    fn set_stage(&mut self, op_id: OperationId, pkg_name: String, stage: &str) {
+        self.stage(op_id, pkg_name, stage);
+    }
+
+    fn stage(&mut self, op_id: OperationId, pkg_name: String, stage: &str) -> &mut Activity {
+        let activity = self.activity.entry(op_id).or_insert_with(|| {
+            Activity {
+                pkg_name: pkg_name.clone(),
+                stage: String::new(),
+                current: 0,
+                total: 0,
+            }
+        });
+        activity.pkg_name = pkg_name;
+        activity.stage = stage.to_string();
+        activity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(name: &str, installed: bool) -> Row {
+        Row {
+            name: name.into(),
+            family: Some(name.into()),
+            repo: "bincache".into(),
+            version: "1.0.0".into(),
+            installed,
+            detail: Detail::default(),
+        }
+    }
+
+    fn press(app: &mut App, code: KeyCode) -> Effect {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn app_with(rows: Vec<Row>) -> App {
+        let mut app = App::default();
+        app.set_rows(0, rows);
+        app
+    }
+
+    #[test]
+    fn space_installs_what_is_missing_and_removes_what_is_there() {
+        let mut app = app_with(vec![row("jq", false), row("fd", true)]);
+
+        press(&mut app, KeyCode::Char(' '));
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char(' '));
+
+        assert_eq!(
+            app.plan(),
+            Plan {
+                remove: vec!["fd/fd:bincache".into()],
+                install: vec!["jq/jq:bincache".into()],
+                update: vec![],
+            }
+        );
+
+        // A second press takes it back off.
+        press(&mut app, KeyCode::Char(' '));
+        assert!(app.plan().remove.is_empty());
+    }
+
+    #[test]
+    fn only_installed_packages_can_be_queued_for_update_or_removal() {
+        let mut app = app_with(vec![row("jq", false)]);
+
+        press(&mut app, KeyCode::Char('u'));
+        press(&mut app, KeyCode::Char('r'));
+
+        assert!(app.queue.is_empty());
+        assert_eq!(app.status, "jq is not installed");
+    }
+
+    #[test]
+    fn a_family_that_cannot_be_queried_is_left_out() {
+        let mut url = row("tool", true);
+        url.family = Some("127.0.0.1:8749.tool.tar.gz".into());
+        url.repo = "local".into();
+        assert_eq!(url.query(), "tool:local");
+    }
+
+    #[test]
+    fn a_new_action_replaces_the_queued_one() {
+        let mut app = app_with(vec![row("fd", true)]);
+
+        press(&mut app, KeyCode::Char('r'));
+        press(&mut app, KeyCode::Char('u'));
+
+        assert_eq!(app.queue.len(), 1);
+        assert_eq!(app.queue[0].action, Action::Update);
+    }
+
+    #[test]
+    fn the_queue_runs_only_once_confirmed() {
+        let mut app = app_with(vec![row("jq", false)]);
+        press(&mut app, KeyCode::Char('i'));
+
+        assert_eq!(press(&mut app, KeyCode::Char('a')), Effect::None);
+        assert_eq!(app.mode, Mode::Confirm);
+        assert_eq!(press(&mut app, KeyCode::Char('n')), Effect::None);
+        assert_eq!(app.queue.len(), 1);
+
+        press(&mut app, KeyCode::Char('a'));
+        assert_eq!(press(&mut app, KeyCode::Enter), Effect::None);
+        assert_eq!(app.queue.len(), 1);
+
+        press(&mut app, KeyCode::Char('a'));
+        let effect = press(&mut app, KeyCode::Char('y'));
+        assert!(matches!(effect, Effect::Apply(plan) if plan.install == ["jq/jq:bincache"]));
+        assert!(app.queue.is_empty());
+        assert!(app.busy);
+        assert_ne!(press(&mut app, KeyCode::Char('q')), Effect::Quit);
+    }
+
+    #[test]
+    fn typing_a_query_outdates_earlier_results() {
+        let mut app = app_with(vec![row("jq", false)]);
+        press(&mut app, KeyCode::Char('/'));
+
+        assert_eq!(press(&mut app, KeyCode::Char('f')), Effect::Search);
+        assert_eq!(press(&mut app, KeyCode::Char('q')), Effect::Search);
+        assert_eq!(app.query, "fq");
+
+        app.set_rows(1, vec![row("stale", false)]);
+        assert_eq!(app.rows[0].name, "jq");
+        app.set_rows(2, vec![row("fq", false)]);
+        assert_eq!(app.rows[0].name, "fq");
+        assert!(!app.searching);
+    }
+
+    #[test]
+    fn download_events_become_progress_until_the_operation_ends() {
+        let mut app = App::default();
+        app.observe(SoarEvent::DownloadProgress {
+            op_id: 7,
+            pkg_name: "jq".into(),
+            current: 25,
+            total: 100,
+            rate: 0,
+        });
+        assert_eq!(app.activity[&7].stage, "downloading");
+        assert_eq!(app.activity[&7].ratio(), 0.25);
+
+        app.observe(SoarEvent::OperationFailed {
+            op_id: 7,
+            pkg_name: "jq".into(),
+            error: "checksum mismatch".into(),
+        });
+        assert!(app.activity.is_empty());
+        assert_eq!(app.log.back().unwrap(), "jq: checksum mismatch");
+    }
+}
@@ -0,0 +1,347 @@
+//! `soar tui`: search every synced repository, read what a package is, and
+//! queue installs, removals and updates on one screen.
+//!
+//! Searches and the queue run as tasks on the runtime, so the screen keeps
+//! answering keys while they work; what they are doing arrives over the same
+//! event stream the progress bars of the one-shot commands are drawn from.
+
+mod app;
+mod ui;
+
+use std::{
+    io::{self, IsTerminal},
+    sync::{mpsc as std_mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use app::{App, Effect, Plan, Row};
+use ratatui::{
+    crossterm::event::{self, Event},
+    DefaultTerminal,
+};
+use soar_config::config::get_config;
+use soar_core::{
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+use soar_events::{ChannelSink, SoarEvent};
+use soar_operations::{
+    install, list, remove, search, update, InstallOptions, RemoveResolveResult, ResolveResult,
+    SoarContext,
+};
+use tokio::sync::mpsc;
+
+use crate::{logging, utils::COLOR};
+
+/// How long typing must pause before the query is searched.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// How often the screen is redrawn when nothing else happens, which is what
+/// keeps progress moving.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Results asked of a search. Enough to scroll through; a query that needs
+/// more should be narrowed.
+const SEARCH_LIMIT: usize = 200;
+
+/// What a task sends back to the screen.
+enum Outcome {
+    Rows { generation: u64, rows: Vec<Row> },
+    SearchFailed { generation: u64, error: String },
+    Applied(Vec<String>),
+}
+
+pub async fn run() -> SoarResult<()> {
+    if !io::stdout().is_terminal() {
+        return Err(SoarError::Custom(
+            "soar tui needs a terminal to draw on".into(),
+        ));
+    }
+
+    let (sink, events) = ChannelSink::new();
+    let ctx = SoarContext::new(get_config(), Arc::new(sink));
+
+    // Log records would scribble over the screen, so they are shown in the
+    // activity pane, without the color codes meant for a terminal.
+    *COLOR.write().unwrap() = false;
+    let (log_tx, log_rx) = std_mpsc::channel();
+    logging::capture(Some(log_tx));
+
+    let mut terminal = ratatui::try_init().with_context(|| "setting up the terminal".into())?;
+    let result = event_loop(&mut terminal, ctx, events, log_rx).await;
+    ratatui::restore();
+    logging::capture(None);
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    ctx: SoarContext,
+    events: std_mpsc::Receiver<SoarEvent>,
+    logs: std_mpsc::Receiver<String>,
+) -> SoarResult<()> {
+    let mut input = spawn_input_reader();
+    let (outcome_tx, mut outcomes) = mpsc::unbounded_channel();
+    let mut tick = tokio::time::interval(TICK);
+
+    let mut app = App {
+        searching: true,
+        ..Default::default()
+    };
+    let mut search_due = Some(Instant::now());
+
+    loop {
+        terminal
+            .draw(|frame| ui::draw(frame, &app))
+            .with_context(|| "drawing the screen".into())?;
+
+        tokio::select! {
+            Some(read) = input.recv() => {
+                let read = read.with_context(|| "reading the terminal".into())?;
+                if let Event::Key(key) = read {
+                    match app.handle_key(key) {
+                        Effect::Quit => return Ok(()),
+                        Effect::Search => search_due = Some(Instant::now() + DEBOUNCE),
+                        Effect::Apply(plan) => {
+                            let ctx = ctx.clone();
+                            let tx = outcome_tx.clone();
+                            tokio::spawn(async move {
+                                tx.send(Outcome::Applied(apply(&ctx, plan).await)).ok();
+                            });
+                        }
+                        Effect::None => {}
+                    }
+                }
+            }
+            Some(outcome) = outcomes.recv() => match outcome {
+                Outcome::Rows { generation, rows } => app.set_rows(generation, rows),
+                Outcome::SearchFailed { generation, error } => {
+                    if generation == app.generation {
+                        app.searching = false;
+                        app.status = format!("Search failed: {error}");
+                    }
+                }
+                Outcome::Applied(lines) => {
+                    for line in lines {
+                        app.push_log(line);
+                    }
+                    app.busy = false;
+                    app.activity.clear();
+                    app.status = "Finished applying the queue".into();
+                    // What is installed has changed under the list.
+                    app.generation += 1;
+                    app.searching = true;
+                    search_due = Some(Instant::now());
+                }
+            },
+            _ = tick.tick() => {}
+        }
+
+        while let Ok(event) = events.try_recv() {
+            app.observe(event);
+        }
+        while let Ok(line) = logs.try_recv() {
+            app.push_log(line);
+        }
+
+        if search_due.is_some_and(|at| Instant::now() >= at) {
+            search_due = None;
+            let ctx = ctx.clone();
+            let tx = outcome_tx.clone();
+            let query = app.query.clone();
+            let generation = app.generation;
+            tokio::spawn(async move {
+                let outcome = match find(&ctx, &query).await {
+                    Ok(rows) => {
+                        Outcome::Rows {
+                            generation,
+                            rows,
+                        }
+                    }
+                    Err(err) => {
+                        Outcome::SearchFailed {
+                            generation,
+                            error: err.to_string(),
+                        }
+                    }
+                };
+                tx.send(outcome).ok();
+            });
+        }
+    }
+}
+
+/// Read the terminal on a thread of its own, since crossterm only blocks.
+fn spawn_input_reader() -> mpsc::UnboundedReceiver<io::Result<Event>> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    thread::spawn(move || {
+        // Polled with a timeout rather than read outright, so the thread
+        // notices the screen has gone away and ends.
+        while !tx.is_closed() {
+            match event::poll(TICK) {
+                Ok(true) => {
+                    if tx.send(event::read()).is_err() {
+                        break;
+                    }
+                }
+                Ok(false) => {}
+                Err(err) => {
+                    tx.send(Err(err)).ok();
+                    break;
+                }
+            }
+        }
+    });
+    rx
+}
+
+/// Installed packages for an empty query, and search results otherwise.
+async fn find(ctx: &SoarContext, query: &str) -> SoarResult<Vec<Row>> {
+    let query = query.trim();
+    if query.is_empty() {
+        let installed = list::list_installed(ctx, None)?;
+        return Ok(installed.packages.iter().map(Row::from).collect());
+    }
+
+    let found = search::search_packages(ctx, query, false, Some(SEARCH_LIMIT)).await?;
+    Ok(found.packages.iter().map(Row::from).collect())
+}
+
+/// Run the queue, saying what came of each part.
+///
+/// A part that fails outright is reported and the rest still run, so one bad
+/// entry does not hold back everything queued after it.
+async fn apply(ctx: &SoarContext, plan: Plan) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    if !plan.remove.is_empty() {
+        if let Err(err) = apply_removals(ctx, &plan.remove, &mut lines).await {
+            lines.push(format!("Removing failed: {err}"));
+        }
+    }
+    if !plan.install.is_empty() {
+        if let Err(err) = apply_installs(ctx, &plan.install, &mut lines).await {
+            lines.push(format!("Installing failed: {err}"));
+        }
+    }
+    if !plan.update.is_empty() {
+        if let Err(err) = apply_updates(ctx, &plan.update, &mut lines).await {
+            lines.push(format!("Updating failed: {err}"));
+        }
+    }
+
+    lines
+}
+
+async fn apply_installs(
+    ctx: &SoarContext,
+    queries: &[String],
+    lines: &mut Vec<String>,
+) -> SoarResult<()> {
+    let options = InstallOptions::default();
+    let mut targets = Vec::new();
+    for result in install::resolve_packages(ctx, queries, &options).await? {
+        match result {
+            ResolveResult::Resolved(resolved) => targets.extend(resolved),
+            ResolveResult::Ambiguous(ambiguous) => {
+                lines.push(format!(
+                    "{}: {} packages match; not installed",
+                    ambiguous.query,
+                    ambiguous.candidates.len()
+                ))
+            }
+            ResolveResult::NotFound(name) => lines.push(format!("{name}: not found")),
+            ResolveResult::AlreadyInstalled {
+                pkg_name,
+                version,
+                ..
+            } => lines.push(format!("{pkg_name} {version} is already installed")),
+        }
+    }
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    let report = install::perform_installation(ctx, targets, &options).await?;
+    for installed in &report.installed {
+        lines.push(format!(
+            "Installed {} {}",
+            installed.pkg_name, installed.version
+        ));
+    }
+    for failed in &report.failed {
+        lines.push(format!("{}: {}", failed.pkg_name, failed.error));
+    }
+    lines.extend(report.warnings);
+    Ok(())
+}
+
+async fn apply_removals(
+    ctx: &SoarContext,
+    queries: &[String],
+    lines: &mut Vec<String>,
+) -> SoarResult<()> {
+    let mut targets = Vec::new();
+    for result in remove::resolve_removals(ctx, queries, false)? {
+        match result {
+            RemoveResolveResult::Resolved(resolved) => targets.extend(resolved),
+            RemoveResolveResult::Ambiguous {
+                query,
+                candidates,
+            } => {
+                lines.push(format!(
+                    "{query}: {} installed packages match; not removed",
+                    candidates.len()
+                ))
+            }
+            RemoveResolveResult::NotInstalled(name) => {
+                lines.push(format!("{name} is not installed"))
+            }
+        }
+    }
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    let report = remove::perform_removal(ctx, targets).await?;
+    for removed in &report.removed {
+        lines.push(format!("Removed {} {}", removed.pkg_name, removed.version));
+    }
+    for failed in &report.failed {
+        lines.push(format!("{}: {}", failed.pkg_name, failed.error));
+    }
+    Ok(())
+}
+
+async fn apply_updates(
+    ctx: &SoarContext,
+    queries: &[String],
+    lines: &mut Vec<String>,
+) -> SoarResult<()> {
+    let checked = update::check_updates(ctx, Some(queries)).await?;
+    for deferred in &checked.deferred {
+        lines.push(format!(
+            "{} {} is held back until {}",
+            deferred.pkg_name,
+            deferred.new_version,
+            deferred.eligible_at.format("%Y-%m-%d %H:%M UTC")
+        ));
+    }
+    if checked.updates.is_empty() {
+        lines.push("Nothing queued for update has a newer version".into());
+        return Ok(());
+    }
+
+    let report = update::perform_update(ctx, checked.updates, false, false).await?;
+    for updated in &report.updated {
+        lines.push(format!(
+            "Updated {} to {}",
+            updated.pkg_name, updated.version
+        ));
+    }
+    for failed in &report.failed {
+        lines.push(format!("{}: {}", failed.pkg_name, failed.error));
+    }
+    Ok(())
+}
@@ -0,0 +1,230 @@
+//! Drawing the TUI from its state.
+
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, LineGauge, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+use soar_utils::bytes::format_bytes;
+
+use super::app::{App, Mode, Row};
+
+const HELP: &str = "/ search  space toggle  i/r/u install/remove/update  a apply  c clear  q quit";
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [search, main, bottom, status] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(8),
+        Constraint::Length(9),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [list, detail] =
+        Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)]).areas(main);
+    let [queue, activity] =
+        Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(bottom);
+
+    draw_search(frame, app, search);
+    draw_rows(frame, app, list);
+    draw_detail(frame, app, detail);
+    draw_queue(frame, app, queue);
+    draw_activity(frame, app, activity);
+
+    let line = if app.status.is_empty() {
+        Line::from(HELP).dark_gray()
+    } else {
+        Line::from(app.status.as_str()).yellow()
+    };
+    frame.render_widget(Paragraph::new(line), status);
+}
+
+fn focused(block: Block<'_>, focus: bool) -> Block<'_> {
+    if focus {
+        block.border_style(Style::new().fg(Color::Cyan))
+    } else {
+        block
+    }
+}
+
+fn draw_search(frame: &mut Frame, app: &App, area: Rect) {
+    let title = if app.query.is_empty() {
+        " Search (showing installed) "
+    } else {
+        " Search all repositories "
+    };
+    let block = focused(
+        Block::default().borders(Borders::ALL).title(title),
+        app.mode == Mode::Search,
+    );
+    frame.render_widget(Paragraph::new(app.query.as_str()).block(block), area);
+    if app.mode == Mode::Search {
+        let x = area.x + 1 + app.query.chars().count() as u16;
+        frame.set_cursor_position((x.min(area.right().saturating_sub(2)), area.y + 1));
+    }
+}
+
+fn display_name(row: &Row) -> String {
+    match row.family.as_deref() {
+        Some(family) if family != row.name => format!("{family}/{}", row.name),
+        _ => row.name.clone(),
+    }
+}
+
+fn draw_rows(frame: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .rows
+        .iter()
+        .map(|row| {
+            let marker = app.queued(row).map_or(' ', |action| action.marker());
+            let installed = if row.installed { "*" } else { " " };
+            ListItem::new(Line::from(vec![
+                Span::from(format!("{marker}{installed} ")).bold(),
+                Span::from(display_name(row)),
+                Span::from(format!(" {}", row.version)).dark_gray(),
+                Span::from(format!(" :{}", row.repo)).blue(),
+            ]))
+        })
+        .collect();
+
+    let title = if app.searching {
+        " Packages (searching...) ".to_string()
+    } else {
+        format!(" Packages ({}) ", app.rows.len())
+    };
+    let list = List::new(items)
+        .block(focused(
+            Block::default().borders(Borders::ALL).title(title),
+            app.mode == Mode::Browse,
+        ))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    let mut state =
+        ListState::default().with_selected((!app.rows.is_empty()).then_some(app.selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn field<'a>(lines: &mut Vec<Line<'a>>, label: &'a str, value: String) {
+    lines.push(Line::from(vec![
+        Span::from(format!("{label:<13}")).bold(),
+        Span::from(value),
+    ]));
+}
+
+fn list_field<'a>(lines: &mut Vec<Line<'a>>, label: &'a str, values: &[String]) {
+    if !values.is_empty() {
+        field(lines, label, values.join(", "));
+    }
+}
+
+fn draw_detail(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title(" Details ");
+    let Some(row) = app.selected_row() else {
+        let text = if app.query.is_empty() {
+            "Nothing is installed. Press / to search."
+        } else {
+            "No package matches."
+        };
+        frame.render_widget(Paragraph::new(text).dark_gray().block(block), area);
+        return;
+    };
+
+    let detail = &row.detail;
+    let mut lines = vec![Line::from(display_name(row)).bold().cyan()];
+    if let Some(description) = &detail.description {
+        lines.push(Line::from(description.as_str()));
+    }
+    lines.push(Line::default());
+    field(&mut lines, "Version", row.version.clone());
+    field(&mut lines, "Repository", row.repo.clone());
+    if let Some(pkg_type) = &detail.pkg_type {
+        field(&mut lines, "Type", pkg_type.clone());
+    }
+    if let Some(size) = detail.size {
+        field(&mut lines, "Size", format_bytes(size, 2));
+    }
+    field(
+        &mut lines,
+        "Installed",
+        if row.installed { "yes" } else { "no" }.into(),
+    );
+    if detail.pinned {
+        field(&mut lines, "Pinned", "yes".into());
+    }
+    if let Some(date) = &detail.installed_date {
+        field(&mut lines, "Installed on", date.clone());
+    }
+    if let Some(path) = &detail.installed_path {
+        field(&mut lines, "Path", path.clone());
+    }
+    list_field(&mut lines, "Homepages", &detail.homepages);
+    list_field(&mut lines, "Licenses", &detail.licenses);
+    list_field(&mut lines, "Maintainers", &detail.maintainers);
+    list_field(&mut lines, "Other versions", &detail.other_versions);
+    if !detail.notes.is_empty() {
+        lines.push(Line::default());
+        lines.push(Line::from("Notes").bold());
+        lines.extend(
+            detail
+                .notes
+                .iter()
+                .map(|note| Line::from(format!("  {note}"))),
+        );
+    }
+
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap {
+                trim: false,
+            })
+            .block(block),
+        area,
+    );
+}
+
+fn draw_queue(frame: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .queue
+        .iter()
+        .map(|queued| ListItem::new(format!("{} {}", queued.action.verb(), queued.query)))
+        .collect();
+    let title = format!(" Queue ({}) ", app.queue.len());
+    frame.render_widget(
+        List::new(items).block(Block::default().borders(Borders::ALL).title(title)),
+        area,
+    );
+}
+
+fn draw_activity(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title(" Activity ");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    // Running operations first, then as much of the log as still fits.
+    let mut y = inner.y;
+    for activity in app.activity.values() {
+        if y >= inner.bottom() {
+            return;
+        }
+        let gauge = LineGauge::default()
+            .filled_style(Style::new().fg(Color::Green))
+            .label(format!("{} {}", activity.pkg_name, activity.stage))
+            .ratio(activity.ratio());
+        frame.render_widget(gauge, Rect::new(inner.x, y, inner.width, 1));
+        y += 1;
+    }
+
+    let room = inner.bottom().saturating_sub(y) as usize;
+    let skip = app.log.len().saturating_sub(room);
+    let lines: Vec<Line> = app
+        .log
+        .iter()
+        .skip(skip)
+        .map(|line| Line::from(line.as_str()))
+        .collect();
+    let height = inner.bottom().saturating_sub(y);
+    frame.render_widget(
+        Paragraph::new(lines),
+        Rect::new(inner.x, y, inner.width, height),
+    );
+}
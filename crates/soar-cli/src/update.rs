@@ -1,6 +1,9 @@
-use nu_ansi_term::Color::{Blue, Cyan, Green, Red};
+use nu_ansi_term::Color::{Blue, Cyan, Green, Red, Yellow};
 use soar_core::SoarResult;
-use soar_operations::{update, SoarContext, UpdateInfo, UpdateReport};
+use soar_operations::{
+    changelog::update_changelogs, update, DeferredUpdate, PackageChangelog, SoarContext,
+    UpdateCheck, UpdateInfo, UpdateReport,
+};
 use tabled::{
     builder::Builder,
     settings::{themes::BorderCorrection, Panel, Style},
@@ -8,7 +11,8 @@ use tabled::{
 use tracing::{error, info};
 
 use crate::{
-    json_output::{self, Listing, UpdateJson},
+    changelog::display_changelogs,
+    json_output::{self, changelog_json, UpdateCheckJson},
     progress::create_wait_job,
     utils::{ask_target_action, display_settings, icon_or, json_enabled, Colored, Icons},
 };
@@ -19,24 +23,34 @@ pub async fn update_packages(
     keep: bool,
     ask: bool,
     check: bool,
+    changelog: bool,
     no_verify: bool,
 ) -> SoarResult<()> {
     // Packages installed from a remote source are checked over the network here.
     let spinner = create_wait_job("checking for updates");
     let checked = update::check_updates(ctx, packages.as_deref()).await;
     spinner.finish_and_clear();
-    let updates = checked?;
+    let checked = checked?;
 
     if check {
-        return report_pending(&updates);
+        return report_pending(ctx, &checked, changelog);
     }
 
+    let UpdateCheck {
+        updates,
+        deferred,
+    } = checked;
+    display_deferred(&deferred);
+
     if updates.is_empty() {
         info!("No packages to update.");
         return Ok(());
     }
 
     display_pending(&updates);
+    if changelog {
+        display_changelogs(&gather_changelogs(ctx, &updates));
+    }
 
     if ask {
         let install_targets: Vec<_> = updates.iter().map(|u| u.target.clone()).collect();
@@ -50,23 +64,45 @@ pub async fn update_packages(
 }
 
 /// Say what is waiting to be updated, and stop there.
-fn report_pending(updates: &[UpdateInfo]) -> SoarResult<()> {
+fn report_pending(ctx: &SoarContext, checked: &UpdateCheck, changelog: bool) -> SoarResult<()> {
+    let changelogs = if changelog {
+        gather_changelogs(ctx, &checked.updates)
+    } else {
+        Vec::new()
+    };
+
     if json_enabled() {
-        let items: Vec<UpdateJson> = updates.iter().map(Into::into).collect();
-        json_output::emit(&Listing::new(items, updates.len()));
+        let mut report = UpdateCheckJson::from(checked);
+        for (item, changelog) in report.items.iter_mut().zip(&changelogs) {
+            item.changelog = Some(changelog_json(changelog));
+        }
+        json_output::emit(&report);
         return Ok(());
     }
 
-    if updates.is_empty() {
+    if checked.updates.is_empty() && checked.deferred.is_empty() {
         info!("No packages to update.");
         return Ok(());
     }
 
-    display_pending(updates);
+    display_pending(&checked.updates);
+    display_deferred(&checked.deferred);
+    display_changelogs(&changelogs);
 
     Ok(())
 }
 
+/// Fetch the release notes for each pending update.
+fn gather_changelogs(ctx: &SoarContext, updates: &[UpdateInfo]) -> Vec<PackageChangelog> {
+    if updates.is_empty() {
+        return Vec::new();
+    }
+    let spinner = create_wait_job("fetching release notes");
+    let changelogs = update_changelogs(ctx, updates);
+    spinner.finish_and_clear();
+    changelogs
+}
+
 fn display_pending(updates: &[UpdateInfo]) {
     for update_info in updates {
         info!(
@@ -78,6 +114,19 @@ fn display_pending(updates: &[UpdateInfo]) {
     }
 }
 
+/// Newer versions still too recent to take, with when they will be.
+fn display_deferred(deferred: &[DeferredUpdate]) {
+    for update in deferred {
+        info!(
+            "{}: {} -> {} (deferred until {})",
+            Colored(Blue, &update.pkg_name),
+            Colored(Red, &update.current_version),
+            Colored(Yellow, &update.new_version),
+            update.eligible_at.format("%Y-%m-%d %H:%M UTC"),
+        );
+    }
+}
+
 fn display_update_report(report: &UpdateReport) {
     let settings = display_settings();
     let use_icons = settings.icons();
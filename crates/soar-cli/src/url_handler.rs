@@ -20,7 +20,7 @@ use soar_core::{
     SoarResult,
 };
 use soar_operations::SoarContext;
-use soar_utils::path::xdg_data_home;
+use soar_utils::path::{install_root, xdg_data_home};
 use tracing::info;
 
 use crate::{
@@ -264,6 +264,13 @@ pub async fn handle(ctx: &SoarContext, url: Option<String>, register_only: bool)
     if json_enabled() {
         return Err(SoarError::Custom("soar url has no JSON output".into()));
     }
+    // Links come from the desktop soar is running on, which is not the one an
+    // install root belongs to.
+    if install_root().is_some() {
+        return Err(SoarError::Custom(
+            "soar url acts on the running desktop and cannot be used with --root".into(),
+        ));
+    }
 
     if register_only {
         let path = register()?;
@@ -309,6 +316,7 @@ pub async fn handle(ctx: &SoarContext, url: Option<String>, register_only: bool)
         false,
         false,
         false,
+        false,
         None,
         None,
         None,
@@ -8,7 +8,7 @@ use std::{
 use documented::{Documented, DocumentedFields};
 use serde::{Deserialize, Serialize};
 use soar_utils::{
-    path::{is_safe_component, resolve_path, xdg_config_home, xdg_data_home},
+    path::{is_safe_component, on_host, xdg_config_home, xdg_data_home},
     system::platform,
 };
 use toml_edit::DocumentMut;
@@ -17,10 +17,11 @@ use tracing::{debug, info, trace, warn};
 use crate::{
     annotations::{annotate_toml_array_of_tables, annotate_toml_table},
     display::DisplaySettings,
+    download::DownloadSettings,
     error::{ConfigError, Result},
     profile::Profile,
     repository::{get_platform_repositories, Repository, SOARPKGS_PUBKEY},
-    utils::default_install_patterns,
+    utils::{default_install_patterns, resolve_install_path},
 };
 
 /// Application's configuration
@@ -105,6 +106,9 @@ pub struct Config {
     /// Display settings for output formatting
     pub display: Option<DisplaySettings>,
 
+    /// Bandwidth and connection limits for downloads
+    pub download: Option<DownloadSettings>,
+
     /// Whether this config is for system mode.
     /// Not serialized - set programmatically.
     #[serde(skip)]
@@ -196,7 +200,11 @@ impl Config {
 
     /// Returns the icons directory path based on system mode.
     pub fn get_icons_path(&self) -> std::path::PathBuf {
-        soar_utils::path::icons_dir(self.system_mode)
+        on_host(soar_utils::path::icons_dir(self.system_mode))
+    }
+
+    pub fn get_metainfo_path(&self) -> std::path::PathBuf {
+        on_host(soar_utils::path::metainfo_dir(self.system_mode))
     }
 
     // Still populated while the OCI path exists; see the field's deprecation.
@@ -239,6 +247,7 @@ impl Config {
                 enabled: repo_info.enabled,
                 signature_verification: repo_info.signature_verification,
                 sync_interval: repo_info.sync_interval.map(String::from),
+                min_release_age: None,
             });
         }
 
@@ -288,6 +297,7 @@ impl Config {
             desktop_integration: None,
             sync_interval: None,
             display: None,
+            download: None,
             system_mode: is_system_mode(),
         }
     }
@@ -335,6 +345,7 @@ impl Config {
                 enabled: repo_info.enabled,
                 signature_verification: repo_info.signature_verification,
                 sync_interval: repo_info.sync_interval.map(String::from),
+                min_release_age: None,
             });
         }
 
@@ -382,6 +393,7 @@ impl Config {
             desktop_integration: None,
             sync_interval: None,
             display: None,
+            download: None,
             system_mode,
         }
     }
@@ -441,6 +453,9 @@ impl Config {
 
         self.ghcr_concurrency.get_or_insert(8);
         self.search_limit.get_or_insert(20);
+        // A rate that does not parse is better caught at load than at the
+        // first download.
+        self.download().rate_limit()?;
         self.cross_repo_updates.get_or_insert(false);
 
         let mut seen_repos = HashSet::new();
@@ -469,6 +484,9 @@ impl Config {
             if explicitly_enabled && repo.pubkey.is_none() {
                 return Err(ConfigError::MissingPubkey(repo.name.clone()));
             }
+
+            // Read now so a typo is reported, not an update let through.
+            repo.min_release_age()?;
         }
 
         Ok(())
@@ -488,10 +506,10 @@ impl Config {
 
     pub fn get_bin_path(&self) -> Result<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_BIN") {
-            return Ok(resolve_path(&env_path)?);
+            return resolve_install_path(&env_path);
         }
         if let Some(bin_path) = &self.bin_path {
-            return Ok(resolve_path(bin_path)?);
+            return resolve_install_path(bin_path);
         }
         self.default_profile()?.get_bin_path()
     }
@@ -501,8 +519,8 @@ impl Config {
         if let Some(shells) = &self.completions {
             return shells.clone();
         }
-        let data = soar_utils::path::xdg_data_home();
-        let config = soar_utils::path::xdg_config_home();
+        let data = on_host(xdg_data_home());
+        let config = on_host(xdg_config_home());
         [
             ("bash", data.join("bash-completion/completions")),
             ("zsh", data.join("zsh/site-functions")),
@@ -516,12 +534,12 @@ impl Config {
 
     pub fn get_desktop_path(&self) -> Result<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_DESKTOP") {
-            return Ok(resolve_path(&env_path)?);
+            return resolve_install_path(&env_path);
         }
         if let Some(desktop_path) = &self.desktop_path {
-            return Ok(resolve_path(desktop_path)?);
+            return resolve_install_path(desktop_path);
         }
-        Ok(soar_utils::path::desktop_dir(self.system_mode))
+        Ok(on_host(soar_utils::path::desktop_dir(self.system_mode)))
     }
 
     /// Creates a new configuration from a specific config file path with the given system mode.
@@ -554,17 +572,17 @@ impl Config {
 
     pub fn get_db_path(&self) -> Result<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_DB") {
-            return Ok(resolve_path(&env_path)?);
+            return resolve_install_path(&env_path);
         }
         if let Some(soar_db) = &self.db_path {
-            return Ok(resolve_path(soar_db)?);
+            return resolve_install_path(soar_db);
         }
         self.default_profile()?.get_db_path()
     }
 
     pub fn get_packages_path(&self, profile_name: Option<String>) -> Result<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_PACKAGES") {
-            return Ok(resolve_path(&env_path)?);
+            return resolve_install_path(&env_path);
         }
         let profile_name = profile_name.unwrap_or_else(get_current_profile);
         self.get_profile(&profile_name)?.get_packages_path()
@@ -572,35 +590,57 @@ impl Config {
 
     pub fn get_cache_path(&self) -> Result<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_CACHE") {
-            return Ok(resolve_path(&env_path)?);
+            return resolve_install_path(&env_path);
         }
         if let Some(soar_cache) = &self.cache_path {
-            return Ok(resolve_path(soar_cache)?);
+            return resolve_install_path(soar_cache);
         }
         self.get_profile(&get_current_profile())?.get_cache_path()
     }
 
     pub fn get_repositories_path(&self) -> Result<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_REPOSITORIES") {
-            return Ok(resolve_path(&env_path)?);
+            return resolve_install_path(&env_path);
         }
         if let Some(repositories_path) = &self.repositories_path {
-            return Ok(resolve_path(repositories_path)?);
+            return resolve_install_path(repositories_path);
         }
         self.default_profile()?.get_repositories_path()
     }
 
     pub fn get_portable_dirs(&self) -> Result<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_PORTABLE_DIRS") {
-            return Ok(resolve_path(&env_path)?);
+            return resolve_install_path(&env_path);
         }
 
         if let Some(portable_dirs) = &self.portable_dirs {
-            return Ok(resolve_path(portable_dirs)?);
+            return resolve_install_path(portable_dirs);
         }
         self.default_profile()?.get_portable_dirs()
     }
 
+    /// Move everything soar writes for installed packages under `root`: the
+    /// core database, every profile's packages, the bin links, the cache and
+    /// portable dirs.
+    ///
+    /// Repository metadata stays where it is, so a relocated install resolves
+    /// packages against what has already been synced.
+    pub fn relocate(&mut self, root: &Path) -> Result<()> {
+        let repositories = self.get_repositories_path()?;
+        let root = root.display().to_string();
+        for profile in self.profile.values_mut() {
+            profile.root_path = root.clone();
+            profile.packages_path = None;
+        }
+        self.bin_path = Some(format!("{root}/bin"));
+        self.db_path = Some(format!("{root}/db"));
+        self.cache_path = Some(format!("{root}/cache"));
+        self.portable_dirs = Some(format!("{root}/portable-dirs"));
+        self.desktop_path = Some(format!("{root}/share/applications"));
+        self.repositories_path = Some(repositories.display().to_string());
+        Ok(())
+    }
+
     pub fn get_repository(&self, repo_name: &str) -> Option<&Repository> {
         self.repositories
             .iter()
@@ -619,6 +659,10 @@ impl Config {
         self.display.clone().unwrap_or_default()
     }
 
+    pub fn download(&self) -> DownloadSettings {
+        self.download.clone().unwrap_or_default()
+    }
+
     pub fn save(&self) -> Result<()> {
         let config_path = CONFIG_PATH.read().unwrap().to_path_buf();
         let annotated_doc = self.to_annotated_document()?;
@@ -724,6 +768,7 @@ mod tests {
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            min_release_age: None,
         }
     }
 
@@ -760,6 +805,7 @@ mod tests {
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            min_release_age: None,
         });
 
         let result = config.resolve();
@@ -777,12 +823,25 @@ mod tests {
             enabled: Some(true),
             signature_verification: Some(true),
             sync_interval: None,
+            min_release_age: None,
         });
 
         let result = config.resolve();
         assert!(matches!(result, Err(ConfigError::MissingPubkey(_))));
     }
 
+    #[test]
+    fn test_config_resolve_rejects_unreadable_release_age() {
+        let mut config = Config::default_config::<&str>(&[]);
+        config.repositories.push(Repository {
+            min_release_age: Some("a week".to_string()),
+            ..repo_named("cautious")
+        });
+
+        let result = config.resolve();
+        assert!(matches!(result, Err(ConfigError::InvalidReleaseAge(_))));
+    }
+
     #[test]
     fn test_config_resolve_duplicate_repo() {
         let mut config = Config::default_config::<&str>(&[]);
@@ -794,6 +853,7 @@ mod tests {
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            min_release_age: None,
         });
         config.repositories.push(Repository {
             name: "duplicate".to_string(),
@@ -803,6 +863,7 @@ mod tests {
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            min_release_age: None,
         });
 
         let result = config.resolve();
@@ -873,6 +934,7 @@ mod tests {
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            min_release_age: None,
         });
         assert!(config.has_desktop_integration("test_repo"));
     }
@@ -892,6 +954,28 @@ mod tests {
         assert_eq!(desktop, PathBuf::from("/custom/desktop/path"));
     }
 
+    #[test]
+    fn test_relocate_moves_install_paths_but_not_repositories() {
+        let mut config = Config::default_config::<&str>(&[]);
+        let repositories = config.get_repositories_path().unwrap();
+
+        config.relocate(Path::new("/scratch/root")).unwrap();
+
+        assert_eq!(
+            config.get_db_path().unwrap(),
+            PathBuf::from("/scratch/root/db")
+        );
+        assert_eq!(
+            config.get_packages_path(Some("default".into())).unwrap(),
+            PathBuf::from("/scratch/root/packages")
+        );
+        assert_eq!(
+            config.get_desktop_path().unwrap(),
+            PathBuf::from("/scratch/root/share/applications")
+        );
+        assert_eq!(config.get_repositories_path().unwrap(), repositories);
+    }
+
     #[test]
     fn test_config_serialization() {
         let config = Config::default_config::<&str>(&[]);
@@ -0,0 +1,69 @@
+use documented::{Documented, DocumentedFields};
+use serde::{Deserialize, Serialize};
+use soar_utils::bytes::parse_rate;
+
+use crate::error::{ConfigError, Result};
+
+/// Default number of connections a single host may hold at once, the figure
+/// browsers settled on.
+pub const DEFAULT_PER_HOST_CONNECTIONS: usize = 6;
+
+/// Limits on how downloads use the network
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+pub struct DownloadSettings {
+    /// Bytes per second shared by all downloads, e.g. "5M" or "500K".
+    /// Default: unlimited
+    pub rate_limit: Option<String>,
+
+    /// Maximum number of connections to a single host at once, so one host's
+    /// downloads do not crowd out another's.
+    /// Default: 6
+    pub per_host_connections: Option<usize>,
+}
+
+impl DownloadSettings {
+    /// The rate limit in bytes per second, if one is set.
+    pub fn rate_limit(&self) -> Result<Option<u64>> {
+        self.rate_limit
+            .as_deref()
+            .map(parse_rate)
+            .transpose()
+            .map_err(|err| ConfigError::Utils(err.into()))
+    }
+
+    pub fn per_host_connections(&self) -> usize {
+        self.per_host_connections
+            .unwrap_or(DEFAULT_PER_HOST_CONNECTIONS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unset_settings_leave_the_rate_unlimited() {
+        let settings = DownloadSettings::default();
+        assert_eq!(settings.rate_limit().unwrap(), None);
+        assert_eq!(
+            settings.per_host_connections(),
+            DEFAULT_PER_HOST_CONNECTIONS
+        );
+    }
+
+    #[test]
+    fn rate_limit_reads_curl_style_sizes() {
+        let settings = DownloadSettings {
+            rate_limit: Some("5M".into()),
+            per_host_connections: Some(2),
+        };
+        assert_eq!(settings.rate_limit().unwrap(), Some(5 * 1024 * 1024));
+        assert_eq!(settings.per_host_connections(), 2);
+
+        let settings = DownloadSettings {
+            rate_limit: Some("quick".into()),
+            ..Default::default()
+        };
+        assert!(settings.rate_limit().is_err());
+    }
+}
@@ -104,6 +104,13 @@ pub enum ConfigError {
     )]
     MissingPubkey(String),
 
+    #[error("Invalid minimum release age: '{0}'")]
+    #[diagnostic(
+        code(soar_config::invalid_release_age),
+        help("Give a duration such as \"3d\", \"12h\" or \"1d12h\"")
+    )]
+    InvalidReleaseAge(String),
+
     #[error(transparent)]
     #[diagnostic(code(soar_config::io))]
     IoError(#[from] std::io::Error),
@@ -1,9 +1,11 @@
 pub mod annotations;
 pub mod config;
 pub mod display;
+pub mod download;
 pub mod error;
 pub mod packages;
 pub mod profile;
+pub mod release;
 pub mod repository;
 pub mod utils;
 
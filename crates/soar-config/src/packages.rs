@@ -7,13 +7,18 @@ use std::{
 
 use documented::{Documented, DocumentedFields};
 use serde::{Deserialize, Serialize};
-use soar_utils::path::xdg_config_home;
+use soar_utils::{
+    error::VersionResult,
+    path::xdg_config_home,
+    version::{is_constraint, VersionReq},
+};
 use toml_edit::DocumentMut;
 use tracing::info;
 
 use crate::{
     annotations::annotate_toml_table,
     error::{ConfigError, Result},
+    release::{parse_release_age, Channel},
 };
 
 /// Path to the packages configuration file
@@ -86,6 +91,28 @@ pub struct BinaryMapping {
     /// Name for the symlink in the bin directory.
     /// If not specified, uses the filename from source.
     pub link_as: Option<String>,
+
+    /// Environment variables to set when the binary runs.
+    /// Values are expanded by the shell at run time; `$INSTALL_DIR` is the
+    /// package's install directory.
+    pub env: Option<HashMap<String, String>>,
+
+    /// Arguments passed to the binary ahead of the ones it is run with.
+    pub args: Option<Vec<String>>,
+
+    /// Link the binary through an exec wrapper script instead of a symlink.
+    /// Implied by `env` and `args`.
+    #[serde(default)]
+    pub wrapper: bool,
+}
+
+impl BinaryMapping {
+    /// Whether the binary needs a wrapper script rather than a symlink.
+    pub fn wants_wrapper(&self) -> bool {
+        self.wrapper
+            || self.env.as_ref().is_some_and(|env| !env.is_empty())
+            || self.args.as_ref().is_some_and(|args| !args.is_empty())
+    }
 }
 
 /// Hook commands to run at various stages of package installation.
@@ -204,7 +231,8 @@ pub struct PackageOptions {
     )]
     pub pkg_id: Option<String>,
 
-    /// Specific version to install.
+    /// Version to install: an exact version, or a constraint such as `^1.4`,
+    /// `~0.9` or `">=2.0, <3"` that updates stay within.
     pub version: Option<String>,
 
     /// Repository to install from.
@@ -213,13 +241,16 @@ pub struct PackageOptions {
     /// Direct URL to download the package from (makes it a "local" package).
     pub url: Option<String>,
 
-    /// Expected BLAKE3 checksum (hex) of the downloaded artifact, for `url`/`github`/
-    /// `gitlab` packages. When set, soar verifies the download against it and refuses to
+    /// Expected BLAKE3 checksum (hex) of the downloaded artifact, for `url` packages
+    /// and those from forge releases. When set, soar verifies the download against it and refuses to
     /// install on mismatch.
     /// Without it, these user-declared sources install on implicit trust.
     /// Has no effect on registry packages, which already ship their own checksum.
     pub bsum: Option<String>,
 
+    /// Upstream signing keys the downloaded artifact must carry a signature from.
+    pub verify: Option<VerifyConfig>,
+
     /// GitHub repository in "owner/repo" format for installing from releases.
     /// When set, soar fetches the latest release and downloads the matching asset.
     pub github: Option<String>,
@@ -228,14 +259,34 @@ pub struct PackageOptions {
     /// When set, soar fetches the latest release and downloads the matching asset.
     pub gitlab: Option<String>,
 
+    /// Gitea or Forgejo repository in "host/owner/repo" format, or "owner/repo"
+    /// on Codeberg, for installing from releases. Codeberg reads a token from
+    /// `GITEA_TOKEN` or `FORGEJO_TOKEN`, and any other instance only from
+    /// `GITEA_TOKEN_<HOST>` or `FORGEJO_TOKEN_<HOST>`.
+    pub gitea: Option<String>,
+
+    /// SourceHut repository in "~owner/repo" format. Its tags are the releases,
+    /// and the artifacts uploaded to them the assets.
+    pub sourcehut: Option<String>,
+
     /// Glob pattern to match release asset filename (e.g., "*linux*.AppImage").
-    /// Required when github/gitlab is set to select the correct asset.
+    /// Required when a forge source (github, gitlab, gitea, sourcehut) is set to
+    /// select the correct asset.
     pub asset_pattern: Option<String>,
 
     /// Whether to include pre-release versions when using github/gitlab sources.
+    /// Same as `channel = "prerelease"`, which takes precedence when both are set.
     #[serde(default)]
     pub include_prerelease: Option<bool>,
 
+    /// Release channel to follow from github/gitlab sources: "stable" (default),
+    /// "prerelease", or "nightly" for rolling builds.
+    pub channel: Option<Channel>,
+
+    /// How long a version must have been out before it is installed or updated to
+    /// (e.g., "3d", "12h"). Overrides the repository's `min_release_age`.
+    pub min_release_age: Option<String>,
+
     /// Glob pattern to match release tag names (e.g., "v*-stable", "nightly-*").
     /// If not set, the first matching release is used.
     pub tag_pattern: Option<String>,
@@ -245,6 +296,29 @@ pub struct PackageOptions {
     /// If not set and github/gitlab is used, version is fetched from releases API.
     pub version_command: Option<String>,
 
+    /// URL whose response names the latest version, read by soar itself rather than
+    /// through a shell. Takes precedence over `version_command`. Without
+    /// `version_regex`, `version_json` or `version_selector`, the whole response,
+    /// trimmed, is the version.
+    pub version_url: Option<String>,
+
+    /// Regex finding the version in the `version_url` response, or in what
+    /// `version_json`/`version_selector` picked out of it. The `version` named group
+    /// is taken, else the first group, else the whole match.
+    pub version_regex: Option<String>,
+
+    /// JSON pointer (e.g., "/tag_name") or JSONPath (e.g., "$[*].name") to the
+    /// version in a JSON `version_url` response.
+    pub version_json: Option<String>,
+
+    /// Element selector to the version in an HTML page or Atom/RSS feed
+    /// (e.g., "entry > title", "a.tag@href").
+    pub version_selector: Option<String>,
+
+    /// Download URL with `{version}`, `{arch}` and `{os}` placeholders, filled in with
+    /// the version found by `version_url` or `version_command`. Falls back to `url`.
+    pub url_template: Option<String>,
+
     /// Package type for URL installs (e.g., appimage, flatimage, archive).
     pub pkg_type: Option<String>,
 
@@ -299,6 +373,22 @@ pub struct PackageOptions {
     pub arch_map: Option<HashMap<String, String>>,
 }
 
+/// Keys an upstream signs its artifacts with.
+///
+/// The detached signature is looked for beside the downloaded artifact, and
+/// the artifact is checked against it before anything is extracted. With both
+/// keys set, both signatures have to hold.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
+pub struct VerifyConfig {
+    /// OpenPGP public key, ASCII-armored, or a path to a file holding one.
+    /// The signature is fetched from `<url>.asc`, `<url>.sig` or `<url>.gpg`.
+    pub pgp_key: Option<String>,
+
+    /// Cosign public key in PEM form, or a path to a file holding one. The
+    /// signature is fetched from `<url>.sig` or `<url>.bundle`.
+    pub cosign_key: Option<String>,
+}
+
 /// Portable directory configuration for a package.
 #[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
 pub struct PortableConfig {
@@ -332,12 +422,22 @@ pub struct ResolvedPackage {
     pub repo: Option<String>,
     pub url: Option<String>,
     pub bsum: Option<String>,
+    pub verify: Option<VerifyConfig>,
     pub github: Option<String>,
     pub gitlab: Option<String>,
+    pub gitea: Option<String>,
+    pub sourcehut: Option<String>,
     pub asset_pattern: Option<String>,
     pub include_prerelease: Option<bool>,
+    pub channel: Option<Channel>,
+    pub min_release_age: Option<String>,
     pub tag_pattern: Option<String>,
     pub version_command: Option<String>,
+    pub version_url: Option<String>,
+    pub version_regex: Option<String>,
+    pub version_json: Option<String>,
+    pub version_selector: Option<String>,
+    pub url_template: Option<String>,
     pub pkg_type: Option<String>,
     pub entrypoint: Option<String>,
     pub binaries: Option<Vec<BinaryMapping>>,
@@ -358,6 +458,48 @@ pub struct ResolvedPackage {
     pub arch_map: Option<HashMap<String, String>>,
 }
 
+impl ResolvedPackage {
+    /// The declared version, when it names exactly one.
+    pub fn exact_version(&self) -> Option<&str> {
+        self.version.as_deref().filter(|v| !is_constraint(v))
+    }
+
+    /// The declared version as a constraint, when it is one.
+    pub fn version_req(&self) -> Option<VersionResult<VersionReq>> {
+        self.version
+            .as_deref()
+            .filter(|v| is_constraint(v))
+            .map(VersionReq::parse)
+    }
+
+    /// Whether versions come from a forge's releases rather than a repository.
+    pub fn has_release_source(&self) -> bool {
+        self.github.is_some()
+            || self.gitlab.is_some()
+            || self.gitea.is_some()
+            || self.sourcehut.is_some()
+    }
+
+    /// The release channel followed, with `include_prerelease` read as the
+    /// prerelease channel.
+    pub fn release_channel(&self) -> Channel {
+        self.channel
+            .unwrap_or(if self.include_prerelease == Some(true) {
+                Channel::Prerelease
+            } else {
+                Channel::Stable
+            })
+    }
+
+    /// The minimum release age in milliseconds, if the package sets one.
+    pub fn min_release_age(&self) -> Result<Option<u128>> {
+        self.min_release_age
+            .as_deref()
+            .map(parse_release_age)
+            .transpose()
+    }
+}
+
 impl PackageSpec {
     /// Resolve the package specification with defaults applied.
     // Still populated while the OCI path exists; see the field's deprecation.
@@ -370,7 +512,9 @@ impl PackageSpec {
                 } else {
                     Some(version_str.clone())
                 };
-                let pinned = version.is_some();
+                // An exact version holds the package where it is; a constraint
+                // is there to let it move within bounds.
+                let pinned = version.as_deref().is_some_and(|v| !is_constraint(v));
                 ResolvedPackage {
                     name: name.to_string(),
                     family: None,
@@ -379,12 +523,22 @@ impl PackageSpec {
                     repo: None,
                     url: None,
                     bsum: None,
+                    verify: None,
                     github: None,
                     gitlab: None,
+                    gitea: None,
+                    sourcehut: None,
                     asset_pattern: None,
                     include_prerelease: None,
+                    channel: None,
+                    min_release_age: None,
                     tag_pattern: None,
                     version_command: None,
+                    version_url: None,
+                    version_regex: None,
+                    version_json: None,
+                    version_selector: None,
+                    url_template: None,
                     pkg_type: None,
                     entrypoint: None,
                     binaries: None,
@@ -404,11 +558,17 @@ impl PackageSpec {
             PackageSpec::Detailed(opts) => {
                 // Treat "*" as None (latest version)
                 let version = opts.version.as_ref().filter(|v| v.as_str() != "*").cloned();
-                // URL/GitHub/GitLab packages: only pinned if explicitly set
-                // Other packages: pinned if explicitly set or if a specific version is requested
-                let is_remote =
-                    opts.url.is_some() || opts.github.is_some() || opts.gitlab.is_some();
-                let pinned = opts.pinned || (version.is_some() && !is_remote);
+                // URL and forge release packages: only pinned if explicitly set
+                // Other packages: pinned if explicitly set or if a specific version is requested;
+                // a constraint is a range to update within, not a pin
+                let is_remote = opts.url.is_some()
+                    || opts.version_url.is_some()
+                    || opts.github.is_some()
+                    || opts.gitlab.is_some()
+                    || opts.gitea.is_some()
+                    || opts.sourcehut.is_some();
+                let pinned = opts.pinned
+                    || (version.as_deref().is_some_and(|v| !is_constraint(v)) && !is_remote);
                 ResolvedPackage {
                     name: name.to_string(),
                     family: opts.family.clone(),
@@ -417,12 +577,22 @@ impl PackageSpec {
                     repo: opts.repo.clone(),
                     url: opts.url.clone(),
                     bsum: opts.bsum.clone(),
+                    verify: opts.verify.clone(),
                     github: opts.github.clone(),
                     gitlab: opts.gitlab.clone(),
+                    gitea: opts.gitea.clone(),
+                    sourcehut: opts.sourcehut.clone(),
                     asset_pattern: opts.asset_pattern.clone(),
                     include_prerelease: opts.include_prerelease,
+                    channel: opts.channel,
+                    min_release_age: opts.min_release_age.clone(),
                     tag_pattern: opts.tag_pattern.clone(),
                     version_command: opts.version_command.clone(),
+                    version_url: opts.version_url.clone(),
+                    version_regex: opts.version_regex.clone(),
+                    version_json: opts.version_json.clone(),
+                    version_selector: opts.version_selector.clone(),
+                    url_template: opts.url_template.clone(),
                     pkg_type: opts.pkg_type.clone(),
                     entrypoint: opts.entrypoint.clone(),
                     binaries: opts.binaries.clone(),
@@ -512,6 +682,7 @@ impl PackagesConfig {
 #   package_name = "*"                    # Latest version
 #   package_name = "1.2.3"                # Specific version (pinned)
 #   package_name = { version = "1.2" }    # Same as above
+#   package_name = "^1.2"                 # Any 1.x from 1.2 on; updates stay within it
 #   package_name = { family = "pkg", repo = "bincache" }
 #   package_name = { pinned = true, portable = { home = "~/.pkg" } }
 
@@ -659,6 +830,54 @@ jq = "1.8.1"
         assert_eq!(jq.version, Some("1.8.1".to_string()));
     }
 
+    #[test]
+    fn a_version_constraint_is_not_a_pin() {
+        let toml_str = r#"
+[packages]
+jq = "1.8.1"
+rg = "^14.1"
+fd = { version = ">=9.0, <11", repo = "bincache" }
+"#;
+        let config: PackagesConfig = toml::from_str(toml_str).unwrap();
+        let resolved = config.resolved_packages();
+        let find = |name: &str| resolved.iter().find(|p| p.name == name).unwrap();
+
+        assert!(find("jq").pinned);
+        assert_eq!(find("jq").exact_version(), Some("1.8.1"));
+        assert!(find("jq").version_req().is_none());
+
+        assert!(!find("rg").pinned);
+        assert_eq!(find("rg").exact_version(), None);
+        let req = find("rg").version_req().unwrap().unwrap();
+        assert!(req.matches("14.1.1").unwrap());
+        assert!(!req.matches("15.0.0").unwrap());
+
+        assert!(!find("fd").pinned);
+        assert!(find("fd").version_req().unwrap().is_ok());
+    }
+
+    #[test]
+    fn a_channel_setting_outranks_include_prerelease() {
+        let toml_str = r#"
+[packages]
+old = { github = "a/old", asset_pattern = "*", include_prerelease = true }
+new = { github = "a/new", asset_pattern = "*", include_prerelease = true, channel = "stable" }
+edge = { github = "a/edge", asset_pattern = "*", channel = "nightly", min_release_age = "2d" }
+"#;
+        let config: PackagesConfig = toml::from_str(toml_str).unwrap();
+        let resolved = config.resolved_packages();
+        let find = |name: &str| resolved.iter().find(|p| p.name == name).unwrap();
+
+        assert_eq!(find("old").release_channel(), Channel::Prerelease);
+        assert_eq!(find("new").release_channel(), Channel::Stable);
+        assert_eq!(find("edge").release_channel(), Channel::Nightly);
+        assert_eq!(
+            find("edge").min_release_age().unwrap(),
+            Some(2 * 86_400_000)
+        );
+        assert_eq!(find("old").min_release_age().unwrap(), None);
+    }
+
     #[test]
     fn test_detailed_package_spec() {
         let toml_str = r#"
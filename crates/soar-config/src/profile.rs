@@ -2,9 +2,8 @@ use std::path::PathBuf;
 
 use documented::{Documented, DocumentedFields};
 use serde::{Deserialize, Serialize};
-use soar_utils::path::resolve_path;
 
-use crate::error::Result;
+use crate::{error::Result, utils::resolve_install_path};
 
 /// A profile defines a local package store and its configuration.
 #[derive(Clone, Deserialize, Serialize, Documented, DocumentedFields)]
@@ -31,7 +30,7 @@ impl Profile {
 
     pub fn get_packages_path(&self) -> Result<PathBuf> {
         if let Some(ref packages_path) = self.packages_path {
-            Ok(resolve_path(packages_path)?)
+            resolve_install_path(packages_path)
         } else {
             Ok(self.get_root_path()?.join("packages"))
         }
@@ -51,9 +50,9 @@ impl Profile {
 
     pub fn get_root_path(&self) -> Result<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_ROOT") {
-            return Ok(resolve_path(&env_path)?);
+            return resolve_install_path(&env_path);
         }
-        Ok(resolve_path(&self.root_path)?)
+        resolve_install_path(&self.root_path)
     }
 }
 
@@ -0,0 +1,53 @@
+//! Which releases a package may move to.
+
+use serde::{Deserialize, Serialize};
+use soar_utils::time::parse_duration;
+
+use crate::error::{ConfigError, Result};
+
+/// The kind of release a package follows from its forge.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Full releases only.
+    #[default]
+    Stable,
+    /// Full releases and prereleases alike, whichever is newest.
+    Prerelease,
+    /// Rolling builds tagged like `nightly` or `continuous`, newest first.
+    Nightly,
+}
+
+/// Read a minimum release age such as `3d` or `12h` into milliseconds.
+///
+/// Unlike a sync interval, a bad value is an error rather than a fallback:
+/// quietly ignoring it would install exactly what it was set to hold back.
+pub fn parse_release_age(value: &str) -> Result<u128> {
+    parse_duration(value.trim())
+        .filter(|_| !value.trim().is_empty())
+        .ok_or_else(|| ConfigError::InvalidReleaseAge(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_release_age_reads_like_a_sync_interval() {
+        assert_eq!(parse_release_age("3d").unwrap(), 3 * 86_400_000);
+        assert_eq!(parse_release_age("1d12h").unwrap(), 36 * 3_600_000);
+        assert!(parse_release_age("").is_err());
+        assert!(parse_release_age("three days").is_err());
+    }
+
+    #[test]
+    fn channels_are_spelled_in_lowercase() {
+        #[derive(Deserialize)]
+        struct Spec {
+            channel: Channel,
+        }
+        let spec: Spec = toml::from_str(r#"channel = "nightly""#).unwrap();
+        assert_eq!(spec.channel, Channel::Nightly);
+        assert!(toml::from_str::<Spec>(r#"channel = "beta""#).is_err());
+    }
+}
@@ -2,9 +2,9 @@ use std::path::PathBuf;
 
 use documented::{Documented, DocumentedFields};
 use serde::{Deserialize, Serialize};
-use soar_utils::time::parse_duration;
+use soar_utils::{path::resolve_path, time::parse_duration};
 
-use crate::{config::get_config, error::Result};
+use crate::{config::get_config, error::Result, release::parse_release_age};
 
 /// Defines a remote repository that provides packages.
 #[derive(Clone, Deserialize, Serialize, Documented, DocumentedFields)]
@@ -12,7 +12,8 @@ pub struct Repository {
     /// Unique name of the repository.
     pub name: String,
 
-    /// URL to the repository's metadata file.
+    /// URL to the repository's metadata file. A `file://` URL or a path
+    /// reads it from disk, and may name the directory holding it.
     pub url: String,
 
     /// Enables desktop integration for packages from this repository.
@@ -33,6 +34,11 @@ pub struct Repository {
     /// Optional sync interval (e.g., "1h", "12h", "1d").
     /// Default: "3h"
     pub sync_interval: Option<String>,
+
+    /// How long a package version must have been out before updates move to
+    /// it (e.g., "3d", "12h"), measured from its build date.
+    /// Packages can set their own in packages.toml.
+    pub min_release_age: Option<String>,
 }
 
 impl Repository {
@@ -40,6 +46,45 @@ impl Repository {
         Ok(get_config().get_repositories_path()?.join(&self.name))
     }
 
+    /// Where the metadata lives on disk, for a repository given as a
+    /// `file://` URL or a path rather than served over http(s).
+    pub fn local_path(&self) -> Option<PathBuf> {
+        local_source_path(&self.url)
+    }
+
+    /// Resolve a URL from the repository's index against where the index is
+    /// published, so an index can name its artifacts relative to itself and
+    /// be moved or mirrored along with them.
+    ///
+    /// Absolute URLs are returned as they are.
+    pub fn resolve_url(&self, url: &str) -> String {
+        if url.is_empty() || url.contains("://") {
+            return url.to_string();
+        }
+
+        if let Some(path) = self.local_path() {
+            let base = if path.is_dir() {
+                path.as_path()
+            } else {
+                path.parent().unwrap_or(&path)
+            };
+            return format!("file://{}", base.join(url).display());
+        }
+
+        let base = self.url.trim();
+        let base = base.split(['?', '#']).next().unwrap_or(base);
+        if let Some(path) = url.strip_prefix('/') {
+            // Rooted at the host the index is served from.
+            let origin_end = base
+                .find("://")
+                .and_then(|scheme| base[scheme + 3..].find('/').map(|i| scheme + 3 + i))
+                .unwrap_or(base.len());
+            return format!("{}/{path}", &base[..origin_end]);
+        }
+        let dir = base.rsplit_once('/').map_or(base, |(dir, _)| dir);
+        format!("{dir}/{}", url.strip_prefix("./").unwrap_or(url))
+    }
+
     pub fn is_enabled(&self) -> bool {
         self.enabled.unwrap_or(true)
     }
@@ -69,6 +114,27 @@ impl Repository {
             value => parse_duration(value).unwrap_or(3 * 3_600_000),
         }
     }
+
+    /// The minimum release age in milliseconds, if one is set.
+    pub fn min_release_age(&self) -> Result<Option<u128>> {
+        self.min_release_age
+            .as_deref()
+            .map(parse_release_age)
+            .transpose()
+    }
+}
+
+/// Resolves a repository URL to a filesystem path when it is a local source
+/// (a `file://` URL or a path), or `None` for http(s) URLs.
+pub fn local_source_path(url: &str) -> Option<PathBuf> {
+    let url = url.trim();
+    if let Some(rest) = url.strip_prefix("file://") {
+        return resolve_path(rest).ok();
+    }
+    if url.starts_with(['/', '~', '.', '$']) {
+        return resolve_path(url).ok();
+    }
+    None
 }
 
 #[derive(Default)]
@@ -112,6 +178,7 @@ mod tests {
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            min_release_age: None,
         };
 
         assert!(repo.is_enabled());
@@ -129,6 +196,52 @@ mod tests {
         assert!(default.is_enabled());
     }
 
+    #[test]
+    fn relative_urls_resolve_against_the_index() {
+        let repo = Repository {
+            name: "test".to_string(),
+            url: "https://example.com/repo/metadata.db.zst?v=2".to_string(),
+            desktop_integration: None,
+            pubkey: None,
+            enabled: None,
+            signature_verification: None,
+            sync_interval: None,
+            min_release_age: None,
+        };
+        assert_eq!(
+            repo.resolve_url("./tool-1.0"),
+            "https://example.com/repo/tool-1.0"
+        );
+        assert_eq!(
+            repo.resolve_url("/files/tool-1.0"),
+            "https://example.com/files/tool-1.0"
+        );
+        assert_eq!(
+            repo.resolve_url("https://cdn.example.com/tool"),
+            "https://cdn.example.com/tool"
+        );
+
+        let dir = std::env::temp_dir();
+        let local = Repository {
+            url: format!("file://{}", dir.display()),
+            ..repo.clone()
+        };
+        assert_eq!(
+            local.resolve_url("tool-1.0"),
+            format!("file://{}", dir.join("tool-1.0").display())
+        );
+
+        let index = Repository {
+            url: dir.join("index.json").display().to_string(),
+            ..repo
+        };
+        assert_eq!(
+            index.resolve_url("bin/tool"),
+            format!("file://{}", dir.join("bin/tool").display())
+        );
+        assert_eq!(index.local_path(), Some(dir.join("index.json")));
+    }
+
     #[test]
     fn test_repository_sync_interval() {
         let repo = Repository {
@@ -139,6 +252,7 @@ mod tests {
             enabled: Some(true),
             signature_verification: None,
             sync_interval: Some("always".to_string()),
+            min_release_age: None,
         };
 
         assert_eq!(repo.sync_interval(), 0);
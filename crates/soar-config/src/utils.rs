@@ -1,3 +1,9 @@
+use std::path::PathBuf;
+
+use soar_utils::path::{on_host, resolve_path};
+
+use crate::error::Result;
+
 pub fn default_install_patterns() -> Vec<String> {
     ["!*.log", "!SBUILD", "!*.json", "!*.version"]
         .into_iter()
@@ -5,6 +11,12 @@ pub fn default_install_patterns() -> Vec<String> {
         .collect::<Vec<String>>()
 }
 
+/// Resolve a configured location for installed files to where it lies from
+/// here, under the install root if one was given.
+pub(crate) fn resolve_install_path(path: &str) -> Result<PathBuf> {
+    Ok(on_host(resolve_path(path)?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,152 @@
+//! Cross-repository metadata queries: one connection per repository, merged
+//! in Rust, against every repository attached to a single connection.
+//!
+//! Run with `cargo bench -p soar-core --bench metadata_queries`.
+
+use std::{hint::black_box, path::Path};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use diesel::Connection;
+use soar_core::database::connection::MetadataManager;
+use soar_db::{
+    connection::DbConnection,
+    migration::DbType,
+    models::metadata::{NewPackage, Package},
+    repository::{metadata::MetadataRepository, unified::UnifiedRepository},
+};
+
+const PACKAGES_PER_REPO: usize = 2_000;
+const REPO_COUNTS: [usize; 3] = [4, 16, 64];
+const PER_PAGE: i64 = 50;
+
+fn write_repo(path: &Path, repo: usize) {
+    let mut db = DbConnection::open(path, DbType::Metadata).unwrap();
+    db.conn()
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            for idx in 0..PACKAGES_PER_REPO {
+                // Names overlap between repositories, as mirrors' do.
+                let name = format!("pkg-{:05}", (idx * 7 + repo * 13) % (PACKAGES_PER_REPO * 2));
+                let package = NewPackage {
+                    pkg_name: &name,
+                    version: "1.0.0",
+                    download_url: "https://example.com/pkg",
+                    description: Some("a synthetic package"),
+                    ..Default::default()
+                };
+                MetadataRepository::insert(conn, &package)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+}
+
+fn manager(dir: &Path, repos: usize) -> MetadataManager {
+    let mut manager = MetadataManager::new();
+    for repo in 0..repos {
+        let path = dir.join(format!("repo-{repo}.db"));
+        if !path.exists() {
+            write_repo(&path, repo);
+        }
+        manager.add_repo(&format!("repo-{repo}"), &path).unwrap();
+    }
+    manager
+}
+
+fn bench_metadata_queries(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut list = c.benchmark_group("list_all");
+    for repos in REPO_COUNTS {
+        let mgr = manager(dir.path(), repos);
+        list.bench_with_input(BenchmarkId::new("per_repo", repos), &mgr, |b, mgr| {
+            b.iter(|| {
+                let mut rows = mgr
+                    .query_all_flat(|repo_name, conn| {
+                        Ok(MetadataRepository::list_all_minimal(conn)?
+                            .into_iter()
+                            .map(|pkg| (repo_name.to_string(), pkg))
+                            .collect())
+                    })
+                    .unwrap();
+                rows.sort_by(|a, b| a.1.pkg_name.cmp(&b.1.pkg_name).then(a.0.cmp(&b.0)));
+                black_box(rows)
+            })
+        });
+        list.bench_with_input(BenchmarkId::new("unified", repos), &mgr, |b, mgr| {
+            b.iter(|| {
+                black_box(
+                    mgr.query_unified_merged(UnifiedRepository::list_all_minimal, |a, b| {
+                        a.1.pkg_name.cmp(&b.1.pkg_name).then(a.0.cmp(&b.0))
+                    })
+                    .unwrap(),
+                )
+            })
+        });
+    }
+    list.finish();
+
+    // A page from the middle: without the view every repository has to give
+    // up everything before it, since any of them may own the rows it holds.
+    let mut page = c.benchmark_group("page");
+    for repos in REPO_COUNTS {
+        let mgr = manager(dir.path(), repos);
+        let offset = (repos * PACKAGES_PER_REPO) as i64 / 2;
+        page.bench_with_input(BenchmarkId::new("per_repo", repos), &mgr, |b, mgr| {
+            b.iter(|| {
+                let end = offset + PER_PAGE;
+                let mut rows: Vec<(String, Package)> = mgr
+                    .query_all_flat(|repo_name, conn| {
+                        Ok(MetadataRepository::list_paginated(conn, 1, end)?
+                            .into_iter()
+                            .map(|pkg| (repo_name.to_string(), pkg))
+                            .collect())
+                    })
+                    .unwrap();
+                rows.sort_by(|a, b| a.1.pkg_name.cmp(&b.1.pkg_name).then(a.0.cmp(&b.0)));
+                black_box(
+                    rows.drain(offset as usize..)
+                        .take(PER_PAGE as usize)
+                        .count(),
+                )
+            })
+        });
+        page.bench_with_input(BenchmarkId::new("unified", repos), &mgr, |b, mgr| {
+            b.iter(|| {
+                black_box(
+                    mgr.query_unified_page(
+                        offset,
+                        PER_PAGE,
+                        UnifiedRepository::list_paginated,
+                        UnifiedRepository::cmp_rows,
+                    )
+                    .unwrap(),
+                )
+            })
+        });
+    }
+    page.finish();
+
+    let mut count = c.benchmark_group("count_names");
+    for repos in REPO_COUNTS {
+        let mgr = manager(dir.path(), repos);
+        count.bench_with_input(BenchmarkId::new("per_repo", repos), &mgr, |b, mgr| {
+            b.iter(|| {
+                black_box(
+                    mgr.query_all(|_, conn| MetadataRepository::count_names(conn))
+                        .unwrap(),
+                )
+            })
+        });
+        count.bench_with_input(BenchmarkId::new("unified", repos), &mgr, |b, mgr| {
+            b.iter(|| black_box(mgr.query_unified(UnifiedRepository::count_names).unwrap()))
+        });
+    }
+    count.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = bench_metadata_queries
+}
+criterion_main!(benches);
@@ -1,12 +1,16 @@
 //! Database connection management.
 
 use std::{
-    path::Path,
-    sync::{Arc, Mutex},
+    cmp::Ordering,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
 };
 
 use diesel::Connection as DieselConnection;
-use soar_db::{connection::DbConnection, migration::DbType};
+use soar_db::{
+    connection::{AttachedDb, DbConnection},
+    migration::DbType,
+};
 use tracing::{debug, trace};
 
 use crate::error::SoarError;
@@ -57,6 +61,21 @@ impl DieselDatabase {
         })
     }
 
+    /// Opens read-only connections over several metadata databases, as few
+    /// as SQLite allows; see [`DbConnection::open_unified_groups`].
+    pub fn open_unified_groups(dbs: &[AttachedDb]) -> Result<Vec<Self>> {
+        let groups = DbConnection::open_unified_groups(dbs)
+            .map_err(|e| SoarError::Custom(format!("opening unified metadata: {}", e)))?;
+        Ok(groups
+            .into_iter()
+            .map(|conn| {
+                Self {
+                    conn: Arc::new(Mutex::new(conn)),
+                }
+            })
+            .collect())
+    }
+
     /// Gets a mutable reference to the underlying connection.
     /// Locks the mutex and returns a guard.
     pub fn conn(&self) -> Result<std::sync::MutexGuard<'_, DbConnection>> {
@@ -97,24 +116,41 @@ impl Clone for DieselDatabase {
     }
 }
 
+/// A repository's metadata database as the manager opened it.
+struct RepoSource {
+    path: PathBuf,
+    readonly: bool,
+}
+
 /// Manager for multiple metadata databases (one per repository).
-/// Replaces the ATTACH DATABASE pattern with separate connections.
+///
+/// Each repository keeps a connection of its own for queries aimed at it.
+/// Queries across all of them go through connections with the databases
+/// attached, opened the first time one is asked for.
 pub struct MetadataManager {
     databases: Vec<(String, DieselDatabase)>,
+    sources: Vec<RepoSource>,
+    unified: OnceLock<std::result::Result<Vec<DieselDatabase>, String>>,
 }
 
 impl MetadataManager {
     pub fn new() -> Self {
         Self {
             databases: Vec::new(),
+            sources: Vec::new(),
+            unified: OnceLock::new(),
         }
     }
 
     /// Adds a metadata database for a repository.
     pub fn add_repo<P: AsRef<Path>>(&mut self, repo_name: &str, path: P) -> Result<()> {
         debug!(repo_name = repo_name, path = %path.as_ref().display(), "adding metadata database to manager");
-        let db = DieselDatabase::open_metadata(path)?;
+        let db = DieselDatabase::open_metadata(&path)?;
         self.databases.push((repo_name.to_string(), db));
+        self.sources.push(RepoSource {
+            path: path.as_ref().to_path_buf(),
+            readonly: false,
+        });
         trace!(
             repo_name = repo_name,
             total_repos = self.databases.len(),
@@ -126,8 +162,12 @@ impl MetadataManager {
     /// Adds a metadata database for a repository in read-only mode.
     pub fn add_repo_readonly<P: AsRef<Path>>(&mut self, repo_name: &str, path: P) -> Result<()> {
         debug!(repo_name = repo_name, path = %path.as_ref().display(), "adding metadata database to manager (readonly)");
-        let db = DieselDatabase::open_metadata_readonly(path)?;
+        let db = DieselDatabase::open_metadata_readonly(&path)?;
         self.databases.push((repo_name.to_string(), db));
+        self.sources.push(RepoSource {
+            path: path.as_ref().to_path_buf(),
+            readonly: true,
+        });
         trace!(
             repo_name = repo_name,
             total_repos = self.databases.len(),
@@ -173,6 +213,104 @@ impl MetadataManager {
         Ok(results)
     }
 
+    /// Runs `f` against every repository together, through the
+    /// `all_packages` view of
+    /// [`UnifiedRepository`](soar_db::repository::unified::UnifiedRepository),
+    /// and returns what it found in all of them.
+    ///
+    /// The repositories usually share one connection. When there are more
+    /// than SQLite attaches to one, `f` runs once per group of them and the
+    /// rows are appended group by group, so nothing about their order holds
+    /// across groups; [`query_unified_merged`](Self::query_unified_merged)
+    /// and [`query_unified_page`](Self::query_unified_page) keep it.
+    pub fn query_unified<F, T>(&self, f: F) -> Result<Vec<T>>
+    where
+        F: Fn(&mut diesel::SqliteConnection) -> diesel::QueryResult<Vec<T>>,
+    {
+        let mut rows = Vec::new();
+        for db in self.unified()? {
+            rows.extend(db.with_conn(&f)?);
+        }
+        Ok(rows)
+    }
+
+    /// Like [`query_unified`](Self::query_unified), for an `f` that returns
+    /// its rows in the order `cmp` describes: the groups' rows are merged so
+    /// the result is in that order over every repository.
+    pub fn query_unified_merged<F, C, T>(&self, f: F, cmp: C) -> Result<Vec<T>>
+    where
+        F: Fn(&mut diesel::SqliteConnection) -> diesel::QueryResult<Vec<T>>,
+        C: Fn(&T, &T) -> Ordering,
+    {
+        let groups = self
+            .unified()?
+            .iter()
+            .map(|db| db.with_conn(&f))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(merge_sorted(groups, &cmp, usize::MAX))
+    }
+
+    /// One page of an ordered query over every repository: `limit` rows
+    /// after the first `offset`, in the order `cmp` describes.
+    ///
+    /// `f` is handed the offset and limit to apply after its `ORDER BY`. On
+    /// one connection that is the page itself. Across several, any group may
+    /// hold rows of the page, so each is asked for everything up to its end,
+    /// the groups are merged in order, and the offset is skipped afterwards.
+    pub fn query_unified_page<F, C, T>(
+        &self,
+        offset: i64,
+        limit: i64,
+        f: F,
+        cmp: C,
+    ) -> Result<Vec<T>>
+    where
+        F: Fn(&mut diesel::SqliteConnection, i64, i64) -> diesel::QueryResult<Vec<T>>,
+        C: Fn(&T, &T) -> Ordering,
+    {
+        let groups = self.unified()?;
+        if let [db] = groups {
+            return db.with_conn(|conn| f(conn, offset, limit));
+        }
+
+        let end = offset.saturating_add(limit);
+        trace!(
+            groups = groups.len(),
+            offset,
+            limit,
+            "paging across unified connections"
+        );
+        let rows = groups
+            .iter()
+            .map(|db| db.with_conn(|conn| f(conn, 0, end)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(merge_sorted(rows, &cmp, end.max(0) as usize)
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .collect())
+    }
+
+    fn unified(&self) -> Result<&[DieselDatabase]> {
+        self.unified
+            .get_or_init(|| {
+                let attached: Vec<AttachedDb> = self
+                    .databases
+                    .iter()
+                    .zip(&self.sources)
+                    .map(|((name, _), source)| {
+                        AttachedDb {
+                            name,
+                            path: &source.path,
+                            immutable: source.readonly,
+                        }
+                    })
+                    .collect();
+                DieselDatabase::open_unified_groups(&attached).map_err(|e| e.to_string())
+            })
+            .as_deref()
+            .map_err(|e| SoarError::Custom(e.clone()))
+    }
+
     /// Queries a specific repository.
     pub fn query_repo<F, T>(&self, repo_name: &str, f: F) -> Result<Option<T>>
     where
@@ -220,3 +358,125 @@ impl Default for MetadataManager {
         Self::new()
     }
 }
+
+/// Merges runs that are each sorted by `cmp` into one sorted run, stopping
+/// after `take` rows. Ties go to the earlier run, so rows that compare equal
+/// keep the order of the runs they came from.
+fn merge_sorted<T, C>(runs: Vec<Vec<T>>, cmp: &C, take: usize) -> Vec<T>
+where
+    C: Fn(&T, &T) -> Ordering,
+{
+    let mut runs: Vec<_> = runs
+        .into_iter()
+        .map(|run| run.into_iter().peekable())
+        .collect();
+    let mut merged = Vec::new();
+    while merged.len() < take {
+        let next = runs
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, run)| run.peek().map(|row| (i, row)))
+            .min_by(|a, b| cmp(a.1, b.1))
+            .map(|(i, _)| i);
+        match next.and_then(|i| runs[i].next()) {
+            Some(row) => merged.push(row),
+            None => break,
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use soar_db::{
+        models::metadata::NewPackage,
+        repository::{metadata::MetadataRepository, unified::UnifiedRepository},
+    };
+
+    use super::*;
+
+    fn manager(dir: &Path, repos: usize) -> MetadataManager {
+        let mut manager = MetadataManager::new();
+        for repo in 0..repos {
+            let path = dir.join(format!("repo{repo:02}.db"));
+            let mut db = DbConnection::open(&path, DbType::Metadata).unwrap();
+            for idx in 0..5 {
+                // interleaved, so every page needs rows from several groups
+                let name = format!("pkg-{:02}", (idx * 7 + repo * 3) % 20);
+                let package = NewPackage {
+                    pkg_name: &name,
+                    version: "1.0",
+                    download_url: "https://example.com/pkg",
+                    ..Default::default()
+                };
+                MetadataRepository::insert(db.conn(), &package).unwrap();
+            }
+            manager.add_repo(&format!("repo{repo:02}"), &path).unwrap();
+        }
+        manager
+    }
+
+    fn names(rows: &[soar_db::models::metadata::PackageWithRepo]) -> Vec<(String, String)> {
+        rows.iter()
+            .map(|p| (p.package.pkg_name.clone(), p.repo_name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn pages_over_more_repositories_than_one_connection_holds_are_ordered_globally() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = manager(dir.path(), 12);
+        assert!(mgr.unified().unwrap().len() > 1);
+
+        let mut everything = mgr
+            .query_unified(|conn| UnifiedRepository::list_paginated(conn, 0, i64::MAX))
+            .unwrap();
+        everything.sort_by(UnifiedRepository::cmp_rows);
+        let everything = names(&everything);
+        assert_eq!(everything.len(), 60);
+
+        for (offset, limit) in [(0, 7), (13, 10), (55, 10), (70, 5)] {
+            let page = mgr
+                .query_unified_page(
+                    offset,
+                    limit,
+                    UnifiedRepository::list_paginated,
+                    UnifiedRepository::cmp_rows,
+                )
+                .unwrap();
+            let expected: Vec<_> = everything
+                .iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .cloned()
+                .collect();
+            assert_eq!(names(&page), expected, "offset {offset}, limit {limit}");
+        }
+
+        let found = mgr
+            .query_unified_page(
+                0,
+                4,
+                |conn, offset, limit| {
+                    UnifiedRepository::search_case_sensitive(conn, "pkg-0", offset, Some(limit))
+                },
+                UnifiedRepository::cmp_rows,
+            )
+            .unwrap();
+        let expected: Vec<_> = everything
+            .iter()
+            .filter(|(name, _)| name.starts_with("pkg-0"))
+            .take(4)
+            .cloned()
+            .collect();
+        assert_eq!(names(&found), expected);
+
+        let merged = mgr
+            .query_unified_merged(
+                |conn| UnifiedRepository::list_paginated(conn, 0, i64::MAX),
+                UnifiedRepository::cmp_rows,
+            )
+            .unwrap();
+        assert_eq!(names(&merged), everything);
+    }
+}
@@ -23,7 +23,11 @@ impl Display for Maintainer {
 }
 
 /// Remote package metadata from repository.
-#[derive(Debug, Clone, Default)]
+///
+/// Serializable so an interrupted operation can record exactly what it was
+/// installing.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Package {
     pub id: u64,
     pub repo_name: String,
@@ -71,6 +75,8 @@ pub struct Package {
     pub extra: Option<Vec<PackageExtra>>,
     /// What the package takes out of its artifact. Absent means all of it.
     pub files: Option<Vec<PackageFile>>,
+    /// Where the package's release notes are published, if not on a forge.
+    pub changelog_url: Option<String>,
 }
 
 impl PackageExt for Package {
@@ -304,6 +310,7 @@ impl From<soar_db::models::metadata::Package> for Package {
             portable: pkg.portable,
             extra: pkg.extra,
             files: pkg.files,
+            changelog_url: pkg.changelog_url,
         }
     }
 }
@@ -4,7 +4,7 @@ use std::error::Error;
 
 use miette::Diagnostic;
 use soar_config::error::ConfigError;
-use soar_utils::error::{FileSystemError, HashError, PathError};
+use soar_utils::error::{FileSystemError, HashError, PathError, VersionError};
 use thiserror::Error;
 
 /// Core error type for soar package manager operations.
@@ -37,6 +37,10 @@ pub enum SoarError {
     #[diagnostic(transparent)]
     PathError(#[from] PathError),
 
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    VersionError(#[from] VersionError),
+
     #[error("Error while {action}")]
     #[diagnostic(code(soar::io), help("Check file permissions and disk space"))]
     IoError {
@@ -117,6 +121,13 @@ pub enum SoarError {
     )]
     InvalidChecksum,
 
+    #[error("Signature verification failed for {artifact}: {reason}")]
+    #[diagnostic(
+        code(soar::signature),
+        help("The artifact is not signed by the key in `verify`. Check the key, or ask upstream.")
+    )]
+    SignatureInvalid { artifact: String, reason: String },
+
     #[error("Invalid package query: {0}")]
     #[diagnostic(
         code(soar::invalid_query),
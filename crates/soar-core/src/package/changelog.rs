@@ -0,0 +1,273 @@
+//! Release notes for the versions an update would move through.
+//!
+//! Notes come from one of two places: the releases a forge publishes, each
+//! carrying its own body, or a changelog document a repository index points
+//! at, which is one file with a heading per version. Either way the result
+//! is the same list, newest first, so the two read alike.
+
+use std::{fs, path::Path};
+
+use soar_dl::http::Http;
+use soar_utils::version::{compare_versions, is_newer};
+
+use crate::{
+    error::{ErrorContext, SoarError},
+    package::release_age::parse_published,
+    SoarResult,
+};
+
+/// What a project published about one version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReleaseNotes {
+    /// The version, as the release or heading names it.
+    pub version: String,
+    /// When the version was published, if the source says.
+    pub published_at: Option<String>,
+    /// The notes themselves, as markdown.
+    pub body: Option<String>,
+    /// Where the notes can be read in full.
+    pub url: Option<String>,
+}
+
+/// Whether the notes for `version` belong between `installed` and
+/// `candidate`.
+///
+/// The candidate's own notes always do. Nothing is installed when a package
+/// is only being looked at, and then only the candidate's notes are wanted:
+/// everything before it is history rather than news.
+pub fn in_range(version: &str, installed: Option<&str>, candidate: &str) -> bool {
+    if same_version(version, candidate) {
+        return true;
+    }
+    installed.is_some_and(|installed| is_newer(version, installed) && !is_newer(version, candidate))
+}
+
+/// Whether two spellings name one version, with or without a leading `v`.
+///
+/// Compared as text rather than by order: ordering holds any two commit
+/// hashes equal, which would pass one build's notes off as another's.
+pub fn same_version(a: &str, b: &str) -> bool {
+    a.strip_prefix(['v', 'V']).unwrap_or(a) == b.strip_prefix(['v', 'V']).unwrap_or(b)
+}
+
+/// The version a changelog heading is about, if it is about one.
+///
+/// Headings are written every which way, `## [1.2.0] - 2026-01-04`,
+/// `# Version 1.2.0` or `### v1.2.0 (2026-01-04)`, so the first word that
+/// reads as a dotted version is taken. A date beside it is not one.
+fn heading_version(title: &str) -> Option<&str> {
+    title
+        .split(|c: char| c.is_whitespace() || "[]()".contains(c))
+        .find(|word| {
+            let bare = word.strip_prefix(['v', 'V']).unwrap_or(word);
+            bare.starts_with(|c: char| c.is_ascii_digit())
+                && bare.contains('.')
+                && parse_published(bare).is_none()
+        })
+}
+
+/// The date a changelog heading gives its version, if it gives one.
+fn heading_date(title: &str) -> Option<&str> {
+    title
+        .split(|c: char| c.is_whitespace() || "[]()".contains(c))
+        .find(|word| parse_published(word).is_some())
+}
+
+/// The sections of a markdown changelog for versions between `installed`
+/// and `candidate`, newest first.
+///
+/// A section runs from a versioned heading to the next heading at its level
+/// or above, so subheadings such as `### Fixed` stay inside it. The level is
+/// taken from the first versioned heading, which keeps a document title
+/// above them from being read as a section.
+pub fn parse_changelog(text: &str, installed: Option<&str>, candidate: &str) -> Vec<ReleaseNotes> {
+    let mut notes: Vec<ReleaseNotes> = Vec::new();
+    let mut level = None;
+    let mut current: Option<(ReleaseNotes, Vec<&str>)> = None;
+
+    let finish = |current: Option<(ReleaseNotes, Vec<&str>)>, notes: &mut Vec<ReleaseNotes>| {
+        if let Some((mut section, lines)) = current {
+            let body = lines.join("\n").trim().to_string();
+            section.body = (!body.is_empty()).then_some(body);
+            notes.push(section);
+        }
+    };
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let depth = trimmed.chars().take_while(|&c| c == '#').count();
+        let is_heading = depth > 0 && trimmed[depth..].starts_with(' ');
+
+        if is_heading && level.is_none_or(|level| depth <= level) {
+            let title = trimmed[depth..].trim();
+            let version = heading_version(title);
+            if version.is_some() && level.is_none() {
+                level = Some(depth);
+            }
+            finish(current.take(), &mut notes);
+            if level == Some(depth) {
+                current = version
+                    .filter(|version| in_range(version, installed, candidate))
+                    .map(|version| {
+                        let section = ReleaseNotes {
+                            version: version.to_string(),
+                            published_at: heading_date(title).map(str::to_string),
+                            body: None,
+                            url: None,
+                        };
+                        (section, Vec::new())
+                    });
+            }
+            continue;
+        }
+
+        if let Some((_, lines)) = current.as_mut() {
+            lines.push(line);
+        }
+    }
+    finish(current, &mut notes);
+
+    notes.sort_by(|a, b| compare_versions(&b.version, &a.version));
+    notes
+}
+
+/// Fetch a changelog document, from disk when the URL is a local one.
+///
+/// The URL comes from a repository's index, so a file on disk is only read
+/// when `local` says that repository is on disk itself. An index served over
+/// the network could otherwise name any file the user can read, and have it
+/// printed or handed to a `soar serve` client as release notes.
+pub fn fetch_changelog(url: &str, local: bool) -> SoarResult<String> {
+    let remote = url.starts_with("https://") || url.starts_with("http://");
+    if !local && !remote {
+        return Err(SoarError::Custom(format!(
+            "changelog {url} is not an http(s) URL, and the repository naming it is not local"
+        )));
+    }
+    if let Some(path) = url.strip_prefix("file://") {
+        return fs::read_to_string(path).with_context(|| format!("reading changelog {path}"));
+    }
+    if !url.contains("://") {
+        return fs::read_to_string(Path::new(url))
+            .with_context(|| format!("reading changelog {url}"));
+    }
+    Ok(Http::text(url)?)
+}
+
+/// The notes a changelog at `url` has for versions between `installed` and
+/// `candidate`. `local` is as for [`fetch_changelog`].
+///
+/// A changelog that has none for them, perhaps because it is not laid out
+/// with a heading per version, is still worth pointing at, so the answer is
+/// then the URL alone under the candidate's version.
+pub fn changelog_notes(
+    url: &str,
+    local: bool,
+    installed: Option<&str>,
+    candidate: &str,
+) -> SoarResult<Vec<ReleaseNotes>> {
+    let text = fetch_changelog(url, local)?;
+    let mut notes = parse_changelog(&text, installed, candidate);
+    if notes.is_empty() {
+        notes.push(ReleaseNotes {
+            version: candidate.to_string(),
+            published_at: None,
+            body: None,
+            url: None,
+        });
+    }
+    for section in &mut notes {
+        section.url = Some(url.to_string());
+    }
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANGELOG: &str = "\
+# Changelog
+
+## [Unreleased]
+
+- Not out yet
+
+## [1.3.0] - 2026-10-02
+
+### Added
+
+- Colour output
+
+## v1.2.1 (2026-09-20)
+
+- Fixed a crash
+
+## 1.2.0
+
+- First stable release
+";
+
+    fn versions(notes: &[ReleaseNotes]) -> Vec<&str> {
+        notes.iter().map(|n| n.version.as_str()).collect()
+    }
+
+    #[test]
+    fn only_versions_after_the_installed_one_are_news() {
+        assert!(in_range("1.3.0", Some("1.2.0"), "1.3.0"));
+        assert!(in_range("v1.2.1", Some("1.2.0"), "1.3.0"));
+        assert!(!in_range("1.2.0", Some("1.2.0"), "1.3.0"));
+        assert!(!in_range("1.4.0", Some("1.2.0"), "1.3.0"));
+        assert!(in_range("v1.3.0", None, "1.3.0"));
+        assert!(!in_range("1.2.1", None, "1.3.0"));
+    }
+
+    #[test]
+    fn changelog_sections_keep_their_subheadings() {
+        let notes = parse_changelog(CHANGELOG, Some("1.2.0"), "1.3.0");
+        assert_eq!(versions(&notes), ["1.3.0", "v1.2.1"]);
+        assert_eq!(notes[0].published_at.as_deref(), Some("2026-10-02"));
+        assert_eq!(
+            notes[0].body.as_deref(),
+            Some("### Added\n\n- Colour output")
+        );
+        assert_eq!(notes[1].published_at.as_deref(), Some("2026-09-20"));
+        assert_eq!(notes[1].body.as_deref(), Some("- Fixed a crash"));
+    }
+
+    #[test]
+    fn a_package_looked_at_gets_its_own_notes_only() {
+        let notes = parse_changelog(CHANGELOG, None, "1.2.0");
+        assert_eq!(versions(&notes), ["1.2.0"]);
+        assert_eq!(notes[0].body.as_deref(), Some("- First stable release"));
+    }
+
+    #[test]
+    fn a_changelog_without_the_version_is_pointed_at() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("NEWS");
+        fs::write(&path, "Lots of things changed.\n").unwrap();
+        let url = format!("file://{}", path.display());
+
+        let notes = changelog_notes(&url, true, Some("1.0"), "2.0").unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].version, "2.0");
+        assert_eq!(notes[0].body, None);
+        assert_eq!(notes[0].url.as_deref(), Some(url.as_str()));
+    }
+
+    #[test]
+    fn a_remote_index_cannot_point_at_files_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secret");
+        fs::write(&path, "## 2.0\n\nnot for the index to read\n").unwrap();
+
+        for url in [
+            format!("file://{}", path.display()),
+            path.display().to_string(),
+        ] {
+            assert!(fetch_changelog(&url, false).is_err(), "{url}");
+            assert!(fetch_changelog(&url, true).is_ok(), "{url}");
+        }
+    }
+}
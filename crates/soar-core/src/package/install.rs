@@ -12,26 +12,26 @@ use std::{
 use chrono::Utc;
 use serde_json::json;
 use soar_config::{
-    config::Config,
-    packages::{BinaryMapping, BuildConfig, PackageHooks, SandboxConfig},
+    config::{get_current_profile, Config},
+    packages::{BinaryMapping, BuildConfig, PackageHooks, SandboxConfig, VerifyConfig},
 };
 use soar_db::{
-    models::types::PackageFile,
+    models::{core::FileKind, types::PackageFile},
     repository::core::{CoreRepository, InstalledPackageWithPortable, NewInstalledPackage},
 };
 use soar_dl::{
+    checksum::{Algorithm, Checksum},
     download::Download,
     error::DownloadError,
     filter::Filter,
     oci::OciDownload,
     types::{OverwriteMode, Progress},
 };
-use soar_events::{BuildStage, EventSinkHandle, InstallStage, OperationId, SoarEvent};
+use soar_events::{BuildStage, EventSinkHandle, InstallStage, OperationId, SoarEvent, VerifyStage};
 use soar_utils::{
     error::FileSystemResult,
     fs::{safe_remove, walk_dir},
-    hash::calculate_checksum,
-    path::is_safe_component,
+    path::{is_safe_component, on_host},
 };
 use tracing::{debug, trace, warn};
 
@@ -40,7 +40,8 @@ use crate::{
     database::{connection::DieselDatabase, models::Package},
     error::{ErrorContext, SoarError},
     package::{
-        local::local_path_from_url, remove::remove_provide_symlinks, update_info::UpdateInfo,
+        local::local_path_from_url, remove::remove_provide_symlinks, signature,
+        update_info::UpdateInfo,
     },
     utils::get_extract_dir,
     SoarResult,
@@ -68,7 +69,13 @@ async fn install_extras(package: &Package, install_dir: &Path) -> SoarResult<()>
         let mut dl = Download::new(&e.url)
             .output(dest.to_string_lossy())
             .overwrite(OverwriteMode::Skip);
-        if let Some(sum) = e.blake3.as_ref() {
+        // An index may only have a SHA-256 to offer; it holds the file to the
+        // same standard.
+        let sum = e
+            .blake3
+            .clone()
+            .or_else(|| e.sha256.as_ref().map(|hex| format!("sha256:{hex}")));
+        if let Some(sum) = sum {
             dl = dl.checksum(sum);
         }
         match dl.execute() {
@@ -92,6 +99,17 @@ async fn install_extras(package: &Package, install_dir: &Path) -> SoarResult<()>
     Ok(())
 }
 
+/// The checksum of `path`, taken with the algorithm `expected` names so the
+/// two compare as strings. Blake3 when there is nothing to match.
+fn checksum_like(path: &Path, expected: Option<&str>) -> SoarResult<String> {
+    let algorithm = expected
+        .and_then(Checksum::parse)
+        .map_or(Algorithm::Blake3, |sum| sum.algorithm);
+    let sum = Checksum::of_file(path, algorithm)
+        .with_context(|| format!("calculating checksum of {}", path.display()))?;
+    Ok(sum.to_string())
+}
+
 /// Lay the package out as its recipe describes: each listed file at its own
 /// path, aliases beside it, and nothing else kept.
 ///
@@ -451,6 +469,7 @@ pub struct PackageInstaller {
     sandbox: Option<SandboxConfig>,
     arch_map: Option<std::collections::HashMap<String, String>>,
     zsync: Option<ZsyncSeed>,
+    verify: Option<VerifyConfig>,
     events: EventSinkHandle,
     op_id: OperationId,
 }
@@ -467,9 +486,13 @@ pub struct ZsyncSeed {
     pub seed: PathBuf,
 }
 
-#[derive(Clone, Default, Debug)]
+/// Serializable without the state it was resolved against: what is installed
+/// and what a zsync seed can offer are looked up again when it is read back.
+#[derive(Clone, Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct InstallTarget {
     pub package: Package,
+    #[serde(skip)]
     pub existing_install: Option<crate::database::models::InstalledPackage>,
     pub pinned: bool,
     pub profile: Option<String>,
@@ -487,7 +510,10 @@ pub struct InstallTarget {
     pub sandbox: Option<SandboxConfig>,
     pub arch_map: Option<std::collections::HashMap<String, String>>,
     /// Set when the new artifact can be rebuilt from the installed one.
+    #[serde(skip)]
     pub zsync: Option<ZsyncSeed>,
+    /// Upstream keys the artifact must be signed with.
+    pub verify: Option<VerifyConfig>,
 }
 
 impl PackageInstaller {
@@ -510,7 +536,9 @@ impl PackageInstaller {
             install_dir = %install_dir.display(),
             "creating package installer"
         );
-        let profile = config.default_profile.clone();
+        // Record the profile the install directory was chosen from, which is
+        // the one an update has to install into again.
+        let profile = target.profile.clone().unwrap_or_else(get_current_profile);
 
         // Early validation of extract_root and nested_extract paths
         if let Some(ref extract_root) = target.extract_root {
@@ -614,6 +642,7 @@ impl PackageInstaller {
             sandbox: target.sandbox.clone(),
             arch_map: target.arch_map.clone(),
             zsync: target.zsync.clone(),
+            verify: target.verify.clone(),
             events,
             op_id,
         })
@@ -887,12 +916,18 @@ impl PackageInstaller {
                 .with_context(|| format!("creating directory {}", parent.display()))?;
         }
 
-        fs::copy(src, dest)
+        // The install must not share an inode with the source: a chmod, a hook
+        // or the package itself writing to it would change the repository's
+        // copy too. A reflink is as cheap as a hard link where the filesystem
+        // has them, and everywhere else this is a plain copy.
+        fs::remove_file(dest).ok();
+        let copied = reflink_copy::reflink_or_copy(src, dest)
             .with_context(|| format!("copying {} to {}", src.display(), dest.display()))?;
+        trace!(reflinked = copied.is_none(), source = %src.display(), "placed local source");
 
         // Honor checksum pinning the same way the direct-download path does.
         if let Some(ref bsum) = self.package.bsum {
-            let actual = calculate_checksum(dest)?;
+            let actual = checksum_like(dest, Some(bsum))?;
             if &actual != bsum {
                 fs::remove_file(dest).ok();
                 return Err(SoarError::Custom(format!(
@@ -928,6 +963,32 @@ impl PackageInstaller {
         Ok(dest.to_path_buf())
     }
 
+    /// Check the downloaded artifact against the keys its entry names,
+    /// removing it when the signature does not hold.
+    fn verify_signature(
+        &self,
+        file_path: &Path,
+        url: &str,
+        verify: &VerifyConfig,
+    ) -> SoarResult<()> {
+        self.events.emit(SoarEvent::Verifying {
+            op_id: self.op_id,
+            pkg_name: self.package.pkg_name.clone(),
+            stage: VerifyStage::Signature,
+        });
+
+        if let Err(err) = signature::verify_artifact(file_path, url, verify) {
+            fs::remove_file(file_path).ok();
+            self.events.emit(SoarEvent::Verifying {
+                op_id: self.op_id,
+                pkg_name: self.package.pkg_name.clone(),
+                stage: VerifyStage::Failed(err.to_string()),
+            });
+            return Err(err);
+        }
+        Ok(())
+    }
+
     pub async fn download_package(&self) -> SoarResult<Option<String>> {
         debug!(
             pkg_name = self.package.pkg_name,
@@ -938,14 +999,19 @@ impl PackageInstaller {
 
         let package = &self.package;
         let output_path = self.install_dir.join(&package.pkg_name);
+        // An index may name its artifacts relative to where it is published.
+        let download_url = match self.config.get_repository(&package.repo_name) {
+            Some(repo) => repo.resolve_url(&package.download_url),
+            None => package.download_url.clone(),
+        };
 
         // fallback to download_url for repositories without ghcr
         let (url, output_path) = if let Some(ref ghcr_pkg) = self.package.ghcr_pkg {
             debug!("source: {} (OCI)", ghcr_pkg);
             (ghcr_pkg, &self.install_dir)
         } else {
-            debug!("source: {}", self.package.download_url);
-            (&self.package.download_url, &output_path.to_path_buf())
+            debug!("source: {}", download_url);
+            (&download_url, &output_path.to_path_buf())
         };
 
         if self.package.ghcr_pkg.is_some() {
@@ -1031,7 +1097,9 @@ impl PackageInstaller {
             // format by magic number and leaves non-archives alone. Relying
             // on pkg_type meant an archive published as "static" installed
             // as an unusable compressed file.
-            let should_extract = true;
+            // An artifact with an upstream signature is checked before any of
+            // it is unpacked, so extraction waits until then.
+            let should_extract = self.verify.is_none();
 
             let file_path = if let Some(seed) = self.zsync.clone() {
                 trace!(
@@ -1071,10 +1139,24 @@ impl PackageInstaller {
                 dl.execute()?
             };
 
+            if let Some(ref verify) = self.verify {
+                let file_path = Path::new(&file_path);
+                self.verify_signature(file_path, url, verify)?;
+                if compak::detect_from_file(file_path).is_ok() {
+                    compak::extract_archive(file_path, &extract_dir).map_err(|e| {
+                        SoarError::Custom(format!(
+                            "Failed to extract archive {}: {}",
+                            file_path.display(),
+                            e
+                        ))
+                    })?;
+                }
+            }
+
             self.run_post_download_hook()?;
 
             let checksum = if PathBuf::from(&file_path).exists() {
-                Some(calculate_checksum(&file_path)?)
+                Some(checksum_like(&file_path, self.package.bsum.as_deref())?)
             } else {
                 None
             };
@@ -1289,6 +1371,7 @@ impl PackageInstaller {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn record(
         &self,
         unlinked: bool,
@@ -1297,6 +1380,7 @@ impl PackageInstaller {
         portable_config: Option<&str>,
         portable_share: Option<&str>,
         portable_cache: Option<&str>,
+        files: &[(PathBuf, FileKind)],
     ) -> SoarResult<()> {
         debug!(
             pkg_name = self.package.pkg_name,
@@ -1338,6 +1422,9 @@ impl PackageInstaller {
             ))
         })?;
 
+        self.db
+            .with_conn(|conn| CoreRepository::replace_files(conn, record_id, files))?;
+
         // Only a local or URL install needs its source recorded; a repository
         // package is found again through the index. The update feed lives in
         // the artifact, which is in place by the time this runs.
@@ -1425,7 +1512,7 @@ impl PackageInstaller {
                 let installed_path = PathBuf::from(&alt_pkg.installed_path);
 
                 let mut remove_action = |path: &Path| -> FileSystemResult<()> {
-                    if let Ok(real_path) = fs::read_link(path) {
+                    if let Ok(real_path) = fs::read_link(path).map(on_host) {
                         if real_path.parent() == Some(&installed_path) {
                             safe_remove(path)?;
                         }
@@ -1440,7 +1527,7 @@ impl PackageInstaller {
                 }
 
                 let mut remove_action = |path: &Path| -> FileSystemResult<()> {
-                    if let Ok(real_path) = fs::read_link(path) {
+                    if let Ok(real_path) = fs::read_link(path).map(on_host) {
                         if real_path.parent() == Some(&installed_path) {
                             safe_remove(path)?;
                         }
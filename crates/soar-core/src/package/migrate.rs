@@ -0,0 +1,251 @@
+//! Moving an installed package from one profile to another.
+//!
+//! Nothing about an install names its profile except where its directory is
+//! and the row recording it, so a move is those two plus every link that
+//! points into the directory: the links are absolute and would dangle.
+
+use std::{
+    fs, io,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+};
+
+use soar_config::config::Config;
+use soar_db::repository::core::CoreRepository;
+use soar_utils::{
+    path::{in_target, on_host},
+    wrapper::retarget_wrapper,
+};
+use tracing::{debug, trace, warn};
+
+use super::remove::{make_tree_writable, shared_link_dirs};
+use crate::{
+    database::{connection::DieselDatabase, models::InstalledPackage},
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+
+/// Repoint every symlink or wrapper under `dir` that goes into `from` so it
+/// goes to the same place under `to`, returning how many were changed.
+///
+/// Links are found by where they go, as removal finds them, so this covers
+/// binaries, completions, man pages, desktop entries, icons and AppStream
+/// files alike.
+fn repoint_links(dir: &Path, from: &Path, to: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut count = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_symlink() {
+            let Ok(target) = fs::read_link(&path).map(on_host) else {
+                continue;
+            };
+            let Ok(rest) = target.strip_prefix(from) else {
+                continue;
+            };
+            let new_target = to.join(rest);
+            trace!("repointing {} -> {}", path.display(), new_target.display());
+            if fs::remove_file(&path).is_ok() && symlink(in_target(&new_target), &path).is_ok() {
+                count += 1;
+            } else {
+                warn!("failed to repoint {}", path.display());
+            }
+        } else if path.is_file() {
+            match retarget_wrapper(&path, from, to) {
+                Ok(true) => count += 1,
+                Ok(false) => {}
+                Err(err) => warn!("failed to repoint {}: {err}", path.display()),
+            }
+        } else if path.is_dir() {
+            count += repoint_links(&path, from, to);
+        }
+    }
+    count
+}
+
+/// Copy the tree at `from` to `to`, keeping symlinks as symlinks and
+/// permissions as they were.
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let source = entry.path();
+        let dest = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            symlink(fs::read_link(&source)?, &dest)?;
+        } else if file_type.is_dir() {
+            copy_tree(&source, &dest)?;
+        } else {
+            fs::copy(&source, &dest)?;
+        }
+    }
+    fs::set_permissions(to, fs::metadata(from)?.permissions())
+}
+
+/// Move a directory, copying it across when the two paths are on different
+/// filesystems and a rename cannot.
+///
+/// Profiles are commonly on different filesystems, a system-wide one beside
+/// one in the home directory, so the copy is an expected path, not a rare one.
+fn move_tree(from: &Path, to: &Path) -> SoarResult<()> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            debug!("{} is on another filesystem, copying", to.display());
+            if let Err(err) = copy_tree(from, to) {
+                // Half a copy is worse than none: the original is untouched,
+                // so drop what was written and report the failure.
+                make_tree_writable(to);
+                let _ = fs::remove_dir_all(to);
+                return Err(err)
+                    .with_context(|| format!("copying {} to {}", from.display(), to.display()));
+            }
+            make_tree_writable(from);
+            fs::remove_dir_all(from).with_context(|| format!("removing {}", from.display()))
+        }
+        Err(err) => {
+            Err(err).with_context(|| format!("moving {} to {}", from.display(), to.display()))
+        }
+    }
+}
+
+/// Move an installed package into `profile`, returning its new directory.
+///
+/// The directory keeps its name under the new profile's packages path. Links
+/// are repointed before the database row is rewritten, and a failure to
+/// rewrite the row moves everything back, so the package is never recorded
+/// somewhere it is not.
+pub fn migrate_package(
+    db: &DieselDatabase,
+    config: &Config,
+    package: &InstalledPackage,
+    profile: &str,
+) -> SoarResult<PathBuf> {
+    if package.profile == profile {
+        return Err(SoarError::Custom(format!(
+            "{} is already in profile '{profile}'",
+            package.pkg_name
+        )));
+    }
+    config.get_profile(profile)?;
+
+    let from = PathBuf::from(&package.installed_path);
+    if !from.is_dir() {
+        return Err(SoarError::Custom(format!(
+            "{} has no install directory at {}",
+            package.pkg_name,
+            from.display()
+        )));
+    }
+    let name = from
+        .file_name()
+        .ok_or_else(|| SoarError::Custom(format!("invalid install path {}", from.display())))?;
+    let packages_path = config.get_packages_path(Some(profile.to_string()))?;
+    let to = packages_path.join(name);
+    if to.exists() {
+        return Err(SoarError::Custom(format!(
+            "{} already exists in profile '{profile}'",
+            to.display()
+        )));
+    }
+
+    fs::create_dir_all(&packages_path)
+        .with_context(|| format!("creating {}", packages_path.display()))?;
+    move_tree(&from, &to)?;
+
+    let bin_path = config.get_bin_path()?;
+    let mut link_dirs = vec![bin_path.clone()];
+    link_dirs.extend(shared_link_dirs(&bin_path));
+    link_dirs.push(config.get_desktop_path()?);
+    link_dirs.push(config.get_icons_path());
+    link_dirs.push(config.get_metainfo_path());
+    // Links inside the package that spell out its own path moved with it.
+    link_dirs.push(to.clone());
+
+    let relinked: usize = link_dirs
+        .iter()
+        .map(|dir| repoint_links(dir, &from, &to))
+        .sum();
+    debug!(
+        pkg_name = package.pkg_name,
+        relinked = relinked,
+        "moved {} to {}",
+        from.display(),
+        to.display()
+    );
+
+    let recorded = db.with_conn(|conn| {
+        CoreRepository::relocate(conn, package.id as i32, profile, &to.to_string_lossy())
+    });
+    if let Err(err) = recorded {
+        warn!(
+            "recording the move failed, moving {} back",
+            package.pkg_name
+        );
+        if move_tree(&to, &from).is_ok() {
+            for dir in link_dirs.iter().take(link_dirs.len() - 1) {
+                repoint_links(dir, &to, &from);
+            }
+            repoint_links(&from, &to, &from);
+        }
+        return Err(err);
+    }
+
+    Ok(to)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn links_into_the_old_directory_follow_it_and_others_are_left_alone() {
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("a/pkg");
+        let to = dir.path().join("b/pkg");
+        let bin = dir.path().join("bin");
+        fs::create_dir_all(bin.join("nested")).unwrap();
+        symlink(from.join("tool"), bin.join("tool")).unwrap();
+        symlink(from.join("share/doc"), bin.join("nested/doc")).unwrap();
+        symlink(dir.path().join("a/other/tool"), bin.join("other")).unwrap();
+
+        assert_eq!(repoint_links(&bin, &from, &to), 2);
+        assert_eq!(fs::read_link(bin.join("tool")).unwrap(), to.join("tool"));
+        assert_eq!(
+            fs::read_link(bin.join("nested/doc")).unwrap(),
+            to.join("share/doc")
+        );
+        assert_eq!(
+            fs::read_link(bin.join("other")).unwrap(),
+            dir.path().join("a/other/tool")
+        );
+    }
+
+    #[test]
+    fn a_copied_tree_keeps_its_symlinks_and_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("from");
+        fs::create_dir_all(from.join("sub")).unwrap();
+        fs::write(from.join("tool"), b"bin").unwrap();
+        fs::set_permissions(from.join("tool"), fs::Permissions::from_mode(0o755)).unwrap();
+        symlink("tool", from.join("sub/alias")).unwrap();
+
+        let to = dir.path().join("to");
+        copy_tree(&from, &to).unwrap();
+
+        assert_eq!(fs::read(to.join("tool")).unwrap(), b"bin");
+        let mode = fs::metadata(to.join("tool")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert_eq!(
+            fs::read_link(to.join("sub/alias")).unwrap(),
+            Path::new("tool")
+        );
+    }
+}
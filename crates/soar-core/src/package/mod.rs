@@ -1,10 +1,15 @@
+pub mod changelog;
 pub mod hooks;
 pub mod install;
 pub mod local;
+pub mod migrate;
 pub mod query;
+pub mod release_age;
 pub mod release_source;
 pub mod remote_update;
 pub mod remove;
+pub mod signature;
 pub mod update;
 pub mod update_info;
 pub mod url;
+pub mod version_source;
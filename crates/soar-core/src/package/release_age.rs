@@ -0,0 +1,73 @@
+//! Holding releases back until they have been out for a while.
+//!
+//! A compromised release tends to be noticed and pulled within days, so a
+//! package that waits that long before moving to a new version rarely ever
+//! sees one. The age counts from when the version was published: a forge's
+//! `published_at`, or the `build_date` a repository index records.
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+
+/// Read a publication date in any of the forms indexes and forges write.
+pub fn parse_published(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(raw) {
+        return Some(date.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(raw, format) {
+            return Some(date.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| date.and_utc())
+}
+
+/// When a version published at `published` becomes old enough to install,
+/// or `None` if it already is.
+///
+/// A version with no date, or one that does not parse, is let through: the
+/// policy can only hold back what it can measure, and refusing every such
+/// version would stop updates from sources that never record one.
+pub fn held_until(
+    published: Option<&str>,
+    min_age: Option<u128>,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let min_age = Duration::milliseconds(i64::try_from(min_age?).unwrap_or(i64::MAX));
+    let eligible = parse_published(published?)?.checked_add_signed(min_age)?;
+    (eligible > now).then_some(eligible)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u128 = 86_400_000;
+
+    #[test]
+    fn forge_and_index_dates_both_read() {
+        let expected = parse_published("2026-10-01T12:00:00Z").unwrap();
+        assert_eq!(parse_published("2026-10-01 12:00:00"), Some(expected));
+        assert_eq!(parse_published("2026-10-01T14:00:00+02:00"), Some(expected));
+        assert!(parse_published("2026-10-01").is_some());
+        assert!(parse_published("last tuesday").is_none());
+    }
+
+    #[test]
+    fn a_young_release_is_held_until_it_has_aged() {
+        let now = parse_published("2026-10-10T00:00:00Z").unwrap();
+
+        let held = held_until(Some("2026-10-09T00:00:00Z"), Some(3 * DAY), now);
+        assert_eq!(held, parse_published("2026-10-12T00:00:00Z"));
+
+        assert_eq!(
+            held_until(Some("2026-10-01T00:00:00Z"), Some(3 * DAY), now),
+            None
+        );
+        assert_eq!(held_until(Some("2026-10-09T00:00:00Z"), None, now), None);
+        assert_eq!(held_until(None, Some(3 * DAY), now), None);
+        assert_eq!(held_until(Some("unknown"), Some(3 * DAY), now), None);
+    }
+}
@@ -1,20 +1,34 @@
-//! Release source resolution for GitHub/GitLab packages.
+//! Release source resolution for packages published on a forge.
 //!
 //! This module provides functionality to resolve package sources from
-//! GitHub or GitLab releases, fetching version and download URL automatically.
+//! GitHub, GitLab, Gitea or SourceHut releases, fetching version and
+//! download URL automatically.
 
-use std::{collections::HashMap, process::Command};
+use std::{cmp::Reverse, collections::HashMap, process::Command};
 
-use soar_config::packages::ResolvedPackage;
+use chrono::{DateTime, Utc};
+use soar_config::{packages::ResolvedPackage, release::Channel};
 use soar_dl::{
-    github::{Github, GithubAsset, GithubRelease},
-    gitlab::{GitLab, GitLabAsset, GitLabRelease},
+    checksum::PublishedChecksums,
+    gitea::Gitea,
+    github::Github,
+    gitlab::GitLab,
+    sourcehut::SourceHut,
     traits::{Asset, Platform, Release},
 };
+use soar_utils::version::{is_constraint, VersionReq};
+use tracing::warn;
 
 use crate::{
-    error::SoarError, package::remote_update::is_valid_download_url,
-    utils::substitute_placeholders, SoarResult,
+    error::SoarError,
+    package::{
+        changelog::{in_range, same_version, ReleaseNotes},
+        release_age::{held_until, parse_published},
+        remote_update::is_valid_download_url,
+        update_info::Forge,
+    },
+    utils::substitute_placeholders,
+    SoarResult,
 };
 
 /// Source for fetching package releases.
@@ -26,12 +40,14 @@ pub enum ReleaseSource {
         repo: String,
         /// Glob pattern to match asset filename.
         asset_pattern: String,
-        /// Whether to include pre-release versions.
-        include_prerelease: bool,
+        /// Which kind of release to follow.
+        channel: Channel,
         /// Optional glob pattern to match tag names.
         tag_pattern: Option<String>,
         /// Custom architecture name mapping.
         arch_map: Option<HashMap<String, String>>,
+        /// How long a release must have been out, in milliseconds.
+        min_release_age: Option<u128>,
     },
     /// GitLab releases source.
     GitLab {
@@ -39,12 +55,44 @@ pub enum ReleaseSource {
         repo: String,
         /// Glob pattern to match asset filename.
         asset_pattern: String,
-        /// Whether to include pre-release versions.
-        include_prerelease: bool,
+        /// Which kind of release to follow.
+        channel: Channel,
+        /// Optional glob pattern to match tag names.
+        tag_pattern: Option<String>,
+        /// Custom architecture name mapping.
+        arch_map: Option<HashMap<String, String>>,
+        /// How long a release must have been out, in milliseconds.
+        min_release_age: Option<u128>,
+    },
+    /// Gitea or Forgejo releases source.
+    Gitea {
+        /// Repository in "host/owner/repo" format, or "owner/repo" on Codeberg.
+        repo: String,
+        /// Glob pattern to match asset filename.
+        asset_pattern: String,
+        /// Which kind of release to follow.
+        channel: Channel,
         /// Optional glob pattern to match tag names.
         tag_pattern: Option<String>,
         /// Custom architecture name mapping.
         arch_map: Option<HashMap<String, String>>,
+        /// How long a release must have been out, in milliseconds.
+        min_release_age: Option<u128>,
+    },
+    /// SourceHut tags and their artifacts.
+    SourceHut {
+        /// Repository in "~owner/repo" format.
+        repo: String,
+        /// Glob pattern to match asset filename.
+        asset_pattern: String,
+        /// Which kind of release to follow.
+        channel: Channel,
+        /// Optional glob pattern to match tag names.
+        tag_pattern: Option<String>,
+        /// Custom architecture name mapping.
+        arch_map: Option<HashMap<String, String>>,
+        /// How long a release must have been out, in milliseconds.
+        min_release_age: Option<u128>,
     },
 }
 
@@ -57,9 +105,74 @@ pub struct ResolvedRelease {
     pub download_url: String,
     /// Optional size of the download in bytes.
     pub size: Option<u64>,
+    /// A newer release passed over for being too recent.
+    pub deferred: Option<DeferredRelease>,
+    /// The checksums the release publishes for the asset.
+    pub checksums: PublishedChecksums,
+}
+
+/// A release held back by the minimum release age.
+#[derive(Debug, Clone)]
+pub struct DeferredRelease {
+    /// The version tag of the release.
+    pub version: String,
+    /// When it becomes old enough to install.
+    pub eligible_at: DateTime<Utc>,
 }
 
 impl ReleaseSource {
+    /// A source on `forge` with nothing but an asset to match and a channel.
+    pub(crate) fn from_forge(
+        forge: Forge,
+        repo: String,
+        asset_pattern: String,
+        channel: Channel,
+        tag_pattern: Option<String>,
+    ) -> Self {
+        match forge {
+            Forge::GitHub => {
+                Self::GitHub {
+                    repo,
+                    asset_pattern,
+                    channel,
+                    tag_pattern,
+                    arch_map: None,
+                    min_release_age: None,
+                }
+            }
+            Forge::GitLab => {
+                Self::GitLab {
+                    repo,
+                    asset_pattern,
+                    channel,
+                    tag_pattern,
+                    arch_map: None,
+                    min_release_age: None,
+                }
+            }
+            Forge::Gitea => {
+                Self::Gitea {
+                    repo,
+                    asset_pattern,
+                    channel,
+                    tag_pattern,
+                    arch_map: None,
+                    min_release_age: None,
+                }
+            }
+            Forge::SourceHut => {
+                Self::SourceHut {
+                    repo,
+                    asset_pattern,
+                    channel,
+                    tag_pattern,
+                    arch_map: None,
+                    min_release_age: None,
+                }
+            }
+        }
+    }
+
     /// The releases a download URL came out of, where its host publishes any.
     ///
     /// A forge download URL names the project, the release it belongs to and
@@ -68,46 +181,72 @@ impl ReleaseSource {
     /// is reported as such rather than guessed at.
     pub fn from_download_url(url: &str) -> Option<Self> {
         let ReleaseDownload {
-            is_github,
-            owner,
+            forge,
             repo,
             tag,
             asset,
         } = ReleaseDownload::parse(url)?;
-        let (owner, repo, tag, asset) = (&owner, &repo, &tag, &asset);
 
-        let source = if is_github {
-            Self::GitHub {
-                repo: format!("{owner}/{repo}"),
-                asset_pattern: asset_glob(tag, asset),
-                include_prerelease: false,
-                tag_pattern: None,
-                arch_map: None,
-            }
-        } else {
-            Self::GitLab {
-                repo: format!("{owner}/{repo}"),
-                asset_pattern: asset_glob(tag, asset),
-                include_prerelease: false,
-                tag_pattern: None,
-                arch_map: None,
-            }
+        Some(Self::from_forge(
+            forge,
+            repo,
+            asset_glob(&tag, &asset),
+            Channel::Stable,
+            None,
+        ))
+    }
+
+    /// The releases of the project a homepage or source URL names, for
+    /// reading their notes.
+    ///
+    /// Only projects on github.com, gitlab.com, codeberg.org and git.sr.ht,
+    /// and paths below them, are understood. The source has no asset to
+    /// match and takes every release, prereleases included, since it is only
+    /// asked which of them fall between two versions.
+    pub fn from_project_url(url: &str) -> Option<Self> {
+        let parsed = url::Url::parse(url).ok()?;
+        let host = parsed.host_str()?;
+        let forge = match host {
+            "github.com" => Forge::GitHub,
+            "gitlab.com" => Forge::GitLab,
+            "codeberg.org" => Forge::Gitea,
+            "git.sr.ht" => Forge::SourceHut,
+            _ => return None,
+        };
+        let mut segments = parsed.path_segments()?.filter(|s| !s.is_empty());
+        let owner = segments.next()?;
+        let repo = segments.next()?;
+        let repo = format!("{owner}/{}", repo.strip_suffix(".git").unwrap_or(repo));
+        let repo = match forge {
+            Forge::Gitea => format!("{host}/{repo}"),
+            _ => repo,
         };
-        Some(source)
+
+        Some(Self::from_forge(
+            forge,
+            repo,
+            "*".to_string(),
+            Channel::Prerelease,
+            None,
+        ))
     }
 
     /// Create a ReleaseSource from a resolved package configuration.
     ///
-    /// Returns `None` if the package doesn't have github/gitlab source configured.
+    /// Returns `None` if the package doesn't have a forge source configured.
+    /// A minimum release age that does not parse is left unset here; it is
+    /// reported where the package's update policy is read.
     pub fn from_resolved(pkg: &ResolvedPackage) -> Option<Self> {
+        let min_release_age = pkg.min_release_age().ok().flatten();
         if let Some(ref repo) = pkg.github {
             let asset_pattern = pkg.asset_pattern.clone()?;
             return Some(ReleaseSource::GitHub {
                 repo: repo.clone(),
                 asset_pattern,
-                include_prerelease: pkg.include_prerelease.unwrap_or(false),
+                channel: pkg.release_channel(),
                 tag_pattern: pkg.tag_pattern.clone(),
                 arch_map: pkg.arch_map.clone(),
+                min_release_age,
             });
         }
 
@@ -116,9 +255,34 @@ impl ReleaseSource {
             return Some(ReleaseSource::GitLab {
                 repo: repo.clone(),
                 asset_pattern,
-                include_prerelease: pkg.include_prerelease.unwrap_or(false),
+                channel: pkg.release_channel(),
                 tag_pattern: pkg.tag_pattern.clone(),
                 arch_map: pkg.arch_map.clone(),
+                min_release_age,
+            });
+        }
+
+        if let Some(ref repo) = pkg.gitea {
+            let asset_pattern = pkg.asset_pattern.clone()?;
+            return Some(ReleaseSource::Gitea {
+                repo: repo.clone(),
+                asset_pattern,
+                channel: pkg.release_channel(),
+                tag_pattern: pkg.tag_pattern.clone(),
+                arch_map: pkg.arch_map.clone(),
+                min_release_age,
+            });
+        }
+
+        if let Some(ref repo) = pkg.sourcehut {
+            let asset_pattern = pkg.asset_pattern.clone()?;
+            return Some(ReleaseSource::SourceHut {
+                repo: repo.clone(),
+                asset_pattern,
+                channel: pkg.release_channel(),
+                tag_pattern: pkg.tag_pattern.clone(),
+                arch_map: pkg.arch_map.clone(),
+                min_release_age,
             });
         }
 
@@ -127,8 +291,8 @@ impl ReleaseSource {
 
     /// Resolve the release source to get version and download URL.
     ///
-    /// Fetches releases from the configured source, finds the latest
-    /// (non-prerelease unless configured), matches the asset pattern,
+    /// Fetches releases from the configured source, finds the latest one
+    /// on its channel that is old enough, matches the asset pattern,
     /// and returns the resolved release info.
     pub fn resolve(&self) -> SoarResult<ResolvedRelease> {
         self.resolve_version(None)
@@ -137,21 +301,28 @@ impl ReleaseSource {
     /// Resolve the release source with a specific version/tag.
     ///
     /// If `version` is Some, fetches that specific tag instead of the latest.
-    /// The version can be with or without 'v' prefix (both "1.0.0" and "v1.0.0" work).
+    /// The version can be with or without 'v' prefix (both "1.0.0" and "v1.0.0" work),
+    /// or a constraint such as `^1.4`, which picks the newest release it accepts.
     pub fn resolve_version(&self, version: Option<&str>) -> SoarResult<ResolvedRelease> {
         match self {
             ReleaseSource::GitHub {
                 repo,
                 asset_pattern,
-                include_prerelease,
+                channel,
                 tag_pattern,
                 arch_map,
+                min_release_age,
             } => {
-                resolve_github(
+                let filter = ReleaseFilter {
+                    channel: *channel,
+                    tag_pattern: tag_pattern.as_deref(),
+                    min_age: *min_release_age,
+                };
+                resolve_platform::<Github>(
+                    "GitHub",
                     repo,
                     asset_pattern,
-                    *include_prerelease,
-                    tag_pattern.as_deref(),
+                    &filter,
                     version,
                     arch_map.as_ref(),
                 )
@@ -159,15 +330,65 @@ impl ReleaseSource {
             ReleaseSource::GitLab {
                 repo,
                 asset_pattern,
-                include_prerelease,
+                channel,
+                tag_pattern,
+                arch_map,
+                min_release_age,
+            } => {
+                let filter = ReleaseFilter {
+                    channel: *channel,
+                    tag_pattern: tag_pattern.as_deref(),
+                    min_age: *min_release_age,
+                };
+                resolve_platform::<GitLab>(
+                    "GitLab",
+                    repo,
+                    asset_pattern,
+                    &filter,
+                    version,
+                    arch_map.as_ref(),
+                )
+            }
+            ReleaseSource::Gitea {
+                repo,
+                asset_pattern,
+                channel,
+                tag_pattern,
+                arch_map,
+                min_release_age,
+            } => {
+                let filter = ReleaseFilter {
+                    channel: *channel,
+                    tag_pattern: tag_pattern.as_deref(),
+                    min_age: *min_release_age,
+                };
+                resolve_platform::<Gitea>(
+                    "Gitea",
+                    repo,
+                    asset_pattern,
+                    &filter,
+                    version,
+                    arch_map.as_ref(),
+                )
+            }
+            ReleaseSource::SourceHut {
+                repo,
+                asset_pattern,
+                channel,
                 tag_pattern,
                 arch_map,
+                min_release_age,
             } => {
-                resolve_gitlab(
+                let filter = ReleaseFilter {
+                    channel: *channel,
+                    tag_pattern: tag_pattern.as_deref(),
+                    min_age: *min_release_age,
+                };
+                resolve_platform::<SourceHut>(
+                    "SourceHut",
                     repo,
                     asset_pattern,
-                    *include_prerelease,
-                    tag_pattern.as_deref(),
+                    &filter,
                     version,
                     arch_map.as_ref(),
                 )
@@ -176,6 +397,147 @@ impl ReleaseSource {
     }
 }
 
+impl ReleaseSource {
+    /// The notes of every release after `installed` up to and including
+    /// `candidate`, newest first.
+    ///
+    /// Releases off the source's channel or tag pattern are left out, as they
+    /// would never have been installed, except the candidate itself. The age
+    /// policy is not applied: the candidate already passed it, and the
+    /// releases before it are older still.
+    pub fn release_notes(
+        &self,
+        installed: Option<&str>,
+        candidate: &str,
+    ) -> SoarResult<Vec<ReleaseNotes>> {
+        match self {
+            ReleaseSource::GitHub {
+                repo,
+                channel,
+                tag_pattern,
+                ..
+            } => {
+                let releases = fetch_releases::<Github>("GitHub", repo)?;
+                let filter = ReleaseFilter {
+                    channel: *channel,
+                    tag_pattern: tag_pattern.as_deref(),
+                    min_age: None,
+                };
+                Ok(notes_between(
+                    &releases,
+                    &filter,
+                    installed,
+                    candidate,
+                    |tag| format!("https://github.com/{repo}/releases/tag/{tag}"),
+                ))
+            }
+            ReleaseSource::GitLab {
+                repo,
+                channel,
+                tag_pattern,
+                ..
+            } => {
+                let releases = fetch_releases::<GitLab>("GitLab", repo)?;
+                let filter = ReleaseFilter {
+                    channel: *channel,
+                    tag_pattern: tag_pattern.as_deref(),
+                    min_age: None,
+                };
+                Ok(notes_between(
+                    &releases,
+                    &filter,
+                    installed,
+                    candidate,
+                    |tag| format!("https://gitlab.com/{repo}/-/releases/{tag}"),
+                ))
+            }
+            ReleaseSource::Gitea {
+                repo,
+                channel,
+                tag_pattern,
+                ..
+            } => {
+                let releases = fetch_releases::<Gitea>("Gitea", repo)?;
+                let filter = ReleaseFilter {
+                    channel: *channel,
+                    tag_pattern: tag_pattern.as_deref(),
+                    min_age: None,
+                };
+                Ok(notes_between(
+                    &releases,
+                    &filter,
+                    installed,
+                    candidate,
+                    |tag| Gitea::release_url(repo, tag),
+                ))
+            }
+            ReleaseSource::SourceHut {
+                repo,
+                channel,
+                tag_pattern,
+                ..
+            } => {
+                let releases = fetch_releases::<SourceHut>("SourceHut", repo)?;
+                let filter = ReleaseFilter {
+                    channel: *channel,
+                    tag_pattern: tag_pattern.as_deref(),
+                    min_age: None,
+                };
+                Ok(notes_between(
+                    &releases,
+                    &filter,
+                    installed,
+                    candidate,
+                    |tag| SourceHut::release_url(repo, tag),
+                ))
+            }
+        }
+    }
+}
+
+/// Every release of `repo` on the forge `P`, which `forge` names in errors.
+fn fetch_releases<P: Platform>(forge: &str, repo: &str) -> SoarResult<Vec<P::Release>> {
+    P::fetch_releases(repo, None).map_err(|e| {
+        SoarError::Custom(format!(
+            "Failed to fetch {} releases for {}: {}",
+            forge, repo, e
+        ))
+    })
+}
+
+/// The notes of the releases in `releases` that fall between two versions.
+fn notes_between<R: Release>(
+    releases: &[R],
+    filter: &ReleaseFilter,
+    installed: Option<&str>,
+    candidate: &str,
+    url: impl Fn(&str) -> String,
+) -> Vec<ReleaseNotes> {
+    releases
+        .iter()
+        .filter(|r| {
+            let tag = r.tag();
+            same_version(tag, candidate)
+                || (filter.allows(*r)
+                    && !is_rolling_tag(tag)
+                    && in_range(tag, installed, candidate))
+        })
+        .map(|r| {
+            ReleaseNotes {
+                version: r.tag().to_string(),
+                published_at: Some(r.published_at().to_string())
+                    .filter(|published| !published.is_empty()),
+                body: r
+                    .body()
+                    .map(str::trim)
+                    .filter(|body| !body.is_empty())
+                    .map(str::to_string),
+                url: Some(url(r.tag())),
+            }
+        })
+        .collect()
+}
+
 /// Check if a release matches the tag pattern.
 fn matches_tag_pattern(tag: &str, pattern: Option<&str>) -> bool {
     match pattern {
@@ -184,11 +546,69 @@ fn matches_tag_pattern(tag: &str, pattern: Option<&str>) -> bool {
     }
 }
 
-/// Resolve a GitHub release source.
+/// Whether a tag names a rolling build rather than a version.
+///
+/// Projects that publish nightlies tend to reuse one tag, or date-stamp one,
+/// under a handful of names; any of them as a word of the tag counts.
+fn is_rolling_tag(tag: &str) -> bool {
+    const ROLLING: &[&str] = &[
+        "nightly",
+        "continuous",
+        "canary",
+        "snapshot",
+        "edge",
+        "dev",
+        "tip",
+        "daily",
+        "weekly",
+    ];
+    tag.to_ascii_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| ROLLING.contains(&word))
+}
+
+/// Which of a project's releases a package may take.
+struct ReleaseFilter<'a> {
+    channel: Channel,
+    tag_pattern: Option<&'a str>,
+    min_age: Option<u128>,
+}
+
+impl ReleaseFilter<'_> {
+    fn allows<R: Release>(&self, release: &R) -> bool {
+        let on_channel = match self.channel {
+            Channel::Stable => !release.is_prerelease(),
+            Channel::Prerelease => true,
+            Channel::Nightly => is_rolling_tag(release.tag()),
+        };
+        on_channel && matches_tag_pattern(release.tag(), self.tag_pattern)
+    }
+
+    fn held_until<R: Release>(&self, release: &R, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        held_until(Some(release.published_at()), self.min_age, now)
+    }
+}
+
+/// A release picked for install, and the newer one it stands in for.
+struct Selected<'a, R> {
+    release: &'a R,
+    deferred: Option<(&'a R, DateTime<Utc>)>,
+}
+
+impl<R: Release> Selected<'_, R> {
+    fn deferred_release(&self) -> Option<DeferredRelease> {
+        self.deferred.map(|(release, eligible_at)| {
+            DeferredRelease {
+                version: release.tag().to_string(),
+                eligible_at,
+            }
+        })
+    }
+}
+
 /// A download URL taken apart into the release it came from.
 struct ReleaseDownload {
-    is_github: bool,
-    owner: String,
+    forge: Forge,
     repo: String,
     tag: String,
     asset: String,
@@ -210,19 +630,30 @@ impl ReleaseDownload {
 
         // github.com/{owner}/{repo}/releases/download/{tag}/{asset}
         // gitlab.com/{owner}/{repo}/-/releases/{tag}/downloads/{asset}
-        let (is_github, owner, repo, tag, asset) = match segments.as_slice() {
-            [owner, repo, "releases", "download", tag, asset] if host == "github.com" => {
-                (true, owner, repo, tag, asset)
+        // codeberg.org/{owner}/{repo}/releases/download/{tag}/{asset}
+        // git.sr.ht/~{owner}/{repo}/refs/download/{tag}/{asset}
+        let (forge, owner, repo, tag, asset) = match (host, segments.as_slice()) {
+            ("github.com", [owner, repo, "releases", "download", tag, asset]) => {
+                (Forge::GitHub, owner, repo, tag, asset)
+            }
+            ("gitlab.com", [owner, repo, "-", "releases", tag, "downloads", asset]) => {
+                (Forge::GitLab, owner, repo, tag, asset)
             }
-            [owner, repo, "-", "releases", tag, "downloads", asset] if host == "gitlab.com" => {
-                (false, owner, repo, tag, asset)
+            ("codeberg.org", [owner, repo, "releases", "download", tag, asset]) => {
+                (Forge::Gitea, owner, repo, tag, asset)
+            }
+            ("git.sr.ht", [owner, repo, "refs", "download", tag, asset]) => {
+                (Forge::SourceHut, owner, repo, tag, asset)
             }
             _ => return None,
         };
+        let repo = match forge {
+            Forge::Gitea => format!("{host}/{owner}/{repo}"),
+            _ => format!("{owner}/{repo}"),
+        };
         Some(Self {
-            is_github,
-            owner: owner.to_string(),
-            repo: repo.to_string(),
+            forge,
+            repo,
             tag: tag.to_string(),
             asset: asset.to_string(),
         })
@@ -262,53 +693,21 @@ fn asset_glob(tag: &str, asset: &str) -> String {
     asset.to_string()
 }
 
-fn resolve_github(
+/// Resolve a release source against the forge `P`, which `forge` names in errors.
+fn resolve_platform<P: Platform>(
+    forge: &str,
     repo: &str,
     asset_pattern: &str,
-    include_prerelease: bool,
-    tag_pattern: Option<&str>,
+    filter: &ReleaseFilter,
     specific_version: Option<&str>,
     arch_map: Option<&HashMap<String, String>>,
 ) -> SoarResult<ResolvedRelease> {
-    let releases: Vec<GithubRelease> = Github::fetch_releases(repo, None).map_err(|e| {
-        SoarError::Custom(format!(
-            "Failed to fetch GitHub releases for {}: {}",
-            repo, e
-        ))
-    })?;
+    let releases = fetch_releases::<P>(forge, repo)?;
 
-    let release = releases
-        .iter()
-        .find(|r| {
-            // If a specific version is requested, match it exactly (with or without 'v' prefix)
-            if let Some(ver) = specific_version {
-                let tag = r.tag();
-                let tag_normalized = tag.strip_prefix('v').unwrap_or(tag);
-                let ver_normalized = ver.strip_prefix('v').unwrap_or(ver);
-                return tag_normalized == ver_normalized || tag == ver;
-            }
+    let selected = select_release(&releases, repo, filter, specific_version, Utc::now())?;
+    let release = selected.release;
 
-            let prerelease_ok = include_prerelease || !r.is_prerelease();
-            let tag_ok = matches_tag_pattern(r.tag(), tag_pattern);
-            prerelease_ok && tag_ok
-        })
-        .ok_or_else(|| {
-            if let Some(ver) = specific_version {
-                SoarError::Custom(format!(
-                    "No release found for {} with version '{}'",
-                    repo, ver
-                ))
-            } else if let Some(pattern) = tag_pattern {
-                SoarError::Custom(format!(
-                    "No releases found for {} matching tag pattern '{}'",
-                    repo, pattern
-                ))
-            } else {
-                SoarError::Custom(format!("No releases found for {}", repo))
-            }
-        })?;
-
-    let assets: &[GithubAsset] = release.assets();
+    let assets = release.assets();
     let asset_pattern = substitute_placeholders(asset_pattern, Some(release.tag()), arch_map);
     let asset = find_matching_asset(assets, &asset_pattern)?;
 
@@ -316,65 +715,111 @@ fn resolve_github(
         version: release.tag().to_string(),
         download_url: asset.url().to_string(),
         size: asset.size(),
+        deferred: selected.deferred_release(),
+        checksums: PublishedChecksums::from_release(assets, asset),
     })
 }
 
-/// Resolve a GitLab release source.
-fn resolve_gitlab(
+/// Pick the release to install out of everything a project published.
+///
+/// A specific version is matched exactly, with or without its `v`, however
+/// recent it is: naming it is the decision the age policy would otherwise
+/// make. A constraint picks the newest release it accepts among those the
+/// channel and tag settings allow; tags it cannot be checked against, like
+/// `nightly`, are reported and passed over. Otherwise the first allowed
+/// release is the latest one, going by publication date on the nightly
+/// channel since rolling tags are moved rather than created.
+///
+/// Releases younger than the minimum age are skipped, and the newest of them
+/// is returned beside the pick so the wait can be reported.
+fn select_release<'a, R: Release>(
+    releases: &'a [R],
     repo: &str,
-    asset_pattern: &str,
-    include_prerelease: bool,
-    tag_pattern: Option<&str>,
+    filter: &ReleaseFilter,
     specific_version: Option<&str>,
-    arch_map: Option<&HashMap<String, String>>,
-) -> SoarResult<ResolvedRelease> {
-    let releases: Vec<GitLabRelease> = GitLab::fetch_releases(repo, None).map_err(|e| {
-        SoarError::Custom(format!(
-            "Failed to fetch GitLab releases for {}: {}",
-            repo, e
-        ))
-    })?;
-
-    let release = releases
-        .iter()
-        .find(|r| {
-            // If a specific version is requested, match it exactly (with or without 'v' prefix)
-            if let Some(ver) = specific_version {
+    now: DateTime<Utc>,
+) -> SoarResult<Selected<'a, R>> {
+    if let Some(ver) = specific_version.filter(|v| !is_constraint(v)) {
+        let ver_normalized = ver.strip_prefix('v').unwrap_or(ver);
+        return releases
+            .iter()
+            .find(|r| {
                 let tag = r.tag();
-                let tag_normalized = tag.strip_prefix('v').unwrap_or(tag);
-                let ver_normalized = ver.strip_prefix('v').unwrap_or(ver);
-                return tag_normalized == ver_normalized || tag == ver;
-            }
-
-            let prerelease_ok = include_prerelease || !r.is_prerelease();
-            let tag_ok = matches_tag_pattern(r.tag(), tag_pattern);
-            prerelease_ok && tag_ok
-        })
-        .ok_or_else(|| {
-            if let Some(ver) = specific_version {
+                tag.strip_prefix('v').unwrap_or(tag) == ver_normalized || tag == ver
+            })
+            .map(|release| {
+                Selected {
+                    release,
+                    deferred: None,
+                }
+            })
+            .ok_or_else(|| {
                 SoarError::Custom(format!(
                     "No release found for {} with version '{}'",
                     repo, ver
                 ))
-            } else if let Some(pattern) = tag_pattern {
-                SoarError::Custom(format!(
-                    "No releases found for {} matching tag pattern '{}'",
-                    repo, pattern
-                ))
-            } else {
-                SoarError::Custom(format!("No releases found for {}", repo))
-            }
-        })?;
+            });
+    }
 
-    let assets: &[GitLabAsset] = release.assets();
-    let asset_pattern = substitute_placeholders(asset_pattern, Some(release.tag()), arch_map);
-    let asset = find_matching_asset(assets, &asset_pattern)?;
+    let mut allowed: Vec<&R> = releases.iter().filter(|r| filter.allows(*r)).collect();
+    if filter.channel == Channel::Nightly {
+        allowed.sort_by_key(|r| Reverse(parse_published(r.published_at())));
+    }
+    let aged = |r: &&R| filter.held_until(*r, now).is_none();
 
-    Ok(ResolvedRelease {
-        version: release.tag().to_string(),
-        download_url: asset.url().to_string(),
-        size: asset.size(),
-    })
+    let (release, newest) = if let Some(ver) = specific_version {
+        let req = VersionReq::parse(ver)?;
+        let (release, unordered) = req.newest(allowed.iter().copied().filter(aged), |r| r.tag());
+        if !unordered.is_empty() {
+            warn!(
+                "{}: skipped release tags that are not versions: {}",
+                repo,
+                unordered.join(", ")
+            );
+        }
+        let (newest, _) = req.newest(allowed.iter().copied(), |r| r.tag());
+        if release.is_none() && newest.is_none() {
+            return Err(SoarError::Custom(format!(
+                "No release of {} matches '{}'",
+                repo, req
+            )));
+        }
+        (release, newest)
+    } else {
+        (allowed.iter().copied().find(aged), allowed.first().copied())
+    };
+
+    let deferred = newest
+        .filter(|n| release.is_none_or(|r| !std::ptr::eq(*n, r)))
+        .and_then(|n| filter.held_until(n, now).map(|until| (n, until)));
+
+    match (release, deferred) {
+        (Some(release), deferred) => {
+            Ok(Selected {
+                release,
+                deferred,
+            })
+        }
+        (None, Some((newest, until))) => {
+            Err(SoarError::Custom(format!(
+                "No release of {} is old enough to install yet; {} becomes eligible at {}",
+                repo,
+                newest.tag(),
+                until.format("%Y-%m-%d %H:%M UTC")
+            )))
+        }
+        (None, None) => {
+            Err(match filter.tag_pattern {
+                Some(pattern) => {
+                    SoarError::Custom(format!(
+                        "No releases found for {} matching tag pattern '{}'",
+                        repo, pattern
+                    ))
+                }
+                None => SoarError::Custom(format!("No releases found for {}", repo)),
+            })
+        }
+    }
 }
 
 /// Find an asset matching the given glob pattern.
@@ -399,7 +844,7 @@ fn find_matching_asset<'a, A: Asset>(assets: &'a [A], pattern: &str) -> SoarResu
         })
 }
 
-/// Result of running a version command.
+/// A version found by a version command or a version query.
 #[derive(Debug, Clone)]
 pub struct VersionCommandResult {
     /// The version string (line 1).
@@ -459,6 +904,8 @@ pub fn run_version_command(command: &str) -> SoarResult<VersionCommandResult> {
 
 #[cfg(test)]
 mod tests {
+    use soar_dl::github::GithubAsset;
+
     use super::*;
 
     #[test]
@@ -476,13 +923,13 @@ mod tests {
             ReleaseSource::GitHub {
                 repo,
                 asset_pattern,
-                include_prerelease,
+                channel,
                 tag_pattern,
                 ..
             } => {
                 assert_eq!(repo, "user/repo");
                 assert_eq!(asset_pattern, "*.AppImage");
-                assert!(include_prerelease);
+                assert_eq!(channel, Channel::Prerelease);
                 assert!(tag_pattern.is_none());
             }
             _ => panic!("Expected GitHub source"),
@@ -503,19 +950,42 @@ mod tests {
             ReleaseSource::GitLab {
                 repo,
                 asset_pattern,
-                include_prerelease,
+                channel,
                 tag_pattern,
                 ..
             } => {
                 assert_eq!(repo, "group/project");
                 assert_eq!(asset_pattern, "*.tar.gz");
-                assert!(!include_prerelease);
+                assert_eq!(channel, Channel::Stable);
                 assert!(tag_pattern.is_none());
             }
             _ => panic!("Expected GitLab source"),
         }
     }
 
+    #[test]
+    fn test_release_source_from_resolved_gitea() {
+        let pkg = ResolvedPackage {
+            name: "test".to_string(),
+            gitea: Some("git.example.org/team/tool".to_string()),
+            asset_pattern: Some("*.tar.gz".to_string()),
+            tag_pattern: Some("v*".to_string()),
+            ..Default::default()
+        };
+
+        match ReleaseSource::from_resolved(&pkg).unwrap() {
+            ReleaseSource::Gitea {
+                repo,
+                tag_pattern,
+                ..
+            } => {
+                assert_eq!(repo, "git.example.org/team/tool");
+                assert_eq!(tag_pattern.as_deref(), Some("v*"));
+            }
+            other => panic!("Expected Gitea source, got {other:?}"),
+        }
+    }
+
     #[test]
     fn test_release_source_from_resolved_none() {
         let pkg = ResolvedPackage {
@@ -584,6 +1054,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn codeberg_and_sourcehut_downloads_name_their_releases_too() {
+        match ReleaseSource::from_download_url(
+            "https://codeberg.org/owner/tool/releases/download/v2.0.1/tool-2.0.1-x86_64.AppImage",
+        ) {
+            Some(ReleaseSource::Gitea {
+                repo,
+                asset_pattern,
+                ..
+            }) => {
+                assert_eq!(repo, "codeberg.org/owner/tool");
+                assert_eq!(asset_pattern, "tool-*-x86_64.AppImage");
+            }
+            other => panic!("expected Gitea, got {other:?}"),
+        }
+        match ReleaseSource::from_download_url(
+            "https://git.sr.ht/~owner/tool/refs/download/1.4/tool-1.4.tar.gz",
+        ) {
+            Some(ReleaseSource::SourceHut {
+                repo,
+                asset_pattern,
+                ..
+            }) => {
+                assert_eq!(repo, "~owner/tool");
+                assert_eq!(asset_pattern, "tool-*.tar.gz");
+            }
+            other => panic!("expected SourceHut, got {other:?}"),
+        }
+    }
+
     #[test]
     fn a_url_no_forge_publishes_releases_for_answers_nothing() {
         assert!(ReleaseSource::from_download_url("https://example.com/app.AppImage").is_none());
@@ -592,4 +1092,189 @@ mod tests {
         )
         .is_none());
     }
+
+    struct Tagged(&'static str, bool, &'static str);
+
+    impl Release for Tagged {
+        type Asset = GithubAsset;
+
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn tag(&self) -> &str {
+            self.0
+        }
+
+        fn is_prerelease(&self) -> bool {
+            self.1
+        }
+
+        fn published_at(&self) -> &str {
+            self.2
+        }
+
+        fn body(&self) -> Option<&str> {
+            None
+        }
+
+        fn assets(&self) -> &[GithubAsset] {
+            &[]
+        }
+    }
+
+    #[test]
+    fn a_constraint_picks_the_newest_release_it_accepts() {
+        let releases = [
+            Tagged("v2.0.0", false, ""),
+            Tagged("nightly", false, ""),
+            Tagged("v1.5.0-rc1", true, ""),
+            Tagged("v1.4.3", false, ""),
+            Tagged("v1.4.10", false, ""),
+            Tagged("v1.3.0", false, ""),
+        ];
+
+        let filter = ReleaseFilter {
+            channel: Channel::Stable,
+            tag_pattern: None,
+            min_age: None,
+        };
+        let pick = |version| {
+            select_release(&releases, "user/repo", &filter, Some(version), Utc::now())
+                .map(|s| s.release.tag())
+        };
+        assert_eq!(pick("^1.4").unwrap(), "v1.4.10");
+        assert_eq!(pick(">=1.0, <1.4").unwrap(), "v1.3.0");
+        assert_eq!(pick("1.4.3").unwrap(), "v1.4.3");
+        assert!(pick("^3").is_err());
+        assert!(pick("^one").is_err());
+    }
+
+    const DAY: u128 = 86_400_000;
+
+    fn now() -> DateTime<Utc> {
+        parse_published("2026-10-10T00:00:00Z").unwrap()
+    }
+
+    #[test]
+    fn a_release_too_young_is_deferred_for_the_last_one_old_enough() {
+        let releases = [
+            Tagged("v1.6.0", false, "2026-10-09T00:00:00Z"),
+            Tagged("v1.5.0", false, "2026-10-01T00:00:00Z"),
+            Tagged("v1.4.0", false, "2026-09-01T00:00:00Z"),
+        ];
+        let filter = ReleaseFilter {
+            channel: Channel::Stable,
+            tag_pattern: None,
+            min_age: Some(3 * DAY),
+        };
+
+        let latest = select_release(&releases, "user/repo", &filter, None, now()).unwrap();
+        assert_eq!(latest.release.tag(), "v1.5.0");
+        let deferred = latest.deferred_release().unwrap();
+        assert_eq!(deferred.version, "v1.6.0");
+        assert_eq!(
+            deferred.eligible_at,
+            parse_published("2026-10-12T00:00:00Z").unwrap()
+        );
+
+        let constrained =
+            select_release(&releases, "user/repo", &filter, Some("^1.4"), now()).unwrap();
+        assert_eq!(constrained.release.tag(), "v1.5.0");
+        assert!(constrained.deferred.is_some());
+
+        // Naming the version is a decision of its own.
+        let named = select_release(&releases, "user/repo", &filter, Some("1.6.0"), now()).unwrap();
+        assert_eq!(named.release.tag(), "v1.6.0");
+        assert!(named.deferred.is_none());
+
+        let err = select_release(&releases[..1], "user/repo", &filter, None, now())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("2026-10-12"));
+    }
+
+    #[test]
+    fn each_channel_takes_its_own_kind_of_release() {
+        let releases = [
+            Tagged("nightly-20261008", true, "2026-10-08T00:00:00Z"),
+            Tagged("v2.0.0-rc1", true, "2026-10-07T00:00:00Z"),
+            Tagged("v1.9.0", false, "2026-10-01T00:00:00Z"),
+            // A rolling tag moved onto the newest build keeps its place in the list.
+            Tagged("continuous", true, "2026-10-09T00:00:00Z"),
+        ];
+        let pick = |channel| {
+            let filter = ReleaseFilter {
+                channel,
+                tag_pattern: None,
+                min_age: None,
+            };
+            select_release(&releases, "user/repo", &filter, None, now())
+                .map(|s| s.release.tag())
+                .unwrap()
+        };
+
+        assert_eq!(pick(Channel::Stable), "v1.9.0");
+        assert_eq!(pick(Channel::Prerelease), "nightly-20261008");
+        assert_eq!(pick(Channel::Nightly), "continuous");
+        assert!(is_rolling_tag("Nightly_2026-10-08"));
+        assert!(!is_rolling_tag("v1.0.0-rc1"));
+        assert!(!is_rolling_tag("develop-tools-1.0"));
+    }
+
+    #[test]
+    fn notes_cover_the_releases_an_update_moves_through() {
+        let releases = [
+            Tagged("v1.6.0", false, "2026-10-09T00:00:00Z"),
+            Tagged("nightly", true, "2026-10-08T00:00:00Z"),
+            Tagged("v1.5.0", false, "2026-10-05T00:00:00Z"),
+            Tagged("v1.5.0-rc1", true, "2026-10-02T00:00:00Z"),
+            Tagged("v1.4.0", false, "2026-09-01T00:00:00Z"),
+        ];
+        let filter = ReleaseFilter {
+            channel: Channel::Stable,
+            tag_pattern: None,
+            min_age: None,
+        };
+        let notes = notes_between(&releases, &filter, Some("1.4.0"), "1.5.0", |tag| {
+            format!("https://example.com/{tag}")
+        });
+        let tags: Vec<&str> = notes.iter().map(|n| n.version.as_str()).collect();
+        assert_eq!(tags, ["v1.5.0"]);
+        assert_eq!(notes[0].url.as_deref(), Some("https://example.com/v1.5.0"));
+
+        let notes = notes_between(&releases, &filter, None, "nightly", |tag| tag.to_string());
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].version, "nightly");
+    }
+
+    #[test]
+    fn a_project_url_names_the_releases_to_read() {
+        match ReleaseSource::from_project_url("https://github.com/user/tool/tree/main") {
+            Some(ReleaseSource::GitHub {
+                repo, ..
+            }) => assert_eq!(repo, "user/tool"),
+            other => panic!("expected a GitHub source, got {other:?}"),
+        }
+        match ReleaseSource::from_project_url("https://gitlab.com/group/tool.git") {
+            Some(ReleaseSource::GitLab {
+                repo, ..
+            }) => assert_eq!(repo, "group/tool"),
+            other => panic!("expected a GitLab source, got {other:?}"),
+        }
+        match ReleaseSource::from_project_url("https://codeberg.org/user/tool") {
+            Some(ReleaseSource::Gitea {
+                repo, ..
+            }) => assert_eq!(repo, "codeberg.org/user/tool"),
+            other => panic!("expected a Gitea source, got {other:?}"),
+        }
+        match ReleaseSource::from_project_url("https://git.sr.ht/~user/tool") {
+            Some(ReleaseSource::SourceHut {
+                repo, ..
+            }) => assert_eq!(repo, "~user/tool"),
+            other => panic!("expected a SourceHut source, got {other:?}"),
+        }
+        assert!(ReleaseSource::from_project_url("https://github.com/user").is_none());
+        assert!(ReleaseSource::from_project_url("https://example.com/user/tool").is_none());
+    }
 }
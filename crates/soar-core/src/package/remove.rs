@@ -1,5 +1,4 @@
 use std::{
-    ffi::OsString,
     fs,
     os::unix::fs::PermissionsExt,
     path::{Path, PathBuf},
@@ -9,13 +8,21 @@ use soar_config::{
     config::Config,
     packages::{PackageHooks, SandboxConfig},
 };
-use soar_db::{models::types::PackageProvide, repository::core::CoreRepository};
-use soar_utils::{error::FileSystemResult, fs::walk_dir};
+use soar_db::{
+    models::{
+        core::{FileKind, InstalledFile},
+        types::PackageProvide,
+    },
+    repository::core::CoreRepository,
+};
+use soar_package::formats::common::{refresh_desktop_caches, remove_integration};
+use soar_utils::wrapper::link_target;
 use tracing::{debug, trace, warn};
 
 use super::hooks::{run_hook, HookEnv};
 
-/// Remove every symlink under `dir` that points into `installed_path`.
+/// Remove every symlink or wrapper under `dir` that points into
+/// `installed_path`.
 ///
 /// Ownership is decided by where the link goes, not by its name: a completion
 /// has to be named after its command, so soar cannot mark its own with a
@@ -27,8 +34,8 @@ fn remove_links_into(dir: &Path, installed_path: &Path, removed: &mut Vec<PathBu
     };
     for entry in entries.flatten() {
         let path = entry.path();
-        if path.is_symlink() {
-            if let Ok(target) = fs::read_link(&path) {
+        if path.is_symlink() || path.is_file() {
+            if let Some(target) = link_target(&path) {
                 if target.starts_with(installed_path) {
                     trace!("removing link: {}", path.display());
                     if fs::remove_file(&path).is_ok() {
@@ -42,19 +49,49 @@ fn remove_links_into(dir: &Path, installed_path: &Path, removed: &mut Vec<PathBu
     }
 }
 
+/// Remove the files recorded for a package that still lead into
+/// `installed_path`, returning those removed.
+///
+/// A recorded file can change hands after it is written: installing another
+/// variant relinks the same command, and a completion can be replaced by
+/// hand. Those belong to someone else now and stay. So do portable
+/// directories, which hold the user's data rather than the package's.
+pub(crate) fn remove_recorded_files(
+    files: &[InstalledFile],
+    installed_path: &Path,
+) -> Vec<PathBuf> {
+    let mut removed = Vec::new();
+    for file in files.iter().filter(|f| f.kind != FileKind::Portable) {
+        let path = PathBuf::from(&file.path);
+        let owned = link_target(&path).is_some_and(|target| target.starts_with(installed_path));
+        if owned && fs::remove_file(&path).is_ok() {
+            trace!("removing recorded {}: {}", file.kind, path.display());
+            removed.push(path);
+        }
+    }
+    removed
+}
+
+/// Whether removing `files` took away any of a package's desktop integration.
+pub(crate) fn removed_integration(files: &[InstalledFile], removed: &[PathBuf]) -> bool {
+    files.iter().any(|f| {
+        f.kind.is_integration() && removed.iter().any(|r| r.as_os_str() == f.path.as_str())
+    })
+}
+
 /// The directories a package's files are linked into, beyond `bin`.
 ///
 /// Every destination, not only the ones the configured shells ask for: a
 /// completion linked while a shell was enabled still has to be unlinked once
 /// that shell is turned off.
-fn shared_link_dirs(bin_path: &Path) -> Vec<PathBuf> {
+pub(crate) fn shared_link_dirs(bin_path: &Path) -> Vec<PathBuf> {
     crate::utils::shared_link_targets(bin_path, &[])
         .into_iter()
         .map(|(_, destination, _)| destination)
         .collect()
 }
 
-/// Removes the bin-directory symlinks a package's `provides` created, keeping
+/// Removes the bin-directory links a package's `provides` created, keeping
 /// only those that live directly in `bin_path` and resolve into
 /// `installed_path`.
 ///
@@ -73,8 +110,8 @@ pub(crate) fn remove_provide_symlinks(
             if link.parent() != Some(bin_path) {
                 continue;
             }
-            match fs::read_link(&link) {
-                Ok(target) if target.starts_with(installed_path) => {
+            match link_target(&link) {
+                Some(target) if target.starts_with(installed_path) => {
                     trace!("removing provide symlink: {}", link.display());
                     fs::remove_file(&link)
                         .with_context(|| format!("removing provide {}", link.display()))?;
@@ -209,10 +246,24 @@ impl PackageRemover {
 
         // Track removed symlinks for logging
         let mut removed_symlinks: Vec<PathBuf> = Vec::new();
+        let package_id = self.package.id as i32;
 
         // to prevent accidentally removing required files by other package,
         // remove only if the installation was successful
-        if self.package.is_installed {
+        let recorded = self
+            .db
+            .with_conn(|conn| CoreRepository::list_files(conn, package_id))?;
+        if self.package.is_installed && !recorded.is_empty() {
+            trace!("package was installed, removing its recorded files");
+            let installed_path = PathBuf::from(&self.package.installed_path);
+            let removed = remove_recorded_files(&recorded, &installed_path);
+            if removed_integration(&recorded, &removed) {
+                refresh_desktop_caches(&self.config);
+            }
+            removed_symlinks.extend(removed);
+        } else if self.package.is_installed {
+            // Installed before soar recorded what a package placed, so its
+            // files are found by where they point.
             trace!("package was installed, removing binaries and links");
             let bin_path = self.config.get_bin_path()?;
             let installed_path = PathBuf::from(&self.package.installed_path);
@@ -233,37 +284,9 @@ impl PackageRemover {
                 remove_links_into(&dir, &installed_path, &mut removed_symlinks);
             }
 
-            let mut remove_action = |path: &Path| -> FileSystemResult<()> {
-                if path.extension() == Some(&OsString::from("desktop")) {
-                    if let Ok(real_path) = fs::read_link(path) {
-                        if real_path.parent() == Some(&installed_path) {
-                            trace!("removing desktop file: {}", path.display());
-                            let _ = fs::remove_file(path);
-                        }
-                    }
-                }
-                Ok(())
-            };
-            // A missing desktop or icon directory means there is nothing of
-            // ours in it, not a reason to abandon the removal half-done.
-            let desktop_path = self.config.get_desktop_path()?;
-            if desktop_path.is_dir() {
-                walk_dir(&desktop_path, &mut remove_action)?;
-            }
-
-            let mut remove_action = |path: &Path| -> FileSystemResult<()> {
-                if let Ok(real_path) = fs::read_link(path) {
-                    if real_path.parent() == Some(&installed_path) {
-                        trace!("removing icon symlink: {}", path.display());
-                        let _ = fs::remove_file(path);
-                    }
-                }
-                Ok(())
-            };
-            let icons_path = self.config.get_icons_path();
-            if icons_path.is_dir() {
-                walk_dir(icons_path, &mut remove_action)?;
-            }
+            // Desktop entries, icons and AppStream files, with the caches
+            // that index them rebuilt so no handler outlives its package.
+            removed_symlinks.extend(remove_integration(&installed_path, &self.config)?);
         }
 
         // Calculate directory size before removal for logging
@@ -305,7 +328,6 @@ impl PackageRemover {
         };
 
         trace!("removing package from database");
-        let package_id = self.package.id as i32;
         self.db.transaction(|conn| {
             CoreRepository::delete_portable(conn, package_id)?;
             CoreRepository::delete(conn, package_id)
@@ -332,6 +354,7 @@ mod tests {
     };
 
     use soar_db::models::types::PackageProvide;
+    use soar_utils::wrapper::{render_wrapper, write_wrapper};
     use tempfile::tempdir;
 
     use super::remove_provide_symlinks;
@@ -387,6 +410,23 @@ mod tests {
         assert!(file.exists(), "a non-symlink file is never removed");
     }
 
+    #[test]
+    fn removes_owned_wrapper() {
+        let install = tempdir().unwrap();
+        let bin = tempdir().unwrap();
+        let target = install.path().join("clipcat");
+        File::create(&target).unwrap();
+        let wrapper = bin.path().join("clipcat");
+        let script = render_wrapper(&target, install.path(), &[("A", "1")], &[]);
+        write_wrapper(&wrapper, &script).unwrap();
+
+        let provides = vec![PackageProvide::from_string("clipcat")];
+        let removed = remove_provide_symlinks(bin.path(), &provides, install.path()).unwrap();
+
+        assert_eq!(removed, vec![wrapper.clone()]);
+        assert!(!wrapper.exists());
+    }
+
     #[test]
     fn rejects_path_traversal_in_provide_name() {
         let root = tempdir().unwrap();
@@ -0,0 +1,531 @@
+//! Upstream signatures on downloaded artifacts: OpenPGP and cosign.
+//!
+//! The key comes from `packages.toml`, so it is trusted as given: there is no
+//! web of trust to walk. What the key itself says still holds, though. A
+//! subkey signs only while the primary binds it for signing, and a revoked or
+//! expired key signs nothing; rPGP does the packet work and the checks
+//! against the key's self-signatures are made here.
+//!
+//! Artifacts run to hundreds of megabytes, so they are hashed as a stream and
+//! the signature checked against the digest, never read into memory whole.
+
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use p256::{
+    ecdsa::{signature::hazmat::PrehashVerifier, Signature as EcdsaSignature, VerifyingKey},
+    pkcs8::DecodePublicKey,
+};
+use pgp::{
+    composed::{Deserializable, DetachedSignature, SignedPublicKey},
+    crypto::hash::HashAlgorithm,
+    packet::{PublicKey, PublicSubkey, Signature, SignatureType, SubpacketData},
+    types::{Duration as PgpDuration, KeyDetails, Tag, Timestamp},
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use soar_config::packages::VerifyConfig;
+use soar_dl::http_client::SHARED_AGENT;
+use soar_utils::path::resolve_path;
+use tracing::{debug, trace};
+
+use crate::{error::SoarError, SoarResult};
+
+/// Signature files are small; a larger one is not a signature.
+const MAX_SIGNATURE_SIZE: u64 = 64 * 1024;
+
+/// Where each kind of signature is published, tried in order.
+const PGP_SUFFIXES: [&str; 3] = ["asc", "sig", "gpg"];
+const COSIGN_SUFFIXES: [&str; 2] = ["sig", "bundle"];
+
+/// Check `artifact`, downloaded from `url`, against every key `verify` sets.
+///
+/// A signature that cannot be found counts as a failure, the same as one that
+/// does not match: the key is a statement that upstream signs, and an
+/// unsigned artifact from such an upstream is not one to install.
+pub fn verify_artifact(artifact: &Path, url: &str, verify: &VerifyConfig) -> SoarResult<()> {
+    let fail = |reason: String| {
+        SoarError::SignatureInvalid {
+            artifact: artifact.display().to_string(),
+            reason,
+        }
+    };
+
+    if let Some(ref key) = verify.pgp_key {
+        let keys = parse_pgp_keys(&read_key(key)?).map_err(fail)?;
+        let signatures =
+            fetch_signatures(url, &PGP_SUFFIXES, parse_pgp_signatures).map_err(fail)?;
+        verify_pgp(artifact, &keys, &signatures).map_err(fail)?;
+        debug!(artifact = %artifact.display(), "OpenPGP signature verified");
+    }
+
+    if let Some(ref key) = verify.cosign_key {
+        let key = VerifyingKey::from_public_key_pem(read_key(key)?.trim())
+            .map_err(|e| fail(format!("cosign key is not a P-256 public key: {e}")))?;
+        let signature =
+            fetch_signatures(url, &COSIGN_SUFFIXES, parse_cosign_signature).map_err(fail)?;
+        let digest = hash_file::<Sha256>(artifact)?;
+        if key.verify_prehash(&digest, &signature).is_err() {
+            return Err(fail("cosign signature does not match".into()));
+        }
+        debug!(artifact = %artifact.display(), "cosign signature verified");
+    }
+
+    Ok(())
+}
+
+/// A key written into the config, or the file it names.
+fn read_key(value: &str) -> SoarResult<String> {
+    if value.contains("-----BEGIN") {
+        return Ok(value.to_string());
+    }
+    let path = resolve_path(value.trim())?;
+    std::fs::read_to_string(&path).map_err(|e| {
+        SoarError::Custom(format!(
+            "Failed to read signing key {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// The first signature file beside `url` that parses as `parse` expects.
+///
+/// A `.sig` is either kind, and one of the other kind is skipped rather than
+/// taken as a bad signature.
+fn fetch_signatures<T>(
+    url: &str,
+    suffixes: &[&str],
+    parse: impl Fn(&[u8]) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut tried = Vec::new();
+    for suffix in suffixes {
+        let sig_url = format!("{url}.{suffix}");
+        match fetch_small(&sig_url) {
+            Ok(bytes) => {
+                match parse(&bytes) {
+                    Ok(parsed) => return Ok(parsed),
+                    Err(e) => trace!(url = sig_url, error = e, "not a usable signature"),
+                }
+            }
+            Err(e) => trace!(url = sig_url, error = e, "no signature"),
+        }
+        tried.push(sig_url);
+    }
+    Err(format!("no signature found at {}", tried.join(", ")))
+}
+
+fn fetch_small(url: &str) -> Result<Vec<u8>, String> {
+    if let Some(path) = crate::package::local::local_path_from_url(url) {
+        return std::fs::read(path).map_err(|e| e.to_string());
+    }
+    // The signature is asked for right after the artifact, over a connection
+    // the server may have closed in the meantime; one that fails before any
+    // response is worth a second try rather than reporting no signature.
+    let resp = match SHARED_AGENT.get(url).call() {
+        Err(e) if !matches!(e, ureq::Error::StatusCode(_)) => {
+            trace!(url, error = %e, "retrying signature request");
+            SHARED_AGENT.get(url).call()
+        }
+        result => result,
+    }
+    .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    resp.into_body()
+        .with_config()
+        .limit(MAX_SIGNATURE_SIZE)
+        .read_to_vec()
+        .map_err(|e| e.to_string())
+}
+
+/// A digest of the file's contents.
+fn hash_file<D: Digest>(path: &Path) -> SoarResult<Vec<u8>> {
+    let file = File::open(path)
+        .map_err(|e| SoarError::Custom(format!("Failed to open {}: {}", path.display(), e)))?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = D::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = reader
+            .read(&mut buffer)
+            .map_err(|e| SoarError::Custom(format!("Failed to read {}: {}", path.display(), e)))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// A cosign signature: the base64 a `.sig` holds, or the one inside a bundle,
+/// whether the older cosign layout or a sigstore bundle.
+fn parse_cosign_signature(bytes: &[u8]) -> Result<EcdsaSignature, String> {
+    let text = std::str::from_utf8(bytes).map_err(|_| "not text".to_string())?;
+    let text = text.trim();
+    let encoded = if text.starts_with('{') {
+        let json: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        json.get("base64Signature")
+            .or_else(|| json.pointer("/messageSignature/signature"))
+            .and_then(Value::as_str)
+            .ok_or("bundle carries no signature")?
+            .to_string()
+    } else {
+        text.to_string()
+    };
+    let der = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("not base64: {e}"))?;
+    EcdsaSignature::from_der(&der).map_err(|e| e.to_string())
+}
+
+/// Every certificate in an OpenPGP key file, armored or binary.
+fn parse_pgp_keys(text: &str) -> Result<Vec<SignedPublicKey>, String> {
+    let (keys, _) = SignedPublicKey::from_reader_many(text.as_bytes())
+        .map_err(|e| format!("OpenPGP key does not parse: {e}"))?;
+    let keys = keys
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("OpenPGP key does not parse: {e}"))?;
+    if keys.is_empty() {
+        return Err("the OpenPGP key file holds no key".into());
+    }
+    Ok(keys)
+}
+
+/// Every signature in a detached signature file, armored or binary.
+fn parse_pgp_signatures(bytes: &[u8]) -> Result<Vec<DetachedSignature>, String> {
+    let (sigs, _) = DetachedSignature::from_reader_many(bytes).map_err(|e| e.to_string())?;
+    let sigs = sigs
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    if sigs.is_empty() {
+        return Err("no signature packet".into());
+    }
+    Ok(sigs)
+}
+
+/// A component key of a certificate that is allowed to sign data.
+#[derive(Clone, Copy)]
+enum PgpSigner<'a> {
+    Primary(&'a PublicKey),
+    Subkey(&'a PublicSubkey),
+}
+
+impl PgpSigner<'_> {
+    /// Whether `sig` names this key as its issuer. A signature that names no
+    /// issuer at all is not attributed to any key.
+    fn issued(self, sig: &Signature) -> bool {
+        let (fingerprint, key_id) = match self {
+            PgpSigner::Primary(key) => (key.fingerprint(), key.legacy_key_id()),
+            PgpSigner::Subkey(key) => (key.fingerprint(), key.legacy_key_id()),
+        };
+        let fingerprints = sig.issuer_fingerprint();
+        if fingerprints.is_empty() {
+            sig.issuer_key_id().contains(&&key_id)
+        } else {
+            fingerprints.contains(&&fingerprint)
+        }
+    }
+
+    fn verify(self, sig: &Signature, data: impl Read) -> bool {
+        match self {
+            PgpSigner::Primary(key) => sig.verify(key, data).is_ok(),
+            PgpSigner::Subkey(key) => sig.verify(key, data).is_ok(),
+        }
+    }
+}
+
+/// Whether `sig` has not yet expired at `at`.
+fn live(sig: &Signature, at: Timestamp) -> bool {
+    within(sig.created(), sig.signature_expiration_time(), at)
+}
+
+/// Whether something created at `created` and lasting `lifetime` is still
+/// valid at `at`. A missing or zero lifetime never runs out.
+fn within(created: Option<Timestamp>, lifetime: Option<PgpDuration>, at: Timestamp) -> bool {
+    let lifetime = lifetime.map_or(0, |d| std::time::Duration::from(d).as_secs());
+    match created {
+        Some(created) if lifetime > 0 => {
+            u64::from(created.as_secs()) + lifetime > u64::from(at.as_secs())
+        }
+        _ => true,
+    }
+}
+
+/// The newest of `sigs` that was made by `at` and had not expired by then.
+///
+/// A key's self-signatures supersede each other, so this is the one that
+/// said what the key was for when a data signature was made.
+fn in_force<'a>(sigs: impl Iterator<Item = &'a Signature>, at: Timestamp) -> Option<&'a Signature> {
+    sigs.filter_map(|sig| Some((sig.created()?, sig)))
+        .filter(|(created, sig)| *created <= at && live(sig, at))
+        .max_by_key(|(created, _)| *created)
+        .map(|(_, sig)| sig)
+}
+
+/// Whether `binding` lets its key sign data. Key flags are optional, and a
+/// self-signature without them puts no limit on the key.
+fn can_sign(binding: &Signature) -> bool {
+    let has_flags = binding.config().is_some_and(|config| {
+        config
+            .hashed_subpackets()
+            .any(|p| matches!(p.data, SubpacketData::KeyFlags(_)))
+    });
+    !has_flags || binding.key_flags().sign()
+}
+
+/// The keys of `cert` that could have made a data signature at `at`.
+///
+/// The primary key vouches for everything else, so a revoked or expired
+/// primary leaves nothing. A subkey counts only when a binding from the
+/// primary is in force with the signing flag, the subkey signed back to
+/// claim the primary, it had not expired, and it has never been revoked;
+/// a revocation is taken to mean the key may be in someone else's hands,
+/// so it voids signatures from before it as well.
+fn pgp_signers(cert: &SignedPublicKey, at: Timestamp) -> Vec<PgpSigner<'_>> {
+    let primary = &cert.primary_key;
+    let details = &cert.details;
+    if details
+        .revocation_signatures
+        .iter()
+        .any(|sig| sig.verify_key(primary).is_ok())
+    {
+        return Vec::new();
+    }
+
+    let direct = details
+        .direct_signatures
+        .iter()
+        .filter(|sig| sig.verify_key(primary).is_ok());
+    let certifications = details.users.iter().flat_map(|user| {
+        user.signatures.iter().filter(move |sig| {
+            sig.typ() != Some(SignatureType::CertRevocation)
+                && sig
+                    .verify_certification(primary, Tag::UserId, &user.id)
+                    .is_ok()
+        })
+    });
+    let Some(binding) = in_force(direct.chain(certifications), at) else {
+        return Vec::new();
+    };
+    if !within(
+        Some(primary.created_at()),
+        binding.key_expiration_time(),
+        at,
+    ) {
+        return Vec::new();
+    }
+
+    let mut signers = Vec::new();
+    if can_sign(binding) {
+        signers.push(PgpSigner::Primary(primary));
+    }
+
+    for subkey in &cert.public_subkeys {
+        let (revocations, bindings): (Vec<&Signature>, Vec<&Signature>) = subkey
+            .signatures
+            .iter()
+            .filter(|sig| sig.verify_subkey_binding(primary, &subkey.key).is_ok())
+            .partition(|sig| sig.typ() == Some(SignatureType::SubkeyRevocation));
+        if !revocations.is_empty() {
+            continue;
+        }
+        let Some(binding) = in_force(bindings.into_iter(), at) else {
+            continue;
+        };
+        // Without the back signature anyone could bind a key they hold to
+        // this primary and sign as it.
+        let signed_back = binding.embedded_signature().is_some_and(|back| {
+            back.verify_primary_key_binding(&subkey.key, primary)
+                .is_ok()
+        });
+        if binding.key_flags().sign()
+            && signed_back
+            && within(
+                Some(subkey.key.created_at()),
+                binding.key_expiration_time(),
+                at,
+            )
+        {
+            signers.push(PgpSigner::Subkey(&subkey.key));
+        }
+    }
+    signers
+}
+
+/// Whether any of `signatures` over `artifact` is made by a key of `certs`
+/// that was allowed to sign when it was made.
+fn verify_pgp(
+    artifact: &Path,
+    certs: &[SignedPublicKey],
+    signatures: &[DetachedSignature],
+) -> Result<(), String> {
+    let now = Timestamp::now();
+    for sig in signatures.iter().map(|detached| &detached.signature) {
+        if !matches!(sig.typ(), Some(SignatureType::Binary | SignatureType::Text)) {
+            trace!(typ = ?sig.typ(), "not a signature over a document");
+            continue;
+        }
+        // Collisions against these are practical, so a signature made with
+        // one says nothing about the artifact.
+        if !matches!(
+            sig.hash_alg(),
+            Some(
+                HashAlgorithm::Sha256
+                    | HashAlgorithm::Sha384
+                    | HashAlgorithm::Sha512
+                    | HashAlgorithm::Sha3_256
+                    | HashAlgorithm::Sha3_512
+            )
+        ) {
+            trace!(hash = ?sig.hash_alg(), "signature hash is too weak");
+            continue;
+        }
+        let Some(created) = sig.created() else {
+            continue;
+        };
+        if !live(sig, now) {
+            trace!("signature has expired");
+            continue;
+        }
+
+        for signer in certs
+            .iter()
+            .flat_map(|cert| pgp_signers(cert, created))
+            .filter(|signer| signer.issued(sig))
+        {
+            let file = File::open(artifact)
+                .map_err(|e| format!("Failed to open {}: {}", artifact.display(), e))?;
+            if signer.verify(sig, BufReader::new(file)) {
+                return Ok(());
+            }
+        }
+    }
+    Err("OpenPGP signature does not match, or its key may not sign".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_str!(concat!("../../tests/fixtures/signature/", $name))
+        };
+    }
+
+    /// An artifact published at a `file://` URL with `signature` beside it
+    /// under `suffix`.
+    fn published(contents: &str, suffix: &str, signature: &str) -> (TempDir, PathBuf, String) {
+        let dir = TempDir::new().unwrap();
+        let artifact = dir.path().join("tool");
+        std::fs::write(&artifact, contents).unwrap();
+        std::fs::write(dir.path().join(format!("tool.{suffix}")), signature).unwrap();
+        let url = format!("file://{}", artifact.display());
+        (dir, artifact, url)
+    }
+
+    fn pgp(key: &str) -> VerifyConfig {
+        VerifyConfig {
+            pgp_key: Some(key.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn cosign(key: &str) -> VerifyConfig {
+        VerifyConfig {
+            cosign_key: Some(key.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pgp_signatures_from_each_supported_key_type_verify() {
+        for (key, sig) in [
+            (fixture!("rsa.pub"), fixture!("rsa.asc")),
+            (fixture!("ed.pub"), fixture!("ed.asc")),
+            (fixture!("p256.pub"), fixture!("p256.asc")),
+        ] {
+            let (_dir, artifact, url) = published(fixture!("data"), "asc", sig);
+            verify_artifact(&artifact, &url, &pgp(key)).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_tampered_artifact_fails_pgp_verification() {
+        let (_dir, artifact, url) = published("hello soar!\n", "asc", fixture!("ed.asc"));
+        let err = verify_artifact(&artifact, &url, &pgp(fixture!("ed.pub"))).unwrap_err();
+        assert!(matches!(err, SoarError::SignatureInvalid { .. }));
+    }
+
+    #[test]
+    fn a_signature_by_another_key_is_rejected() {
+        let (_dir, artifact, url) = published(fixture!("data"), "asc", fixture!("rsa.asc"));
+        assert!(verify_artifact(&artifact, &url, &pgp(fixture!("p256.pub"))).is_err());
+    }
+
+    #[test]
+    fn a_signing_subkey_bound_to_the_key_verifies() {
+        let (_dir, artifact, url) = published(fixture!("data"), "asc", fixture!("subkey.asc"));
+        verify_artifact(&artifact, &url, &pgp(fixture!("subkey.pub"))).unwrap();
+    }
+
+    #[test]
+    fn a_revoked_subkey_no_longer_verifies_what_it_signed() {
+        let (_dir, artifact, url) = published(fixture!("data"), "asc", fixture!("revoked.asc"));
+        assert!(verify_artifact(&artifact, &url, &pgp(fixture!("revoked.pub"))).is_err());
+    }
+
+    #[test]
+    fn a_signature_made_after_the_subkey_expired_is_rejected() {
+        let (_dir, artifact, url) = published(fixture!("data"), "asc", fixture!("expired.asc"));
+        assert!(verify_artifact(&artifact, &url, &pgp(fixture!("expired.pub"))).is_err());
+    }
+
+    #[test]
+    fn a_missing_signature_is_a_failure() {
+        let dir = TempDir::new().unwrap();
+        let artifact = dir.path().join("tool");
+        std::fs::write(&artifact, fixture!("data")).unwrap();
+        let url = format!("file://{}", artifact.display());
+        let err = verify_artifact(&artifact, &url, &pgp(fixture!("rsa.pub"))).unwrap_err();
+        assert!(err.to_string().contains("no signature found"));
+    }
+
+    #[test]
+    fn the_pgp_key_may_be_given_as_a_path() {
+        let (dir, artifact, url) = published(fixture!("data"), "sig", fixture!("p256.asc"));
+        let key = dir.path().join("upstream.asc");
+        std::fs::write(&key, fixture!("p256.pub")).unwrap();
+        verify_artifact(&artifact, &url, &pgp(&key.to_string_lossy())).unwrap();
+    }
+
+    #[test]
+    fn cosign_signatures_verify_from_a_sig_or_a_bundle() {
+        let (_dir, artifact, url) = published(fixture!("data"), "sig", fixture!("cosign.sig"));
+        verify_artifact(&artifact, &url, &cosign(fixture!("cosign.pub"))).unwrap();
+
+        let bundle = format!(
+            r#"{{"base64Signature":"{}"}}"#,
+            fixture!("cosign.sig").trim()
+        );
+        let (_dir, artifact, url) = published(fixture!("data"), "bundle", &bundle);
+        verify_artifact(&artifact, &url, &cosign(fixture!("cosign.pub"))).unwrap();
+    }
+
+    #[test]
+    fn a_tampered_artifact_fails_cosign_verification() {
+        let (_dir, artifact, url) = published("hello soar!\n", "sig", fixture!("cosign.sig"));
+        let err = verify_artifact(&artifact, &url, &cosign(fixture!("cosign.pub"))).unwrap_err();
+        assert!(matches!(err, SoarError::SignatureInvalid { .. }));
+    }
+}
@@ -1,10 +1,13 @@
 use std::{fs, path::Path};
 
+use soar_config::config::get_config;
 use soar_db::repository::core::CoreRepository;
+use soar_package::formats::common::refresh_desktop_caches;
 
 use crate::{
     database::{connection::DieselDatabase, models::Package},
     error::ErrorContext,
+    package::remove::{remove_recorded_files, removed_integration},
     SoarResult,
 };
 
@@ -33,8 +36,14 @@ pub fn remove_old_versions(package: &Package, db: &DieselDatabase, force: bool)
         )
     })?;
 
-    for (_id, installed_path) in &old_packages {
+    let mut integration_removed = false;
+    for (id, installed_path) in &old_packages {
         let path = Path::new(installed_path);
+        // The new version has relinked what the two have in common, so what
+        // still leads here is only what it dropped.
+        let recorded = db.with_conn(|conn| CoreRepository::list_files(conn, *id))?;
+        let removed = remove_recorded_files(&recorded, path);
+        integration_removed |= removed_integration(&recorded, &removed);
         if path.exists() {
             // An archive may ship its directories read-only, and removing an
             // entry needs write permission on the directory holding it.
@@ -55,5 +64,9 @@ pub fn remove_old_versions(package: &Package, db: &DieselDatabase, force: bool)
         )
     })?;
 
+    if integration_removed {
+        refresh_desktop_caches(&get_config());
+    }
+
     Ok(())
 }
@@ -7,6 +7,8 @@
 
 use std::path::Path;
 
+use soar_config::release::Channel;
+
 use crate::{error::SoarError, package::release_source::ReleaseSource, SoarResult};
 
 /// The section an AppImage records its update information in.
@@ -33,6 +35,8 @@ pub enum UpdateInfo {
 pub enum Forge {
     GitHub,
     GitLab,
+    Gitea,
+    SourceHut,
 }
 
 impl UpdateInfo {
@@ -112,31 +116,22 @@ impl UpdateInfo {
             } => {
                 // `latest-pre` is the only form that asks for a prerelease;
                 // any other tag is matched literally.
-                let include_prerelease = tag == "latest-pre";
+                let channel = if tag == "latest-pre" {
+                    Channel::Prerelease
+                } else {
+                    Channel::Stable
+                };
                 let tag_pattern = match tag.as_str() {
                     "latest" | "latest-pre" | "" => None,
                     other => Some(other.to_string()),
                 };
-                let source = match forge {
-                    Forge::GitHub => {
-                        ReleaseSource::GitHub {
-                            repo: repo.clone(),
-                            asset_pattern: filename.clone(),
-                            include_prerelease,
-                            tag_pattern,
-                            arch_map: None,
-                        }
-                    }
-                    Forge::GitLab => {
-                        ReleaseSource::GitLab {
-                            repo: repo.clone(),
-                            asset_pattern: filename.clone(),
-                            include_prerelease,
-                            tag_pattern,
-                            arch_map: None,
-                        }
-                    }
-                };
+                let source = ReleaseSource::from_forge(
+                    *forge,
+                    repo.clone(),
+                    filename.clone(),
+                    channel,
+                    tag_pattern,
+                );
                 let release = source.resolve()?;
                 if release.download_url.is_empty() {
                     return Err(SoarError::Custom(format!(
@@ -0,0 +1,417 @@
+//! Finding the latest version of a package no forge publishes releases for.
+//!
+//! A `version_command` learns it by running a shell command, which executes
+//! whatever the configuration says with the user's rights and only works
+//! where a shell and the tools it calls are at hand. A version query asks a
+//! URL instead and picks the version out of the response in-process: with a
+//! regex, a JSON pointer or JSONPath, or an element selector over an HTML page
+//! or an Atom/RSS feed.
+
+use regex::Regex;
+use scraper::{Html, HtmlTreeSink};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use soar_config::packages::ResolvedPackage;
+use soar_dl::http_client::SHARED_AGENT;
+use soar_utils::version::{compare_versions, is_ordered, VersionReq};
+use xml5ever::tendril::TendrilSink;
+
+use crate::{
+    error::SoarError,
+    package::release_source::{run_version_command, VersionCommandResult},
+    SoarResult,
+};
+
+/// Where a package learns its latest version when it has no release source.
+#[derive(Debug, Clone)]
+pub enum VersionSource<'a> {
+    /// A shell command printing the version, and optionally the URL and size.
+    Command(&'a str),
+    /// A URL whose response names the version.
+    Query(VersionQuery<'a>),
+}
+
+impl<'a> VersionSource<'a> {
+    /// The source a package declares, if any. A query wins over a command
+    /// when both are set, as the one that runs nothing.
+    pub fn from_resolved(pkg: &'a ResolvedPackage) -> Option<Self> {
+        if let Some(url) = pkg.version_url.as_deref() {
+            return Some(Self::Query(VersionQuery {
+                url,
+                regex: pkg.version_regex.as_deref(),
+                json: pkg.version_json.as_deref(),
+                selector: pkg.version_selector.as_deref(),
+            }));
+        }
+        pkg.version_command.as_deref().map(Self::Command)
+    }
+
+    /// The field the source is declared with, for naming it in messages.
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::Command(_) => "version_command",
+            Self::Query(_) => "version_url",
+        }
+    }
+
+    /// The version to install, with the download URL when the source gives one.
+    ///
+    /// A command reports a single version. A query may turn up several, of
+    /// which the newest one `req` accepts is taken; when it accepts none the
+    /// newest of all is returned, for the caller to report as out of bounds.
+    pub fn resolve(&self, req: Option<&VersionReq>) -> SoarResult<VersionCommandResult> {
+        match self {
+            Self::Command(command) => run_version_command(command),
+            Self::Query(query) => {
+                let versions = query.versions()?;
+                let version = pick_version(&versions, req).ok_or_else(|| {
+                    SoarError::Custom(format!("No version found in {}", query.url))
+                })?;
+                Ok(VersionCommandResult {
+                    version,
+                    download_url: None,
+                    size: None,
+                })
+            }
+        }
+    }
+}
+
+/// A URL to fetch and the way to find the version in what it returns.
+#[derive(Debug, Clone)]
+pub struct VersionQuery<'a> {
+    pub url: &'a str,
+    /// Applied to the whole response, or to each value `json` or `selector`
+    /// picked out of it.
+    pub regex: Option<&'a str>,
+    /// A JSON pointer, starting with `/`, or a JSONPath, starting with `$`.
+    pub json: Option<&'a str>,
+    /// An element selector over HTML or XML, see [`Selector`].
+    pub selector: Option<&'a str>,
+}
+
+impl VersionQuery<'_> {
+    /// Every version the response names, in the order it names them.
+    pub fn versions(&self) -> SoarResult<Vec<String>> {
+        let body = fetch_text(self.url)?;
+        self.extract(&body)
+    }
+
+    /// Every version `body` names, in the order it names them.
+    pub fn extract(&self, body: &str) -> SoarResult<Vec<String>> {
+        let picked = match (self.json, self.selector) {
+            (Some(_), Some(_)) => {
+                return Err(SoarError::Custom(
+                    "version_json and version_selector cannot be used together".into(),
+                ));
+            }
+            (Some(path), None) => json_values(body, path)?,
+            (None, Some(selector)) => Selector::parse(selector)?.select(body),
+            (None, None) => vec![body.to_string()],
+        };
+
+        let versions: Vec<String> = match self.regex {
+            Some(pattern) => {
+                let re = Regex::new(pattern)?;
+                picked
+                    .iter()
+                    .flat_map(|text| {
+                        re.captures_iter(text)
+                            .filter_map(|caps| {
+                                caps.name("version")
+                                    .or_else(|| caps.get(1))
+                                    .or_else(|| caps.get(0))
+                                    .map(|m| m.as_str().trim().to_string())
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect()
+            }
+            None => picked.iter().map(|text| text.trim().to_string()).collect(),
+        };
+
+        Ok(versions.into_iter().filter(|v| !v.is_empty()).collect())
+    }
+}
+
+/// The version to take out of those a query found.
+///
+/// Pages list old releases beside the new one and feeds are not always in
+/// order, so the newest wins rather than the first. Text that carries no
+/// order, a word or a commit hash, is only taken when nothing else was found.
+fn pick_version(versions: &[String], req: Option<&VersionReq>) -> Option<String> {
+    if let Some(req) = req {
+        if let (Some(version), _) = req.newest(versions.iter(), |v| v.as_str()) {
+            return Some(version.clone());
+        }
+    }
+    versions
+        .iter()
+        .filter(|v| {
+            is_ordered(v)
+                && v.trim_start_matches(['v', 'V'])
+                    .starts_with(|c: char| c.is_ascii_digit())
+        })
+        .max_by(|a, b| compare_versions(a, b))
+        .or_else(|| versions.first())
+        .cloned()
+}
+
+/// The body of `url` as text, through the shared agent so proxy and header
+/// settings apply.
+fn fetch_text(url: &str) -> SoarResult<String> {
+    let resp = SHARED_AGENT
+        .get(url)
+        .call()
+        .map_err(|e| SoarError::Custom(format!("Failed to fetch {}: {}", url, e)))?;
+    if !resp.status().is_success() {
+        return Err(SoarError::Custom(format!(
+            "Failed to fetch {}: HTTP {}",
+            url,
+            resp.status()
+        )));
+    }
+    resp.into_body()
+        .read_to_string()
+        .map_err(|e| SoarError::Custom(format!("Failed to read {}: {}", url, e)))
+}
+
+/// The strings and numbers `path` reaches in a JSON document.
+fn json_values(body: &str, path: &str) -> SoarResult<Vec<String>> {
+    let doc: Value = serde_json::from_str(body)
+        .map_err(|e| SoarError::Custom(format!("version_url did not return JSON: {}", e)))?;
+
+    let found: Vec<&Value> = if path.is_empty() || path.starts_with('/') {
+        doc.pointer(path).into_iter().collect()
+    } else if path.starts_with('$') {
+        JsonPath::parse(path)
+            .map_err(|e| SoarError::Custom(format!("Invalid JSONPath '{}': {}", path, e)))?
+            .query(&doc)
+            .all()
+    } else {
+        return Err(SoarError::Custom(format!(
+            "'{}' is neither a JSON pointer (starting with '/') nor a JSONPath (starting with '$')",
+            path
+        )));
+    };
+
+    Ok(found
+        .into_iter()
+        .filter_map(|value| {
+            match value {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+/// A CSS selector over an HTML page or an XML feed.
+///
+/// A trailing `@attr` reads that attribute of each matched element instead
+/// of its text, as in `a.tag@href`.
+#[derive(Debug)]
+pub struct Selector {
+    css: scraper::Selector,
+    attr: Option<String>,
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> SoarResult<Self> {
+        let (css, attr) = match selector.rsplit_once('@') {
+            Some((css, attr)) if !attr.is_empty() && !attr.contains([']', ' ', '>']) => {
+                (css, Some(attr.to_string()))
+            }
+            _ => (selector, None),
+        };
+        let css = scraper::Selector::parse(css)
+            .map_err(|e| SoarError::Custom(format!("Invalid selector '{}': {}", selector, e)))?;
+        Ok(Self {
+            css,
+            attr,
+        })
+    }
+
+    /// The text, or the attribute, of every element the selector matches in
+    /// `doc`, in document order.
+    ///
+    /// Feeds are parsed as XML, where CDATA is text and `<link>` may hold
+    /// some; anything else is parsed as HTML, which forgives the unclosed
+    /// elements pages are full of.
+    pub fn select(&self, doc: &str) -> Vec<String> {
+        let head = doc.trim_start();
+        let tree =
+            if head.starts_with("<?xml") || head.starts_with("<rss") || head.starts_with("<feed") {
+                xml5ever::driver::parse_document(
+                    HtmlTreeSink::new(Html::new_document()),
+                    Default::default(),
+                )
+                .one(doc)
+            } else {
+                Html::parse_document(doc)
+            };
+
+        tree.select(&self.css)
+            .filter_map(|element| {
+                match &self.attr {
+                    Some(attr) => element.attr(attr).map(str::to_string),
+                    None => Some(element.text().collect::<String>()),
+                }
+            })
+            .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|text| !text.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    /// A server answering one request with `body`, and the URL to ask it at.
+    fn serve_once(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+        format!("http://{addr}/latest")
+    }
+
+    fn query<'a>(
+        url: &'a str,
+        regex: Option<&'a str>,
+        json: Option<&'a str>,
+        selector: Option<&'a str>,
+    ) -> VersionQuery<'a> {
+        VersionQuery {
+            url,
+            regex,
+            json,
+            selector,
+        }
+    }
+
+    #[test]
+    fn a_package_reads_its_version_from_a_local_api() {
+        let pkg = ResolvedPackage {
+            name: "tool".into(),
+            version_url: Some(serve_once(
+                r#"{"releases": [{"version": "1.9.0"}, {"version": "2.1.0"}, {"version": "2.0.3"}]}"#,
+            )),
+            version_json: Some("$.releases[*].version".into()),
+            version_command: Some("echo never".into()),
+            ..Default::default()
+        };
+
+        let source = VersionSource::from_resolved(&pkg).unwrap();
+        assert_eq!(source.field(), "version_url");
+        let req = VersionReq::parse("^2.0, <2.1").unwrap();
+        assert_eq!(source.resolve(Some(&req)).unwrap().version, "2.0.3");
+    }
+
+    #[test]
+    fn a_response_that_is_only_the_version_needs_no_extractor() {
+        let url = serve_once("v3.4.5\n");
+        assert_eq!(
+            query(&url, None, None, None).versions().unwrap(),
+            ["v3.4.5"]
+        );
+    }
+
+    #[test]
+    fn a_json_pointer_and_a_json_path_reach_the_same_version() {
+        let url = "http://unused.test";
+        let body = r#"{"tag_name": "v1.2.3", "assets": [{"name": "a-1.2.3"}]}"#;
+        for path in ["/tag_name", "$.tag_name"] {
+            assert_eq!(
+                query(url, None, Some(path), None).extract(body).unwrap(),
+                ["v1.2.3"]
+            );
+        }
+        assert_eq!(
+            query(url, Some(r"-(?<version>[\d.]+)$"), Some("$..name"), None)
+                .extract(body)
+                .unwrap(),
+            ["1.2.3"]
+        );
+        assert!(query(url, None, Some("tag_name"), None)
+            .extract(body)
+            .is_err());
+    }
+
+    #[test]
+    fn a_regex_over_a_listing_takes_the_newest_version() {
+        let page = "tool-1.9.tar.gz tool-1.10.tar.gz tool-1.2.tar.gz";
+        let versions = query(
+            "http://unused.test",
+            Some(r"tool-([\d.]+)\.tar"),
+            None,
+            None,
+        )
+        .extract(page)
+        .unwrap();
+        assert_eq!(pick_version(&versions, None).as_deref(), Some("1.10"));
+    }
+
+    #[test]
+    fn a_selector_reads_feed_titles_and_page_links() {
+        let feed = serve_once(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0"><channel><title>Releases</title>
+  <item><title>Release 2.4.0</title><link>https://e.test/2.4.0</link></item>
+  <item><title><![CDATA[Release 2.3.1]]></title><link>https://e.test/2.3.1</link></item>
+</channel></rss>"#,
+        );
+        let q = query(&feed, Some(r"(\d+\.\d+\.\d+)"), None, Some("item > title"));
+        assert_eq!(q.versions().unwrap(), ["2.4.0", "2.3.1"]);
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Releases</title>
+  <entry><title>Release 2.5.0</title><link href="https://e.test/2.5.0"/></entry>
+</feed>"#;
+        let url = "http://unused.test";
+        assert_eq!(
+            query(url, None, None, Some("entry > title"))
+                .extract(atom)
+                .unwrap(),
+            ["Release 2.5.0"]
+        );
+
+        let html = r#"<html><body>
+<ul class="tags"><li><a class="tag" href="/v/0.9.1">0.9.1</a>
+<li><a class="tag latest" href="/v/1.0.0">1.0.0 &amp; notes</a></ul>
+<a class="tag" href="/elsewhere">ignored</a>
+</body></html>"#;
+        assert_eq!(
+            query(url, None, None, Some("ul.tags a.tag@href"))
+                .extract(html)
+                .unwrap(),
+            ["/v/0.9.1", "/v/1.0.0"]
+        );
+        assert_eq!(
+            query(url, None, None, Some("a.latest"))
+                .extract(html)
+                .unwrap(),
+            ["1.0.0 & notes"]
+        );
+        assert!(query(url, None, None, Some("entry >"))
+            .extract(html)
+            .is_err());
+    }
+}
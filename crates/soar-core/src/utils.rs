@@ -10,6 +10,8 @@ use soar_config::config::get_config;
 use soar_utils::{
     error::FileSystemResult,
     fs::{safe_remove, walk_dir},
+    path::{on_host, xdg_config_home, xdg_data_home},
+    wrapper::is_broken_link,
 };
 use tracing::info;
 
@@ -59,7 +61,7 @@ pub fn cleanup_cache() -> Result<()> {
 }
 
 fn remove_action(path: &Path) -> FileSystemResult<()> {
-    if path.is_symlink() && !path.exists() {
+    if is_broken_link(path) {
         safe_remove(path)?;
         info!("Removed broken symlink: {}", path.display());
     }
@@ -132,8 +134,8 @@ pub fn shared_link_targets(
     shells: &[String],
 ) -> Vec<(&'static str, PathBuf, bool)> {
     let prefix = bin_dir.parent().unwrap_or(bin_dir);
-    let data = soar_utils::path::xdg_data_home();
-    let config = soar_utils::path::xdg_config_home();
+    let data = on_host(xdg_data_home());
+    let config = on_host(xdg_config_home());
     let wants = |name: &str| shells.iter().any(|s| s == name);
     vec![
         ("share/man", prefix.join("share/man"), true),
@@ -0,0 +1,24 @@
+-- One row per package an operation has planned and not finished, written
+-- before each step is taken. A row that outlives its process says where that
+-- package stood when the process died.
+CREATE TABLE operation_journal (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  operation TEXT NOT NULL,
+  pkg_name TEXT NOT NULL,
+  repo_name TEXT NOT NULL,
+  version TEXT NOT NULL,
+  -- Unknown until the package is about to be downloaded.
+  install_dir TEXT,
+  -- The installed row an update replaces, to fall back to on a rollback.
+  replaces_id INTEGER,
+  step TEXT NOT NULL,
+  -- The install target as JSON, enough to start the package over.
+  target TEXT NOT NULL,
+  -- The options the operation ran with, as JSON, so it starts over the same.
+  options TEXT NOT NULL,
+  -- Names the lock the running process holds for the row. One that can be
+  -- taken means the process is gone.
+  owner TEXT NOT NULL,
+  started_at TEXT NOT NULL,
+  updated_at TEXT NOT NULL
+);
@@ -0,0 +1,12 @@
+-- Every file a package placed outside its install directory: command links,
+-- shared files, desktop integration and portable directories. Removal deletes
+-- what is listed here instead of searching for what points into the package.
+CREATE TABLE package_files (
+  package_id INTEGER NOT NULL,
+  path TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  FOREIGN KEY (package_id) REFERENCES packages (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_package_files_package_id ON package_files (package_id);
+CREATE INDEX idx_package_files_path ON package_files (path);
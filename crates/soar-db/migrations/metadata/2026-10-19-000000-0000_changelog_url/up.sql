@@ -0,0 +1,3 @@
+-- Where a package's release notes are published, for indexes whose packages
+-- do not come from a forge that already has them.
+ALTER TABLE packages ADD COLUMN changelog_url TEXT;
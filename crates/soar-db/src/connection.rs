@@ -5,13 +5,19 @@
 //!
 //! - **Core database**: Tracks installed packages
 //! - **Metadata databases**: One per repository, contains package metadata
+//! - **Unified metadata**: Every metadata database attached to one connection
 
 use std::{collections::HashMap, path::Path};
 
-use diesel::{sql_query, Connection, ConnectionError, RunQueryDsl, SqliteConnection};
+use diesel::{
+    sql_query, sql_types::Text, Connection, ConnectionError, RunQueryDsl, SqliteConnection,
+};
 use tracing::{debug, trace};
 
-use crate::migration::{apply_migrations, migrate_metadata_json_to_jsonb, DbType};
+use crate::{
+    migration::{apply_migrations, migrate_metadata_json_to_jsonb, DbType},
+    schema::unified::PACKAGE_COLUMNS,
+};
 
 /// How long to wait for another process to let go of the database.
 ///
@@ -24,6 +30,29 @@ pub struct DbConnection {
     conn: SqliteConnection,
 }
 
+/// A repository's metadata database, as attached to a unified connection.
+pub struct AttachedDb<'a> {
+    /// The repository name, which becomes the view's `repo_name`.
+    pub name: &'a str,
+    pub path: &'a Path,
+    /// Read without locking, for a database this process cannot write the
+    /// lock files of, as [`DbConnection::open_readonly`] does.
+    pub immutable: bool,
+}
+
+/// `path` as the path part of an SQLite `file:` URI.
+fn uri_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('%', "%25")
+        .replace('?', "%3f")
+        .replace('#', "%23")
+}
+
+/// `text` as an SQL string literal.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
 /// Set the pragmas every connection wants, before anything reads or writes.
 fn prepare(conn: &mut SqliteConnection) -> Result<(), ConnectionError> {
     sql_query(format!("PRAGMA busy_timeout = {BUSY_TIMEOUT_MS};"))
@@ -136,6 +165,124 @@ impl DbConnection {
         })
     }
 
+    /// Opens one connection that reads every database in `dbs` at once.
+    ///
+    /// Each is attached read-only, and a temporary `all_packages` view joins
+    /// their packages with the repository name as a column, so a query can
+    /// filter, order and group across repositories in SQLite instead of
+    /// repeating itself per repository. The databases should already have
+    /// been opened with [`DbConnection::open_metadata`], which the view
+    /// relies on for its JSONB columns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a database cannot be attached, including when
+    /// there are more than SQLite allows attached to one connection; see
+    /// [`DbConnection::open_unified_groups`] for that case.
+    pub fn open_unified(dbs: &[AttachedDb]) -> Result<Self, ConnectionError> {
+        let (conn, attached) = Self::attach_unified(dbs)?;
+        if attached < dbs.len() {
+            return Err(ConnectionError::BadConnection(format!(
+                "only {attached} of {} metadata databases fit on one connection",
+                dbs.len()
+            )));
+        }
+        Ok(conn)
+    }
+
+    /// Opens as many unified connections as it takes to attach every
+    /// database in `dbs`, each over the next of them in order.
+    ///
+    /// How many databases a connection may attach is fixed when SQLite is
+    /// built, ten unless the build raised it, and is not something to ask the
+    /// connection for. So each connection takes databases until SQLite
+    /// refuses one, and the next connection starts from there.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a database cannot be attached for any other
+    /// reason.
+    pub fn open_unified_groups(dbs: &[AttachedDb]) -> Result<Vec<Self>, ConnectionError> {
+        let mut groups = Vec::new();
+        let mut rest = dbs;
+        loop {
+            let (conn, attached) = Self::attach_unified(rest)?;
+            groups.push(conn);
+            rest = &rest[attached..];
+            if rest.is_empty() {
+                debug!(
+                    count = dbs.len(),
+                    groups = groups.len(),
+                    "unified metadata connections opened"
+                );
+                return Ok(groups);
+            }
+        }
+    }
+
+    /// A connection with `dbs` attached from the first for as long as SQLite
+    /// takes them and the `all_packages` view over those, with how many that
+    /// was.
+    fn attach_unified(dbs: &[AttachedDb]) -> Result<(Self, usize), ConnectionError> {
+        debug!(count = dbs.len(), "opening unified metadata connection");
+        let bad = |e: diesel::result::Error| ConnectionError::BadConnection(e.to_string());
+
+        let mut conn = SqliteConnection::establish(":memory:")?;
+        sql_query(format!("PRAGMA busy_timeout = {BUSY_TIMEOUT_MS};"))
+            .execute(&mut conn)
+            .map_err(bad)?;
+
+        let columns = PACKAGE_COLUMNS.join(", ");
+        let mut selects = Vec::with_capacity(dbs.len());
+        for (idx, db) in dbs.iter().enumerate() {
+            let mut uri = format!("file:{}?mode=ro", uri_path(db.path));
+            if db.immutable {
+                uri.push_str("&immutable=1");
+            }
+            let attached = sql_query(format!("ATTACH DATABASE ? AS repo{idx}"))
+                .bind::<Text, _>(&uri)
+                .execute(&mut conn);
+            match attached {
+                Ok(_) => {}
+                Err(e) if idx > 0 && e.to_string().starts_with("too many attached databases") => {
+                    trace!(attached = idx, "no more databases fit on this connection");
+                    break;
+                }
+                Err(e) => return Err(bad(e)),
+            }
+            trace!(
+                repo_name = db.name,
+                schema = idx,
+                "metadata database attached"
+            );
+            selects.push(format!(
+                "SELECT {} AS repo_name, {columns} FROM repo{idx}.packages",
+                quote(db.name)
+            ));
+        }
+        let attached = selects.len();
+
+        // A view of nothing still needs the columns, so an empty one selects
+        // them from no rows.
+        let body = if selects.is_empty() {
+            let nulls = vec!["NULL"; PACKAGE_COLUMNS.len() + 1].join(", ");
+            format!("SELECT {nulls} WHERE 0")
+        } else {
+            selects.join(" UNION ALL ")
+        };
+        let names = format!("repo_name, {columns}");
+        sql_query(format!("CREATE TEMP VIEW all_packages ({names}) AS {body}"))
+            .execute(&mut conn)
+            .map_err(bad)?;
+
+        Ok((
+            Self {
+                conn,
+            },
+            attached,
+        ))
+    }
+
     /// Gets a mutable reference to the underlying connection.
     pub fn conn(&mut self) -> &mut SqliteConnection {
         &mut self.conn
@@ -1,5 +1,6 @@
 use diesel::{prelude::*, sqlite::Sqlite};
 use serde_json::Value;
+use soar_utils::path::on_host;
 
 use crate::{json_vec, models::types::PackageProvide, schema::core::*};
 
@@ -64,7 +65,7 @@ impl Queryable<packages::SqlType, Sqlite> for Package {
             version: row.6,
             size: row.7,
             checksum: row.8,
-            installed_path: row.9,
+            installed_path: on_host(row.9).to_string_lossy().into_owned(),
             installed_date: row.10,
             profile: row.11,
             pinned: row.12,
@@ -91,7 +92,7 @@ pub struct PortablePackage {
     pub portable_cache: Option<String>,
 }
 
-#[derive(Default, Insertable)]
+#[derive(Clone, Default, Insertable)]
 #[diesel(table_name = packages)]
 pub struct NewPackage<'a> {
     pub repo_name: &'a str,
@@ -125,3 +126,132 @@ pub struct NewPortablePackage<'a> {
     pub portable_share: Option<&'a str>,
     pub portable_cache: Option<&'a str>,
 }
+
+/// A package an operation planned and has not finished.
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = operation_journal)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct JournalEntry {
+    pub id: i32,
+    pub operation: String,
+    pub pkg_name: String,
+    pub repo_name: String,
+    pub version: String,
+    pub install_dir: Option<String>,
+    pub replaces_id: Option<i32>,
+    pub step: String,
+    pub target: String,
+    pub options: String,
+    pub owner: String,
+    pub started_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = operation_journal)]
+pub struct NewJournalEntry<'a> {
+    pub operation: &'a str,
+    pub pkg_name: &'a str,
+    pub repo_name: &'a str,
+    pub version: &'a str,
+    pub install_dir: Option<&'a str>,
+    pub replaces_id: Option<i32>,
+    pub step: &'a str,
+    pub target: &'a str,
+    pub options: &'a str,
+    pub owner: &'a str,
+    pub started_at: &'a str,
+    pub updated_at: &'a str,
+}
+
+/// What an installed file is to the package that placed it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileKind {
+    /// A command in the bin directory, as a symlink or a wrapper.
+    Binary,
+    /// A man page or a shell completion.
+    Shared,
+    Desktop,
+    Icon,
+    /// An AppStream file.
+    Metainfo,
+    /// A directory a portable install keeps its state in. It holds the user's
+    /// data, so it is listed but never deleted with the package.
+    Portable,
+}
+
+impl FileKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Binary => "binary",
+            Self::Shared => "shared",
+            Self::Desktop => "desktop",
+            Self::Icon => "icon",
+            Self::Metainfo => "metainfo",
+            Self::Portable => "portable",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "binary" => Some(Self::Binary),
+            "shared" => Some(Self::Shared),
+            "desktop" => Some(Self::Desktop),
+            "icon" => Some(Self::Icon),
+            "metainfo" => Some(Self::Metainfo),
+            "portable" => Some(Self::Portable),
+            _ => None,
+        }
+    }
+
+    /// Whether the file belongs to desktop integration, whose caches have to
+    /// be rebuilt once it goes.
+    pub fn is_integration(&self) -> bool {
+        matches!(self, Self::Desktop | Self::Icon | Self::Metainfo)
+    }
+}
+
+impl std::fmt::Display for FileKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// A file an installed package placed outside its install directory.
+#[derive(Debug, Clone)]
+pub struct InstalledFile {
+    pub package_id: i32,
+    pub path: String,
+    pub kind: FileKind,
+}
+
+impl
+    Queryable<
+        (
+            diesel::sql_types::Integer,
+            diesel::sql_types::Text,
+            diesel::sql_types::Text,
+        ),
+        Sqlite,
+    > for InstalledFile
+{
+    type Row = (i32, String, String);
+
+    fn build(row: Self::Row) -> diesel::deserialize::Result<Self> {
+        let kind = FileKind::parse(&row.2)
+            .ok_or_else(|| format!("unknown installed file kind '{}'", row.2))?;
+        Ok(Self {
+            package_id: row.0,
+            path: on_host(row.1).to_string_lossy().into_owned(),
+            kind,
+        })
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = package_files)]
+pub struct NewInstalledFile<'a> {
+    pub package_id: i32,
+    pub path: &'a str,
+    pub kind: &'a str,
+}
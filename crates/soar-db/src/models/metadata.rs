@@ -48,6 +48,7 @@ pub struct Package {
     pub extra: Option<Vec<PackageExtra>>,
     /// What the package takes out of its artifact.
     pub files: Option<Vec<PackageFile>>,
+    pub changelog_url: Option<String>,
 }
 
 impl Queryable<packages::SqlType, Sqlite> for Package {
@@ -88,6 +89,7 @@ impl Queryable<packages::SqlType, Sqlite> for Package {
         Option<bool>,
         Option<Value>,
         Option<Value>,
+        Option<String>,
     );
 
     fn build(row: Self::Row) -> diesel::deserialize::Result<Self> {
@@ -128,6 +130,7 @@ impl Queryable<packages::SqlType, Sqlite> for Package {
             portable: row.33,
             extra: json_vec!(row.34),
             files: json_vec!(row.35),
+            changelog_url: row.36,
         })
     }
 }
@@ -229,6 +232,7 @@ pub struct NewPackage<'a> {
     pub portable: Option<bool>,
     pub extra: Option<Value>,
     pub files: Option<Value>,
+    pub changelog_url: Option<&'a str>,
 }
 
 #[derive(Default, Insertable)]
@@ -1,17 +1,23 @@
 //! Core database repository for installed packages.
 
+use std::path::PathBuf;
+
 use diesel::{
     prelude::*,
     sql_types::{Bool, Nullable},
     sqlite::Sqlite,
 };
+use soar_utils::path::{in_target, on_host};
 
 use crate::{
     models::{
-        core::{NewPackage, NewPortablePackage, Package, PortablePackage},
+        core::{
+            FileKind, InstalledFile, JournalEntry, NewInstalledFile, NewJournalEntry, NewPackage,
+            NewPortablePackage, Package, PortablePackage,
+        },
         types::PackageProvide,
     },
-    schema::core::{packages, portable_package},
+    schema::core::{operation_journal, package_files, packages, portable_package},
 };
 
 /// An installed row reduced to what identifies it: id, repository, package id,
@@ -43,6 +49,17 @@ fn match_pkg_family(
     }
 }
 
+/// An installed path as it is stored: the way the install root names it, so
+/// the database stays right once that root is the running system.
+fn stored_path(path: &str) -> String {
+    in_target(path).to_string_lossy().into_owned()
+}
+
+/// The other half of [`stored_path`], for paths read back out.
+fn host_path(path: String) -> String {
+    on_host(path).to_string_lossy().into_owned()
+}
+
 /// Sort direction for queries.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SortDirection {
@@ -305,6 +322,17 @@ impl CoreRepository {
             .first(conn)
     }
 
+    /// Counts the rows recorded under a profile, installed or not.
+    ///
+    /// A half-finished install still owns a directory in the profile, so it
+    /// counts as much as a complete one.
+    pub fn count_by_profile(conn: &mut SqliteConnection, profile: &str) -> QueryResult<i64> {
+        packages::table
+            .filter(packages::profile.eq(profile))
+            .count()
+            .get_result(conn)
+    }
+
     /// Finds an installed package by ID.
     pub fn find_by_id(conn: &mut SqliteConnection, id: i32) -> QueryResult<Option<Package>> {
         packages::table
@@ -434,8 +462,13 @@ impl CoreRepository {
 
     /// Inserts a new installed package and returns the inserted ID.
     pub fn insert(conn: &mut SqliteConnection, package: &NewPackage) -> QueryResult<i32> {
+        let installed_path = stored_path(package.installed_path);
+        let package = NewPackage {
+            installed_path: &installed_path,
+            ..package.clone()
+        };
         diesel::insert_into(packages::table)
-            .values(package)
+            .values(&package)
             .returning(packages::id)
             .get_result(conn)
     }
@@ -481,7 +514,7 @@ impl CoreRepository {
             packages::is_installed.eq(true),
             packages::provides.eq(provides),
             packages::checksum.eq(checksum),
-            packages::installed_path.eq(installed_path),
+            packages::installed_path.eq(stored_path(installed_path)),
         ))
         .returning(packages::id)
         .get_result(conn)
@@ -495,6 +528,21 @@ impl CoreRepository {
             .execute(conn)
     }
 
+    /// Record that a package now lives under another profile, at `installed_path`.
+    pub fn relocate(
+        conn: &mut SqliteConnection,
+        id: i32,
+        profile: &str,
+        installed_path: &str,
+    ) -> QueryResult<usize> {
+        diesel::update(packages::table.filter(packages::id.eq(id)))
+            .set((
+                packages::profile.eq(profile),
+                packages::installed_path.eq(stored_path(installed_path)),
+            ))
+            .execute(conn)
+    }
+
     /// Sets the unlinked status of a package.
     pub fn set_unlinked(
         conn: &mut SqliteConnection,
@@ -588,8 +636,9 @@ impl CoreRepository {
         .execute(conn)
     }
 
-    /// Deletes an installed package by ID.
+    /// Deletes an installed package by ID, along with the files recorded for it.
     pub fn delete(conn: &mut SqliteConnection, id: i32) -> QueryResult<usize> {
+        Self::delete_files(conn, id)?;
         diesel::delete(packages::table.filter(packages::id.eq(id))).execute(conn)
     }
 
@@ -638,7 +687,7 @@ impl CoreRepository {
         )
         .execute(conn)?;
 
-        Ok(paths)
+        Ok(paths.into_iter().map(host_path).collect())
     }
 
     /// Gets the portable package configuration for a package.
@@ -747,9 +796,13 @@ impl CoreRepository {
             query.filter(packages::pinned.eq(false))
         };
 
-        query
+        let paths: Vec<(i32, String)> = query
             .select((packages::id, packages::installed_path))
-            .load(conn)
+            .load(conn)?;
+        Ok(paths
+            .into_iter()
+            .map(|(id, path)| (id, host_path(path)))
+            .collect())
     }
 
     /// Deletes old package versions (all except the newest one).
@@ -791,7 +844,15 @@ impl CoreRepository {
             .filter(packages::id.ne(latest_id))
             .filter(pinned_filter);
 
-        diesel::delete(query).execute(conn)
+        let deleted = diesel::delete(query).execute(conn)?;
+        // Foreign keys are not enforced on this connection, so the files of
+        // the rows just deleted are not cascaded away with them.
+        diesel::delete(
+            package_files::table
+                .filter(package_files::package_id.ne_all(packages::table.select(packages::id))),
+        )
+        .execute(conn)?;
+        Ok(deleted)
     }
 
     /// Finds installs fetched from a given URL.
@@ -835,4 +896,214 @@ impl CoreRepository {
             .set(packages::unlinked.eq(false))
             .execute(conn)
     }
+
+    /// Write a planned package to the journal and return its entry id.
+    pub fn begin_journal(conn: &mut SqliteConnection, entry: &NewJournalEntry) -> QueryResult<i32> {
+        diesel::insert_into(operation_journal::table)
+            .values(entry)
+            .returning(operation_journal::id)
+            .get_result(conn)
+    }
+
+    /// Record the step a journaled package is about to take, and where it is
+    /// being installed once that is known.
+    pub fn advance_journal(
+        conn: &mut SqliteConnection,
+        id: i32,
+        step: &str,
+        install_dir: Option<&str>,
+        updated_at: &str,
+    ) -> QueryResult<usize> {
+        let target = operation_journal::table.filter(operation_journal::id.eq(id));
+        match install_dir {
+            Some(dir) => {
+                diesel::update(target)
+                    .set((
+                        operation_journal::step.eq(step),
+                        operation_journal::install_dir.eq(dir),
+                        operation_journal::updated_at.eq(updated_at),
+                    ))
+                    .execute(conn)
+            }
+            None => {
+                diesel::update(target)
+                    .set((
+                        operation_journal::step.eq(step),
+                        operation_journal::updated_at.eq(updated_at),
+                    ))
+                    .execute(conn)
+            }
+        }
+    }
+
+    /// Drop a journal entry whose package no longer needs recovering.
+    pub fn finish_journal(conn: &mut SqliteConnection, id: i32) -> QueryResult<usize> {
+        diesel::delete(operation_journal::table.filter(operation_journal::id.eq(id))).execute(conn)
+    }
+
+    /// Every journal entry left behind, oldest first.
+    pub fn list_journal(conn: &mut SqliteConnection) -> QueryResult<Vec<JournalEntry>> {
+        operation_journal::table
+            .order(operation_journal::id.asc())
+            .select(JournalEntry::as_select())
+            .load(conn)
+    }
+
+    /// The journal entry with `id`, if it has not been dropped.
+    pub fn find_journal(conn: &mut SqliteConnection, id: i32) -> QueryResult<Option<JournalEntry>> {
+        operation_journal::table
+            .filter(operation_journal::id.eq(id))
+            .select(JournalEntry::as_select())
+            .first(conn)
+            .optional()
+    }
+
+    /// Replace the files recorded for a package with `files`.
+    ///
+    /// A reinstall or relink places its files again, and whatever the last
+    /// one placed is what the package owns now.
+    pub fn replace_files(
+        conn: &mut SqliteConnection,
+        package_id: i32,
+        files: &[(PathBuf, FileKind)],
+    ) -> QueryResult<usize> {
+        Self::delete_files(conn, package_id)?;
+        let paths: Vec<String> = files
+            .iter()
+            .map(|(path, _)| stored_path(&path.to_string_lossy()))
+            .collect();
+        let rows: Vec<NewInstalledFile> = files
+            .iter()
+            .zip(&paths)
+            .map(|((_, kind), path)| {
+                NewInstalledFile {
+                    package_id,
+                    path,
+                    kind: kind.as_str(),
+                }
+            })
+            .collect();
+        diesel::insert_into(package_files::table)
+            .values(&rows)
+            .execute(conn)
+    }
+
+    /// The files recorded for a package, in the order they were placed.
+    pub fn list_files(
+        conn: &mut SqliteConnection,
+        package_id: i32,
+    ) -> QueryResult<Vec<InstalledFile>> {
+        package_files::table
+            .filter(package_files::package_id.eq(package_id))
+            .order(package_files::rowid.asc())
+            .select((
+                package_files::package_id,
+                package_files::path,
+                package_files::kind,
+            ))
+            .load(conn)
+    }
+
+    /// Every recorded file at `path`, whichever package placed it.
+    pub fn find_files_at(
+        conn: &mut SqliteConnection,
+        path: &str,
+    ) -> QueryResult<Vec<InstalledFile>> {
+        package_files::table
+            .filter(package_files::path.eq(stored_path(path)))
+            .order(package_files::rowid.asc())
+            .select((
+                package_files::package_id,
+                package_files::path,
+                package_files::kind,
+            ))
+            .load(conn)
+    }
+
+    /// Forget the files recorded for a package.
+    pub fn delete_files(conn: &mut SqliteConnection, package_id: i32) -> QueryResult<usize> {
+        diesel::delete(package_files::table.filter(package_files::package_id.eq(package_id)))
+            .execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{connection::DbConnection, migration::DbType};
+
+    fn install(conn: &mut SqliteConnection, version: &str) -> i32 {
+        let package = NewPackage {
+            repo_name: "bincache",
+            pkg_name: "htop",
+            version,
+            installed_path: "/opt/soar/packages/htop",
+            installed_date: "2026-10-19 00:00:00",
+            profile: "default",
+            ..Default::default()
+        };
+        CoreRepository::insert(conn, &package).unwrap()
+    }
+
+    #[test]
+    fn recorded_files_are_listed_found_and_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = DbConnection::open(dir.path().join("soar.db"), DbType::Core).unwrap();
+        let conn = db.conn();
+        let id = install(conn, "3.3.0");
+
+        let files = [
+            (PathBuf::from("/bin/htop"), FileKind::Binary),
+            (PathBuf::from("/share/man/man1/htop.1"), FileKind::Shared),
+        ];
+        CoreRepository::replace_files(conn, id, &files).unwrap();
+        let listed = CoreRepository::list_files(conn, id).unwrap();
+        let listed: Vec<_> = listed.iter().map(|f| (f.path.as_str(), f.kind)).collect();
+        assert_eq!(
+            listed,
+            [
+                ("/bin/htop", FileKind::Binary),
+                ("/share/man/man1/htop.1", FileKind::Shared)
+            ]
+        );
+
+        let owners = CoreRepository::find_files_at(conn, "/share/man/man1/htop.1").unwrap();
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[0].package_id, id);
+
+        CoreRepository::replace_files(conn, id, &files[..1]).unwrap();
+        assert_eq!(CoreRepository::list_files(conn, id).unwrap().len(), 1);
+        assert!(
+            CoreRepository::find_files_at(conn, "/share/man/man1/htop.1")
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn files_go_with_the_package_that_recorded_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = DbConnection::open(dir.path().join("soar.db"), DbType::Core).unwrap();
+        let conn = db.conn();
+        let old = install(conn, "3.2.0");
+        let new = install(conn, "3.3.0");
+        let removed = install(conn, "3.4.0");
+        for id in [old, new, removed] {
+            CoreRepository::replace_files(
+                conn,
+                id,
+                &[(PathBuf::from("/bin/htop"), FileKind::Binary)],
+            )
+            .unwrap();
+        }
+
+        CoreRepository::delete(conn, removed).unwrap();
+        assert!(CoreRepository::list_files(conn, removed)
+            .unwrap()
+            .is_empty());
+
+        CoreRepository::delete_old_packages(conn, None, None, "htop", "bincache", false).unwrap();
+        assert!(CoreRepository::list_files(conn, old).unwrap().is_empty());
+        assert_eq!(CoreRepository::list_files(conn, new).unwrap().len(), 1);
+    }
 }
@@ -21,6 +21,7 @@ static MAINTAINER_RE: OnceLock<Regex> = OnceLock::new();
 
 use super::core::SortDirection;
 use crate::{
+    json_vec,
     models::{
         metadata::{
             FuzzyCandidate, Maintainer, NewMaintainer, NewPackage, NewPackageMaintainer,
@@ -98,6 +99,26 @@ fn supersedes_unordered(
     }
 }
 
+/// The commands a package puts in the bin directory.
+///
+/// The package's own name always counts: it is what a package without
+/// `provides` is linked as, and what someone typing the name of a tool they
+/// know expects to find. Provides that could not be linked safely are left
+/// out, since an install would skip them too.
+fn provided_commands<'a>(pkg_name: &'a str, provides: &'a [PackageProvide]) -> Vec<&'a str> {
+    let mut commands = vec![pkg_name];
+    for name in provides
+        .iter()
+        .filter(|provide| provide.is_safe())
+        .flat_map(PackageProvide::bin_symlink_names)
+    {
+        if !commands.contains(&name) {
+            commands.push(name);
+        }
+    }
+    commands
+}
+
 /// Repository for package metadata operations.
 pub struct MetadataRepository;
 
@@ -573,6 +594,50 @@ impl MetadataRepository {
         .get_result(conn)
     }
 
+    /// Lists every command the packages provide, as
+    /// `(command, pkg_name, pkg_family)`, ordered by command.
+    ///
+    /// Only the three columns are read, which keeps this cheap enough to run
+    /// on every sync for the index the command-not-found lookup reads.
+    pub fn list_commands(
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<(String, String, Option<String>)>> {
+        trace!("listing provided commands");
+        let rows: Vec<(String, Option<String>, Option<serde_json::Value>)> = packages::table
+            .select((packages::pkg_name, packages::pkg_family, packages::provides))
+            .load(conn)?;
+
+        let mut commands = Vec::new();
+        for (pkg_name, pkg_family, provides) in rows {
+            let provides: Vec<PackageProvide> = json_vec!(provides).unwrap_or_default();
+            for command in provided_commands(&pkg_name, &provides) {
+                commands.push((command.to_string(), pkg_name.clone(), pkg_family.clone()));
+            }
+        }
+        commands.sort();
+        commands.dedup();
+        debug!(count = commands.len(), "listed provided commands");
+        Ok(commands)
+    }
+
+    /// Lists every published package as `(pkg_name, pkg_family, version)`,
+    /// ordered by name.
+    ///
+    /// Shell completion reads these from an index written at sync, so only the
+    /// columns a completed name is made of are loaded.
+    pub fn list_names(
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<(String, Option<String>, String)>> {
+        trace!("listing package names");
+        let mut names: Vec<(String, Option<String>, String)> = packages::table
+            .select((packages::pkg_name, packages::pkg_family, packages::version))
+            .load(conn)?;
+        names.sort();
+        names.dedup();
+        debug!(count = names.len(), "listed package names");
+        Ok(names)
+    }
+
     /// Imports packages from remote metadata (JSON format).
     pub fn import_packages(
         conn: &mut SqliteConnection,
@@ -696,6 +761,7 @@ impl MetadataRepository {
             portable: package.portable,
             extra: package.extra.as_ref().map(|e| json!(e)),
             files: package.files.as_ref().map(|f| json!(f)),
+            changelog_url: package.changelog_url.as_deref(),
         };
 
         let inserted = diesel::insert_into(packages::table)
@@ -738,7 +804,8 @@ impl MetadataRepository {
 
 #[cfg(test)]
 mod tests {
-    use super::supersedes_unordered;
+    use super::{provided_commands, supersedes_unordered};
+    use crate::models::types::PackageProvide;
 
     const HELD: &str = "89c99d2a9";
     const OFFERED: &str = "0f3a21b";
@@ -787,4 +854,17 @@ mod tests {
         assert!(!supersedes_unordered(OFFERED, None, HELD, Some("bsum-old")));
         assert!(!supersedes_unordered(OFFERED, Some("bsum-new"), HELD, None));
     }
+
+    #[test]
+    fn a_package_provides_its_name_and_what_it_links() {
+        let provides = [
+            PackageProvide::from_string("clipcatd"),
+            PackageProvide::from_string("clipcat-menu=>clipcat"),
+            PackageProvide::from_string("../escape"),
+        ];
+        assert_eq!(
+            provided_commands("clipcat", &provides),
+            ["clipcat", "clipcatd"]
+        );
+    }
 }
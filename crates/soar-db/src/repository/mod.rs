@@ -5,6 +5,8 @@
 //!
 //! - [`CoreRepository`] - Installed package operations
 //! - [`MetadataRepository`] - Package metadata queries
+//! - [`UnifiedRepository`] - Package metadata queries across every repository
 
 pub mod core;
 pub mod metadata;
+pub mod unified;
@@ -0,0 +1,406 @@
+//! Queries across every repository at once, through the unified view.
+//!
+//! These run on a connection from
+//! [`DbConnection::open_unified`](crate::connection::DbConnection::open_unified),
+//! where `all_packages` holds every attached repository's packages. Ordering,
+//! paging and counting happen in SQLite, over all repositories together, and
+//! each row comes back with the repository it belongs to.
+
+use std::cmp::Ordering;
+
+use diesel::{dsl::sql, prelude::*};
+use tracing::{debug, trace};
+
+use crate::{
+    models::metadata::{FuzzyCandidate, Package, PackageListing, PackageWithRepo},
+    schema::unified::{all_packages, package_columns},
+};
+
+/// Name first and repository second, which keeps one package's offers from
+/// different repositories together.
+macro_rules! by_name_then_repo {
+    () => {
+        (
+            all_packages::pkg_name.asc(),
+            all_packages::repo_name.asc(),
+            all_packages::id.asc(),
+        )
+    };
+}
+
+fn with_repo(rows: Vec<(String, Package)>) -> Vec<PackageWithRepo> {
+    rows.into_iter()
+        .map(|(repo_name, package)| PackageWithRepo::new(repo_name, package))
+        .collect()
+}
+
+/// Repository for queries spanning every attached repository.
+pub struct UnifiedRepository;
+
+impl UnifiedRepository {
+    /// Orders rows the way the queries here do: by name, then repository,
+    /// then id.
+    ///
+    /// Results from separate unified connections are merged with it, and it
+    /// has to agree with the `ORDER BY` each of them ran, byte-wise text
+    /// comparison included.
+    pub fn cmp_rows(a: &PackageWithRepo, b: &PackageWithRepo) -> Ordering {
+        a.package
+            .pkg_name
+            .cmp(&b.package.pkg_name)
+            .then_with(|| a.repo_name.cmp(&b.repo_name))
+            .then_with(|| a.package.id.cmp(&b.package.id))
+    }
+
+    /// Lists every package of every repository with only the fields needed
+    /// for display.
+    pub fn list_all_minimal(
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<(String, PackageListing)>> {
+        trace!("listing all packages across repositories (minimal fields)");
+        let result = all_packages::table
+            .order(by_name_then_repo!())
+            .select((
+                all_packages::repo_name,
+                (
+                    all_packages::pkg_id,
+                    all_packages::pkg_family,
+                    all_packages::pkg_name,
+                    all_packages::pkg_type,
+                    all_packages::version,
+                ),
+            ))
+            .load(conn);
+        if let Ok(ref packages) = result {
+            debug!(
+                count = packages.len(),
+                "listed all packages across repositories"
+            );
+        }
+        result
+    }
+
+    /// `limit` packages of every repository, ordered by name, after skipping
+    /// the first `offset`.
+    pub fn list_paginated(
+        conn: &mut SqliteConnection,
+        offset: i64,
+        limit: i64,
+    ) -> QueryResult<Vec<PackageWithRepo>> {
+        trace!(
+            offset,
+            limit,
+            "listing paginated packages across repositories"
+        );
+        all_packages::table
+            .order(by_name_then_repo!())
+            .limit(limit)
+            .offset(offset)
+            .select((all_packages::repo_name, package_columns!()))
+            .load(conn)
+            .map(with_repo)
+    }
+
+    /// Counts the packages of every repository together.
+    pub fn count(conn: &mut SqliteConnection) -> QueryResult<i64> {
+        all_packages::table.count().get_result(conn)
+    }
+
+    /// How many packages each repository offers under each name, as
+    /// `(repo_name, pkg_name, count)`.
+    ///
+    /// Counted the way
+    /// [`MetadataRepository::count_names`](super::metadata::MetadataRepository::count_names)
+    /// counts a single repository: once per family a name is published under.
+    pub fn count_names(conn: &mut SqliteConnection) -> QueryResult<Vec<(String, String, i64)>> {
+        trace!("counting what each package name stands for, across repositories");
+        all_packages::table
+            .group_by((all_packages::repo_name, all_packages::pkg_name))
+            .select((
+                all_packages::repo_name,
+                all_packages::pkg_name,
+                sql::<diesel::sql_types::BigInt>("COUNT(DISTINCT COALESCE(pkg_family, ''))"),
+            ))
+            .load(conn)
+    }
+
+    /// Loads what fuzzy matching needs from every repository.
+    pub fn load_fuzzy_candidates(
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<(String, FuzzyCandidate)>> {
+        trace!("loading fuzzy candidates across repositories");
+        all_packages::table
+            .select((
+                all_packages::repo_name,
+                (
+                    all_packages::id,
+                    all_packages::pkg_name,
+                    all_packages::pkg_id,
+                    all_packages::description,
+                ),
+            ))
+            .load(conn)
+    }
+
+    /// Fetches full package details for `(repo_name, id)` pairs.
+    pub fn find_by_ids(
+        conn: &mut SqliteConnection,
+        ids: &[(&str, i32)],
+    ) -> QueryResult<Vec<PackageWithRepo>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut by_repo: Vec<(&str, Vec<i32>)> = Vec::new();
+        for &(repo_name, id) in ids {
+            match by_repo.iter_mut().find(|(name, _)| *name == repo_name) {
+                Some((_, repo_ids)) => repo_ids.push(id),
+                None => by_repo.push((repo_name, vec![id])),
+            }
+        }
+
+        let mut query = all_packages::table.into_boxed();
+        for (repo_name, repo_ids) in by_repo {
+            query = query.or_filter(
+                all_packages::repo_name
+                    .eq(repo_name)
+                    .and(all_packages::id.eq_any(repo_ids)),
+            );
+        }
+        query
+            .select((all_packages::repo_name, package_columns!()))
+            .load(conn)
+            .map(with_repo)
+    }
+
+    /// Searches every repository by name or id (case-sensitive `LIKE`),
+    /// ordered by name, with `offset` and `limit` applied to the results as a
+    /// whole.
+    pub fn search_case_sensitive(
+        conn: &mut SqliteConnection,
+        pattern: &str,
+        offset: i64,
+        limit: Option<i64>,
+    ) -> QueryResult<Vec<PackageWithRepo>> {
+        let like_pattern = format!("%{}%", pattern);
+
+        let mut query = all_packages::table
+            .filter(
+                all_packages::pkg_name
+                    .like(&like_pattern)
+                    .or(all_packages::pkg_id.like(&like_pattern)),
+            )
+            .order(by_name_then_repo!())
+            .into_boxed();
+
+        if let Some(lim) = limit {
+            query = query.limit(lim);
+        }
+        if offset > 0 {
+            query = query.offset(offset);
+        }
+
+        query
+            .select((all_packages::repo_name, package_columns!()))
+            .load(conn)
+            .map(with_repo)
+    }
+
+    /// Finds packages in every repository by name, id and family, ordered by
+    /// name and then repository.
+    ///
+    /// Filters the way
+    /// [`MetadataRepository::find_filtered`](super::metadata::MetadataRepository::find_filtered)
+    /// does, `"all"` included.
+    pub fn find_filtered(
+        conn: &mut SqliteConnection,
+        pkg_name: Option<&str>,
+        pkg_id: Option<&str>,
+        pkg_family: Option<&str>,
+    ) -> QueryResult<Vec<PackageWithRepo>> {
+        let mut query = all_packages::table.into_boxed();
+
+        if let Some(name) = pkg_name {
+            query = query.filter(all_packages::pkg_name.eq(name));
+        }
+        if let Some(family) = pkg_family {
+            if family != "all" {
+                query = query.filter(all_packages::pkg_family.eq(family));
+            }
+        }
+        if let Some(id) = pkg_id {
+            if id != "all" {
+                query = query.filter(all_packages::pkg_id.eq(id));
+            }
+        }
+
+        query
+            .order(by_name_then_repo!())
+            .select((all_packages::repo_name, package_columns!()))
+            .load(conn)
+            .map(with_repo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{
+        connection::{AttachedDb, DbConnection},
+        migration::DbType,
+        models::metadata::NewPackage,
+        repository::metadata::MetadataRepository,
+    };
+
+    fn repo(dir: &Path, name: &str, packages: &[(&str, &str)]) -> std::path::PathBuf {
+        let path = dir.join(format!("{name}.db"));
+        let mut db = DbConnection::open(&path, DbType::Metadata).unwrap();
+        for (pkg_name, version) in packages {
+            let package = NewPackage {
+                pkg_name,
+                version,
+                download_url: "https://example.com/pkg",
+                ..Default::default()
+            };
+            MetadataRepository::insert(db.conn(), &package).unwrap();
+        }
+        path
+    }
+
+    fn unified(dir: &Path, repos: &[(&str, &[(&str, &str)])]) -> DbConnection {
+        let paths: Vec<_> = repos
+            .iter()
+            .map(|(name, packages)| (*name, repo(dir, name, packages)))
+            .collect();
+        let attached: Vec<_> = paths
+            .iter()
+            .map(|(name, path)| {
+                AttachedDb {
+                    name,
+                    path,
+                    immutable: false,
+                }
+            })
+            .collect();
+        DbConnection::open_unified(&attached).unwrap()
+    }
+
+    #[test]
+    fn rows_from_every_repository_are_ordered_and_paged_together() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = unified(
+            dir.path(),
+            &[
+                ("beta", &[("zellij", "0.40"), ("bat", "0.24")]),
+                ("alpha", &[("bat", "0.23"), ("fd", "10.1")]),
+            ],
+        );
+
+        let listed: Vec<(String, String)> = UnifiedRepository::list_all_minimal(db.conn())
+            .unwrap()
+            .into_iter()
+            .map(|(repo, pkg)| (repo, pkg.pkg_name))
+            .collect();
+        let expected = [
+            ("alpha", "bat"),
+            ("beta", "bat"),
+            ("alpha", "fd"),
+            ("beta", "zellij"),
+        ];
+        assert_eq!(
+            listed,
+            expected.map(|(r, n)| (r.to_string(), n.to_string()))
+        );
+
+        assert_eq!(UnifiedRepository::count(db.conn()).unwrap(), 4);
+        let page: Vec<_> = UnifiedRepository::list_paginated(db.conn(), 3, 3)
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.repo_name, p.package.pkg_name))
+            .collect();
+        assert_eq!(page, [("beta".to_string(), "zellij".to_string())]);
+
+        let found = UnifiedRepository::search_case_sensitive(db.conn(), "a", 0, Some(2)).unwrap();
+        let found: Vec<_> = found.iter().map(|p| p.repo_name.as_str()).collect();
+        assert_eq!(found, ["alpha", "beta"]);
+        let found = UnifiedRepository::search_case_sensitive(db.conn(), "a", 1, Some(2)).unwrap();
+        let found: Vec<_> = found.iter().map(|p| p.repo_name.as_str()).collect();
+        assert_eq!(found, ["beta"]);
+    }
+
+    #[test]
+    fn rows_keep_the_repository_they_came_from() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = unified(
+            dir.path(),
+            &[("it's", &[("bat", "0.24")]), ("alpha", &[("bat", "0.23")])],
+        );
+
+        let mut names = UnifiedRepository::count_names(db.conn()).unwrap();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                ("alpha".to_string(), "bat".to_string(), 1),
+                ("it's".to_string(), "bat".to_string(), 1)
+            ]
+        );
+
+        let candidates = UnifiedRepository::load_fuzzy_candidates(db.conn()).unwrap();
+        let quoted = candidates
+            .iter()
+            .find(|(repo, _)| repo == "it's")
+            .map(|(_, c)| c.id)
+            .unwrap();
+        let found = UnifiedRepository::find_by_ids(db.conn(), &[("it's", quoted)]).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].package.version, "0.24");
+
+        let bats = UnifiedRepository::find_filtered(db.conn(), Some("bat"), None, None).unwrap();
+        assert_eq!(bats.len(), 2);
+    }
+
+    #[test]
+    fn a_unified_connection_over_nothing_has_no_packages() {
+        let mut db = DbConnection::open_unified(&[]).unwrap();
+        assert_eq!(UnifiedRepository::count(db.conn()).unwrap(), 0);
+        assert!(UnifiedRepository::list_all_minimal(db.conn())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn more_repositories_than_sqlite_attaches_are_split_across_connections() {
+        let dir = tempfile::tempdir().unwrap();
+        let names: Vec<String> = (0..12).map(|i| format!("repo{i:02}")).collect();
+        let paths: Vec<_> = names
+            .iter()
+            .map(|name| repo(dir.path(), name, &[("bat", "0.24")]))
+            .collect();
+        let attached: Vec<_> = names
+            .iter()
+            .zip(&paths)
+            .map(|(name, path)| {
+                AttachedDb {
+                    name,
+                    path,
+                    immutable: false,
+                }
+            })
+            .collect();
+
+        let mut groups = DbConnection::open_unified_groups(&attached).unwrap();
+        let repos: Vec<String> = groups
+            .iter_mut()
+            .flat_map(|db| UnifiedRepository::list_all_minimal(db.conn()).unwrap())
+            .map(|(repo, _)| repo)
+            .collect();
+
+        assert_eq!(repos, names);
+        assert_eq!(
+            DbConnection::open_unified(&attached).is_ok(),
+            groups.len() == 1
+        );
+    }
+}
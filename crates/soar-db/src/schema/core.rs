@@ -36,6 +36,39 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    operation_journal (id) {
+        id -> Integer,
+        operation -> Text,
+        pkg_name -> Text,
+        repo_name -> Text,
+        version -> Text,
+        install_dir -> Nullable<Text>,
+        replaces_id -> Nullable<Integer>,
+        step -> Text,
+        target -> Text,
+        options -> Text,
+        owner -> Text,
+        started_at -> Text,
+        updated_at -> Text,
+    }
+}
+
+diesel::table! {
+    package_files (rowid) {
+        rowid -> Integer,
+        package_id -> Integer,
+        path -> Text,
+        kind -> Text,
+    }
+}
+
 diesel::joinable!(portable_package -> packages (package_id));
+diesel::joinable!(package_files -> packages (package_id));
 
-diesel::allow_tables_to_appear_in_same_query!(packages, portable_package,);
+diesel::allow_tables_to_appear_in_same_query!(
+    packages,
+    portable_package,
+    operation_journal,
+    package_files,
+);
@@ -52,6 +52,7 @@ diesel::table! {
         portable -> Nullable<Bool>,
         extra -> Nullable<Jsonb>,
         files -> Nullable<Jsonb>,
+        changelog_url -> Nullable<Text>,
     }
 }
 
@@ -0,0 +1,77 @@
+//! The cross-repository view a unified metadata connection exposes.
+//!
+//! `all_packages` is not a table in any database file. It is a temporary view
+//! [`DbConnection::open_unified`](crate::connection::DbConnection::open_unified)
+//! creates over the `packages` table of every repository it attaches, with
+//! the repository's name as an extra column.
+
+/// Declares `all_packages` with `repo_name` and then the given `packages`
+/// columns, along with [`PACKAGE_COLUMNS`] naming them for the view and
+/// `package_columns!` selecting them, so the three cannot fall out of step.
+macro_rules! all_packages {
+    ($($column:ident -> $ty:ty,)*) => {
+        diesel::table! {
+            all_packages (repo_name, id) {
+                repo_name -> Text,
+                $($column -> $ty,)*
+            }
+        }
+
+        /// The `packages` columns the view carries over, in the order it
+        /// lists them.
+        ///
+        /// Named rather than `*`, since `UNION ALL` pairs columns by position
+        /// and two repositories' tables need not have been created in the
+        /// same order.
+        pub const PACKAGE_COLUMNS: &[&str] = &[$(stringify!($column)),*];
+
+        /// Every column a [`Package`](crate::models::metadata::Package) is
+        /// read from, in its order.
+        macro_rules! package_columns {
+            () => {
+                ($($crate::schema::unified::all_packages::$column,)*)
+            };
+        }
+        pub(crate) use package_columns;
+    };
+}
+
+all_packages! {
+    id -> Integer,
+    pkg_id -> Nullable<Text>,
+    pkg_name -> Text,
+    pkg_family -> Nullable<Text>,
+    pkg_type -> Nullable<Text>,
+    app_id -> Nullable<Text>,
+    description -> Nullable<Text>,
+    version -> Text,
+    licenses -> Nullable<Jsonb>,
+    download_url -> Text,
+    size -> Nullable<BigInt>,
+    ghcr_pkg -> Nullable<Text>,
+    ghcr_size -> Nullable<BigInt>,
+    ghcr_blob -> Nullable<Text>,
+    ghcr_url -> Nullable<Text>,
+    bsum -> Nullable<Text>,
+    icon -> Nullable<Text>,
+    desktop -> Nullable<Text>,
+    appstream -> Nullable<Text>,
+    homepages -> Nullable<Jsonb>,
+    notes -> Nullable<Jsonb>,
+    source_urls -> Nullable<Jsonb>,
+    categories -> Nullable<Jsonb>,
+    build_id -> Nullable<Text>,
+    build_date -> Nullable<Text>,
+    build_action -> Nullable<Text>,
+    build_script -> Nullable<Text>,
+    build_log -> Nullable<Text>,
+    provides -> Nullable<Jsonb>,
+    snapshots -> Nullable<Jsonb>,
+    replaces -> Nullable<Jsonb>,
+    soar_syms -> Bool,
+    desktop_integration -> Nullable<Bool>,
+    portable -> Nullable<Bool>,
+    extra -> Nullable<Jsonb>,
+    files -> Nullable<Jsonb>,
+    changelog_url -> Nullable<Text>,
+}
@@ -0,0 +1,378 @@
+use std::{fmt, fs::File, io::Read, path::Path};
+
+use sha2::{Digest, Sha256, Sha512};
+use tracing::{debug, trace};
+
+use crate::{error::DownloadError, http_client::SHARED_AGENT, traits::Asset};
+
+/// Checksum files are a few lines per asset; anything larger is not one.
+const MAX_CHECKSUM_FILE: u64 = 1024 * 1024;
+
+/// The hash functions a checksum can be taken with.
+///
+/// MD5 and SHA-1 are left out on purpose: upstreams still publish them, but a
+/// match against either says too little to install on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Blake3,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Blake3 => "blake3",
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('-', "").as_str() {
+            "blake3" | "b3" => Some(Algorithm::Blake3),
+            "sha256" => Some(Algorithm::Sha256),
+            "sha512" => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    /// The algorithm a checksum file's name announces, like `SHA512SUMS` or
+    /// `tool.b3`.
+    fn named_in(file_name: &str) -> Option<Self> {
+        let lower = file_name.to_ascii_lowercase();
+        if lower.contains("sha512") {
+            Some(Algorithm::Sha512)
+        } else if lower.contains("sha256") {
+            Some(Algorithm::Sha256)
+        } else if lower.contains("blake3") || lower.contains("b3sum") || lower.ends_with(".b3") {
+            Some(Algorithm::Blake3)
+        } else {
+            None
+        }
+    }
+
+    /// The algorithm a bare digest of this many hex digits most likely came
+    /// from. A 64-digit one could be BLAKE3 as well, but files that do not
+    /// say which are written by `sha256sum` in practice.
+    fn guessed_from(hex: &str) -> Option<Self> {
+        match hex.len() {
+            64 => Some(Algorithm::Sha256),
+            128 => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+}
+
+/// A digest together with the algorithm it was taken with.
+///
+/// Written as `sha256:<hex>` or `sha512:<hex>`. A bare hex digest is BLAKE3,
+/// which is what a `bsum` has always been, so sums recorded before other
+/// algorithms were understood keep their meaning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    pub hex: String,
+}
+
+impl Checksum {
+    /// Reads a checksum as `bsum` and the core database write it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use soar_dl::checksum::{Algorithm, Checksum};
+    ///
+    /// let sum = Checksum::parse("SHA256:ABCD").unwrap();
+    /// assert_eq!(sum.algorithm, Algorithm::Sha256);
+    /// assert_eq!(sum.to_string(), "sha256:abcd");
+    /// assert_eq!(Checksum::parse("abcd").unwrap().algorithm, Algorithm::Blake3);
+    /// assert!(Checksum::parse("md5:abcd").is_none());
+    /// ```
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (algorithm, hex) = match value.split_once(':') {
+            Some((name, hex)) => (Algorithm::from_name(name)?, hex),
+            None => (Algorithm::Blake3, value),
+        };
+        Self::new(algorithm, hex)
+    }
+
+    fn new(algorithm: Algorithm, hex: &str) -> Option<Self> {
+        (!hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit())).then(|| {
+            Checksum {
+                algorithm,
+                hex: hex.to_ascii_lowercase(),
+            }
+        })
+    }
+
+    /// Hashes the file at `path` with `algorithm`.
+    pub fn of_file(path: &Path, algorithm: Algorithm) -> std::io::Result<Self> {
+        let hex = match algorithm {
+            Algorithm::Blake3 => {
+                soar_utils::hash::calculate_checksum(path)
+                    .map_err(|e| std::io::Error::other(e.to_string()))?
+            }
+            Algorithm::Sha256 => hash_file::<Sha256>(path)?,
+            Algorithm::Sha512 => hash_file::<Sha512>(path)?,
+        };
+        Ok(Checksum {
+            algorithm,
+            hex,
+        })
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.algorithm {
+            Algorithm::Blake3 => f.write_str(&self.hex),
+            algorithm => write!(f, "{}:{}", algorithm.name(), self.hex),
+        }
+    }
+}
+
+fn hash_file<D: Digest>(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = D::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+/// What a release publishes to check one of its assets against.
+///
+/// Gathered from the release listing alone, so resolving a release costs no
+/// more requests than before; the files are only read by [`Self::resolve`],
+/// once there is something to install.
+#[derive(Debug, Clone, Default)]
+pub struct PublishedChecksums {
+    asset: String,
+    digest: Option<Checksum>,
+    /// Checksum files as `(name, url)`, the asset's own before lists.
+    files: Vec<(String, String)>,
+}
+
+impl PublishedChecksums {
+    pub fn from_release<A: Asset>(assets: &[A], asset: &A) -> Self {
+        let name = asset.name();
+        let sidecars = assets.iter().filter(|a| {
+            a.name()
+                .strip_prefix(name)
+                .and_then(|ext| ext.strip_prefix('.'))
+                .is_some_and(|ext| {
+                    Algorithm::from_name(ext.strip_suffix("sum").unwrap_or(ext)).is_some()
+                })
+        });
+        let lists = assets.iter().filter(|a| is_checksum_list(a.name()));
+        PublishedChecksums {
+            asset: name.to_string(),
+            digest: asset.digest().and_then(Checksum::parse),
+            files: sidecars
+                .chain(lists)
+                .map(|a| (a.name().to_string(), a.url().to_string()))
+                .collect(),
+        }
+    }
+
+    /// The checksum the release publishes for the asset, if it publishes one.
+    ///
+    /// A digest the forge computed itself is taken first, since it costs no
+    /// request. Otherwise a file beside the asset that names only it, then a
+    /// list covering the whole release. A checksum file that cannot be
+    /// fetched or does not mention the asset is passed over: it is no worse
+    /// than having none.
+    pub fn resolve(&self) -> Option<Checksum> {
+        if let Some(ref digest) = self.digest {
+            return Some(digest.clone());
+        }
+        self.files
+            .iter()
+            .find_map(|(name, url)| fetch_checksum(url, name, &self.asset))
+    }
+}
+
+/// The checksum published beside a file served on its own, as
+/// `<url>.sha256` or `<url>.sha512`.
+///
+/// Only those two are tried. A plain server has no listing to look in, and
+/// every guess is a request.
+pub fn find_beside(url: &str) -> Option<Checksum> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let file_name = path.rsplit('/').next().filter(|n| !n.is_empty())?;
+    ["sha256", "sha512"].into_iter().find_map(|ext| {
+        let sidecar = format!("{file_name}.{ext}");
+        fetch_checksum(&format!("{path}.{ext}"), &sidecar, file_name)
+    })
+}
+
+/// Whether a release asset is a list of checksums for the other assets.
+fn is_checksum_list(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    let stem = lower.strip_suffix(".txt").unwrap_or(&lower);
+    stem.ends_with("sums")
+        || stem.ends_with("checksums")
+        || stem.ends_with("checksum")
+        || stem.ends_with("hashes")
+}
+
+fn fetch_checksum(url: &str, file_name: &str, asset: &str) -> Option<Checksum> {
+    match fetch_small(url) {
+        Ok(body) => {
+            let sum = parse_checksum_file(&body, file_name, asset);
+            trace!(url, found = sum.is_some(), "read checksum file");
+            sum
+        }
+        Err(err) => {
+            debug!(url, error = %err, "could not fetch checksum file");
+            None
+        }
+    }
+}
+
+fn fetch_small(url: &str) -> Result<String, DownloadError> {
+    let resp = SHARED_AGENT.get(url).call()?;
+    if !resp.status().is_success() {
+        return Err(DownloadError::HttpError {
+            status: resp.status().as_u16(),
+            url: url.to_string(),
+        });
+    }
+    resp.into_body()
+        .with_config()
+        .limit(MAX_CHECKSUM_FILE)
+        .read_to_string()
+        .map_err(DownloadError::from)
+}
+
+/// Finds the checksum of `asset` in the contents of a checksum file.
+///
+/// Understands what `sha256sum` and its kin write, in text or binary mode,
+/// the BSD `SHA256 (file) = <hex>` form, and a file holding nothing but the
+/// digest. A bare digest is only trusted in a file named after the asset.
+pub fn parse_checksum_file(contents: &str, file_name: &str, asset: &str) -> Option<Checksum> {
+    let named = Algorithm::named_in(file_name);
+    let own_file = file_name
+        .strip_prefix(asset)
+        .is_some_and(|rest| rest.starts_with('.'));
+    let names_asset = |listed: &str| {
+        let listed = listed.trim();
+        let listed = listed.strip_prefix('*').unwrap_or(listed);
+        listed == asset || listed.rsplit('/').next() == Some(asset)
+    };
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // SHA256 (tool.tar.gz) = <hex>
+        if let Some((head, hex)) = line.rsplit_once(" = ") {
+            if let Some((name, listed)) = head.split_once(" (") {
+                let listed = listed.strip_suffix(')').unwrap_or(listed);
+                if names_asset(listed) {
+                    return Algorithm::from_name(name).and_then(|a| Checksum::new(a, hex.trim()));
+                }
+                continue;
+            }
+        }
+
+        let mut fields = line.splitn(2, char::is_whitespace);
+        let hex = fields.next().unwrap_or_default();
+        let listed = fields.next();
+        let applies = match listed {
+            Some(listed) => names_asset(listed),
+            None => own_file,
+        };
+        if applies {
+            let algorithm = named.or_else(|| Algorithm::guessed_from(hex))?;
+            return Checksum::new(algorithm, hex);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn a_list_is_searched_for_the_asset_in_any_of_its_formats() {
+        let sha = "a".repeat(64);
+        let list = format!(
+            "{}  other.tar.gz\n{sha} *./dist/tool.tar.gz\n",
+            "b".repeat(64)
+        );
+        assert_eq!(
+            parse_checksum_file(&list, "checksums.txt", "tool.tar.gz"),
+            Some(Checksum::new(Algorithm::Sha256, &sha).unwrap())
+        );
+
+        let sha512 = "c".repeat(128);
+        let bsd = format!("SHA512 (tool.tar.gz) = {sha512}\n");
+        assert_eq!(
+            parse_checksum_file(&bsd, "CHECKSUMS", "tool.tar.gz")
+                .unwrap()
+                .to_string(),
+            format!("sha512:{sha512}")
+        );
+
+        assert_eq!(
+            parse_checksum_file(&list, "checksums.txt", "missing.zip"),
+            None
+        );
+    }
+
+    #[test]
+    fn a_bare_digest_only_counts_in_the_assets_own_file() {
+        let hex = "d".repeat(64);
+        let sum = parse_checksum_file(&hex, "tool.AppImage.sha256", "tool.AppImage").unwrap();
+        assert_eq!(sum.algorithm, Algorithm::Sha256);
+        assert_eq!(
+            parse_checksum_file(&hex, "SHA256SUMS", "tool.AppImage"),
+            None
+        );
+
+        // A file's name wins over a guess from the digest's length.
+        let sum = parse_checksum_file(&hex, "tool.AppImage.b3", "tool.AppImage").unwrap();
+        assert_eq!(sum.algorithm, Algorithm::Blake3);
+    }
+
+    #[test]
+    fn files_hash_with_the_algorithm_asked_for() {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(b"hello world\n").unwrap();
+
+        let sum = Checksum::of_file(f.path(), Algorithm::Sha256).unwrap();
+        assert_eq!(
+            sum.to_string(),
+            "sha256:a948904f2f0f479b8f8197694b30184b0d2ed1c1cd2a1ec0fb85d299a192a447"
+        );
+        let sum = Checksum::of_file(f.path(), Algorithm::Blake3).unwrap();
+        assert_eq!(
+            sum.to_string(),
+            "dc5a4edb8240b018124052c330270696f96771a63b45250a5c17d3000e823355"
+        );
+    }
+
+    #[test]
+    fn only_release_checksum_lists_are_taken_for_lists() {
+        assert!(is_checksum_list("SHA256SUMS"));
+        assert!(is_checksum_list("checksums.txt"));
+        assert!(is_checksum_list("tool_1.2.0_checksums.txt"));
+        assert!(!is_checksum_list("tool-x86_64.tar.gz"));
+    }
+}
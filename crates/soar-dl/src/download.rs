@@ -17,8 +17,11 @@ use ureq::{
 };
 
 use crate::{
+    checksum::Checksum,
     error::DownloadError,
     http::Http,
+    segmented::{accepts_ranges, split, SegmentedDownload, DEFAULT_SEGMENTS, MIN_SEGMENT_SIZE},
+    throttle::{host_slot, try_host_slot, RateMeter, ThrottledReader},
     types::{OverwriteMode, Progress, ResumeInfo},
     utils::{filename_from_header, filename_from_url, resolve_output_path},
     xattr::{read_resume, remove_resume, write_resume},
@@ -34,6 +37,7 @@ pub struct Download {
     pub on_progress: Option<Arc<dyn Fn(Progress) + Send + Sync>>,
     pub ghcr_blob: bool,
     pub expected_checksum: Option<String>,
+    pub segments: usize,
 }
 
 impl Download {
@@ -44,7 +48,8 @@ impl Download {
     /// - `OverwriteMode::Prompt` for existing files,
     /// - extraction disabled,
     /// - no extraction destination,
-    /// - no progress callback.
+    /// - no progress callback,
+    /// - large files split into up to four segments where the server allows.
     ///
     /// # Examples
     ///
@@ -65,11 +70,34 @@ impl Download {
             on_progress: None,
             ghcr_blob: false,
             expected_checksum: None,
+            segments: DEFAULT_SEGMENTS,
         }
     }
 
-    /// Sets the expected blake3 checksum (hex) to verify the downloaded file
-    /// against before it is made executable or extracted.
+    /// Sets how many byte ranges a large file may be split into and fetched
+    /// over at once. `1` keeps every download on a single connection.
+    ///
+    /// Only a file of at least two segments' worth, from a server that
+    /// advertises `Accept-Ranges: bytes`, is split, and never over more
+    /// connections than the host's limit leaves free.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use soar_dl::download::Download;
+    ///
+    /// let _ = Download::new("https://example.com/large.AppImage").segments(8);
+    /// ```
+    pub fn segments(mut self, segments: usize) -> Self {
+        self.segments = segments.max(1);
+        self
+    }
+
+    /// Sets the expected checksum to verify the downloaded file against before
+    /// it is made executable or extracted.
+    ///
+    /// Bare hex is blake3; `sha256:<hex>` and `sha512:<hex>` name their
+    /// algorithm.
     ///
     /// # Examples
     ///
@@ -190,7 +218,9 @@ impl Download {
     /// let _dl = Download::new("https://example.com/file")
     ///     .progress(|event: Progress| match event {
     ///         Progress::Starting { total } => eprintln!("starting, total={}", total),
-    ///         Progress::Chunk { total, current } => eprintln!("downloaded {} (+{})", total, current),
+    ///         Progress::Chunk { total, current, rate } => {
+    ///             eprintln!("downloaded {}/{} at {}/s", current, total, rate)
+    ///         }
     ///         Progress::Complete { total } => eprintln!("complete, total={}", total),
     ///         _ => {}
     ///     });
@@ -312,7 +342,12 @@ impl Download {
             std::fs::create_dir_all(parent)?;
         }
 
-        self.download_to_file(&output_path, resume_info)?;
+        // The connection is given back before verifying and extracting, which
+        // need the disk, not the host.
+        {
+            let _slot = host_slot(&self.url);
+            self.download_to_file(&output_path, resume_info)?;
+        }
 
         if let Err(e) = self.verify_checksum(&output_path) {
             fs::remove_file(&output_path).ok();
@@ -363,14 +398,20 @@ impl Download {
         let Some(ref expected) = self.expected_checksum else {
             return Ok(());
         };
-        let actual = soar_utils::hash::calculate_checksum(path)
-            .map_err(|e| DownloadError::Io(std::io::Error::other(e.to_string())))?;
-        if actual.eq_ignore_ascii_case(expected) {
+        // A sum naming an algorithm nothing here computes cannot be met.
+        let Some(expected) = Checksum::parse(expected) else {
+            return Err(DownloadError::ChecksumMismatch {
+                expected: expected.clone(),
+                got: String::new(),
+            });
+        };
+        let actual = Checksum::of_file(path, expected.algorithm)?;
+        if actual == expected {
             Ok(())
         } else {
             Err(DownloadError::ChecksumMismatch {
-                expected: expected.clone(),
-                got: actual,
+                expected: expected.to_string(),
+                got: actual.to_string(),
             })
         }
     }
@@ -391,9 +432,10 @@ impl Download {
     ///
     /// `PathBuf::from("-")` on success.
     fn download_to_stdout(&self) -> Result<PathBuf, DownloadError> {
+        let _slot = host_slot(&self.url);
         let resp = Http::fetch(&self.url, None, None, self.ghcr_blob)?;
         let mut stdout = std::io::stdout();
-        let mut reader = resp.into_body().into_reader();
+        let mut reader = ThrottledReader::new(resp.into_body().into_reader());
 
         std::io::copy(&mut reader, &mut stdout)?;
         stdout.flush()?;
@@ -420,6 +462,19 @@ impl Download {
         path: &Path,
         resume_info: Option<ResumeInfo>,
     ) -> Result<(), DownloadError> {
+        if let Some(info) = resume_info
+            .as_ref()
+            .filter(|info| !info.segments.is_empty())
+        {
+            return match self.resume_segments(path, info) {
+                Err(DownloadError::ResumeMismatch) => {
+                    warn!("server no longer serves the same file in ranges, restarting download");
+                    self.download_to_file(path, None)
+                }
+                result => result,
+            };
+        }
+
         let (resume_from, etag) = resume_info
             .as_ref()
             .map(|r| (Some(r.downloaded), r.etag.as_deref()))
@@ -468,6 +523,50 @@ impl Download {
             }
         }
 
+        if !is_resuming
+            && self.segments > 1
+            && total >= 2 * MIN_SEGMENT_SIZE
+            && accepts_ranges(&resp)
+        {
+            let segments = split(total, self.segments);
+            let extra: Vec<_> = (1..segments.len())
+                .map_while(|_| try_host_slot(&self.url))
+                .collect();
+            if !extra.is_empty() {
+                let file = File::create(path)?;
+                file.set_len(total)?;
+                drop(file);
+
+                let job = SegmentedDownload {
+                    url: &self.url,
+                    ghcr_blob: self.ghcr_blob,
+                    on_progress: self.on_progress.as_ref(),
+                    path,
+                    total,
+                    etag: new_etag,
+                };
+                match job.run(segments, Some(resp), extra) {
+                    // The server said it serves ranges and then did not.
+                    Err(DownloadError::ResumeMismatch) => {
+                        warn!("server refused a range request, downloading in one piece");
+                        return Download {
+                            segments: 1,
+                            ..self.clone()
+                        }
+                        .download_to_file(path, None);
+                    }
+                    result => result?,
+                }
+
+                if let Some(ref cb) = self.on_progress {
+                    cb(Progress::Complete {
+                        total,
+                    });
+                }
+                return Ok(());
+            }
+        }
+
         let mut file = if is_resuming {
             let resume_pos = resume_from.unwrap();
             trace!(path = %path.display(), resume_pos = resume_pos, "opening file for resume");
@@ -482,10 +581,11 @@ impl Download {
             File::create(path)?
         };
 
-        let mut reader = resp.into_body().into_reader();
+        let mut reader = ThrottledReader::new(resp.into_body().into_reader());
         let mut buffer = [0u8; 8192];
         let mut downloaded = resume_from.unwrap_or(0);
         let mut last_checkpoint = downloaded / (1024 * 1024);
+        let mut meter = RateMeter::default();
 
         loop {
             let n = reader.read(&mut buffer)?;
@@ -508,6 +608,7 @@ impl Download {
                         total,
                         etag: new_etag.clone(),
                         last_modified: None,
+                        segments: Vec::new(),
                     },
                 ) {
                     trace!(%err, "failed to save resume checkpoint");
@@ -518,6 +619,7 @@ impl Download {
                 cb(Progress::Chunk {
                     current: downloaded,
                     total,
+                    rate: meter.observe(downloaded),
                 });
             }
         }
@@ -533,6 +635,56 @@ impl Download {
         Ok(())
     }
 
+    /// Pick up a segmented download where each of its segments stopped.
+    ///
+    /// Returns [`DownloadError::ResumeMismatch`] when what is on disk or what
+    /// the server now serves no longer matches the recorded segments.
+    fn resume_segments(&self, path: &Path, info: &ResumeInfo) -> Result<(), DownloadError> {
+        let on_disk = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if on_disk != info.total {
+            debug!(
+                on_disk,
+                total = info.total,
+                "partial file does not match its segments"
+            );
+            return Err(DownloadError::ResumeMismatch);
+        }
+
+        let current = info.segments.iter().map(|s| s.downloaded).sum();
+        debug!(
+            offset = current,
+            segments = info.segments.len(),
+            "resuming segmented download"
+        );
+        if let Some(ref cb) = self.on_progress {
+            cb(Progress::Resuming {
+                current,
+                total: info.total,
+            });
+        }
+
+        let pending = info.segments.iter().filter(|s| s.remaining() > 0).count();
+        let extra: Vec<_> = (1..pending)
+            .map_while(|_| try_host_slot(&self.url))
+            .collect();
+        let job = SegmentedDownload {
+            url: &self.url,
+            ghcr_blob: self.ghcr_blob,
+            on_progress: self.on_progress.as_ref(),
+            path,
+            total: info.total,
+            etag: info.etag.clone(),
+        };
+        job.run(info.segments.clone(), None, extra)?;
+
+        if let Some(ref cb) = self.on_progress {
+            cb(Progress::Complete {
+                total: info.total,
+            });
+        }
+        Ok(())
+    }
+
     /// Determine the total size of the response body from HTTP headers.
     ///
     /// Checks the `Content-Range` header first (parsing the value after the final '/'),
@@ -613,4 +765,16 @@ mod tests {
             other => panic!("expected ChecksumMismatch, got {other:?}"),
         }
     }
+
+    #[test]
+    fn verify_checksum_uses_the_algorithm_the_sum_names() {
+        let f = temp_with(b"hello soar");
+        let sha = Checksum::of_file(f.path(), crate::checksum::Algorithm::Sha512).unwrap();
+
+        let dl = Download::new("https://example.com/x").checksum(sha.to_string());
+        assert!(dl.verify_checksum(f.path()).is_ok());
+
+        let dl = Download::new("https://example.com/x").checksum(format!("sha256:{}", sha.hex));
+        assert!(dl.verify_checksum(f.path()).is_err());
+    }
 }
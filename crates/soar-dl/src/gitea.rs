@@ -0,0 +1,249 @@
+use serde::Deserialize;
+
+use crate::{
+    error::DownloadError,
+    platform::fetch_releases_json,
+    traits::{Asset, Platform, Release},
+};
+
+/// Gitea and its forks, Forgejo and Codeberg among them.
+///
+/// Unlike GitHub and GitLab there is no single host: a project is named as
+/// `host/owner/repo`, or `https://host/owner/repo` for an instance served
+/// some other way, and `owner/repo` alone means Codeberg.
+pub struct Gitea;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GiteaRelease {
+    pub name: Option<String>,
+    pub tag_name: String,
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub published_at: Option<String>,
+    pub body: Option<String>,
+    #[serde(default)]
+    pub assets: Vec<GiteaAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GiteaAsset {
+    pub name: String,
+    pub size: u64,
+    pub browser_download_url: String,
+}
+
+impl Gitea {
+    /// Splits a project into the instance it lives on and its `owner/repo`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use soar_dl::gitea::Gitea;
+    ///
+    /// assert_eq!(
+    ///     Gitea::split_project("git.example.org/team/tool"),
+    ///     ("https://git.example.org".to_string(), "team/tool".to_string())
+    /// );
+    /// assert_eq!(
+    ///     Gitea::split_project("owner/repo"),
+    ///     ("https://codeberg.org".to_string(), "owner/repo".to_string())
+    /// );
+    /// ```
+    pub fn split_project(project: &str) -> (String, String) {
+        let (scheme, rest) = match project.split_once("://") {
+            Some((scheme, rest)) => (Some(scheme), rest),
+            None => (None, project),
+        };
+        let rest = rest.trim_matches('/');
+        let rest = rest.strip_suffix(".git").unwrap_or(rest);
+
+        let segments: Vec<&str> = rest.split('/').filter(|s| !s.is_empty()).collect();
+        match (scheme, segments.as_slice()) {
+            (None, [owner, repo]) => (Self::API_BASE.to_string(), format!("{owner}/{repo}")),
+            (scheme, [host, owner, repo, ..]) => {
+                (
+                    format!("{}://{host}", scheme.unwrap_or("https")),
+                    format!("{owner}/{repo}"),
+                )
+            }
+            _ => (Self::API_BASE.to_string(), rest.to_string()),
+        }
+    }
+
+    /// The variables a token for the instance at `base` is read from.
+    ///
+    /// A token is good only for the instance that issued it, and a project
+    /// line can name any host, so the plain variables are sent to Codeberg
+    /// alone. Any other instance reads `GITEA_TOKEN_<HOST>` or
+    /// `FORGEJO_TOKEN_<HOST>`, the host and port upper-cased with everything
+    /// but letters and digits turned into `_`.
+    fn token_env(base: &str) -> [String; 2] {
+        if base == Self::API_BASE {
+            return Self::TOKEN_ENV.map(String::from);
+        }
+        let host = base.split_once("://").map_or(base, |(_, host)| host);
+        let suffix: String = host
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Self::TOKEN_ENV.map(|name| format!("{name}_{suffix}"))
+    }
+
+    /// The web page of a release, for pointing readers at its notes.
+    pub fn release_url(project: &str, tag: &str) -> String {
+        let (base, repo) = Self::split_project(project);
+        format!("{base}/{repo}/releases/tag/{tag}")
+    }
+}
+
+impl Platform for Gitea {
+    type Release = GiteaRelease;
+
+    const API_BASE: &'static str = "https://codeberg.org";
+    const TOKEN_ENV: [&str; 2] = ["GITEA_TOKEN", "FORGEJO_TOKEN"];
+
+    /// Fetches releases for a project on a Gitea instance, optionally narrowed to one tag.
+    ///
+    /// Drafts are only visible to a token with write access and have nothing
+    /// published yet, so they are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use soar_dl::gitea::Gitea;
+    /// use soar_dl::traits::Platform;
+    ///
+    /// let _ = Gitea::fetch_releases("codeberg.org/forgejo/forgejo", None);
+    /// let _ = Gitea::fetch_releases("forgejo/forgejo", Some("v9.0.0"));
+    /// ```
+    fn fetch_releases(
+        project: &str,
+        tag: Option<&str>,
+    ) -> Result<Vec<Self::Release>, DownloadError> {
+        let (base, repo) = Self::split_project(project);
+        let path = match tag {
+            Some(tag) => {
+                let encoded_tag =
+                    url::form_urlencoded::byte_serialize(tag.as_bytes()).collect::<String>();
+                format!("/api/v1/repos/{repo}/releases/tags/{encoded_tag}")
+            }
+            None => format!("/api/v1/repos/{repo}/releases?limit=50"),
+        };
+
+        let [gitea_env, forgejo_env] = Self::token_env(&base);
+        let mut releases =
+            fetch_releases_json::<Self::Release>(&path, &base, [&gitea_env, &forgejo_env])?;
+        releases.retain(|r| !r.draft);
+        Ok(releases)
+    }
+}
+
+impl Release for GiteaRelease {
+    type Asset = GiteaAsset;
+
+    fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("")
+    }
+
+    fn tag(&self) -> &str {
+        &self.tag_name
+    }
+
+    fn is_prerelease(&self) -> bool {
+        self.prerelease
+    }
+
+    fn published_at(&self) -> &str {
+        self.published_at.as_deref().unwrap_or("")
+    }
+
+    fn body(&self) -> Option<&str> {
+        self.body.as_deref()
+    }
+
+    fn assets(&self) -> &[Self::Asset] {
+        &self.assets
+    }
+}
+
+impl Asset for GiteaAsset {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn size(&self) -> Option<u64> {
+        Some(self.size)
+    }
+
+    fn url(&self) -> &str {
+        &self.browser_download_url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_project_names_its_instance_or_falls_back_to_codeberg() {
+        assert_eq!(
+            Gitea::split_project("codeberg.org/owner/repo"),
+            ("https://codeberg.org".into(), "owner/repo".into())
+        );
+        assert_eq!(
+            Gitea::split_project("http://localhost:3000/owner/repo.git"),
+            ("http://localhost:3000".into(), "owner/repo".into())
+        );
+        assert_eq!(
+            Gitea::split_project("owner/repo"),
+            ("https://codeberg.org".into(), "owner/repo".into())
+        );
+    }
+
+    #[test]
+    fn only_codeberg_reads_the_plain_token_variables() {
+        assert_eq!(
+            Gitea::token_env("https://codeberg.org"),
+            ["GITEA_TOKEN", "FORGEJO_TOKEN"]
+        );
+        assert_eq!(
+            Gitea::token_env("https://git.example.org"),
+            [
+                "GITEA_TOKEN_GIT_EXAMPLE_ORG",
+                "FORGEJO_TOKEN_GIT_EXAMPLE_ORG"
+            ]
+        );
+        assert_eq!(
+            Gitea::token_env("http://localhost:3000"),
+            ["GITEA_TOKEN_LOCALHOST_3000", "FORGEJO_TOKEN_LOCALHOST_3000"]
+        );
+    }
+
+    #[test]
+    fn release_json_parses_without_optional_fields() {
+        let json = r#"{
+            "name": "v1.2.0",
+            "tag_name": "v1.2.0",
+            "prerelease": false,
+            "published_at": "2026-01-02T03:04:05Z",
+            "body": null,
+            "assets": [{
+                "name": "tool-x86_64.tar.gz",
+                "size": 42,
+                "browser_download_url": "https://codeberg.org/o/r/releases/download/v1.2.0/tool-x86_64.tar.gz"
+            }]
+        }"#;
+        let release: GiteaRelease = serde_json::from_str(json).unwrap();
+        assert!(!release.draft);
+        assert_eq!(release.tag(), "v1.2.0");
+        assert_eq!(release.assets()[0].size(), Some(42));
+    }
+}
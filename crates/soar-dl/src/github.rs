@@ -23,6 +23,10 @@ pub struct GithubAsset {
     pub name: String,
     pub size: u64,
     pub browser_download_url: String,
+    /// `sha256:<hex>`, computed by GitHub on upload. Assets older than the
+    /// field have none.
+    #[serde(default)]
+    pub digest: Option<String>,
 }
 
 impl Platform for Github {
@@ -196,6 +200,7 @@ impl Release for GithubRelease {
     ///     name: "example.zip".into(),
     ///     size: 1024,
     ///     browser_download_url: "https://example.com/example.zip".into(),
+    ///     digest: None,
     /// };
     ///
     /// let release = GithubRelease {
@@ -231,6 +236,7 @@ impl Asset for GithubAsset {
     ///     name: "file.zip".to_string(),
     ///     size: 123,
     ///     browser_download_url: "https://example.com/file.zip".to_string(),
+    ///     digest: None,
     /// };
     /// assert_eq!(asset.name(), "file.zip");
     /// ```
@@ -254,7 +260,7 @@ impl Asset for GithubAsset {
     /// use soar_dl::github::GithubAsset;
     /// use soar_dl::traits::Asset;
     ///
-    /// let asset = GithubAsset { name: "file".into(), size: 12345, browser_download_url: "https://example.com".into() };
+    /// let asset = GithubAsset { name: "file".into(), size: 12345, browser_download_url: "https://example.com".into(), digest: None };
     /// assert_eq!(asset.size(), Some(12345));
     /// ```
     fn size(&self) -> Option<u64> {
@@ -273,10 +279,15 @@ impl Asset for GithubAsset {
     ///     name: "example".into(),
     ///     size: 123,
     ///     browser_download_url: "https://example.com/download".into(),
+    ///     digest: None,
     /// };
     /// assert_eq!(asset.url(), "https://example.com/download");
     /// ```
     fn url(&self) -> &str {
         &self.browser_download_url
     }
+
+    fn digest(&self) -> Option<&str> {
+        self.digest.as_deref()
+    }
 }
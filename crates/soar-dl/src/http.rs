@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use tracing::{debug, trace};
 use ureq::{
     http::{
@@ -103,6 +105,49 @@ impl Http {
         result
     }
 
+    /// Fetches the bytes `range` of `url`, with an `If-Range` on `etag` when one is given,
+    /// so a server whose file has changed answers with the whole new file instead.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Response<Body>)` with the HTTP response on success, `Err(DownloadError)` on failure.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use soar_dl::http::Http;
+    ///
+    /// let resp = Http::fetch_range("https://example.com/resource", 0..1024, None, false).unwrap();
+    /// assert_eq!(resp.status(), 206);
+    /// ```
+    pub fn fetch_range(
+        url: &str,
+        range: Range<u64>,
+        etag: Option<&str>,
+        ghcr_blob: bool,
+    ) -> Result<Response<Body>, DownloadError> {
+        debug!("GET {}", url);
+        let mut req = SHARED_AGENT.get(url);
+
+        if ghcr_blob {
+            trace!("adding GHCR authorization header");
+            req = req.header("Authorization", "Bearer QQ==");
+        }
+
+        debug!("  Range: bytes={}-{}", range.start, range.end - 1);
+        req = req.header("Range", &format!("bytes={}-{}", range.start, range.end - 1));
+        if let Some(tag) = etag {
+            trace!(etag = tag, "adding If-Range header");
+            req = req.header("If-Range", tag);
+        }
+
+        let result = req.call().map_err(DownloadError::from);
+        if let Ok(ref resp) = result {
+            Self::log_response_headers(resp, "GET");
+        }
+        result
+    }
+
     /// Fetches JSON from the given URL and deserializes it into `T`.
     ///
     /// Performs an HTTP GET request to `url` and deserializes the response body into the requested type.
@@ -132,4 +177,23 @@ impl Http {
         }
         result
     }
+
+    /// Fetches the body at the given URL as text.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use soar_dl::http::Http;
+    ///
+    /// let notes = Http::text("https://example.com/CHANGELOG.md").unwrap();
+    /// ```
+    pub fn text(url: &str) -> Result<String, DownloadError> {
+        debug!(url = url, "fetching text");
+        SHARED_AGENT
+            .get(url)
+            .call()?
+            .body_mut()
+            .read_to_string()
+            .map_err(|_| DownloadError::InvalidResponse)
+    }
 }
@@ -1,6 +1,8 @@
+pub mod checksum;
 pub mod download;
 pub mod error;
 pub mod filter;
+pub mod gitea;
 pub mod github;
 pub mod gitlab;
 pub mod http;
@@ -8,6 +10,9 @@ pub mod http_client;
 pub mod oci;
 pub mod platform;
 pub mod release;
+pub mod segmented;
+pub mod sourcehut;
+pub mod throttle;
 pub mod traits;
 pub mod types;
 pub mod utils;
@@ -10,7 +10,7 @@ use std::{
     thread,
 };
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use soar_utils::fs::is_elf;
 use tracing::{debug, trace};
@@ -21,6 +21,7 @@ use crate::{
     error::DownloadError,
     filter::Filter,
     http_client::SHARED_AGENT,
+    throttle::{host_slot, RateMeter, ThrottledReader},
     types::{OverwriteMode, Progress, ResumeInfo},
     xattr::{read_resume, remove_resume, write_resume},
 };
@@ -89,7 +90,7 @@ impl From<&str> for OciReference {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OciManifest {
     #[serde(rename = "mediaType")]
     pub media_type: String,
@@ -97,7 +98,7 @@ pub struct OciManifest {
     pub layers: Vec<OciLayer>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OciConfig {
     #[serde(rename = "mediaType")]
     pub media_type: String,
@@ -105,13 +106,13 @@ pub struct OciConfig {
     pub size: u64,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OciLayer {
     #[serde(rename = "mediaType")]
     pub media_type: String,
     pub digest: String,
     pub size: u64,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
     pub annotations: std::collections::HashMap<String, String>,
 }
 
@@ -526,6 +527,7 @@ impl OciDownload {
                             cb(Progress::Chunk {
                                 current: downloaded,
                                 total: total_size,
+                                rate: 0,
                             });
                         }
                         paths.push(path);
@@ -587,6 +589,9 @@ impl OciDownload {
         output_dir: &Path,
     ) -> Result<Vec<PathBuf>, DownloadError> {
         let downloaded = Arc::new(AtomicU64::new(0));
+        // One meter for all the workers, so the rate reported is the layers'
+        // combined throughput, as the byte count is.
+        let meter = Arc::new(Mutex::new(RateMeter::default()));
         let paths = Arc::new(Mutex::new(Vec::new()));
         let errors = Arc::new(Mutex::new(Vec::new()));
 
@@ -605,6 +610,7 @@ impl OciDownload {
                 let reference = self.reference.clone();
                 let output_dir = output_dir.to_path_buf();
                 let downloaded = Arc::clone(&downloaded);
+                let meter = Arc::clone(&meter);
                 let paths = Arc::clone(&paths);
                 let errors = Arc::clone(&errors);
                 let extract = self.extract;
@@ -639,6 +645,7 @@ impl OciDownload {
                                         cb(Progress::Chunk {
                                             current,
                                             total: total_size,
+                                            rate: meter.lock().unwrap().rate(),
                                         });
                                     }
                                     paths.lock().unwrap().push(path);
@@ -656,6 +663,7 @@ impl OciDownload {
                             &mut local_downloaded,
                             on_progress.as_ref(),
                             &downloaded,
+                            &meter,
                             total_size,
                         );
 
@@ -860,6 +868,7 @@ impl OciDownload {
             downloaded,
             self.on_progress.as_ref(),
             &Arc::new(AtomicU64::new(0)),
+            &Mutex::new(RateMeter::default()),
             total_size,
         )
     }
@@ -871,7 +880,8 @@ impl OciDownload {
 /// - Resumes partially downloaded blobs when resume metadata exists (uses Range and If-Range headers).
 /// - Appends to the existing file when a partial response (206) is returned, otherwise creates a new file.
 /// - Periodically persists resume metadata while downloading (every 1 MiB).
-/// - Emits `Progress::Chunk` updates via the optional `on_progress` callback using the shared downloaded counter.
+/// - Holds a connection slot for the registry host and pays the shared rate limit for every byte read.
+/// - Emits `Progress::Chunk` updates via the optional `on_progress` callback using the shared downloaded counter and rate meter.
 /// - Marks the file executable (0o755) if it appears to be an ELF binary and removes any resume metadata on success.
 ///
 /// Returns `Ok(())` on success or a `DownloadError` on failure.
@@ -884,6 +894,7 @@ fn download_layer_impl(
     local_downloaded: &mut u64,
     on_progress: Option<&Arc<dyn Fn(Progress) + Send + Sync>>,
     shared_downloaded: &Arc<AtomicU64>,
+    meter: &Mutex<RateMeter>,
     total_size: u64,
 ) -> Result<(), DownloadError> {
     let url = format!(
@@ -916,6 +927,7 @@ fn download_layer_impl(
         }
     }
 
+    let slot = host_slot(&url);
     let resp = req.call()?;
 
     if !resp.status().is_success() {
@@ -957,7 +969,7 @@ fn download_layer_impl(
         .get(ETAG)
         .and_then(|h| h.to_str().ok())
         .map(String::from);
-    let mut reader = resp.into_body().into_reader();
+    let mut reader = ThrottledReader::new(resp.into_body().into_reader());
     let mut buffer = [0u8; 8192];
     let resume_offset = resume_from.unwrap_or(0);
     *local_downloaded = resume_offset;
@@ -989,6 +1001,7 @@ fn download_layer_impl(
                     total: layer.size,
                     etag: new_etag.clone(),
                     last_modified: None,
+                    segments: Vec::new(),
                 },
             ) {
                 trace!(%err, "failed to save resume checkpoint");
@@ -999,10 +1012,13 @@ fn download_layer_impl(
             cb(Progress::Chunk {
                 current: current_total,
                 total: total_size,
+                rate: meter.lock().unwrap().observe(current_total),
             });
         }
     }
 
+    drop(slot);
+
     verify_layer_digest(path, &layer.digest)?;
 
     if is_elf(path) {
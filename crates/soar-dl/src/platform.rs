@@ -17,6 +17,16 @@ pub enum PlatformUrl {
         project: String,
         tag: Option<String>,
     },
+    /// A Gitea or Forgejo project, as `host/owner/repo` or `owner/repo` on Codeberg.
+    Gitea {
+        project: String,
+        tag: Option<String>,
+    },
+    /// A SourceHut repository, as `~owner/repo`.
+    SourceHut {
+        project: String,
+        tag: Option<String>,
+    },
     Oci {
         reference: String,
     },
@@ -37,6 +47,21 @@ static GITLAB_RE: LazyLock<Regex> = LazyLock::new(|| {
     .expect("unable to compile gitlab release regex")
 });
 
+// Codeberg is matched by host; any other instance has to be asked for with a
+// `gitea:` or `forgejo:` prefix, since nothing in its URL says what it runs.
+// Only the bare project is taken, so release download links stay direct.
+static GITEA_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?i)(?:(?:https?://)?codeberg\.org/([^/@]+/[^/@]+?)|(?:gitea|forgejo):((?:https?://)?[^/@]+/[^/@]+/[^/@]+?))/?(?:@([^\r\n]+))?$",
+    )
+    .expect("unable to compile gitea release regex")
+});
+
+static SOURCEHUT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?i)(?:(?:https?://)?git\.sr\.ht/|(?:sourcehut|srht):)(~?[^/@]+/[^/@]+?)/?(?:@([^\r\n]+))?$")
+        .expect("unable to compile sourcehut release regex")
+});
+
 impl PlatformUrl {
     /// Classifies an input string as a platform URL and returns the corresponding `PlatformUrl` variant.
     ///
@@ -45,6 +70,8 @@ impl PlatformUrl {
     /// - `Github` when it matches the GitHub repository pattern, extracting project and optional tag.
     /// - `Gitlab` when it matches the GitLab repository pattern, extracting project and optional tag
     ///   (except when the project looks like an API path or contains `/-/`, which is treated as `Direct`).
+    /// - `Gitea` for a Codeberg project, or one on another instance behind `gitea:` or `forgejo:`.
+    /// - `SourceHut` for a `git.sr.ht` repository, or one behind `sourcehut:`.
     /// - `Direct` when the input parses as a valid URL with a scheme and host.
     ///
     /// Returns `None` if the input cannot be classified or parsed as a valid URL.
@@ -96,6 +123,26 @@ impl PlatformUrl {
             });
         }
 
+        if let Some(caps) = GITEA_RE.captures(url) {
+            let project = caps.get(1).or_else(|| caps.get(2))?.as_str();
+            let project = if caps.get(1).is_some() {
+                format!("codeberg.org/{project}")
+            } else {
+                project.to_string()
+            };
+            return Some(Self::Gitea {
+                project,
+                tag: caps.get(3).and_then(|m| Self::parse_tag(m.as_str())),
+            });
+        }
+
+        if let Some((project, tag)) = Self::parse_repo(&SOURCEHUT_RE, url) {
+            return Some(Self::SourceHut {
+                project,
+                tag,
+            });
+        }
+
         Url::parse(url)
             .ok()
             .filter(|u| !u.scheme().is_empty() && u.host().is_some())
@@ -114,18 +161,21 @@ impl PlatformUrl {
     fn parse_repo(re: &Regex, url: &str) -> Option<(String, Option<String>)> {
         let caps = re.captures(url)?;
         let project = caps.get(1)?.as_str().to_string();
-        let tag = caps
-            .get(2)
-            .map(|m| m.as_str().trim_matches(&['\'', '"', ' '][..]))
+        let tag = caps.get(2).and_then(|m| Self::parse_tag(m.as_str()));
+
+        Some((project, tag))
+    }
+
+    /// A tag as written after the `@`, unquoted and URI-decoded.
+    fn parse_tag(raw: &str) -> Option<String> {
+        Some(raw.trim_matches(&['\'', '"', ' '][..]))
             .filter(|s| !s.is_empty())
             .and_then(|s| {
                 percent_decode_str(s)
                     .decode_utf8()
                     .ok()
                     .map(|cow| cow.into_owned())
-            });
-
-        Some((project, tag))
+            })
     }
 }
 
@@ -363,6 +413,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_platform_url_parse_codeberg() {
+        match PlatformUrl::parse("https://codeberg.org/owner/repo@v1.2") {
+            Some(PlatformUrl::Gitea {
+                project,
+                tag,
+            }) => {
+                assert_eq!(project, "codeberg.org/owner/repo");
+                assert_eq!(tag, Some("v1.2".to_string()));
+            }
+            other => panic!("Expected Gitea variant, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_platform_url_parse_self_hosted_forgejo() {
+        match PlatformUrl::parse("forgejo:git.example.org/team/tool") {
+            Some(PlatformUrl::Gitea {
+                project,
+                tag,
+            }) => {
+                assert_eq!(project, "git.example.org/team/tool");
+                assert_eq!(tag, None);
+            }
+            other => panic!("Expected Gitea variant, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_platform_url_parse_codeberg_download_as_direct() {
+        let url = "https://codeberg.org/owner/repo/releases/download/v1/tool.tar.gz";
+        assert!(matches!(
+            PlatformUrl::parse(url),
+            Some(PlatformUrl::Direct { .. })
+        ));
+    }
+
+    #[test]
+    fn test_platform_url_parse_sourcehut() {
+        match PlatformUrl::parse("https://git.sr.ht/~owner/repo@1.0.0") {
+            Some(PlatformUrl::SourceHut {
+                project,
+                tag,
+            }) => {
+                assert_eq!(project, "~owner/repo");
+                assert_eq!(tag, Some("1.0.0".to_string()));
+            }
+            other => panic!("Expected SourceHut variant, got {other:?}"),
+        }
+    }
+
     #[test]
     fn test_platform_url_parse_direct_url() {
         let result = PlatformUrl::parse("https://example.com/download/file.tar.gz");
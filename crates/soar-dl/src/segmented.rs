@@ -0,0 +1,511 @@
+//! Downloads split into byte ranges fetched over parallel connections.
+//!
+//! A single TCP stream rarely fills a fast link to a distant host, so a large
+//! artifact from a server that accepts ranges is cut into segments fetched at
+//! once, each written straight to its place in the output file. Every
+//! segment's progress goes into the resume xattr, so an interrupted download
+//! picks each of them up where it stopped.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Read},
+    os::unix::fs::FileExt as _,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use tracing::{debug, trace};
+use ureq::{
+    http::{header::ACCEPT_RANGES, Response},
+    Body,
+};
+
+use crate::{
+    error::DownloadError,
+    http::Http,
+    throttle::{HostSlot, RateMeter, ThrottledReader},
+    types::{Progress, ResumeInfo, Segment},
+    xattr::write_resume,
+};
+
+/// Segments a download is split into unless told otherwise.
+pub const DEFAULT_SEGMENTS: usize = 4;
+
+/// Below this a segment costs more in setup than it wins back.
+pub const MIN_SEGMENT_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Cut `total` bytes into at most `count` segments of at least
+/// [`MIN_SEGMENT_SIZE`] each.
+pub fn split(total: u64, count: usize) -> Vec<Segment> {
+    let count = (count as u64).min(total / MIN_SEGMENT_SIZE).max(1);
+    let size = total.div_ceil(count);
+    (0..count)
+        .map(|i| {
+            Segment {
+                start: i * size,
+                end: ((i + 1) * size).min(total),
+                downloaded: 0,
+            }
+        })
+        .collect()
+}
+
+/// Whether a response is worth splitting: the whole file, of a known length,
+/// from a server that says it serves byte ranges.
+pub(crate) fn accepts_ranges(resp: &Response<Body>) -> bool {
+    resp.status() == 200
+        && resp
+            .headers()
+            .get(ACCEPT_RANGES)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("bytes"))
+}
+
+/// A download whose output file is already allocated at its full length.
+pub(crate) struct SegmentedDownload<'a> {
+    pub url: &'a str,
+    pub ghcr_blob: bool,
+    pub on_progress: Option<&'a Arc<dyn Fn(Progress) + Send + Sync>>,
+    pub path: &'a Path,
+    pub total: u64,
+    pub etag: Option<String>,
+}
+
+struct Ledger {
+    segments: Vec<Segment>,
+    /// Segments no connection has taken up yet.
+    pending: Vec<usize>,
+    downloaded: u64,
+    last_checkpoint: u64,
+}
+
+struct Shared<'a> {
+    job: &'a SegmentedDownload<'a>,
+    ledger: Mutex<Ledger>,
+    meter: Mutex<RateMeter>,
+    failed: AtomicBool,
+}
+
+impl SegmentedDownload<'_> {
+    /// Fetch what `segments` still lack, over this connection and one more per
+    /// slot in `extra`.
+    ///
+    /// `first`, when given, is a response already streaming the file from its
+    /// start, which is put to use on the first segment rather than dropped.
+    ///
+    /// A range the server answers with anything but partial content means the
+    /// file changed under the recorded ETag, or ranges are not served after
+    /// all, and is reported as [`DownloadError::ResumeMismatch`] so the caller
+    /// can start over.
+    pub fn run(
+        &self,
+        segments: Vec<Segment>,
+        first: Option<Response<Body>>,
+        extra: Vec<HostSlot>,
+    ) -> Result<(), DownloadError> {
+        let mut pending: Vec<usize> = (0..segments.len())
+            .filter(|&i| segments[i].remaining() > 0)
+            .collect();
+        let first = first.and_then(|resp| {
+            let index = pending.iter().position(|&i| segments[i].start == 0)?;
+            Some((pending.remove(index), resp))
+        });
+        // Taken from the back, so the segments are begun in order.
+        pending.reverse();
+
+        debug!(
+            segments = segments.len(),
+            connections = extra.len() + 1,
+            "downloading in segments"
+        );
+
+        let downloaded = segments.iter().map(|s| s.downloaded).sum();
+        let shared = Shared {
+            job: self,
+            ledger: Mutex::new(Ledger {
+                segments,
+                pending,
+                downloaded,
+                last_checkpoint: downloaded / (1024 * 1024),
+            }),
+            meter: Mutex::new(RateMeter::default()),
+            failed: AtomicBool::new(false),
+        };
+        shared.checkpoint(&shared.ledger.lock().unwrap());
+
+        let mut results = thread::scope(|scope| {
+            let workers: Vec<_> = extra
+                .into_iter()
+                .map(|slot| {
+                    let shared = &shared;
+                    scope.spawn(move || {
+                        let _slot = slot;
+                        shared.work(None)
+                    })
+                })
+                .collect();
+
+            let mut results = vec![shared.work(first)];
+            results.extend(workers.into_iter().map(|worker| {
+                worker.join().unwrap_or_else(|_| {
+                    Err(DownloadError::Io(io::Error::other(
+                        "segment worker panicked",
+                    )))
+                })
+            }));
+            results
+        });
+
+        if let Some(index) = results
+            .iter()
+            .position(|r| matches!(r, Err(DownloadError::ResumeMismatch)))
+        {
+            return results.swap_remove(index);
+        }
+        results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+        let ledger = shared.ledger.lock().unwrap();
+        if ledger.downloaded != self.total {
+            return Err(DownloadError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "segmented download ended at {} of {} bytes",
+                    ledger.downloaded, self.total
+                ),
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Shared<'_> {
+    /// Fetch segments until none are left, starting with `first` if given.
+    fn work(&self, first: Option<(usize, Response<Body>)>) -> Result<(), DownloadError> {
+        let result = self.fill_all(first);
+        if result.is_err() {
+            self.failed.store(true, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn fill_all(&self, first: Option<(usize, Response<Body>)>) -> Result<(), DownloadError> {
+        if let Some((index, resp)) = first {
+            self.fill(index, resp)?;
+        }
+        loop {
+            if self.failed.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let Some(index) = self.ledger.lock().unwrap().pending.pop() else {
+                return Ok(());
+            };
+            let segment = self.ledger.lock().unwrap().segments[index];
+            let resp = Http::fetch_range(
+                self.job.url,
+                segment.start + segment.downloaded..segment.end,
+                self.job.etag.as_deref(),
+                self.job.ghcr_blob,
+            )?;
+            if resp.status() != 206 {
+                debug!(
+                    status = resp.status().as_u16(),
+                    "range request not answered with partial content"
+                );
+                return Err(DownloadError::ResumeMismatch);
+            }
+            self.fill(index, resp)?;
+        }
+    }
+
+    /// Write what `resp` streams into segment `index`, from where it stopped.
+    fn fill(&self, index: usize, resp: Response<Body>) -> Result<(), DownloadError> {
+        let segment = self.ledger.lock().unwrap().segments[index];
+        let mut offset = segment.start + segment.downloaded;
+        trace!(index, offset, end = segment.end, "filling segment");
+
+        let file = OpenOptions::new().write(true).open(self.job.path)?;
+        let mut reader =
+            ThrottledReader::new(resp.into_body().into_reader().take(segment.end - offset));
+        let mut buffer = [0u8; 8192];
+
+        while offset < segment.end {
+            if self.failed.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let n = reader.read(&mut buffer)?;
+            if n == 0 {
+                return Err(DownloadError::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("segment {index} ended at byte {offset} of {}", segment.end),
+                )));
+            }
+            file.write_all_at(&buffer[..n], offset)?;
+            offset += n as u64;
+            self.advance(index, n as u64);
+        }
+        Ok(())
+    }
+
+    /// Count `n` more bytes of segment `index` as written.
+    fn advance(&self, index: usize, n: u64) {
+        let current = {
+            let mut ledger = self.ledger.lock().unwrap();
+            ledger.segments[index].downloaded += n;
+            ledger.downloaded += n;
+            let checkpoint = ledger.downloaded / (1024 * 1024);
+            if checkpoint > ledger.last_checkpoint {
+                ledger.last_checkpoint = checkpoint;
+                self.checkpoint(&ledger);
+            }
+            ledger.downloaded
+        };
+
+        if let Some(cb) = self.job.on_progress {
+            cb(Progress::Chunk {
+                current,
+                total: self.job.total,
+                rate: self.meter.lock().unwrap().observe(current),
+            });
+        }
+    }
+
+    /// Record every segment's progress, under the ledger lock so two
+    /// connections never write the xattr at once.
+    fn checkpoint(&self, ledger: &Ledger) {
+        trace!(downloaded = ledger.downloaded, "saving resume checkpoint");
+        // don't fail on filesystems without xattr support
+        if let Err(err) = write_resume(
+            self.job.path,
+            &ResumeInfo {
+                downloaded: ledger.downloaded,
+                total: self.job.total,
+                etag: self.job.etag.clone(),
+                last_modified: None,
+                segments: ledger.segments.clone(),
+            },
+        ) {
+            trace!(%err, "failed to save resume checkpoint");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::Write,
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+    use crate::{download::Download, types::OverwriteMode, xattr::read_resume};
+
+    /// Large enough to be split in two.
+    const TOTAL: u64 = 2 * MIN_SEGMENT_SIZE + 5;
+
+    fn body() -> Arc<Vec<u8>> {
+        Arc::new((0..TOTAL).map(|i| (i % 251) as u8).collect())
+    }
+
+    /// A server that says it serves ranges, and answers them with partial
+    /// content only when `partial` is set. Returns its URL and the `Range`
+    /// header of every request it was sent, `None` where there was none.
+    fn serve(body: Arc<Vec<u8>>, partial: bool) -> (String, Arc<Mutex<Vec<Option<String>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file.bin", listener.local_addr().unwrap());
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let seen = asked.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let body = body.clone();
+                let seen = seen.clone();
+                thread::spawn(move || answer(stream, &body, partial, &seen));
+            }
+        });
+        (url, asked)
+    }
+
+    fn answer(
+        mut stream: TcpStream,
+        body: &[u8],
+        partial: bool,
+        seen: &Mutex<Vec<Option<String>>>,
+    ) {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            match stream.read(&mut buffer) {
+                Ok(0) | Err(_) => return,
+                Ok(n) => request.extend_from_slice(&buffer[..n]),
+            }
+        }
+        let request = String::from_utf8_lossy(&request);
+        let range = request.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("range")
+                .then(|| value.trim().to_string())
+        });
+        seen.lock().unwrap().push(range.clone());
+
+        let bounds = range.filter(|_| partial).and_then(|range| {
+            let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+            let start: usize = start.parse().ok()?;
+            let end = end.parse::<usize>().map_or(body.len(), |end| end + 1);
+            Some((start, end))
+        });
+        // The client drops a response once it has what it needs, so a failed
+        // write here is expected.
+        let _ = match bounds {
+            Some((start, end)) => {
+                write!(
+                    stream,
+                    "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {start}-{}/{}\r\n\
+                     Content-Length: {}\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n",
+                    end - 1,
+                    body.len(),
+                    end - start
+                )
+                .and_then(|_| stream.write_all(&body[start..end]))
+            }
+            None => {
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nContent-Length: {}\r\n\
+                     ETag: \"v1\"\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .and_then(|_| stream.write_all(body))
+            }
+        };
+    }
+
+    fn fetch(url: &str, path: &Path) -> Result<std::path::PathBuf, DownloadError> {
+        Download::new(url)
+            .output(path.to_str().unwrap())
+            .overwrite(OverwriteMode::Skip)
+            .execute()
+    }
+
+    #[test]
+    fn a_large_file_arrives_whole_over_several_connections() {
+        let body = body();
+        let (url, asked) = serve(body.clone(), true);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.bin");
+
+        fetch(&url, &path).unwrap();
+
+        assert!(fs::read(&path).unwrap() == *body);
+        // the first segment rides on the initial response
+        let asked = asked.lock().unwrap();
+        assert_eq!(asked.len(), 2);
+        assert_eq!(asked[0], None);
+        let second = split(TOTAL, DEFAULT_SEGMENTS)[1];
+        assert_eq!(
+            asked[1].as_deref(),
+            Some(format!("bytes={}-{}", second.start, second.end - 1).as_str())
+        );
+        assert!(read_resume(&path).is_none());
+    }
+
+    #[test]
+    fn an_interrupted_download_resumes_every_segment_where_it_stopped() {
+        let body = body();
+        let (url, asked) = serve(body.clone(), true);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.bin");
+
+        let mut segments = split(TOTAL, DEFAULT_SEGMENTS);
+        segments[0].downloaded = 1024 * 1024 + 3;
+        segments[1].downloaded = 2 * 1024 * 1024;
+        let mut partial = vec![0u8; TOTAL as usize];
+        for segment in &segments {
+            let (from, to) = (
+                segment.start as usize,
+                (segment.start + segment.downloaded) as usize,
+            );
+            partial[from..to].copy_from_slice(&body[from..to]);
+        }
+        fs::write(&path, &partial).unwrap();
+        write_resume(
+            &path,
+            &ResumeInfo {
+                downloaded: segments.iter().map(|s| s.downloaded).sum(),
+                total: TOTAL,
+                etag: Some("\"v1\"".into()),
+                last_modified: None,
+                segments: segments.clone(),
+            },
+        )
+        .unwrap();
+
+        fetch(&url, &path).unwrap();
+
+        assert!(fs::read(&path).unwrap() == *body);
+        let mut asked = asked.lock().unwrap().clone();
+        asked.sort();
+        let mut expected: Vec<_> = segments
+            .iter()
+            .map(|s| Some(format!("bytes={}-{}", s.start + s.downloaded, s.end - 1)))
+            .collect();
+        expected.sort();
+        assert_eq!(asked, expected);
+        assert!(read_resume(&path).is_none());
+    }
+
+    #[test]
+    fn a_server_that_ignores_ranges_gets_the_file_in_one_piece() {
+        let body = body();
+        let (url, asked) = serve(body.clone(), false);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.bin");
+
+        let job = SegmentedDownload {
+            url: &url,
+            ghcr_blob: false,
+            on_progress: None,
+            path: &path,
+            total: TOTAL,
+            etag: None,
+        };
+        assert!(matches!(
+            job.run(split(TOTAL, DEFAULT_SEGMENTS), None, Vec::new()),
+            Err(DownloadError::ResumeMismatch)
+        ));
+        asked.lock().unwrap().clear();
+
+        fetch(&url, &path).unwrap();
+
+        assert!(fs::read(&path).unwrap() == *body);
+        let asked = asked.lock().unwrap();
+        assert!(asked.iter().any(Option::is_some));
+        assert_eq!(asked.last(), Some(&None));
+    }
+
+    #[test]
+    fn split_covers_the_whole_file_without_overlap() {
+        let total = 100 * 1024 * 1024 + 3;
+        let segments = split(total, 4);
+        assert_eq!(segments.len(), 4);
+        assert_eq!(segments[0].start, 0);
+        assert_eq!(segments.last().unwrap().end, total);
+        for pair in segments.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+        assert!(segments.iter().all(|s| s.downloaded == 0));
+    }
+
+    #[test]
+    fn split_keeps_segments_above_the_minimum() {
+        let segments = split(3 * MIN_SEGMENT_SIZE - 1, 8);
+        assert_eq!(segments.len(), 2);
+        assert!(segments.iter().all(|s| s.end - s.start >= MIN_SEGMENT_SIZE));
+
+        assert_eq!(split(MIN_SEGMENT_SIZE, 4).len(), 1);
+        assert_eq!(split(1, 4).len(), 1);
+    }
+}
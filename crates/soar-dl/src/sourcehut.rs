@@ -0,0 +1,233 @@
+use std::cmp::Reverse;
+
+use serde::Deserialize;
+
+use crate::{
+    error::DownloadError,
+    platform::fetch_releases_json,
+    traits::{Asset, Platform, Release},
+};
+
+/// SourceHut's git service.
+///
+/// SourceHut has no releases as such: a tag is the release, and files
+/// uploaded against it are its artifacts. Projects are `~owner/repo`; the
+/// tilde may be left off.
+pub struct SourceHut;
+
+/// One page of a repository's refs.
+#[derive(Debug, Clone, Deserialize)]
+struct RefPage {
+    #[serde(default)]
+    next: Option<serde_json::Value>,
+    #[serde(default)]
+    results: Vec<GitRef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GitRef {
+    name: String,
+    #[serde(default)]
+    artifacts: Vec<SourceHutAsset>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceHutRelease {
+    pub tag: String,
+    /// When the first artifact was uploaded, as the closest thing to a
+    /// release date a tag has.
+    pub published_at: String,
+    pub assets: Vec<SourceHutAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SourceHutAsset {
+    pub filename: String,
+    pub url: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub created: Option<String>,
+}
+
+impl SourceHut {
+    /// `~owner/repo`, whichever way the project was written.
+    pub fn normalize_project(project: &str) -> String {
+        let project = project.trim_matches('/');
+        if project.starts_with('~') {
+            project.to_string()
+        } else {
+            format!("~{project}")
+        }
+    }
+
+    /// The web page of a tag, for pointing readers at its notes.
+    pub fn release_url(project: &str, tag: &str) -> String {
+        format!(
+            "{}/{}/refs/{tag}",
+            Self::API_BASE,
+            Self::normalize_project(project)
+        )
+    }
+}
+
+impl GitRef {
+    /// The release a tag stands for; branches are not releases.
+    fn into_release(self) -> Option<SourceHutRelease> {
+        let tag = self.name.strip_prefix("refs/tags/")?.to_string();
+        let published_at = self
+            .artifacts
+            .iter()
+            .filter_map(|a| a.created.as_deref())
+            .min()
+            .unwrap_or_default()
+            .to_string();
+        Some(SourceHutRelease {
+            tag,
+            published_at,
+            assets: self.artifacts,
+        })
+    }
+}
+
+impl Platform for SourceHut {
+    type Release = SourceHutRelease;
+
+    const API_BASE: &'static str = "https://git.sr.ht";
+    const TOKEN_ENV: [&str; 2] = ["SRHT_TOKEN", "SOURCEHUT_TOKEN"];
+
+    /// Fetches the tags of a SourceHut repository as releases, newest first.
+    ///
+    /// Refs come back in name order, which says nothing about which release
+    /// is the latest, so they are ordered by when their artifacts went up.
+    /// Tags without artifacts come last.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use soar_dl::sourcehut::SourceHut;
+    /// use soar_dl::traits::Platform;
+    ///
+    /// let _ = SourceHut::fetch_releases("~sircmpwn/scdoc", None);
+    /// ```
+    fn fetch_releases(
+        project: &str,
+        tag: Option<&str>,
+    ) -> Result<Vec<Self::Release>, DownloadError> {
+        let project = Self::normalize_project(project);
+        let mut releases = Vec::new();
+        let mut start: Option<String> = None;
+
+        loop {
+            let path = match &start {
+                Some(start) => format!("/api/{project}/refs?start={start}"),
+                None => format!("/api/{project}/refs"),
+            };
+            let pages = fetch_releases_json::<RefPage>(&path, Self::API_BASE, Self::TOKEN_ENV)?;
+            let page = pages
+                .into_iter()
+                .next()
+                .ok_or(DownloadError::InvalidResponse)?;
+
+            releases.extend(
+                page.results
+                    .into_iter()
+                    .filter_map(GitRef::into_release)
+                    .filter(|r| tag.is_none_or(|t| r.tag == t)),
+            );
+
+            let next = match page.next {
+                Some(serde_json::Value::String(next)) => next,
+                Some(serde_json::Value::Number(next)) => next.to_string(),
+                _ => break,
+            };
+            if start.as_ref() == Some(&next) {
+                break;
+            }
+            start = Some(next);
+        }
+
+        releases.sort_by_key(|r| Reverse(r.published_at.clone()));
+        Ok(releases)
+    }
+}
+
+impl Release for SourceHutRelease {
+    type Asset = SourceHutAsset;
+
+    fn name(&self) -> &str {
+        &self.tag
+    }
+
+    fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    fn is_prerelease(&self) -> bool {
+        false
+    }
+
+    fn published_at(&self) -> &str {
+        &self.published_at
+    }
+
+    fn body(&self) -> Option<&str> {
+        None
+    }
+
+    fn assets(&self) -> &[Self::Asset] {
+        &self.assets
+    }
+}
+
+impl Asset for SourceHutAsset {
+    fn name(&self) -> &str {
+        &self.filename
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_tags_become_releases() {
+        let page: RefPage = serde_json::from_str(
+            r#"{
+                "next": null,
+                "results": [
+                    {"name": "refs/heads/master", "artifacts": []},
+                    {"name": "refs/tags/1.0.0", "artifacts": [{
+                        "filename": "tool-1.0.0.tar.gz",
+                        "url": "https://git.sr.ht/~o/tool/refs/download/1.0.0/tool-1.0.0.tar.gz",
+                        "created": "2026-03-01T00:00:00Z"
+                    }]}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let releases: Vec<SourceHutRelease> = page
+            .results
+            .into_iter()
+            .filter_map(GitRef::into_release)
+            .collect();
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].tag(), "1.0.0");
+        assert_eq!(releases[0].published_at(), "2026-03-01T00:00:00Z");
+        assert_eq!(releases[0].assets()[0].size(), None);
+    }
+
+    #[test]
+    fn the_tilde_is_optional() {
+        assert_eq!(SourceHut::normalize_project("o/tool"), "~o/tool");
+        assert_eq!(SourceHut::normalize_project("~o/tool"), "~o/tool");
+    }
+}
@@ -0,0 +1,298 @@
+//! Limits on how hard downloads lean on the network.
+//!
+//! `parallel_limit` and `ghcr_concurrency` bound how many transfers run, not
+//! how fast they go. The rate limit here is one budget shared by every
+//! transfer in the process, so ten downloads under `5M` split five megabytes a
+//! second between them rather than taking five each. Host slots bound how many
+//! of those transfers one host holds at once, so a package of many GHCR layers
+//! does not leave a GitHub release queued behind it.
+
+use std::{
+    collections::HashMap,
+    io::Read,
+    sync::{Condvar, LazyLock, Mutex, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use tracing::trace;
+use ureq::http::Uri;
+
+/// How long a rate is measured over before it is reported.
+const RATE_WINDOW: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Debug, Default)]
+pub struct ThrottleConfig {
+    /// Bytes per second shared by every download. `None` is unlimited.
+    pub rate_limit: Option<u64>,
+    /// Connections a single host may hold at once. `None` is unlimited.
+    pub per_host: Option<usize>,
+}
+
+static THROTTLE_CONFIG: LazyLock<RwLock<ThrottleConfig>> =
+    LazyLock::new(|| RwLock::new(ThrottleConfig::default()));
+
+static BUCKET: LazyLock<Mutex<Bucket>> = LazyLock::new(|| Mutex::new(Bucket::new(Instant::now())));
+
+static HOSTS: LazyLock<(Mutex<HashMap<String, usize>>, Condvar)> =
+    LazyLock::new(|| (Mutex::new(HashMap::new()), Condvar::new()));
+
+/// Updates the limits every download in the process is held to.
+///
+/// # Examples
+///
+/// ```
+/// use soar_dl::throttle::configure_throttle;
+///
+/// configure_throttle(|cfg| {
+///     cfg.rate_limit = Some(5 * 1024 * 1024);
+///     cfg.per_host = Some(6);
+/// });
+/// # configure_throttle(|cfg| *cfg = Default::default());
+/// ```
+pub fn configure_throttle<F>(updater: F)
+where
+    F: FnOnce(&mut ThrottleConfig),
+{
+    let mut config = THROTTLE_CONFIG.write().unwrap();
+    updater(&mut config);
+    // A limit of nothing would never let a byte through.
+    config.rate_limit = config.rate_limit.filter(|rate| *rate > 0);
+    config.per_host = config.per_host.filter(|limit| *limit > 0);
+    *BUCKET.lock().unwrap() = Bucket::new(Instant::now());
+    // A raised host limit may already cover someone waiting.
+    HOSTS.1.notify_all();
+}
+
+/// Account for `bytes` just transferred, sleeping until the shared budget
+/// covers them.
+pub fn consume(bytes: u64) {
+    let Some(rate) = THROTTLE_CONFIG.read().unwrap().rate_limit else {
+        return;
+    };
+    let wait = BUCKET.lock().unwrap().take(bytes, rate, Instant::now());
+    if !wait.is_zero() {
+        thread::sleep(wait);
+    }
+}
+
+/// A token bucket that lets the balance go negative.
+///
+/// Whoever takes more than is there is told how long the debt takes to clear,
+/// and the next taker inherits it, so concurrent transfers queue up behind one
+/// another instead of each seeing a budget to themselves.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new(now: Instant) -> Self {
+        Self {
+            tokens: 0.0,
+            refilled: now,
+        }
+    }
+
+    fn take(&mut self, bytes: u64, rate: u64, now: Instant) -> Duration {
+        let rate = rate as f64;
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.refilled = now;
+        // At most a second's worth is banked, so an idle spell is not followed
+        // by a burst well over the limit.
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+/// A connection held against its host's limit, given back when dropped.
+#[derive(Debug)]
+pub struct HostSlot {
+    host: Option<String>,
+}
+
+/// Wait for a free connection to the host `url` points at.
+///
+/// A URL without a host holds nothing, since there is nothing to share.
+pub fn host_slot(url: &str) -> HostSlot {
+    acquire(url, true).expect("a waiting acquire always ends with a slot")
+}
+
+/// Take a free connection to the host `url` points at, if there is one to take
+/// right now.
+pub fn try_host_slot(url: &str) -> Option<HostSlot> {
+    acquire(url, false)
+}
+
+fn acquire(url: &str, wait: bool) -> Option<HostSlot> {
+    let Some(host) = host_of(url) else {
+        return Some(HostSlot {
+            host: None,
+        });
+    };
+
+    let (held, freed) = &*HOSTS;
+    let mut held = held.lock().unwrap();
+    loop {
+        let limit = THROTTLE_CONFIG.read().unwrap().per_host;
+        let in_use = held.get(&host).copied().unwrap_or(0);
+        if limit.is_none_or(|limit| in_use < limit) {
+            break;
+        }
+        if !wait {
+            return None;
+        }
+        trace!(host, in_use, "waiting for a connection to the host");
+        held = freed.wait(held).unwrap();
+    }
+    *held.entry(host.clone()).or_default() += 1;
+
+    Some(HostSlot {
+        host: Some(host),
+    })
+}
+
+impl Drop for HostSlot {
+    fn drop(&mut self) {
+        let Some(ref host) = self.host else {
+            return;
+        };
+        let (held, freed) = &*HOSTS;
+        let mut held = held.lock().unwrap();
+        if let Some(in_use) = held.get_mut(host) {
+            *in_use -= 1;
+            if *in_use == 0 {
+                held.remove(host);
+            }
+        }
+        freed.notify_all();
+    }
+}
+
+/// The host and port a URL connects to.
+fn host_of(url: &str) -> Option<String> {
+    let uri = url.parse::<Uri>().ok()?;
+    Some(uri.authority()?.as_str().to_ascii_lowercase())
+}
+
+/// A reader that pays the rate limit for everything read through it.
+pub struct ThrottledReader<R> {
+    inner: R,
+}
+
+impl<R: Read> ThrottledReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+        }
+    }
+}
+
+impl<R: Read> Read for ThrottledReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        consume(n as u64);
+        Ok(n)
+    }
+}
+
+/// How fast a transfer is going, in bytes per second, measured over the last
+/// half second so the figure reflects any limit it is held to.
+#[derive(Debug, Default)]
+pub struct RateMeter {
+    window: Option<(Instant, u64)>,
+    rate: u64,
+}
+
+impl RateMeter {
+    /// Note that `current` bytes have arrived in all, and return the rate.
+    pub fn observe(&mut self, current: u64) -> u64 {
+        self.observe_at(current, Instant::now())
+    }
+
+    /// The rate as of the last completed window.
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    fn observe_at(&mut self, current: u64, now: Instant) -> u64 {
+        match self.window {
+            None => self.window = Some((now, current)),
+            Some((started, from)) => {
+                let elapsed = now.saturating_duration_since(started);
+                if elapsed >= RATE_WINDOW {
+                    self.rate =
+                        (current.saturating_sub(from) as f64 / elapsed.as_secs_f64()) as u64;
+                    self.window = Some((now, current));
+                }
+            }
+        }
+        self.rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_makes_a_transfer_over_budget_wait_it_off() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(start);
+
+        // Half a second in, half the budget has accrued.
+        let now = start + Duration::from_millis(500);
+        assert_eq!(bucket.take(500, 1000, now), Duration::ZERO);
+        // The next second's worth has to be waited for in full.
+        assert_eq!(bucket.take(1000, 1000, now), Duration::from_secs(1));
+        // And whoever comes after queues behind that debt.
+        assert_eq!(bucket.take(500, 1000, now), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn bucket_banks_no_more_than_a_second() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(start);
+
+        let later = start + Duration::from_secs(60);
+        assert_eq!(bucket.take(1000, 1000, later), Duration::ZERO);
+        assert_eq!(bucket.take(1000, 1000, later), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn rate_meter_reports_bytes_per_second_per_window() {
+        let start = Instant::now();
+        let mut meter = RateMeter::default();
+
+        assert_eq!(meter.observe_at(4096, start), 0);
+        // Within the window the last rate stands.
+        assert_eq!(
+            meter.observe_at(8192, start + Duration::from_millis(100)),
+            0
+        );
+        assert_eq!(
+            meter.observe_at(4096 + 1000, start + Duration::from_secs(1)),
+            1000
+        );
+        assert_eq!(meter.rate(), 1000);
+    }
+
+    #[test]
+    fn host_slots_are_keyed_by_host_and_port() {
+        assert_eq!(
+            host_of("https://GHCR.io/v2/pkgforge/blobs/sha256:00").as_deref(),
+            Some("ghcr.io")
+        );
+        assert_eq!(
+            host_of("http://localhost:8080/file").as_deref(),
+            Some("localhost:8080")
+        );
+        assert_eq!(host_of("not a url"), None);
+    }
+}
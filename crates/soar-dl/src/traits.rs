@@ -4,6 +4,11 @@ pub trait Asset: Clone {
     fn name(&self) -> &str;
     fn size(&self) -> Option<u64>;
     fn url(&self) -> &str;
+
+    /// A digest of the asset the forge computed itself, as `<algorithm>:<hex>`.
+    fn digest(&self) -> Option<&str> {
+        None
+    }
 }
 
 pub trait Release {
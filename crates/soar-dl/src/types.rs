@@ -7,10 +7,22 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Copy)]
 pub enum Progress {
     Preparing,
-    Starting { total: u64 },
-    Resuming { current: u64, total: u64 },
-    Chunk { current: u64, total: u64 },
-    Complete { total: u64 },
+    Starting {
+        total: u64,
+    },
+    Resuming {
+        current: u64,
+        total: u64,
+    },
+    /// `rate` is bytes per second as of the last moment, after any rate limit.
+    Chunk {
+        current: u64,
+        total: u64,
+        rate: u64,
+    },
+    Complete {
+        total: u64,
+    },
     Error,
     Aborted,
     Recovered,
@@ -31,6 +43,28 @@ pub struct ResumeInfo {
     pub total: u64,
     pub etag: Option<String>,
     pub last_modified: Option<String>,
+    /// Set when the download was split into byte ranges, each picked up again
+    /// from where it stopped. `downloaded` is then their sum.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<Segment>,
+}
+
+/// A byte range of a download, fetched over a connection of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Segment {
+    /// Offset of the first byte.
+    pub start: u64,
+    /// Offset one past the last byte.
+    pub end: u64,
+    /// Bytes written so far, counted from `start`.
+    pub downloaded: u64,
+}
+
+impl Segment {
+    /// Bytes of the range still to fetch.
+    pub fn remaining(&self) -> u64 {
+        (self.end - self.start).saturating_sub(self.downloaded)
+    }
 }
 
 #[cfg(test)]
@@ -55,14 +89,17 @@ mod tests {
         let progress = Progress::Chunk {
             current: 512,
             total: 1024,
+            rate: 256,
         };
         match progress {
             Progress::Chunk {
                 current,
                 total,
+                rate,
             } => {
                 assert_eq!(current, 512);
                 assert_eq!(total, 1024);
+                assert_eq!(rate, 256);
             }
             _ => panic!("Expected Progress::Chunk"),
         }
@@ -127,6 +164,7 @@ mod tests {
             total: 1024,
             etag: Some("\"abc123\"".to_string()),
             last_modified: None,
+            segments: Vec::new(),
         };
 
         assert_eq!(info.downloaded, 512);
@@ -142,6 +180,7 @@ mod tests {
             total: 1024,
             etag: None,
             last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+            segments: Vec::new(),
         };
 
         assert_eq!(info.downloaded, 256);
@@ -158,6 +197,7 @@ mod tests {
             total: 2048,
             etag: Some("etag-value".to_string()),
             last_modified: Some("date".to_string()),
+            segments: Vec::new(),
         };
 
         let json = serde_json::to_string(&info).unwrap();
@@ -169,6 +209,42 @@ mod tests {
         assert_eq!(deserialized.last_modified, info.last_modified);
     }
 
+    #[test]
+    fn test_resume_info_without_segments_reads_back_empty() {
+        let info: ResumeInfo =
+            serde_json::from_str(r#"{"downloaded":1,"total":2,"etag":null,"last_modified":null}"#)
+                .unwrap();
+        assert!(info.segments.is_empty());
+    }
+
+    #[test]
+    fn test_resume_info_segments_roundtrip() {
+        let info = ResumeInfo {
+            downloaded: 30,
+            total: 200,
+            etag: None,
+            last_modified: None,
+            segments: vec![
+                Segment {
+                    start: 0,
+                    end: 100,
+                    downloaded: 10,
+                },
+                Segment {
+                    start: 100,
+                    end: 200,
+                    downloaded: 20,
+                },
+            ],
+        };
+
+        let json = serde_json::to_string(&info).unwrap();
+        let deserialized: ResumeInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.segments, info.segments);
+        assert_eq!(deserialized.segments[0].remaining(), 90);
+        assert_eq!(deserialized.segments[1].remaining(), 80);
+    }
+
     #[test]
     fn test_resume_info_clone() {
         let info1 = ResumeInfo {
@@ -176,6 +252,7 @@ mod tests {
             total: 200,
             etag: Some("tag".to_string()),
             last_modified: None,
+            segments: Vec::new(),
         };
 
         let info2 = info1.clone();
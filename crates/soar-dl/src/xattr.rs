@@ -46,6 +46,7 @@ pub fn read_resume<P: AsRef<Path>>(path: P) -> Option<ResumeInfo> {
 ///     total: 10240,
 ///     etag: Some("etag-value".into()),
 ///     last_modified: None,
+///     segments: Vec::new(),
 /// };
 /// let path = std::path::Path::new("/tmp/download.partial");
 /// write_resume(path, &info).unwrap();
@@ -6,12 +6,23 @@
 //! Both matter for an AppImage, where a release changes a fraction of a file
 //! measured in tens of megabytes.
 
-use std::{fs::File, path::Path};
+use std::{
+    fs::File,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
 
 use tracing::debug;
 use zsync_rs::{checksum::calc_sha1_stream, ControlFile, HttpClient, ZsyncAssembly};
 
-use crate::{error::DownloadError, types::Progress};
+use crate::{
+    error::DownloadError,
+    throttle::{self, host_slot, RateMeter},
+    types::Progress,
+};
 
 /// What a control file says the remote artifact is.
 #[derive(Debug, Clone)]
@@ -85,6 +96,28 @@ pub fn file_sha1(path: impl AsRef<Path>) -> Result<String, DownloadError> {
     Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
 }
 
+/// Write the control file for `artifact` to `output`, for publishing beside it.
+///
+/// The artifact is recorded under its bare file name, which a reader resolves
+/// against the control file's own location, so the pair can be moved or
+/// mirrored together without regenerating anything.
+pub fn write_control_file(artifact: &Path, output: &Path) -> Result<(), DownloadError> {
+    let name = artifact
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or(DownloadError::NoFilename)?;
+    let mut file = File::open(artifact)?;
+    let control = ControlFile::generate(&mut file, name, name, None)
+        .map_err(|e| DownloadError::Zsync(format!("generating zsync control file: {e}")))?;
+
+    let mut out = std::io::BufWriter::new(File::create(output)?);
+    control
+        .write(&mut out)
+        .map_err(|e| DownloadError::Zsync(format!("writing zsync control file: {e}")))?;
+    std::io::Write::flush(&mut out)?;
+    Ok(())
+}
+
 /// Whether the artifact the control file describes differs from `installed`.
 ///
 /// A control file without a SHA-1 leaves nothing to compare, so the artifact
@@ -121,16 +154,9 @@ where
     let mut assembly = ZsyncAssembly::from_url(url, output)
         .map_err(|e| DownloadError::Zsync(format!("reading zsync control file: {e}")))?;
 
-    if let Some(callback) = on_progress {
-        let total = 0;
+    if let Some(ref callback) = on_progress {
         callback(Progress::Starting {
-            total,
-        });
-        assembly.set_progress_callback(move |done, total| {
-            callback(Progress::Chunk {
-                total,
-                current: done,
-            });
+            total: 0,
         });
     }
 
@@ -144,6 +170,23 @@ where
         debug!("zsync: {reused}/{total} blocks taken from the installed copy");
     }
 
+    // The assembly reports after every block it reads off the wire, so that
+    // is where the rate limit is paid, which holds back the next read. Blocks
+    // taken from the seed are already counted and cost nothing.
+    let paid = AtomicU64::new(assembly.progress().0);
+    let meter = Mutex::new(RateMeter::default());
+    assembly.set_progress_callback(move |done, total| {
+        throttle::consume(done.saturating_sub(paid.swap(done, Ordering::Relaxed)));
+        if let Some(ref callback) = on_progress {
+            callback(Progress::Chunk {
+                total,
+                current: done,
+                rate: meter.lock().unwrap().observe(done),
+            });
+        }
+    });
+
+    let _slot = host_slot(url);
     while !assembly.is_complete() {
         let fetched = assembly
             .download_missing_blocks()
@@ -28,6 +28,8 @@ pub enum SoarEvent {
         pkg_name: String,
         current: u64,
         total: u64,
+        /// Bytes per second, after any rate limit.
+        rate: u64,
     },
     /// Download completed successfully.
     DownloadComplete {
@@ -135,7 +137,7 @@ pub enum SoarEvent {
 pub enum VerifyStage {
     /// Calculating and verifying checksum (blake3).
     Checksum,
-    /// Verifying signature with repository public key.
+    /// Verifying signature with the repository or upstream public key.
     Signature,
     /// All verification passed.
     Passed,
@@ -216,6 +218,13 @@ pub enum UpdateCheckStatus {
     UpToDate { version: String },
     /// Skipped (pinned, no update source, etc.).
     Skipped { reason: String },
+    /// A newer version exists but is held back by the minimum release age
+    /// until `eligible_at` (RFC 3339).
+    Deferred {
+        current_version: String,
+        new_version: String,
+        eligible_at: String,
+    },
 }
 
 /// Old version cleanup stages after update.
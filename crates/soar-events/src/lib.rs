@@ -38,6 +38,7 @@ mod tests {
             pkg_name: "test-pkg".to_string(),
             current: 512,
             total: 1024,
+            rate: 0,
         });
         sink.emit(SoarEvent::DownloadComplete {
             op_id: 1,
@@ -133,6 +134,34 @@ mod tests {
         assert_send_sync::<NullSink>();
         assert_send_sync::<ChannelSink>();
         assert_send_sync::<CollectorSink>();
+        assert_send_sync::<JsonRpcSink<Vec<u8>>>();
+    }
+
+    #[test]
+    fn test_json_rpc_sink_writes_one_notification_per_line() {
+        let buffer = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = JsonRpcSink::new(buffer.clone());
+        sink.emit(SoarEvent::OperationComplete {
+            op_id: 7,
+            pkg_name: "pkg".to_string(),
+        });
+        sink.emit(SoarEvent::BatchProgress {
+            completed: 1,
+            total: 2,
+            failed: 0,
+        });
+
+        let written = String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = written
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["jsonrpc"], "2.0");
+        assert_eq!(lines[0]["method"], EVENT_NOTIFICATION);
+        assert!(lines[0].get("id").is_none());
+        assert_eq!(lines[0]["params"]["type"], "operation_complete");
+        assert_eq!(lines[0]["params"]["op_id"], 7);
     }
 
     #[test]
@@ -156,6 +185,7 @@ mod tests {
             pkg_name: "a".into(),
             current: 75,
             total: 100,
+            rate: 0,
         });
         collector.emit(SoarEvent::DownloadComplete {
             op_id: 1,
@@ -298,6 +328,14 @@ mod tests {
                 reason: "pinned".into(),
             },
         });
+        collector.emit(SoarEvent::UpdateCheck {
+            pkg_name: "i".into(),
+            status: UpdateCheckStatus::Deferred {
+                current_version: "1.0.0".into(),
+                new_version: "1.1.0".into(),
+                eligible_at: "2026-10-12T00:00:00+00:00".into(),
+            },
+        });
 
         // Update cleanup
         collector.emit(SoarEvent::UpdateCleanup {
@@ -451,6 +489,6 @@ mod tests {
             message: "error".into(),
         });
 
-        assert_eq!(collector.len(), 55);
+        assert_eq!(collector.len(), 56);
     }
 }
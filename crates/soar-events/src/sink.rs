@@ -2,7 +2,7 @@ use std::{
     io::{self, Write},
     sync::{
         mpsc::{self, Receiver, Sender},
-        Mutex,
+        Arc, Mutex,
     },
 };
 
@@ -120,3 +120,38 @@ impl<W: Write + Send + Sync> EventSink for JsonLinesSink<W> {
         let _ = writer.flush();
     }
 }
+
+/// The method every [`JsonRpcSink`] notification is sent under.
+pub const EVENT_NOTIFICATION: &str = "event";
+
+/// Writes each event as a JSON-RPC 2.0 notification, one per line.
+///
+/// The writer is shared with whoever answers requests on the same stream, so a
+/// notification is never written into the middle of a response.
+pub struct JsonRpcSink<W: Write + Send> {
+    writer: Arc<Mutex<W>>,
+}
+
+impl<W: Write + Send> JsonRpcSink<W> {
+    pub fn new(writer: Arc<Mutex<W>>) -> Self {
+        Self {
+            writer,
+        }
+    }
+}
+
+impl<W: Write + Send> EventSink for JsonRpcSink<W> {
+    fn emit(&self, event: SoarEvent) {
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": EVENT_NOTIFICATION,
+            "params": event,
+        });
+        let Ok(mut writer) = self.writer.lock() else {
+            return;
+        };
+        // A client that went away is not a reason to fail the operation.
+        let _ = writeln!(writer, "{notification}");
+        let _ = writer.flush();
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashSet;
 
+use chrono::Utc;
 use soar_config::packages::{PackagesConfig, ResolvedPackage};
 use soar_core::{
     database::{
@@ -7,10 +8,8 @@ use soar_core::{
         models::{InstalledPackage, Package},
     },
     package::{
-        install::InstallTarget,
-        release_source::{run_version_command, ReleaseSource},
-        remove::PackageRemover,
-        url::UrlPackage,
+        install::InstallTarget, release_age::held_until, release_source::ReleaseSource,
+        remove::PackageRemover, url::UrlPackage, version_source::VersionSource,
     },
     utils::substitute_placeholders,
     SoarResult,
@@ -19,12 +18,16 @@ use soar_db::repository::{
     core::{CoreRepository, SortDirection},
     metadata::MetadataRepository,
 };
+use soar_dl::checksum::PublishedChecksums;
 use soar_events::{RemoveStage, SoarEvent};
+use soar_utils::version::is_newer;
 use tracing::{debug, warn};
 
 use crate::{
-    install::perform_installation, progress::next_op_id, utils::get_package_hooks, ApplyDiff,
-    ApplyReport, InstallOptions, SoarContext,
+    install::perform_installation,
+    progress::next_op_id,
+    utils::{get_package_hooks, min_release_age, newest_aged, newest_matching, published_checksum},
+    ApplyDiff, ApplyReport, InstallOptions, SoarContext,
 };
 
 /// Status of a URL package compared against installed packages.
@@ -57,12 +60,20 @@ pub async fn compute_diff(
     for pkg in resolved {
         declared_keys.insert(declared_key(pkg));
 
-        let is_github_or_gitlab = pkg.github.is_some() || pkg.gitlab.is_some();
-        if is_github_or_gitlab || pkg.url.is_some() {
-            handle_local_package(pkg, is_github_or_gitlab, &diesel_db, &mut diff)?;
+        let has_release_source = pkg.has_release_source();
+        if has_release_source || pkg.url.is_some() || VersionSource::from_resolved(pkg).is_some() {
+            handle_local_package(pkg, has_release_source, &diesel_db, &mut diff)?;
             continue;
         }
 
+        let req = match pkg.version_req().transpose() {
+            Ok(req) => req,
+            Err(e) => {
+                diff.not_found.push(format!("{} ({})", pkg.name, e));
+                continue;
+            }
+        };
+
         // Find package in metadata
         let found_packages: Vec<Package> = if let Some(ref repo_name) = pkg.repo {
             metadata_mgr
@@ -72,7 +83,7 @@ pub async fn compute_diff(
                         Some(&pkg.name),
                         declared_pkg_id(pkg),
                         pkg.family.as_deref(),
-                        pkg.version.as_deref(),
+                        pkg.exact_version(),
                         None,
                         Some(SortDirection::Asc),
                     )
@@ -92,7 +103,7 @@ pub async fn compute_diff(
                     Some(&pkg.name),
                     declared_pkg_id(pkg),
                     pkg.family.as_deref(),
-                    pkg.version.as_deref(),
+                    pkg.exact_version(),
                     None,
                     Some(SortDirection::Asc),
                 )?;
@@ -112,7 +123,21 @@ pub async fn compute_diff(
             continue;
         }
 
-        let metadata_pkg = found_packages.into_iter().next().unwrap();
+        // A constraint settles on the newest version it accepts, which the
+        // package then resolves to; an exact version was already matched.
+        let metadata_pkg = match req {
+            Some(ref req) => {
+                match newest_matching(req, found_packages.clone()) {
+                    Some(pkg) => pkg,
+                    None => {
+                        diff.not_found
+                            .push(format!("{} (no version matches '{}')", pkg.name, req));
+                        continue;
+                    }
+                }
+            }
+            None => found_packages[0].clone(),
+        };
 
         let installed_packages: Vec<InstalledPackage> = diesel_db
             .with_conn(|conn| {
@@ -137,14 +162,67 @@ pub async fn compute_diff(
             ip.is_installed && ip.pkg_family.as_deref() == metadata_pkg.pkg_family.as_deref()
         });
 
+        // A version named outright is taken however recent it is; otherwise
+        // one too recent gives way to the newest that has aged, if any.
+        let min_age = match min_release_age(ctx.config(), Some(pkg), &metadata_pkg.repo_name) {
+            Ok(age) => age.filter(|_| pkg.exact_version().is_none()),
+            Err(e) => {
+                diff.not_found.push(format!("{} ({})", pkg.name, e));
+                continue;
+            }
+        };
+        let held =
+            held_until(metadata_pkg.build_date.as_deref(), min_age, Utc::now()).filter(|_| {
+                existing_install
+                    .as_ref()
+                    .is_none_or(|e| e.version != metadata_pkg.version)
+            });
+        let metadata_pkg = match held {
+            None => metadata_pkg,
+            Some(eligible_at) => {
+                let installed_version = existing_install.as_ref().map(|e| e.version.as_str());
+                let same_source: Vec<Package> = found_packages
+                    .into_iter()
+                    .filter(|p| p.repo_name == metadata_pkg.repo_name)
+                    .collect();
+                match newest_aged(req.as_ref(), min_age, installed_version, same_source) {
+                    Some(mut older) => {
+                        older.repo_name = metadata_pkg.repo_name.clone();
+                        older
+                    }
+                    None => {
+                        let held = format!(
+                            "{} deferred until {}",
+                            metadata_pkg.version,
+                            eligible_at.format("%Y-%m-%d %H:%M UTC")
+                        );
+                        match existing_install {
+                            Some(existing) => {
+                                diff.in_sync.push(format!(
+                                    "{}@{} ({held})",
+                                    existing.pkg_name, existing.version
+                                ));
+                            }
+                            None => diff.not_found.push(format!("{} ({held})", pkg.name)),
+                        }
+                        continue;
+                    }
+                }
+            }
+        };
+
         if let Some(ref existing) = existing_install {
-            let version_matches = pkg.version.as_ref().is_none_or(|v| existing.version == *v);
+            let version_matches = match (&req, pkg.exact_version()) {
+                (Some(req), _) => req.matches(&existing.version).unwrap_or(false),
+                (None, Some(v)) => existing.version == v,
+                (None, None) => true,
+            };
 
             if version_matches && existing.version == metadata_pkg.version {
                 diff.in_sync
                     .push(format!("{}@{}", existing.pkg_name, existing.version));
             } else if !existing.pinned || pkg.version.is_some() {
-                let resolved_pkg = metadata_pkg.resolve(pkg.version.as_deref());
+                let resolved_pkg = metadata_pkg.resolve(pkg.exact_version());
                 let target = create_install_target(pkg, resolved_pkg, Some(existing.clone()));
                 diff.to_update.push((pkg.clone(), target));
             } else {
@@ -154,7 +232,7 @@ pub async fn compute_diff(
                 ));
             }
         } else {
-            let resolved_pkg = metadata_pkg.resolve(pkg.version.as_deref());
+            let resolved_pkg = metadata_pkg.resolve(pkg.exact_version());
             let target = create_install_target(pkg, resolved_pkg, None);
             diff.to_install.push((pkg.clone(), target));
         }
@@ -233,11 +311,7 @@ pub async fn execute_apply(
     if !diff.to_install.is_empty() {
         let mut version_updates: Vec<(String, String)> = Vec::new();
         for (pkg, target) in &diff.to_install {
-            let declared_version = pkg
-                .version
-                .as_ref()
-                .map(|v| v.strip_prefix('v').unwrap_or(v));
-            if declared_version != Some(target.package.version.as_str()) {
+            if records_version(pkg, &target.package.version) {
                 version_updates.push((pkg.name.clone(), target.package.version.clone()));
             }
         }
@@ -279,11 +353,7 @@ pub async fn execute_apply(
     if !diff.to_update.is_empty() {
         let mut update_version_updates: Vec<(String, String)> = Vec::new();
         for (pkg, target) in &diff.to_update {
-            let declared_version = pkg
-                .version
-                .as_ref()
-                .map(|v| v.strip_prefix('v').unwrap_or(v));
-            if declared_version != Some(target.package.version.as_str()) {
+            if records_version(pkg, &target.package.version) {
                 update_version_updates.push((pkg.name.clone(), target.package.version.clone()));
             }
         }
@@ -376,7 +446,22 @@ pub async fn execute_apply(
     })
 }
 
-/// Handle local (URL/github/gitlab) packages in apply diff.
+/// Whether packages.toml should be rewritten to the version `pkg` ended up at.
+///
+/// A constraint is kept as written: recording the version it settled on would
+/// turn it into a pin.
+fn records_version(pkg: &ResolvedPackage, version: &str) -> bool {
+    if pkg.version_req().is_some() {
+        return false;
+    }
+    let declared = pkg
+        .version
+        .as_deref()
+        .map(|v| v.strip_prefix('v').unwrap_or(v));
+    declared != Some(version)
+}
+
+/// Handle local (URL and forge release) packages in apply diff.
 /// What a declaration identifies: name, package id, family and repository.
 type DeclaredKeys = HashSet<(String, Option<String>, Option<String>, Option<String>)>;
 
@@ -400,7 +485,7 @@ fn declared_key(pkg: &ResolvedPackage) -> (String, Option<String>, Option<String
 
 fn handle_local_package(
     pkg: &ResolvedPackage,
-    is_github_or_gitlab: bool,
+    has_release_source: bool,
     diesel_db: &DieselDatabase,
     diff: &mut ApplyDiff,
 ) -> SoarResult<()> {
@@ -426,9 +511,9 @@ fn handle_local_package(
         .next()
         .map(Into::into);
 
-    // Handle version_command packages
-    if let Some(ref cmd) = pkg.version_command {
-        if let Some(ref declared) = pkg.version {
+    // Handle version_url and version_command packages
+    if let Some(source) = VersionSource::from_resolved(pkg) {
+        if let Some(declared) = pkg.exact_version() {
             let normalized = declared.strip_prefix('v').unwrap_or(declared);
             if let Some(ref existing) = installed {
                 if existing.version == normalized {
@@ -438,12 +523,13 @@ fn handle_local_package(
             }
         }
 
-        let result = match run_version_command(cmd) {
+        let req = pkg.version_req().and_then(Result::ok);
+        let result = match source.resolve(req.as_ref()) {
             Ok(r) => r,
             Err(e) => {
-                warn!("Failed to run version_command for {}: {}", pkg.name, e);
+                warn!("Failed to run {} for {}: {}", source.field(), pkg.name, e);
                 diff.not_found
-                    .push(format!("{} (version_command failed: {})", pkg.name, e));
+                    .push(format!("{} ({} failed: {})", pkg.name, source.field(), e));
                 return Ok(());
             }
         };
@@ -454,13 +540,45 @@ fn handle_local_package(
             .unwrap_or(&result.version)
             .to_string();
 
+        // The source settles on one version, so a constraint can only accept
+        // or refuse it; a refused one leaves what is installed alone.
+        match pkg.version_req().transpose() {
+            Ok(Some(req)) => {
+                match req.matches(&version) {
+                    Ok(true) => {}
+                    Ok(false) if installed.is_some() => {
+                        diff.in_sync.push(format!(
+                            "{} (local, {} is outside '{}')",
+                            pkg.name, version, req
+                        ));
+                        return Ok(());
+                    }
+                    Ok(false) => {
+                        diff.not_found.push(format!(
+                            "{} ({} returned {}, outside '{}')",
+                            pkg.name,
+                            source.field(),
+                            version,
+                            req
+                        ));
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        diff.not_found.push(format!("{} ({})", pkg.name, e));
+                        return Ok(());
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                diff.not_found.push(format!("{} ({})", pkg.name, e));
+                return Ok(());
+            }
+        }
+
         if let Some(ref existing) = installed {
             if existing.version == version {
-                let declared = pkg
-                    .version
-                    .as_ref()
-                    .map(|s| s.strip_prefix('v').unwrap_or(s));
-                if declared != Some(version.as_str()) {
+                if records_version(pkg, &version) {
                     diff.pending_version_updates
                         .push((pkg.name.clone(), version.clone()));
                 }
@@ -472,14 +590,15 @@ fn handle_local_package(
         let download_url = match result.download_url {
             Some(url) => url,
             None => {
-                match &pkg.url {
+                match pkg.url_template.as_ref().or(pkg.url.as_ref()) {
                     Some(url) => {
                         substitute_placeholders(url, Some(&version), pkg.arch_map.as_ref())
                     }
                     None => {
                         diff.not_found.push(format!(
-                            "{} (version_command returned no URL and no url field configured)",
-                            pkg.name
+                            "{} ({} returned no URL and no url_template or url is configured)",
+                            pkg.name,
+                            source.field()
                         ));
                         return Ok(());
                     }
@@ -496,7 +615,7 @@ fn handle_local_package(
         )?;
         url_pkg.size = result.size;
 
-        match check_url_package_status(&url_pkg, pkg, "local", diesel_db)? {
+        match check_url_package_status(&url_pkg, pkg, None, "local", diesel_db)? {
             UrlPackageStatus::ToInstall(target) => diff.to_install.push((pkg.clone(), target)),
             UrlPackageStatus::ToUpdate(target) => diff.to_update.push((pkg.clone(), target)),
             UrlPackageStatus::InSync(label) => diff.in_sync.push(label),
@@ -504,9 +623,9 @@ fn handle_local_package(
         return Ok(());
     }
 
-    // Handle github/gitlab packages
-    if is_github_or_gitlab {
-        if let Some(ref declared) = pkg.version {
+    // Handle forge release packages
+    if has_release_source {
+        if let Some(declared) = pkg.exact_version() {
             let normalized = declared.strip_prefix('v').unwrap_or(declared);
             if let Some(ref existing) = installed {
                 if existing.version == normalized {
@@ -520,7 +639,7 @@ fn handle_local_package(
             Some(s) => s,
             None => {
                 diff.not_found.push(format!(
-                    "{} (missing asset_pattern for github/gitlab source)",
+                    "{} (missing asset_pattern for release source)",
                     pkg.name
                 ));
                 return Ok(());
@@ -540,6 +659,20 @@ fn handle_local_package(
             .unwrap_or(&release.version)
             .to_string();
 
+        // Standing in for a release too recent to take, the pick may be no
+        // newer than what is installed, and going back to it is no update.
+        if let (Some(existing), Some(held)) = (&installed, &release.deferred) {
+            if !is_newer(&version, &existing.version) {
+                diff.in_sync.push(format!(
+                    "{} (local, {} deferred until {})",
+                    pkg.name,
+                    held.version,
+                    held.eligible_at.format("%Y-%m-%d %H:%M UTC")
+                ));
+                return Ok(());
+            }
+        }
+
         let url_pkg = UrlPackage::from_remote(
             &release.download_url,
             Some(&pkg.name),
@@ -548,7 +681,8 @@ fn handle_local_package(
             local_pkg_id.as_deref(),
         )?;
 
-        match check_url_package_status(&url_pkg, pkg, "local", diesel_db)? {
+        let published = Some(&release.checksums);
+        match check_url_package_status(&url_pkg, pkg, published, "local", diesel_db)? {
             UrlPackageStatus::ToInstall(target) => diff.to_install.push((pkg.clone(), target)),
             UrlPackageStatus::ToUpdate(target) => diff.to_update.push((pkg.clone(), target)),
             UrlPackageStatus::InSync(label) => diff.in_sync.push(label),
@@ -558,7 +692,15 @@ fn handle_local_package(
 
     // Handle plain URL packages
     if let Some(ref url) = pkg.url {
-        if let Some(ref declared) = pkg.version {
+        // A bare URL offers no list of versions for a constraint to choose from.
+        if pkg.version_req().is_some() {
+            diff.not_found.push(format!(
+                "{} (a version constraint needs a release source, version_url or version_command to find versions)",
+                pkg.name
+            ));
+            return Ok(());
+        }
+        if let Some(declared) = pkg.exact_version() {
             let normalized = declared.strip_prefix('v').unwrap_or(declared);
             if let Some(ref existing) = installed {
                 if existing.version == normalized {
@@ -577,7 +719,7 @@ fn handle_local_package(
             declared_pkg_id(pkg),
         )?;
 
-        match check_url_package_status(&url_pkg, pkg, "local", diesel_db)? {
+        match check_url_package_status(&url_pkg, pkg, None, "local", diesel_db)? {
             UrlPackageStatus::ToInstall(target) => diff.to_install.push((pkg.clone(), target)),
             UrlPackageStatus::ToUpdate(target) => diff.to_update.push((pkg.clone(), target)),
             UrlPackageStatus::InSync(label) => diff.in_sync.push(label),
@@ -590,6 +732,7 @@ fn handle_local_package(
 fn check_url_package_status(
     url_pkg: &UrlPackage,
     pkg: &ResolvedPackage,
+    published: Option<&PublishedChecksums>,
     display_label: &str,
     diesel_db: &DieselDatabase,
 ) -> SoarResult<UrlPackageStatus> {
@@ -620,7 +763,7 @@ fn check_url_package_status(
 
     if let Some(ref existing) = installed {
         if url_pkg.version != existing.version {
-            let target = create_url_install_target(url_pkg, pkg, installed);
+            let target = create_url_install_target(url_pkg, pkg, published, installed);
             Ok(UrlPackageStatus::ToUpdate(target))
         } else {
             Ok(UrlPackageStatus::InSync(format!(
@@ -630,7 +773,7 @@ fn check_url_package_status(
         }
     } else {
         let existing_install = installed_packages.into_iter().next();
-        let target = create_url_install_target(url_pkg, pkg, existing_install);
+        let target = create_url_install_target(url_pkg, pkg, published, existing_install);
         Ok(UrlPackageStatus::ToInstall(target))
     }
 }
@@ -659,16 +802,24 @@ fn create_install_target(
         build: resolved.build.clone(),
         sandbox: resolved.sandbox.clone(),
         arch_map: resolved.arch_map.clone(),
+        verify: resolved.verify.clone(),
     }
 }
 
+/// Only reached for what is about to be installed, so a package already in
+/// sync costs no request for its checksum.
 fn create_url_install_target(
     url_pkg: &UrlPackage,
     resolved: &ResolvedPackage,
+    published: Option<&PublishedChecksums>,
     existing: Option<InstalledPackage>,
 ) -> InstallTarget {
     let mut package = url_pkg.to_package();
-    package.bsum = resolved.bsum.as_ref().map(|s| s.trim().to_lowercase());
+    // A pin is what the user vouches for; upstream's word only fills in for it.
+    package.bsum = match resolved.bsum {
+        Some(ref pin) => Some(pin.trim().to_lowercase()),
+        None => published_checksum(&package.download_url, published),
+    };
     InstallTarget {
         package,
         existing_install: existing,
@@ -688,5 +839,6 @@ fn create_url_install_target(
         build: resolved.build.clone(),
         sandbox: resolved.sandbox.clone(),
         arch_map: resolved.arch_map.clone(),
+        verify: resolved.verify.clone(),
     }
 }
@@ -0,0 +1,167 @@
+use soar_config::packages::{PackagesConfig, ResolvedPackage};
+use soar_core::{
+    database::models::{InstalledPackage, Package},
+    package::{
+        changelog::{changelog_notes, ReleaseNotes},
+        release_source::ReleaseSource,
+    },
+    SoarResult,
+};
+use soar_db::repository::core::{CoreRepository, SortDirection};
+use soar_utils::version::is_newer;
+use tracing::{debug, warn};
+
+use crate::{update::declaration, PackageChangelog, SoarContext, UpdateInfo};
+
+/// Somewhere a package's release notes can be read.
+enum NoteSource {
+    Forge(ReleaseSource),
+    /// A changelog URL, and whether the repository naming it is on disk and
+    /// may therefore point at files there.
+    Changelog(String, bool),
+}
+
+/// Where a package's release notes can be read, best first.
+///
+/// A packages.toml declaration names its forge and channel outright. After
+/// that comes what the index says: a changelog it points at, then the forge
+/// a download comes from, then one named as the project's source or
+/// homepage.
+fn note_sources(
+    ctx: &SoarContext,
+    package: &Package,
+    declared: Option<&ResolvedPackage>,
+) -> Vec<NoteSource> {
+    let mut sources = Vec::new();
+    if let Some(source) = declared.and_then(ReleaseSource::from_resolved) {
+        sources.push(NoteSource::Forge(source));
+    }
+    if let Some(url) = package.changelog_url.as_deref() {
+        let source = match ctx.config().get_repository(&package.repo_name) {
+            Some(repo) => NoteSource::Changelog(repo.resolve_url(url), repo.local_path().is_some()),
+            None => NoteSource::Changelog(url.to_string(), false),
+        };
+        sources.push(source);
+    }
+    if let Some(source) = ReleaseSource::from_download_url(&package.download_url) {
+        sources.push(NoteSource::Forge(source));
+    }
+    let project_urls = package
+        .source_urls
+        .iter()
+        .chain(package.homepages.iter())
+        .flatten();
+    if let Some(source) = project_urls
+        .filter_map(|url| ReleaseSource::from_project_url(url))
+        .next()
+    {
+        sources.push(NoteSource::Forge(source));
+    }
+    sources
+}
+
+/// Read the notes between two versions from the first source that has any.
+///
+/// A source that cannot be reached is reported and the next one tried, so
+/// notes never stand in the way of the update they describe.
+fn gather(
+    ctx: &SoarContext,
+    package: &Package,
+    declared: Option<&ResolvedPackage>,
+    installed: Option<&str>,
+    candidate: &str,
+) -> Vec<ReleaseNotes> {
+    for source in note_sources(ctx, package, declared) {
+        let notes = match &source {
+            NoteSource::Forge(source) => source.release_notes(installed, candidate),
+            NoteSource::Changelog(url, local) => changelog_notes(url, *local, installed, candidate),
+        };
+        match notes {
+            Ok(notes) if !notes.is_empty() => return notes,
+            Ok(_) => {}
+            Err(err) => {
+                warn!(
+                    "could not read release notes for {}: {}",
+                    package.pkg_name, err
+                )
+            }
+        }
+    }
+    debug!(pkg_name = package.pkg_name, "no release notes found");
+    Vec::new()
+}
+
+/// Release notes for each pending update, from the installed version up to
+/// the one the update moves to.
+pub fn update_changelogs(ctx: &SoarContext, updates: &[UpdateInfo]) -> Vec<PackageChangelog> {
+    let resolved_packages = PackagesConfig::load(None)
+        .map(|c| c.resolved_packages())
+        .unwrap_or_default();
+
+    updates
+        .iter()
+        .map(|update| {
+            let declared = update
+                .target
+                .existing_install
+                .as_ref()
+                .and_then(|pkg| declaration(pkg, &resolved_packages));
+            let package = &update.target.package;
+            PackageChangelog {
+                pkg_name: update.pkg_name.clone(),
+                repo_name: update.repo_name.clone(),
+                from_version: Some(update.current_version.clone()),
+                to_version: update.new_version.clone(),
+                notes: gather(
+                    ctx,
+                    package,
+                    declared,
+                    Some(&update.current_version),
+                    &update.new_version,
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Release notes for a package being looked at.
+///
+/// When an older version of it is installed, the notes run from there, as
+/// they would for an update; otherwise they are the package's own version's.
+pub fn package_changelog(ctx: &SoarContext, package: &Package) -> SoarResult<PackageChangelog> {
+    let installed: Option<InstalledPackage> = ctx
+        .diesel_core_db()?
+        .with_conn(|conn| {
+            CoreRepository::list_filtered(
+                conn,
+                Some(&package.repo_name),
+                Some(&package.pkg_name),
+                package.pkg_id.as_deref(),
+                None,
+                Some(true),
+                None,
+                Some(1),
+                Some(SortDirection::Asc),
+            )
+        })?
+        .into_iter()
+        .map(Into::into)
+        .next();
+    let from_version = installed
+        .map(|pkg| pkg.version)
+        .filter(|version| is_newer(&package.version, version));
+
+    Ok(PackageChangelog {
+        pkg_name: package.pkg_name.clone(),
+        repo_name: package.repo_name.clone(),
+        notes: gather(
+            ctx,
+            package,
+            None,
+            from_version.as_deref(),
+            &package.version,
+        ),
+        from_version,
+        to_version: package.version.clone(),
+    })
+}
@@ -0,0 +1,166 @@
+//! Which package provides a command, for a shell's command-not-found hook.
+//!
+//! The hook runs on every mistyped command, so it has to answer before the
+//! prompt comes back. Opening each repository's database for that would run
+//! its migrations and load far more than one name, so sync writes a plain
+//! index of commands beside each database instead, sorted so a lookup can
+//! stop as soon as it has passed the name.
+
+use std::{fs, path::Path};
+
+use soar_core::{database::connection::DieselDatabase, error::ErrorContext, SoarResult};
+use soar_db::repository::metadata::MetadataRepository;
+use tracing::{debug, trace};
+
+use crate::{CommandProvider, SoarContext};
+
+/// The index file written beside a repository's `metadata.db`.
+pub const COMMAND_INDEX: &str = "commands.tsv";
+
+/// Render the index for the metadata database at `metadata_db`.
+///
+/// One `command<TAB>pkg_name<TAB>pkg_family` line per command a package
+/// provides, with the family left empty for packages that carry none.
+fn render_command_index(metadata_db: &Path) -> SoarResult<String> {
+    let db = DieselDatabase::open_metadata_readonly(metadata_db)?;
+    let commands = db.with_conn(MetadataRepository::list_commands)?;
+
+    let mut index = String::new();
+    for (command, pkg_name, pkg_family) in commands {
+        index.push_str(&command);
+        index.push('\t');
+        index.push_str(&pkg_name);
+        index.push('\t');
+        index.push_str(pkg_family.as_deref().unwrap_or_default());
+        index.push('\n');
+    }
+    Ok(index)
+}
+
+/// Write the command index for the repository stored at `repo_path`.
+pub fn write_command_index(repo_path: &Path) -> SoarResult<()> {
+    let index = render_command_index(&repo_path.join("metadata.db"))?;
+    store_index(repo_path, COMMAND_INDEX, &index)
+        .with_context(|| format!("writing command index in {}", repo_path.display()))?;
+    trace!(repo_path = %repo_path.display(), "wrote command index");
+    Ok(())
+}
+
+/// Put an index in place through a temporary file, so a hook firing mid-sync
+/// reads the old index or the new one, never half of either.
+pub(crate) fn store_index(repo_path: &Path, file_name: &str, index: &str) -> std::io::Result<()> {
+    let tmp = repo_path.join(format!(".{file_name}.tmp"));
+    let result = fs::write(&tmp, index).and_then(|_| fs::rename(&tmp, repo_path.join(file_name)));
+    if result.is_err() {
+        fs::remove_file(&tmp).ok();
+    }
+    result
+}
+
+/// Whether the index at `index` was written from the current database.
+fn is_current(index: &Path, metadata_db: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(index), modified(metadata_db)) {
+        (Some(index), Some(db)) => index >= db,
+        _ => false,
+    }
+}
+
+/// The index named `file_name` for the repository stored at `repo_path`.
+///
+/// A repository synced before the index existed, or whose database was
+/// replaced by something other than sync, has none or a stale one. It is
+/// rebuilt with `render` then, and kept if the directory can be written, so
+/// only the first lookup after such a change pays for opening the database.
+pub(crate) fn read_index(
+    repo_path: &Path,
+    file_name: &str,
+    render: fn(&Path) -> SoarResult<String>,
+) -> SoarResult<Option<String>> {
+    let metadata_db = repo_path.join("metadata.db");
+    if !metadata_db.is_file() {
+        return Ok(None);
+    }
+    let path = repo_path.join(file_name);
+    if is_current(&path, &metadata_db) {
+        if let Ok(index) = fs::read_to_string(&path) {
+            return Ok(Some(index));
+        }
+    }
+
+    debug!(path = %path.display(), "index missing or stale; rebuilding");
+    let index = render(&metadata_db)?;
+    if let Err(err) = store_index(repo_path, file_name, &index) {
+        debug!(%err, "could not keep rebuilt index");
+    }
+    Ok(Some(index))
+}
+
+/// The `(pkg_name, pkg_family)` pairs an index lists for `command`.
+fn lookup<'a>(index: &'a str, command: &str) -> Vec<(&'a str, Option<&'a str>)> {
+    let mut found = Vec::new();
+    for line in index.lines() {
+        let mut fields = line.split('\t');
+        let Some(name) = fields.next() else {
+            continue;
+        };
+        match name.cmp(command) {
+            std::cmp::Ordering::Less => continue,
+            std::cmp::Ordering::Greater => break,
+            std::cmp::Ordering::Equal => {}
+        }
+        let Some(pkg_name) = fields.next() else {
+            continue;
+        };
+        let pkg_family = fields.next().filter(|family| !family.is_empty());
+        found.push((pkg_name, pkg_family));
+    }
+    found
+}
+
+/// The packages in enabled repositories that provide `command`, in the
+/// order the repositories are configured.
+///
+/// Only the indexes are read: a lookup never syncs, so a stale repository
+/// answers from what it last fetched rather than making the shell wait.
+pub fn which_package(ctx: &SoarContext, command: &str) -> SoarResult<Vec<CommandProvider>> {
+    let mut providers = Vec::new();
+    for repo in ctx.config().repositories.iter().filter(|r| r.is_enabled()) {
+        let Ok(repo_path) = repo.get_path() else {
+            continue;
+        };
+        let Some(index) = read_index(&repo_path, COMMAND_INDEX, render_command_index)? else {
+            continue;
+        };
+        for (pkg_name, pkg_family) in lookup(&index, command) {
+            providers.push(CommandProvider {
+                pkg_name: pkg_name.to_string(),
+                pkg_family: pkg_family.map(str::to_string),
+                repo_name: repo.name.clone(),
+            });
+        }
+    }
+    Ok(providers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INDEX: &str = "\
+bat\tbat\tbat
+rg\tripgrep\tripgrep
+rg\tripgrep-glibc\t
+ripgrep\tripgrep\tripgrep
+";
+
+    #[test]
+    fn every_package_providing_a_command_is_found() {
+        assert_eq!(
+            lookup(INDEX, "rg"),
+            [("ripgrep", Some("ripgrep")), ("ripgrep-glibc", None)]
+        );
+        assert!(lookup(INDEX, "r").is_empty());
+        assert!(lookup(INDEX, "zstd").is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+//! Candidates for completing package, repository and profile names in a shell.
+//!
+//! The shell waits on these at every tab, so nothing here syncs, migrates or
+//! loads whole packages. Published names come from an index sync writes beside
+//! each repository's database, the way the command-not-found index is, and
+//! installed names from the core database opened read-only.
+
+use std::{collections::BTreeSet, path::Path};
+
+use soar_core::{database::connection::DieselDatabase, error::ErrorContext, SoarResult};
+use soar_db::repository::{core::CoreRepository, metadata::MetadataRepository};
+use tracing::trace;
+
+use crate::{
+    commands::{read_index, store_index},
+    SoarContext,
+};
+
+/// The index file written beside a repository's `metadata.db`.
+pub const NAME_INDEX: &str = "names.tsv";
+
+/// Render the index for the metadata database at `metadata_db`.
+///
+/// One `pkg_name<TAB>pkg_family<TAB>version` line per published version, with
+/// the family left empty for packages that carry none.
+fn render_name_index(metadata_db: &Path) -> SoarResult<String> {
+    let db = DieselDatabase::open_metadata_readonly(metadata_db)?;
+    let names = db.with_conn(MetadataRepository::list_names)?;
+
+    let mut index = String::new();
+    for (pkg_name, pkg_family, version) in names {
+        index.push_str(&pkg_name);
+        index.push('\t');
+        index.push_str(pkg_family.as_deref().unwrap_or_default());
+        index.push('\t');
+        index.push_str(&version);
+        index.push('\n');
+    }
+    Ok(index)
+}
+
+/// Write the name index for the repository stored at `repo_path`.
+pub fn write_name_index(repo_path: &Path) -> SoarResult<()> {
+    let index = render_name_index(&repo_path.join("metadata.db"))?;
+    store_index(repo_path, NAME_INDEX, &index)
+        .with_context(|| format!("writing name index in {}", repo_path.display()))?;
+    trace!(repo_path = %repo_path.display(), "wrote name index");
+    Ok(())
+}
+
+/// One package as completion sees it.
+struct Entry<'a> {
+    name: &'a str,
+    family: Option<&'a str>,
+    version: &'a str,
+    repo: &'a str,
+}
+
+/// Whether a family can be written before a `/` in a query. One derived from
+/// a URL can hold the characters a query is split on.
+fn is_queryable(family: &str) -> bool {
+    !family.contains(['/', '#', '@', ':'])
+}
+
+/// Complete `typed` as a `family/name@version:repo` query.
+///
+/// Each part before the one being typed has to match exactly, and the part
+/// being typed is matched as a prefix, so a candidate always extends what is
+/// already on the command line.
+fn complete<'a>(entries: impl IntoIterator<Item = Entry<'a>>, typed: &str) -> Vec<String> {
+    let (head, repo) = match typed.rsplit_once(':') {
+        Some((head, repo)) => (head, Some(repo)),
+        None => (typed, None),
+    };
+    let (spec, version) = match head.split_once('@') {
+        Some((spec, version)) => (spec, Some(version)),
+        None => (head, None),
+    };
+    let (family, name) = match spec.split_once('/') {
+        Some((family, name)) => (Some(family), name),
+        None => (None, spec),
+    };
+
+    let mut found = BTreeSet::new();
+    for entry in entries {
+        if family.is_some_and(|family| entry.family != Some(family)) {
+            continue;
+        }
+
+        if let Some(repo) = repo {
+            let settled = entry.name == name && version.is_none_or(|v| entry.version == v);
+            if settled && entry.repo.starts_with(repo) {
+                found.insert(format!("{head}:{}", entry.repo));
+            }
+        } else if let Some(version) = version {
+            if entry.name == name && entry.version.starts_with(version) {
+                found.insert(format!("{spec}@{}", entry.version));
+            }
+        } else if let Some(family) = family {
+            if entry.name.starts_with(name) {
+                found.insert(format!("{family}/{}", entry.name));
+            }
+        } else {
+            if entry.name.starts_with(name) {
+                found.insert(entry.name.to_string());
+            }
+            // A family is offered with the name it holds, so tabbing on a
+            // family's name reaches every package in it.
+            if let Some(family) = entry.family {
+                if family != entry.name && family.starts_with(name) && is_queryable(family) {
+                    found.insert(format!("{family}/{}", entry.name));
+                }
+            }
+        }
+    }
+    found.into_iter().collect()
+}
+
+/// Packages the enabled repositories publish that complete `typed`.
+///
+/// Repositories never synced have no index and offer nothing; completing a
+/// name is no reason to fetch metadata.
+pub fn available_packages(ctx: &SoarContext, typed: &str) -> SoarResult<Vec<String>> {
+    let mut indexes = Vec::new();
+    for repo in ctx.config().repositories.iter().filter(|r| r.is_enabled()) {
+        let Ok(repo_path) = repo.get_path() else {
+            continue;
+        };
+        if let Some(index) = read_index(&repo_path, NAME_INDEX, render_name_index)? {
+            indexes.push((repo.name.as_str(), index));
+        }
+    }
+
+    let entries = indexes.iter().flat_map(|(repo, index)| {
+        index.lines().filter_map(move |line| {
+            let mut fields = line.split('\t');
+            let name = fields.next()?;
+            let family = fields.next().filter(|family| !family.is_empty());
+            let version = fields.next()?;
+            Some(Entry {
+                name,
+                family,
+                version,
+                repo,
+            })
+        })
+    });
+    Ok(complete(entries, typed))
+}
+
+/// Installed packages that complete `typed`, only those installed under
+/// `profile` when one is given.
+pub fn installed_packages(
+    ctx: &SoarContext,
+    typed: &str,
+    profile: Option<&str>,
+) -> SoarResult<Vec<String>> {
+    let core_db = ctx.config().get_db_path()?.join("soar.db");
+    if !core_db.is_file() {
+        return Ok(Vec::new());
+    }
+    let db = DieselDatabase::open_core_readonly(&core_db)?;
+    let installed = db.with_conn(|conn| {
+        CoreRepository::list_filtered(conn, None, None, None, None, Some(true), None, None, None)
+    })?;
+
+    let entries = installed
+        .iter()
+        .filter(|pkg| profile.is_none_or(|profile| pkg.profile == profile))
+        .map(|pkg| {
+            Entry {
+                name: &pkg.pkg_name,
+                family: pkg.pkg_family.as_deref(),
+                version: &pkg.version,
+                repo: &pkg.repo_name,
+            }
+        });
+    Ok(complete(entries, typed))
+}
+
+/// Configured repositories whose names start with `typed`.
+pub fn repository_names(ctx: &SoarContext, typed: &str) -> Vec<String> {
+    ctx.config()
+        .repositories
+        .iter()
+        .map(|repo| repo.name.clone())
+        .filter(|name| name.starts_with(typed))
+        .collect()
+}
+
+/// Configured profiles whose names start with `typed`.
+pub fn profile_names(ctx: &SoarContext, typed: &str) -> Vec<String> {
+    let mut names: Vec<String> = ctx
+        .config()
+        .profile
+        .keys()
+        .filter(|name| name.starts_with(typed))
+        .cloned()
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGES: &[(&str, Option<&str>, &str, &str)] = &[
+        ("bat", Some("bat"), "0.24.0", "bincache"),
+        ("bat", Some("bat"), "0.25.0", "bincache"),
+        ("bat", Some("bat"), "0.25.0", "pkgcache"),
+        ("ripgrep", Some("ripgrep"), "14.1.0", "bincache"),
+        ("rg-static", Some("ripgrep"), "14.1.0", "bincache"),
+        ("tool", Some("127.0.0.1:8000.tool.tar.gz"), "1.0.0", "local"),
+    ];
+
+    fn candidates(typed: &str) -> Vec<String> {
+        let entries = PACKAGES.iter().map(|&(name, family, version, repo)| {
+            Entry {
+                name,
+                family,
+                version,
+                repo,
+            }
+        });
+        complete(entries, typed)
+    }
+
+    #[test]
+    fn a_name_completes_to_names_and_the_packages_of_matching_families() {
+        assert_eq!(candidates("b"), ["bat"]);
+        assert_eq!(
+            candidates("r"),
+            ["rg-static", "ripgrep", "ripgrep/rg-static"]
+        );
+        assert_eq!(
+            candidates("ripgrep/"),
+            ["ripgrep/rg-static", "ripgrep/ripgrep"]
+        );
+        assert_eq!(candidates("t"), ["tool"]);
+    }
+
+    #[test]
+    fn versions_and_repositories_complete_after_the_name() {
+        assert_eq!(candidates("bat@0.2"), ["bat@0.24.0", "bat@0.25.0"]);
+        assert_eq!(candidates("bat:"), ["bat:bincache", "bat:pkgcache"]);
+        assert_eq!(candidates("bat@0.24.0:"), ["bat@0.24.0:bincache"]);
+        assert_eq!(
+            candidates("ripgrep/rg-static:b"),
+            ["ripgrep/rg-static:bincache"]
+        );
+        assert!(candidates("ba:").is_empty());
+    }
+}
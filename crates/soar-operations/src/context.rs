@@ -21,6 +21,8 @@ use soar_registry::{fetch_metadata, write_metadata_db, MetadataContent, RemotePa
 use tokio::sync::OnceCell as AsyncOnceCell;
 use tracing::{debug, trace};
 
+use crate::{commands::write_command_index, completion::write_name_index};
+
 type SyncTaskResult = (
     soar_registry::Result<Option<(String, MetadataContent)>>,
     String,
@@ -215,6 +217,14 @@ impl SoarContext {
                     });
 
                     self.validate_packages(repo, &etag).await?;
+                    // Without the index every lookup would rebuild it, but a
+                    // sync that got this far is not undone for want of it.
+                    if let Err(err) = write_command_index(&repo_path) {
+                        debug!(repo_name = repo.name, %err, "could not write command index");
+                    }
+                    if let Err(err) = write_name_index(&repo_path) {
+                        debug!(repo_name = repo.name, %err, "could not write name index");
+                    }
 
                     self.inner.events.emit(SoarEvent::SyncProgress {
                         repo_name: repo_name.clone(),
@@ -0,0 +1,87 @@
+//! Which files an installed package placed, and which package placed a file.
+
+use std::path::{Path, PathBuf};
+
+use soar_core::{error::ErrorContext, package::query::PackageQuery, SoarResult};
+use soar_db::repository::core::{CoreRepository, SortDirection};
+use tracing::debug;
+
+use crate::{FileOwner, PackageFiles, SoarContext};
+
+/// The files recorded for every installed package matching `query`.
+///
+/// Every match is listed rather than asking which was meant: reading a
+/// listing commits to nothing. A package installed before soar recorded its
+/// files is listed with none.
+pub fn list_files(ctx: &SoarContext, query: &str) -> SoarResult<Vec<PackageFiles>> {
+    debug!(query = query, "listing installed files");
+    let query = PackageQuery::try_from(query)?;
+    let diesel_db = ctx.diesel_core_db()?;
+
+    let packages = diesel_db.with_conn(|conn| {
+        CoreRepository::list_filtered(
+            conn,
+            query.repo_name.as_deref(),
+            query.name.as_deref(),
+            query.pkg_id.as_deref(),
+            query.version.as_deref(),
+            None,
+            None,
+            None,
+            Some(SortDirection::Asc),
+        )
+    })?;
+
+    packages
+        .into_iter()
+        .map(|package| {
+            let files = diesel_db.with_conn(|conn| CoreRepository::list_files(conn, package.id))?;
+            Ok(PackageFiles {
+                package: package.into(),
+                files,
+            })
+        })
+        .collect()
+}
+
+/// The installed packages `path` belongs to.
+///
+/// A file a package placed outside its install directory is found in the
+/// record. Anything else is owned by the package whose directory holds it.
+/// The path is made absolute but not resolved: a link is owned by whoever
+/// placed the link, not by whoever owns what it points to.
+pub fn find_owners(ctx: &SoarContext, path: &Path) -> SoarResult<Vec<FileOwner>> {
+    let path =
+        std::path::absolute(path).with_context(|| format!("resolving {}", path.display()))?;
+    debug!(path = %path.display(), "finding file owners");
+    let diesel_db = ctx.diesel_core_db()?;
+
+    let recorded =
+        diesel_db.with_conn(|conn| CoreRepository::find_files_at(conn, &path.to_string_lossy()))?;
+    let mut owners = Vec::with_capacity(recorded.len());
+    for file in recorded {
+        if let Some(package) =
+            diesel_db.with_conn(|conn| CoreRepository::find_by_id(conn, file.package_id))?
+        {
+            owners.push(FileOwner {
+                package: package.into(),
+                kind: Some(file.kind),
+            });
+        }
+    }
+    if !owners.is_empty() {
+        return Ok(owners);
+    }
+
+    Ok(diesel_db
+        .with_conn(CoreRepository::list_all)?
+        .into_iter()
+        .filter(|package| path.starts_with(PathBuf::from(&package.installed_path)))
+        .map(|package| {
+            FileOwner {
+                package: package.into(),
+                kind: None,
+            }
+        })
+        .collect())
+}
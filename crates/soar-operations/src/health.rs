@@ -1,9 +1,22 @@
-use std::path::PathBuf;
+use std::{
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
 
 use soar_core::{package::remove::PackageRemover, SoarResult};
 use soar_db::repository::core::CoreRepository;
 use soar_events::{RemoveStage, SoarEvent};
-use soar_utils::{error::FileSystemResult, fs::walk_dir, path::resolve_path};
+use soar_package::{
+    formats::appdir::{can_mount, is_mountable},
+    get_file_type,
+};
+use soar_utils::{
+    error::FileSystemResult,
+    fs::walk_dir,
+    path::resolve_path,
+    wrapper::{is_broken_link, link_target},
+};
 use tracing::debug;
 
 use crate::{
@@ -48,6 +61,7 @@ pub fn check_health(ctx: &SoarContext) -> SoarResult<HealthReport> {
 
     let broken_packages = get_broken_packages(ctx)?;
     let broken_symlinks = get_broken_symlinks(ctx)?;
+    let needs_fuse = get_needs_fuse(&bin_path)?;
 
     Ok(HealthReport {
         path_configured,
@@ -56,6 +70,7 @@ pub fn check_health(ctx: &SoarContext) -> SoarResult<HealthReport> {
         man_path_configured,
         broken_packages,
         broken_symlinks,
+        needs_fuse,
     })
 }
 
@@ -111,6 +126,8 @@ pub async fn remove_broken_packages(ctx: &SoarContext) -> SoarResult<RemoveRepor
                 });
                 failed.push(FailedInfo {
                     pkg_name,
+                    repo_name,
+                    version,
                     error: err.to_string(),
                 });
             }
@@ -152,6 +169,37 @@ fn get_broken_packages(ctx: &SoarContext) -> SoarResult<Vec<BrokenPackage>> {
         .collect())
 }
 
+/// Links in `bin_path` to images that would fail to mount on this host.
+///
+/// Only plain symlinks can lead to an image: one that was unpacked is linked
+/// through a launcher for its `AppRun` instead.
+fn get_needs_fuse(bin_path: &Path) -> SoarResult<Vec<PathBuf>> {
+    let mut needs_fuse = Vec::new();
+    if !bin_path.is_dir() {
+        return Ok(needs_fuse);
+    }
+    walk_dir(bin_path, &mut |path: &Path| -> FileSystemResult<()> {
+        if !path.is_symlink() {
+            return Ok(());
+        }
+        // Followed one step by hand, since the link names its target as the
+        // install root will see it.
+        let Some(target) =
+            link_target(path).and_then(|target| fs::canonicalize(path.parent()?.join(target)).ok())
+        else {
+            return Ok(());
+        };
+        let format = fs::File::open(&target)
+            .ok()
+            .and_then(|file| get_file_type(&mut BufReader::new(file)).ok());
+        if format.is_some_and(|format| is_mountable(&format) && !can_mount(&target, &format)) {
+            needs_fuse.push(path.to_path_buf());
+        }
+        Ok(())
+    })?;
+    Ok(needs_fuse)
+}
+
 fn get_broken_symlinks(ctx: &SoarContext) -> SoarResult<Vec<PathBuf>> {
     let config = ctx.config();
     let mut broken = Vec::new();
@@ -164,7 +212,7 @@ fn get_broken_symlinks(ctx: &SoarContext) -> SoarResult<Vec<PathBuf>> {
         walk_dir(
             &bin_path,
             &mut |path: &std::path::Path| -> FileSystemResult<()> {
-                if !path.exists() {
+                if is_broken_link(path) {
                     broken.push(path.to_path_buf());
                 }
                 Ok(())
@@ -175,7 +223,7 @@ fn get_broken_symlinks(ctx: &SoarContext) -> SoarResult<Vec<PathBuf>> {
     let desktop_path = config.get_desktop_path()?;
     let mut soar_check = |path: &std::path::Path| -> FileSystemResult<()> {
         if let Some(filename) = path.file_stem().and_then(|s| s.to_str()) {
-            if filename.ends_with("-soar") && !path.exists() {
+            if filename.ends_with("-soar") && is_broken_link(path) {
                 broken.push(path.to_path_buf());
             }
         }
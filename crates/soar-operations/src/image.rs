@@ -0,0 +1,519 @@
+//! Container images built from a set of declared packages.
+//!
+//! The packages are installed the usual way, into a scratch root instead of
+//! the user's, and that root becomes the image's single layer under a fixed
+//! prefix. Nothing here talks to a container runtime: the result is an OCI
+//! image layout written as a tarball, which `podman load`, `docker load` and
+//! `skopeo copy oci-archive:` all read.
+
+use std::{
+    fs::{self, File},
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use soar_config::{config::Config, packages::ResolvedPackage};
+use soar_core::{
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+use soar_dl::{
+    checksum::{Algorithm, Checksum},
+    oci::{OciConfig, OciLayer, OciManifest},
+};
+use soar_utils::wrapper::{retarget_script, wrapper_target};
+use tar::{Builder, EntryType, Header, HeaderMode};
+use tracing::debug;
+
+use crate::{
+    apply::compute_diff, install::perform_installation, InstallOptions, InstalledInfo, SoarContext,
+};
+
+/// Where the scratch root lands inside the image unless told otherwise.
+pub const DEFAULT_PREFIX: &str = "/opt/soar";
+
+const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const CONFIG_MEDIA_TYPE: &str = "application/vnd.oci.image.config.v1+json";
+const LAYER_MEDIA_TYPE: &str = "application/vnd.oci.image.layer.v1.tar";
+
+/// What of the scratch root goes into the image. The database and cache
+/// describe the build machine's paths and would only mislead inside it.
+const LAYER_DIRS: [&str; 4] = ["bin", "packages", "share", "portable-dirs"];
+
+pub struct ImageOptions {
+    /// Absolute path the scratch root is placed at inside the image.
+    pub prefix: PathBuf,
+    /// Name recorded for the image in the layout's index.
+    pub tag: String,
+    pub no_verify: bool,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            prefix: PathBuf::from(DEFAULT_PREFIX),
+            tag: "latest".to_string(),
+            no_verify: false,
+        }
+    }
+}
+
+pub struct ImageReport {
+    pub installed: Vec<InstalledInfo>,
+    /// Digest of the image manifest, the image's identity once loaded.
+    pub digest: String,
+    pub layer_size: u64,
+}
+
+/// The configuration to install an image's packages with: `config` moved
+/// under `root`, with nothing written to the desktop or shell of the machine
+/// building it.
+pub fn image_config(config: &Config, root: &Path) -> SoarResult<Config> {
+    let mut config = config.clone();
+    config.relocate(root)?;
+    config.desktop_integration = Some(false);
+    config.completions = Some(Vec::new());
+    Ok(config)
+}
+
+/// Install `packages` into `root` and write them out as an image at `output`.
+///
+/// `ctx` has to be one made from [`image_config`] for the same `root`; the
+/// installs go wherever its configuration says.
+pub async fn build_image(
+    ctx: &SoarContext,
+    root: &Path,
+    packages: &[ResolvedPackage],
+    output: &Path,
+    options: &ImageOptions,
+) -> SoarResult<ImageReport> {
+    if !options.prefix.is_absolute() {
+        return Err(SoarError::Custom(format!(
+            "Image prefix must be an absolute path: {}",
+            options.prefix.display()
+        )));
+    }
+
+    let diff = compute_diff(ctx, packages, false).await?;
+    if !diff.not_found.is_empty() {
+        return Err(SoarError::Custom(format!(
+            "Packages not found: {}",
+            diff.not_found.join(", ")
+        )));
+    }
+
+    let targets = diff
+        .to_install
+        .into_iter()
+        .chain(diff.to_update)
+        .map(|(_, target)| target)
+        .collect();
+    let install_options = InstallOptions {
+        no_verify: options.no_verify,
+        ..Default::default()
+    };
+    let report = perform_installation(ctx, targets, &install_options).await?;
+    // An image missing one of its tools is not the image that was asked for.
+    if !report.failed.is_empty() {
+        let failed: Vec<String> = report
+            .failed
+            .iter()
+            .map(|f| format!("{}: {}", f.pkg_name, f.error))
+            .collect();
+        return Err(SoarError::Custom(format!(
+            "Image not written, packages failed to install:\n  {}",
+            failed.join("\n  ")
+        )));
+    }
+
+    let layer_path = root.join("layer.tar");
+    write_layer(root, &options.prefix, &layer_path)?;
+    let (digest, layer_size) = write_layout(&layer_path, &options.prefix, &options.tag, output)?;
+    fs::remove_file(&layer_path).ok();
+
+    Ok(ImageReport {
+        installed: report.installed,
+        digest,
+        layer_size,
+    })
+}
+
+/// Archive what was installed under `root` as a layer placing it at `prefix`.
+///
+/// Soar links by absolute path, and those paths name the scratch root; each
+/// link and wrapper script pointing into it is rewritten to where the same
+/// file sits in the image.
+/// Timestamps and ownership are left out, so the same packages give the same
+/// layer.
+fn write_layer(root: &Path, prefix: &Path, layer_path: &Path) -> SoarResult<()> {
+    let file = File::create(layer_path)
+        .with_context(|| format!("creating image layer {}", layer_path.display()))?;
+    let mut builder = Builder::new(file);
+    builder.mode(HeaderMode::Deterministic);
+    builder.follow_symlinks(false);
+
+    let in_image = prefix.strip_prefix("/").unwrap_or(prefix);
+    let mut parent = PathBuf::new();
+    for component in in_image.components() {
+        parent.push(component);
+        append_dir(&mut builder, &parent)?;
+    }
+
+    for dir in LAYER_DIRS {
+        let path = root.join(dir);
+        if path.is_dir() {
+            append_tree(&mut builder, root, prefix, &path)?;
+        }
+    }
+
+    builder
+        .into_inner()
+        .and_then(|mut file| file.flush())
+        .with_context(|| format!("writing image layer {}", layer_path.display()))?;
+    Ok(())
+}
+
+fn append_dir(builder: &mut Builder<File>, name: &Path) -> SoarResult<()> {
+    let mut header = Header::new_gnu();
+    header.set_entry_type(EntryType::Directory);
+    header.set_mode(0o755);
+    header.set_size(0);
+    header.set_mtime(0);
+    builder
+        .append_data(&mut header, name, std::io::empty())
+        .with_context(|| format!("adding {} to image layer", name.display()))
+}
+
+fn append_tree(
+    builder: &mut Builder<File>,
+    root: &Path,
+    prefix: &Path,
+    path: &Path,
+) -> SoarResult<()> {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let name = prefix.strip_prefix("/").unwrap_or(prefix).join(relative);
+    let meta = fs::symlink_metadata(path)
+        .with_context(|| format!("reading metadata of {}", path.display()))?;
+
+    if meta.file_type().is_symlink() {
+        let target =
+            fs::read_link(path).with_context(|| format!("reading symlink {}", path.display()))?;
+        let target = match target.strip_prefix(root) {
+            Ok(inside) if target.is_absolute() => prefix.join(inside),
+            _ => target,
+        };
+        let mut header = Header::new_gnu();
+        header.set_metadata_in_mode(&meta, HeaderMode::Deterministic);
+        builder
+            .append_link(&mut header, &name, &target)
+            .with_context(|| format!("adding {} to image layer", path.display()))?;
+        return Ok(());
+    }
+
+    // A wrapper names its target, and whatever install directory or portable
+    // home it exports, by absolute path, so it moves to the prefix the same
+    // way a link does.
+    if meta.is_file() && wrapper_target(path).is_some() {
+        let script = fs::read_to_string(path)
+            .with_context(|| format!("reading wrapper {}", path.display()))?;
+        let script = retarget_script(&script, root, prefix);
+        let mut header = Header::new_gnu();
+        header.set_metadata_in_mode(&meta, HeaderMode::Deterministic);
+        header.set_size(script.len() as u64);
+        builder
+            .append_data(&mut header, &name, script.as_bytes())
+            .with_context(|| format!("adding {} to image layer", path.display()))?;
+        return Ok(());
+    }
+
+    builder
+        .append_path_with_name(path, &name)
+        .with_context(|| format!("adding {} to image layer", path.display()))?;
+
+    if meta.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)
+            .with_context(|| format!("reading directory {}", path.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .collect();
+        // Directory order is whatever the filesystem gives; sorting keeps the
+        // layer, and so its digest, the same from one build to the next.
+        entries.sort();
+        for entry in entries {
+            append_tree(builder, root, prefix, &entry)?;
+        }
+    }
+    Ok(())
+}
+
+/// The architecture name OCI uses for the one soar is running on.
+fn oci_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "loongarch64" => "loong64",
+        "powerpc64" => "ppc64le",
+        other => other,
+    }
+}
+
+fn sha256_digest(data: &[u8]) -> String {
+    let hex: String = Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!("sha256:{hex}")
+}
+
+/// Write the OCI image layout for a single `layer` to a tarball at `output`,
+/// returning the manifest's digest and the layer's size.
+fn write_layout(
+    layer: &Path,
+    prefix: &Path,
+    tag: &str,
+    output: &Path,
+) -> SoarResult<(String, u64)> {
+    let layer_digest = Checksum::of_file(layer, Algorithm::Sha256)
+        .with_context(|| format!("hashing image layer {}", layer.display()))?;
+    let layer_digest = format!("sha256:{}", layer_digest.hex);
+    let layer_size = fs::metadata(layer)
+        .with_context(|| format!("reading metadata of {}", layer.display()))?
+        .len();
+
+    // The layer is stored uncompressed, so its digest is also its diff ID.
+    let config = json!({
+        "architecture": oci_architecture(),
+        "os": "linux",
+        "config": {
+            "Env": [format!(
+                "PATH={}:/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin",
+                prefix.join("bin").display()
+            )],
+        },
+        "rootfs": {
+            "type": "layers",
+            "diff_ids": [layer_digest],
+        },
+        "history": [{ "created_by": "soar image build" }],
+    })
+    .to_string()
+    .into_bytes();
+    let config_digest = sha256_digest(&config);
+
+    let manifest = OciManifest {
+        media_type: MANIFEST_MEDIA_TYPE.to_string(),
+        config: OciConfig {
+            media_type: CONFIG_MEDIA_TYPE.to_string(),
+            digest: config_digest.clone(),
+            size: config.len() as u64,
+        },
+        layers: vec![OciLayer {
+            media_type: LAYER_MEDIA_TYPE.to_string(),
+            digest: layer_digest.clone(),
+            size: layer_size,
+            annotations: Default::default(),
+        }],
+    };
+    let mut manifest = serde_json::to_value(&manifest)
+        .map_err(|err| SoarError::Custom(format!("encoding image manifest: {err}")))?;
+    manifest["schemaVersion"] = json!(2);
+    let manifest = manifest.to_string().into_bytes();
+    let manifest_digest = sha256_digest(&manifest);
+
+    let index = json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.index.v1+json",
+        "manifests": [{
+            "mediaType": MANIFEST_MEDIA_TYPE,
+            "digest": manifest_digest,
+            "size": manifest.len(),
+            "annotations": { "org.opencontainers.image.ref.name": tag },
+        }],
+    })
+    .to_string()
+    .into_bytes();
+
+    debug!(
+        output = %output.display(),
+        manifest = manifest_digest,
+        layer = layer_digest,
+        "writing image layout"
+    );
+
+    let blob = |digest: &str| format!("blobs/sha256/{}", digest.trim_start_matches("sha256:"));
+    let file =
+        File::create(output).with_context(|| format!("creating image {}", output.display()))?;
+    let mut builder = Builder::new(file);
+    let mut append = |name: &str, data: &[u8]| {
+        let mut header = file_header(data.len() as u64);
+        builder
+            .append_data(&mut header, name, data)
+            .with_context(|| format!("writing {name} to {}", output.display()))
+    };
+    append("oci-layout", br#"{"imageLayoutVersion":"1.0.0"}"#)?;
+    append("index.json", &index)?;
+    append(&blob(&manifest_digest), &manifest)?;
+    append(&blob(&config_digest), &config)?;
+
+    let mut layer_file =
+        File::open(layer).with_context(|| format!("opening image layer {}", layer.display()))?;
+    layer_file
+        .seek(SeekFrom::Start(0))
+        .with_context(|| format!("reading image layer {}", layer.display()))?;
+    let mut header = file_header(layer_size);
+    builder
+        .append_data(&mut header, blob(&layer_digest), layer_file)
+        .and_then(|_| builder.into_inner())
+        .and_then(|mut file| file.flush())
+        .with_context(|| format!("writing image {}", output.display()))?;
+
+    Ok((manifest_digest, layer_size))
+}
+
+fn file_header(size: u64) -> Header {
+    let mut header = Header::new_gnu();
+    header.set_entry_type(EntryType::Regular);
+    header.set_mode(0o644);
+    header.set_size(size);
+    header.set_mtime(0);
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::Read, os::unix::fs::symlink};
+
+    use serde_json::Value;
+    use soar_utils::wrapper::{render_wrapper, wrap_with_env, write_wrapper};
+    use tar::Archive;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// A scratch root holding one installed package with a bin link, and a
+    /// wrapper that exports its install directory and a portable home.
+    fn scratch_root() -> TempDir {
+        let root = TempDir::new().unwrap();
+        let pkg = root.path().join("packages/tool-1.0");
+        fs::create_dir_all(&pkg).unwrap();
+        fs::write(pkg.join("tool"), "#!/bin/sh\n").unwrap();
+        fs::create_dir_all(root.path().join("bin")).unwrap();
+        symlink(pkg.join("tool"), root.path().join("bin/tool")).unwrap();
+
+        let wrapper = root.path().join("bin/tool-env");
+        let script = render_wrapper(
+            &pkg.join("tool"),
+            &pkg,
+            &[("TOOL_DATA", "$INSTALL_DIR/share")],
+            &[],
+        );
+        write_wrapper(&wrapper, &script).unwrap();
+        let home = root.path().join("portable-dirs/tool/home");
+        wrap_with_env(&wrapper, &pkg.join("tool"), &[("HOME", &home)]).unwrap();
+        fs::create_dir_all(root.path().join("db")).unwrap();
+        fs::write(root.path().join("db/soar.db"), "").unwrap();
+        root
+    }
+
+    fn entries(path: &Path) -> HashMap<String, (EntryType, Vec<u8>, Option<PathBuf>)> {
+        let mut archive = Archive::new(File::open(path).unwrap());
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().display().to_string();
+                let kind = entry.header().entry_type();
+                let link = entry.link_name().unwrap().map(|l| l.into_owned());
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).unwrap();
+                (name, (kind, data, link))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_layer_places_the_root_at_the_prefix_and_rewrites_links_into_it() {
+        let root = scratch_root();
+        let layer = root.path().join("layer.tar");
+        write_layer(root.path(), Path::new("/opt/soar"), &layer).unwrap();
+
+        let entries = entries(&layer);
+        assert_eq!(entries["opt"].0, EntryType::Directory);
+        assert_eq!(entries["opt/soar"].0, EntryType::Directory);
+        assert_eq!(entries["opt/soar/packages/tool-1.0/tool"].1, b"#!/bin/sh\n");
+        assert_eq!(
+            entries["opt/soar/bin/tool"].2.as_deref(),
+            Some(Path::new("/opt/soar/packages/tool-1.0/tool"))
+        );
+        assert!(!entries.keys().any(|name| name.contains("soar.db")));
+    }
+
+    #[test]
+    fn wrappers_in_the_layer_name_paths_under_the_prefix() {
+        let root = scratch_root();
+        let layer = root.path().join("layer.tar");
+        write_layer(root.path(), Path::new("/opt/soar"), &layer).unwrap();
+
+        let entries = entries(&layer);
+        let (kind, script, _) = &entries["opt/soar/bin/tool-env"];
+        assert_eq!(*kind, EntryType::Regular);
+        let script = String::from_utf8(script.clone()).unwrap();
+        assert_eq!(
+            script,
+            "#!/bin/sh\n\
+             # soar-wrapper: /opt/soar/packages/tool-1.0/tool\n\
+             export HOME=\"/opt/soar/portable-dirs/tool/home\"\n\
+             export TOOL_DATA=\"/opt/soar/packages/tool-1.0/share\"\n\
+             exec \"/opt/soar/packages/tool-1.0/tool\" \"$@\"\n"
+        );
+        assert!(!script.contains(&*root.path().to_string_lossy()));
+    }
+
+    #[test]
+    fn the_same_root_gives_the_same_layer() {
+        let root = scratch_root();
+        let first = root.path().join("first.tar");
+        let second = root.path().join("second.tar");
+        write_layer(root.path(), Path::new("/opt/soar"), &first).unwrap();
+        write_layer(root.path(), Path::new("/opt/soar"), &second).unwrap();
+        assert_eq!(fs::read(first).unwrap(), fs::read(second).unwrap());
+    }
+
+    #[test]
+    fn the_layout_links_index_manifest_config_and_layer_by_digest() {
+        let root = scratch_root();
+        let layer = root.path().join("layer.tar");
+        let output = root.path().join("image.tar");
+        write_layer(root.path(), Path::new("/opt/soar"), &layer).unwrap();
+        let (digest, _) = write_layout(&layer, Path::new("/opt/soar"), "tools", &output).unwrap();
+
+        let entries = entries(&output);
+        let blob = |digest: &str| {
+            let data = &entries[&format!("blobs/sha256/{}", &digest[7..])].1;
+            assert_eq!(sha256_digest(data), digest);
+            data.clone()
+        };
+
+        let index: Value = serde_json::from_slice(&entries["index.json"].1).unwrap();
+        let entry = &index["manifests"][0];
+        assert_eq!(entry["digest"], digest.as_str());
+        assert_eq!(
+            entry["annotations"]["org.opencontainers.image.ref.name"],
+            "tools"
+        );
+
+        let manifest: Value = serde_json::from_slice(&blob(&digest)).unwrap();
+        assert_eq!(manifest["schemaVersion"], 2);
+        let config: Value =
+            serde_json::from_slice(&blob(manifest["config"]["digest"].as_str().unwrap())).unwrap();
+        let layer_digest = manifest["layers"][0]["digest"].as_str().unwrap();
+        assert_eq!(blob(layer_digest), fs::read(&layer).unwrap());
+        assert_eq!(config["rootfs"]["diff_ids"][0], layer_digest);
+        assert!(config["config"]["Env"][0]
+            .as_str()
+            .unwrap()
+            .starts_with("PATH=/opt/soar/bin:"));
+    }
+}
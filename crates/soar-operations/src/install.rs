@@ -32,7 +32,10 @@ use soar_db::repository::{
     metadata::{narrow_by_pkg_id, MetadataRepository},
 };
 use soar_events::{InstallStage, SoarEvent, VerifyStage};
-use soar_package::integrate_package;
+use soar_package::{
+    formats::{appdir::has_appdir, common::PortableDirs},
+    integrate_package,
+};
 use soar_utils::{
     hash::{calculate_checksum, hash_string},
     lock::FileLock,
@@ -44,8 +47,12 @@ use tokio::sync::Semaphore;
 use tracing::{debug, trace, warn};
 
 use crate::{
+    journal::{JournalGuard, JournalStep},
     progress::{create_progress_bridge, next_op_id},
-    utils::{has_desktop_integration, link_shared_files, mangle_package_symlinks},
+    utils::{
+        has_desktop_integration, link_extracted_images, link_portable_launchers, link_shared_files,
+        mangle_package_symlinks, placed_files, published_checksum,
+    },
     FailedInfo, InstallOptions, InstallReport, InstalledInfo, ResolveResult, SoarContext,
 };
 
@@ -164,7 +171,15 @@ fn resolve_url_package(
         options.pkg_id_override.as_deref(),
     )?;
 
-    resolve_synthetic_target(diesel_db, url_pkg.to_package(), options)
+    let mut result = resolve_synthetic_target(diesel_db, url_pkg.to_package(), options)?;
+    // Looked for once the package is known to be wanted, not before.
+    if let ResolveResult::Resolved(ref mut targets) = result {
+        for target in targets {
+            let package = &mut target.package;
+            package.bsum = published_checksum(&package.download_url, None);
+        }
+    }
+    Ok(result)
 }
 
 fn resolve_local_package(
@@ -757,9 +772,18 @@ pub async fn perform_installation(
         });
     }
 
+    // Every package is written down before the first one starts, so a process
+    // that dies part-way leaves a record of the ones it never reached too.
+    let journaled = targets
+        .into_iter()
+        .map(|target| {
+            JournalGuard::begin(&diesel_db, &target, options).map(|guard| (target, guard))
+        })
+        .collect::<SoarResult<Vec<_>>>()?;
+
     let mut handles = Vec::new();
 
-    for target in targets {
+    for (target, journal) in journaled {
         let permit = semaphore.clone().acquire_owned().await.unwrap();
         let ctx = ctx.clone();
         let db = diesel_db.clone();
@@ -770,6 +794,7 @@ pub async fn perform_installation(
         let failed_count = failed_count.clone();
         let binary_only = options.binary_only;
         let no_verify = options.no_verify;
+        let extract = options.extract;
         let portable = options.portable.clone();
         let portable_home = options.portable_home.clone();
         let portable_config = options.portable_config.clone();
@@ -780,9 +805,11 @@ pub async fn perform_installation(
             let result = install_single_package(
                 &ctx,
                 &target,
+                &journal,
                 db.clone(),
                 binary_only,
                 no_verify,
+                extract,
                 portable.as_deref(),
                 portable_home.as_deref(),
                 portable_config.as_deref(),
@@ -826,6 +853,8 @@ pub async fn perform_installation(
                             });
                             failed.lock().unwrap().push(FailedInfo {
                                 pkg_name: target.package.pkg_name.clone(),
+                                repo_name: target.package.repo_name.clone(),
+                                version: target.package.version.clone(),
                                 error: err.to_string(),
                             });
                             failed_count.fetch_add(1, Ordering::Relaxed);
@@ -877,9 +906,11 @@ fn source_skips_integrity_gate(pkg: &Package) -> bool {
 async fn install_single_package(
     ctx: &SoarContext,
     target: &InstallTarget,
+    journal: &JournalGuard,
     core_db: DieselDatabase,
     binary_only: bool,
     no_verify: bool,
+    extract: bool,
     portable: Option<&str>,
     portable_home: Option<&str>,
     portable_config: Option<&str>,
@@ -1039,13 +1070,15 @@ async fn install_single_package(
             existing.install_patterns.as_deref(),
         )
     } else {
+        // packages.toml declares portable directories per package, the
+        // command line for the whole batch; the package's own come first.
         (
             false,
-            portable,
-            portable_home,
-            portable_config,
-            portable_share,
-            portable_cache,
+            target.portable.as_deref().or(portable),
+            target.portable_home.as_deref().or(portable_home),
+            target.portable_config.as_deref().or(portable_config),
+            target.portable_share.as_deref().or(portable_share),
+            target.portable_cache.as_deref().or(portable_cache),
             None,
         )
     };
@@ -1095,6 +1128,8 @@ async fn install_single_package(
     // Create progress bridge for download events
     let progress_callback = create_progress_bridge(events.clone(), op_id, pkg.pkg_name.clone());
 
+    journal.download(&install_dir)?;
+
     trace!(install_dir = %install_dir.display(), "creating package installer");
     let installer = PackageInstaller::new(
         target,
@@ -1111,6 +1146,8 @@ async fn install_single_package(
     // Download
     let downloaded_checksum = installer.download_package().await?;
 
+    journal.step(JournalStep::Verify)?;
+
     // Signature verification
     let mut verified_sig_count = 0usize;
     if let Some(repository) = config.get_repository(&pkg.repo_name) {
@@ -1135,6 +1172,12 @@ async fn install_single_package(
         cleanup_sig_files(&install_dir);
     }
 
+    // An upstream signature was checked during the download, which fails
+    // rather than returning when it does not hold.
+    if target.verify.is_some() && pkg.ghcr_pkg.is_none() {
+        verified_sig_count += 1;
+    }
+
     if !no_verify && !skip_integrity_gate && pkg.bsum.is_none() && verified_sig_count == 0 {
         return Err(SoarError::Custom(format!(
             "Refusing to install {}: no checksum and no valid signature found to verify integrity (use --no-verify to override)",
@@ -1196,6 +1239,8 @@ async fn install_single_package(
         }
     }
 
+    journal.step(JournalStep::Link)?;
+
     // Create symlinks
     events.emit(SoarEvent::Installing {
         op_id,
@@ -1247,6 +1292,33 @@ async fn install_single_package(
         .await?;
     }
 
+    // An update carries over how the version it replaces was installed.
+    let extract = extract
+        || target
+            .existing_install
+            .as_ref()
+            .is_some_and(|existing| has_appdir(Path::new(&existing.installed_path)));
+    let symlinks = link_extracted_images(&install_dir, symlinks, extract)?;
+
+    let portable_dirs = PortableDirs::resolve(
+        pkg,
+        eff_portable,
+        eff_portable_home,
+        eff_portable_config,
+        eff_portable_share,
+        eff_portable_cache,
+    )?;
+    link_portable_launchers(&symlinks, &portable_dirs)?;
+    let files = placed_files(
+        &install_dir,
+        &symlinks,
+        shared.iter().map(|(_, link)| link.clone()),
+        &portable_dirs,
+        ctx.config(),
+    )?;
+
+    journal.step(JournalStep::Record)?;
+
     // Record to database
     events.emit(SoarEvent::Installing {
         op_id,
@@ -1262,6 +1334,7 @@ async fn install_single_package(
             eff_portable_config,
             eff_portable_share,
             eff_portable_cache,
+            &files,
         )
         .await?;
 
@@ -0,0 +1,566 @@
+//! The write-ahead record of what an install is in the middle of.
+//!
+//! Every package an operation is about to install is written down before any
+//! of them starts, and each step is recorded before it is taken. An entry is
+//! cleared however the install ends while this process is alive, since a
+//! failure is reported as it happens. What is left behind therefore marks a
+//! process that died, and says how far each of its packages got.
+//!
+//! An entry also names a lock its process holds until the entry is cleared.
+//! A row whose lock is still held belongs to an install that is running, in
+//! this process or another, and is not touched.
+
+use std::{
+    fmt, fs,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use chrono::Utc;
+use soar_core::{
+    database::{connection::DieselDatabase, models::InstalledPackage},
+    error::SoarError,
+    package::{install::InstallTarget, remove::make_tree_writable},
+    utils::remove_broken_symlinks,
+    SoarResult,
+};
+use soar_db::{
+    models::core::{JournalEntry, NewJournalEntry},
+    repository::core::CoreRepository,
+};
+use soar_utils::lock::FileLock;
+use tracing::{debug, warn};
+
+use crate::{
+    install::perform_installation, utils::mangle_package_symlinks, FailedInfo, InstallOptions,
+    ResumeReport, RolledBackInfo, SoarContext,
+};
+
+/// Where a journaled package stood. Each is recorded before it is taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalStep {
+    /// Part of an operation that has not reached it yet.
+    Planned,
+    /// Fetching the artifact, and extracting it as it lands.
+    Download,
+    Verify,
+    /// Linking binaries, shared files and desktop entries.
+    Link,
+    /// Marking the install as done in the database.
+    Record,
+}
+
+impl JournalStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Planned => "planned",
+            Self::Download => "download",
+            Self::Verify => "verify",
+            Self::Link => "link",
+            Self::Record => "record",
+        }
+    }
+
+    pub fn parse(step: &str) -> Option<Self> {
+        match step {
+            "planned" => Some(Self::Planned),
+            "download" => Some(Self::Download),
+            "verify" => Some(Self::Verify),
+            "link" => Some(Self::Link),
+            "record" => Some(Self::Record),
+            _ => None,
+        }
+    }
+
+    /// Whether anything outside the package directory may have been touched.
+    fn has_linked(&self) -> bool {
+        matches!(self, Self::Link | Self::Record)
+    }
+}
+
+impl fmt::Display for JournalStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+fn now() -> String {
+    Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// The lock held for a journal entry while something is working on it.
+///
+/// Its file goes with it, so finished entries leave nothing in the lock
+/// directory. The row is always dropped first: whoever takes the lock after
+/// that finds no row left to act on.
+struct OwnerLock(FileLock);
+
+impl OwnerLock {
+    /// A lock no other entry uses, taken before its row is written so that no
+    /// running install is ever without one.
+    fn create() -> SoarResult<(String, Self)> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let owner = format!(
+            "{}-{}-{}",
+            std::process::id(),
+            Utc::now().timestamp_micros(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+        let lock = FileLock::acquire(&lock_name(&owner))
+            .map_err(|err| SoarError::Custom(format!("locking the journal entry: {err}")))?;
+        Ok((owner, Self(lock)))
+    }
+
+    /// The entry's lock, unless the process that wrote it is still running.
+    fn take(entry: &JournalEntry) -> SoarResult<Option<Self>> {
+        FileLock::try_acquire(&lock_name(&entry.owner))
+            .map(|lock| lock.map(Self))
+            .map_err(|err| SoarError::Custom(format!("checking {}: {err}", entry.pkg_name)))
+    }
+}
+
+impl Drop for OwnerLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.0.path());
+    }
+}
+
+fn lock_name(owner: &str) -> String {
+    format!("journal-{owner}")
+}
+
+/// One journaled package, cleared when dropped.
+pub struct JournalGuard {
+    db: DieselDatabase,
+    id: i32,
+    // after the row is cleared in `drop`, never before
+    _lock: OwnerLock,
+}
+
+impl JournalGuard {
+    /// Write a package down as planned, along with the options it is being
+    /// installed with.
+    pub fn begin(
+        db: &DieselDatabase,
+        target: &InstallTarget,
+        options: &InstallOptions,
+    ) -> SoarResult<Self> {
+        let package = &target.package;
+        let operation = if target.existing_install.is_some() {
+            "update"
+        } else {
+            "install"
+        };
+        let record = |err: serde_json::Error| {
+            SoarError::Custom(format!("recording {}: {err}", package.pkg_name))
+        };
+        let serialized = serde_json::to_string(target).map_err(record)?;
+        let options = serde_json::to_string(options).map_err(record)?;
+        let replaces_id = target.existing_install.as_ref().map(|e| e.id as i32);
+        let (owner, lock) = OwnerLock::create()?;
+        let started_at = now();
+        let id = db.with_conn(|conn| {
+            CoreRepository::begin_journal(
+                conn,
+                &NewJournalEntry {
+                    operation,
+                    pkg_name: &package.pkg_name,
+                    repo_name: &package.repo_name,
+                    version: &package.version,
+                    install_dir: None,
+                    replaces_id,
+                    step: JournalStep::Planned.as_str(),
+                    target: &serialized,
+                    options: &options,
+                    owner: &owner,
+                    started_at: &started_at,
+                    updated_at: &started_at,
+                },
+            )
+        })?;
+        Ok(Self {
+            db: db.clone(),
+            id,
+            _lock: lock,
+        })
+    }
+
+    /// Record the step about to be taken.
+    pub fn step(&self, step: JournalStep) -> SoarResult<()> {
+        self.advance(step, None)
+    }
+
+    /// Record that the download is about to start, and where it goes.
+    pub fn download(&self, install_dir: &std::path::Path) -> SoarResult<()> {
+        self.advance(JournalStep::Download, Some(&install_dir.to_string_lossy()))
+    }
+
+    fn advance(&self, step: JournalStep, install_dir: Option<&str>) -> SoarResult<()> {
+        let updated_at = now();
+        self.db.with_conn(|conn| {
+            CoreRepository::advance_journal(conn, self.id, step.as_str(), install_dir, &updated_at)
+        })?;
+        Ok(())
+    }
+}
+
+impl Drop for JournalGuard {
+    fn drop(&mut self) {
+        if let Err(err) = self
+            .db
+            .with_conn(|conn| CoreRepository::finish_journal(conn, self.id))
+        {
+            warn!(error = %err, "could not clear the operation journal");
+        }
+    }
+}
+
+/// Packages a process that died left unfinished. Those of installs still
+/// running are left out.
+pub fn unfinished(ctx: &SoarContext) -> SoarResult<Vec<JournalEntry>> {
+    let entries = ctx
+        .diesel_core_db()?
+        .with_conn(CoreRepository::list_journal)?;
+    let mut abandoned = Vec::new();
+    for entry in entries {
+        if OwnerLock::take(&entry)?.is_some() {
+            abandoned.push(entry);
+        }
+    }
+    Ok(abandoned)
+}
+
+/// How many packages a process that died left unfinished.
+pub fn unfinished_count(ctx: &SoarContext) -> SoarResult<usize> {
+    Ok(unfinished(ctx)?.len())
+}
+
+/// An abandoned entry, with its lock held so nothing else picks it up too.
+struct Claimed {
+    entry: JournalEntry,
+    _lock: OwnerLock,
+}
+
+/// Takes over every entry whose process is gone.
+///
+/// The rows are read again once their locks are held: an install that ended
+/// between the listing and the lock has cleared its row, and is left alone.
+fn claim_abandoned(db: &DieselDatabase) -> SoarResult<Vec<Claimed>> {
+    let mut claimed = Vec::new();
+    for entry in db.with_conn(CoreRepository::list_journal)? {
+        let Some(lock) = OwnerLock::take(&entry)? else {
+            debug!(pkg_name = entry.pkg_name, "still being installed, skipping");
+            continue;
+        };
+        if let Some(entry) = db.with_conn(|conn| CoreRepository::find_journal(conn, entry.id))? {
+            claimed.push(Claimed {
+                entry,
+                _lock: lock,
+            });
+        }
+    }
+    Ok(claimed)
+}
+
+/// Abandoned packages that were being installed with the same options.
+struct Batch<'a> {
+    /// The options as the journal holds them.
+    recorded: &'a str,
+    options: InstallOptions,
+    to_finish: Vec<(&'a JournalEntry, InstallTarget)>,
+}
+
+/// Finish each package an interrupted operation left behind, or with
+/// `rollback`, undo it and fall back to what it was replacing.
+///
+/// A package that cannot be finished is rolled back, so either way nothing is
+/// left half installed. One the database already records as installed got
+/// past the last step that matters and is counted as finished. Packages are
+/// finished with the options their operation was started with.
+pub async fn resume(ctx: &SoarContext, rollback: bool) -> SoarResult<ResumeReport> {
+    let db = ctx.diesel_core_db()?.clone();
+    let claimed = claim_abandoned(&db)?;
+    debug!(
+        count = claimed.len(),
+        rollback, "resuming interrupted operations"
+    );
+
+    let mut report = ResumeReport::default();
+    // One batch per set of options, in the order they were first seen.
+    let mut batches: Vec<Batch> = Vec::new();
+
+    for entry in claimed.iter().map(|c| &c.entry) {
+        if is_recorded(&db, entry)? {
+            clear(&db, entry)?;
+            report.already_complete.push(entry.pkg_name.clone());
+            continue;
+        }
+        if rollback {
+            roll_back(ctx, entry).await?;
+            report.rolled_back.push(rolled_back_info(entry));
+            continue;
+        }
+        let parsed = serde_json::from_str::<InstallTarget>(&entry.target).and_then(|target| {
+            Ok((
+                target,
+                serde_json::from_str::<InstallOptions>(&entry.options)?,
+            ))
+        });
+        match parsed {
+            Ok((mut target, options)) => {
+                target.existing_install = replaced(&db, entry)?;
+                match batches.iter_mut().find(|b| b.recorded == entry.options) {
+                    Some(batch) => batch.to_finish.push((entry, target)),
+                    None => {
+                        batches.push(Batch {
+                            recorded: &entry.options,
+                            options,
+                            to_finish: vec![(entry, target)],
+                        })
+                    }
+                }
+            }
+            Err(err) => {
+                roll_back(ctx, entry).await?;
+                report.failed.push(FailedInfo {
+                    pkg_name: entry.pkg_name.clone(),
+                    repo_name: entry.repo_name.clone(),
+                    version: entry.version.clone(),
+                    error: format!("the journal entry could not be read ({err}), rolled back"),
+                });
+            }
+        }
+    }
+
+    for Batch {
+        options,
+        to_finish,
+        ..
+    } in batches
+    {
+        let targets = to_finish.iter().map(|(_, t)| t.clone()).collect();
+        let install_report = perform_installation(ctx, targets, &options).await?;
+
+        for failure in &install_report.failed {
+            if let Some((entry, _)) = to_finish.iter().find(|(entry, _)| {
+                entry.pkg_name == failure.pkg_name
+                    && entry.repo_name == failure.repo_name
+                    && entry.version == failure.version
+            }) {
+                roll_back(ctx, entry).await?;
+            }
+        }
+        // Everything that finished, and everything rolled back above, no
+        // longer needs the entry the dead process left.
+        for (entry, _) in &to_finish {
+            clear(&db, entry)?;
+        }
+
+        report.finished.extend(install_report.installed);
+        report
+            .failed
+            .extend(install_report.failed.into_iter().map(|f| {
+                FailedInfo {
+                    error: format!("{}, rolled back", f.error),
+                    ..f
+                }
+            }));
+    }
+    Ok(report)
+}
+
+fn rolled_back_info(entry: &JournalEntry) -> RolledBackInfo {
+    RolledBackInfo {
+        pkg_name: entry.pkg_name.clone(),
+        repo_name: entry.repo_name.clone(),
+        version: entry.version.clone(),
+        step: entry.step.clone(),
+    }
+}
+
+fn clear(db: &DieselDatabase, entry: &JournalEntry) -> SoarResult<()> {
+    db.with_conn(|conn| CoreRepository::finish_journal(conn, entry.id))?;
+    Ok(())
+}
+
+/// Whether the install got as far as being recorded before the process died.
+fn is_recorded(db: &DieselDatabase, entry: &JournalEntry) -> SoarResult<bool> {
+    let Some(ref install_dir) = entry.install_dir else {
+        return Ok(false);
+    };
+    let rows = db.with_conn(|conn| CoreRepository::find_by_name(conn, &entry.pkg_name))?;
+    Ok(rows
+        .iter()
+        .any(|row| row.is_installed && row.installed_path == *install_dir))
+}
+
+/// The install an update was replacing, if it is still there.
+fn replaced(db: &DieselDatabase, entry: &JournalEntry) -> SoarResult<Option<InstalledPackage>> {
+    let Some(id) = entry.replaces_id else {
+        return Ok(None);
+    };
+    Ok(db
+        .with_conn(|conn| CoreRepository::find_by_id(conn, id))?
+        .filter(|row| row.is_installed)
+        .map(Into::into))
+}
+
+/// Undo what an interrupted install did, and relink the version it replaced.
+async fn roll_back(ctx: &SoarContext, entry: &JournalEntry) -> SoarResult<()> {
+    let db = ctx.diesel_core_db()?;
+    let step = JournalStep::parse(&entry.step).unwrap_or(JournalStep::Record);
+    debug!(pkg_name = entry.pkg_name, step = %step, "rolling back");
+
+    // The unfinished row is the only one for this directory that was never
+    // marked installed.
+    if let Some(ref install_dir) = entry.install_dir {
+        let rows = db.with_conn(|conn| CoreRepository::find_by_name(conn, &entry.pkg_name))?;
+        for row in rows
+            .iter()
+            .filter(|row| !row.is_installed && row.installed_path == *install_dir)
+        {
+            db.with_conn(|conn| CoreRepository::delete(conn, row.id))?;
+        }
+        let path = PathBuf::from(install_dir);
+        if path.exists() {
+            make_tree_writable(&path);
+            fs::remove_dir_all(&path)
+                .map_err(|err| SoarError::Custom(format!("removing {}: {err}", path.display())))?;
+        }
+    }
+
+    if !step.has_linked() {
+        clear(db, entry)?;
+        return Ok(());
+    }
+
+    // Links were being pointed at the new version, so some of them now point
+    // at nothing and the rest still need to go back.
+    remove_broken_symlinks()?;
+    if let Some(previous) = replaced(db, entry)? {
+        let install_dir = PathBuf::from(&previous.installed_path);
+        if install_dir.exists() && !previous.unlinked {
+            mangle_package_symlinks(
+                &install_dir,
+                &ctx.config().get_bin_path()?,
+                previous.provides.as_deref(),
+                &previous.pkg_name,
+                &previous.version,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        }
+    }
+
+    clear(db, entry)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_step_reads_back_as_itself() {
+        for step in [
+            JournalStep::Planned,
+            JournalStep::Download,
+            JournalStep::Verify,
+            JournalStep::Link,
+            JournalStep::Record,
+        ] {
+            assert_eq!(JournalStep::parse(step.as_str()), Some(step));
+        }
+        assert_eq!(JournalStep::parse("extracting"), None);
+    }
+
+    #[test]
+    fn only_a_package_that_reached_linking_needs_its_links_repaired() {
+        assert!(!JournalStep::Download.has_linked());
+        assert!(!JournalStep::Verify.has_linked());
+        assert!(JournalStep::Link.has_linked());
+        assert!(JournalStep::Record.has_linked());
+    }
+
+    #[test]
+    fn a_target_survives_the_journal() {
+        let mut target = InstallTarget::default();
+        target.package.pkg_name = "htop".into();
+        target.package.version = "3.3.0".into();
+        target.pinned = true;
+
+        let read_back: InstallTarget =
+            serde_json::from_str(&serde_json::to_string(&target).unwrap()).unwrap();
+        assert_eq!(read_back.package.pkg_name, "htop");
+        assert_eq!(read_back.package.version, "3.3.0");
+        assert!(read_back.pinned);
+        assert!(read_back.existing_install.is_none());
+    }
+
+    #[test]
+    fn options_survive_the_journal() {
+        let options = InstallOptions {
+            no_verify: true,
+            binary_only: true,
+            extract: true,
+            portable_home: Some("/tmp/home".into()),
+            ..Default::default()
+        };
+
+        let read_back: InstallOptions =
+            serde_json::from_str(&serde_json::to_string(&options).unwrap()).unwrap();
+        assert!(read_back.no_verify && read_back.binary_only && read_back.extract);
+        assert_eq!(read_back.portable_home.as_deref(), Some("/tmp/home"));
+        assert!(!read_back.force);
+    }
+
+    #[test]
+    fn only_entries_whose_process_is_gone_are_claimed() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = DieselDatabase::open_core(dir.path().join("soar.db")).unwrap();
+        let mut target = InstallTarget::default();
+        target.package.pkg_name = "htop".into();
+
+        let running = JournalGuard::begin(&db, &target, &InstallOptions::default()).unwrap();
+        // what a process that died leaves: a row nobody holds the lock of
+        let abandoned = db
+            .with_conn(|conn| {
+                CoreRepository::begin_journal(
+                    conn,
+                    &NewJournalEntry {
+                        operation: "install",
+                        pkg_name: "btop",
+                        repo_name: "bincache",
+                        version: "1.0",
+                        install_dir: None,
+                        replaces_id: None,
+                        step: JournalStep::Download.as_str(),
+                        target: "{}",
+                        options: "{}",
+                        owner: &format!("gone-{}", std::process::id()),
+                        started_at: "",
+                        updated_at: "",
+                    },
+                )
+            })
+            .unwrap();
+
+        let claimed = claim_abandoned(&db).unwrap();
+        let ids: Vec<i32> = claimed.iter().map(|c| c.entry.id).collect();
+        assert_eq!(ids, [abandoned]);
+        // and held, so a second resume does not take it as well
+        assert!(claim_abandoned(&db).unwrap().is_empty());
+
+        drop(claimed);
+        drop(running);
+        let left: Vec<String> = claim_abandoned(&db)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.entry.pkg_name)
+            .collect();
+        assert_eq!(left, ["btop"]);
+    }
+}
@@ -4,9 +4,16 @@ pub mod types;
 pub mod utils;
 
 pub mod apply;
+pub mod changelog;
+pub mod commands;
+pub mod completion;
+pub mod files;
 pub mod health;
+pub mod image;
 pub mod install;
+pub mod journal;
 pub mod list;
+pub mod profile;
 pub mod remove;
 pub mod repo;
 pub mod run;
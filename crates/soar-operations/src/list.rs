@@ -7,13 +7,13 @@ use soar_core::{
 };
 use soar_db::{
     models::metadata::PackageListing,
-    repository::{core::CoreRepository, metadata::MetadataRepository},
+    repository::{core::CoreRepository, metadata::MetadataRepository, unified::UnifiedRepository},
 };
 use soar_utils::{fs::dir_size, version::compare_versions};
 use tracing::{debug, trace};
 
 use crate::{
-    utils::{is_installed, InstalledIndex, NameCounts, PackageKey},
+    utils::{is_installed, offered_names, InstalledIndex, PackageKey},
     InstalledEntry, InstalledListResult, PackageListEntry, PackageListResult, SoarContext,
 };
 
@@ -44,24 +44,25 @@ pub async fn list_packages(
             })
             .collect()
     } else {
-        metadata_mgr.query_all_flat(|repo_name, conn| {
-            let pkgs = MetadataRepository::list_all_minimal(conn)?;
-            Ok(pkgs
-                .into_iter()
-                .map(|pkg| {
-                    ListingWithRepo {
-                        repo_name: repo_name.to_string(),
-                        pkg,
-                    }
-                })
-                .collect())
-        })?
+        metadata_mgr
+            .query_unified_merged(UnifiedRepository::list_all_minimal, |a, b| {
+                a.1.pkg_name.cmp(&b.1.pkg_name).then_with(|| a.0.cmp(&b.0))
+            })?
+            .into_iter()
+            .map(|(repo_name, pkg)| {
+                ListingWithRepo {
+                    repo_name,
+                    pkg,
+                }
+            })
+            .collect()
     };
 
     // One row per package, not per version. A repository publishes every
     // version it knows, and listing them all buries the packages themselves.
     let mut newest: HashMap<PackageKey, ListingWithRepo> = HashMap::new();
     let mut counts: HashMap<PackageKey, Vec<String>> = HashMap::new();
+    let mut order: Vec<PackageKey> = Vec::new();
     for entry in packages {
         // family included: two packages sharing a name are different
         // packages, not two versions of one
@@ -78,17 +79,18 @@ pub async fn list_packages(
         match newest.get(&key) {
             Some(kept) if compare_versions(&kept.pkg.version, &entry.pkg.version).is_ge() => {}
             _ => {
+                if !newest.contains_key(&key) {
+                    order.push(key.clone());
+                }
                 newest.insert(key, entry);
             }
         }
     }
-    let mut packages: Vec<ListingWithRepo> = newest.into_values().collect();
-    packages.sort_by(|a, b| {
-        a.pkg
-            .pkg_name
-            .cmp(&b.pkg.pkg_name)
-            .then(a.repo_name.cmp(&b.repo_name))
-    });
+    // the rows arrive ordered by name and repository, first sighting included
+    let packages: Vec<ListingWithRepo> = order
+        .into_iter()
+        .filter_map(|k| newest.remove(&k))
+        .collect();
 
     let installed_pkgs: InstalledIndex = diesel_db
         .with_conn(|conn| {
@@ -120,15 +122,7 @@ pub async fn list_packages(
             acc
         });
 
-    let offered: NameCounts = metadata_mgr
-        .query_all(|_repo_name, conn| MetadataRepository::count_names(conn))?
-        .into_iter()
-        .flat_map(|(repo_name, rows)| {
-            rows.into_iter().map(move |(pkg_name, offered)| {
-                ((repo_name.clone(), pkg_name), offered.max(0) as usize)
-            })
-        })
-        .collect();
+    let offered = offered_names(metadata_mgr)?;
 
     let total = packages.len();
 
@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+
+use soar_config::{error::ConfigError, profile::Profile};
+use soar_core::{
+    database::models::InstalledPackage, error::SoarError, package::query::PackageQuery, SoarResult,
+};
+use soar_db::repository::core::{CoreRepository, SortDirection};
+use tracing::debug;
+
+use crate::{repo::modify_config, SoarContext};
+
+/// A package moved from one profile to another.
+pub struct MigratedPackage {
+    pub package: InstalledPackage,
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+impl SoarContext {
+    /// Add a new profile to the configuration.
+    pub fn add_profile(&self, name: &str, profile: Profile) -> SoarResult<()> {
+        modify_config(|config| {
+            if config.profile.contains_key(name) {
+                return Err(ConfigError::Custom(format!("Profile '{name}' already exists")).into());
+            }
+
+            config.profile.insert(name.to_string(), profile);
+            config.resolve()?;
+            Ok(())
+        })
+    }
+
+    /// Remove a profile from the configuration.
+    ///
+    /// A profile that still holds packages is refused rather than orphaning
+    /// them: nothing else records where they are. Its directories are left on
+    /// disk either way.
+    pub fn remove_profile(&self, name: &str) -> SoarResult<()> {
+        let installed = self
+            .diesel_core_db()?
+            .with_conn(|conn| CoreRepository::count_by_profile(conn, name))?;
+
+        modify_config(|config| {
+            if !config.profile.contains_key(name) {
+                return Err(ConfigError::MissingProfile(name.to_string()).into());
+            }
+            if config.default_profile == name {
+                return Err(ConfigError::Custom(format!(
+                    "Profile '{name}' is the default profile; set another default first"
+                ))
+                .into());
+            }
+            if installed > 0 {
+                return Err(ConfigError::Custom(format!(
+                    "Profile '{name}' still has {installed} package(s); migrate or remove them first"
+                ))
+                .into());
+            }
+
+            config.profile.remove(name);
+            Ok(())
+        })
+    }
+
+    /// Make `name` the default profile.
+    pub fn set_default_profile(&self, name: &str) -> SoarResult<()> {
+        modify_config(|config| {
+            if !config.profile.contains_key(name) {
+                return Err(ConfigError::MissingProfile(name.to_string()).into());
+            }
+
+            config.default_profile = name.to_string();
+            config.resolve()?;
+            Ok(())
+        })
+    }
+}
+
+/// Move the installed package `package` names into the profile `to`.
+///
+/// The query has to name exactly one installed package; a name that several
+/// match is refused with the candidates, as removal does.
+pub fn migrate_package(ctx: &SoarContext, package: &str, to: &str) -> SoarResult<MigratedPackage> {
+    debug!(package = package, to = to, "migrating package");
+    let diesel_db = ctx.diesel_core_db()?;
+    let query = PackageQuery::try_from(package)?;
+
+    let candidates: Vec<InstalledPackage> = diesel_db
+        .with_conn(|conn| {
+            CoreRepository::list_filtered(
+                conn,
+                query.repo_name.as_deref(),
+                query.name.as_deref(),
+                query.pkg_id.as_deref(),
+                query.version.as_deref(),
+                Some(true),
+                None,
+                None,
+                Some(SortDirection::Asc),
+            )
+        })?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    let installed = match candidates.len() {
+        0 => return Err(SoarError::Custom(format!("{package} is not installed"))),
+        1 => candidates.into_iter().next().unwrap(),
+        _ => {
+            let names: Vec<String> = candidates
+                .iter()
+                .map(|p| {
+                    format!(
+                        "{}#{}:{}",
+                        p.pkg_name,
+                        p.pkg_id.as_deref().unwrap_or("-"),
+                        p.repo_name
+                    )
+                })
+                .collect();
+            return Err(SoarError::Custom(format!(
+                "{package} matches several installed packages: {}",
+                names.join(", ")
+            )));
+        }
+    };
+
+    let from = PathBuf::from(&installed.installed_path);
+    let to = soar_core::package::migrate::migrate_package(diesel_db, ctx.config(), &installed, to)?;
+
+    Ok(MigratedPackage {
+        package: installed,
+        from,
+        to,
+    })
+}
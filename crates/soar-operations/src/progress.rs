@@ -46,12 +46,14 @@ pub fn create_progress_bridge(
             Progress::Chunk {
                 current,
                 total,
+                rate,
             } => {
                 SoarEvent::DownloadProgress {
                     op_id,
                     pkg_name: pkg_name.clone(),
                     current,
                     total,
+                    rate,
                 }
             }
             Progress::Complete {
@@ -125,6 +127,7 @@ mod tests {
         bridge(Progress::Chunk {
             current: 750,
             total: 1000,
+            rate: 250,
         });
         bridge(Progress::Complete {
             total: 1000,
@@ -166,6 +169,7 @@ mod tests {
             SoarEvent::DownloadProgress {
                 current: 750,
                 total: 1000,
+                rate: 250,
                 ..
             }
         ));
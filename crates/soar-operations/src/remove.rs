@@ -178,6 +178,8 @@ pub async fn perform_removal(
 
                 failed.push(FailedInfo {
                     pkg_name: pkg.pkg_name,
+                    repo_name: pkg.repo_name,
+                    version: pkg.version,
                     error: err.to_string(),
                 });
             }
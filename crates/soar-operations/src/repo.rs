@@ -16,10 +16,11 @@ pub struct RepoUpdate {
     pub desktop_integration: Option<bool>,
     pub signature_verification: Option<bool>,
     pub sync_interval: Option<String>,
+    pub min_release_age: Option<String>,
 }
 
 /// Loads a fresh config from disk, applies the mutation, validates, saves, and updates the global.
-fn modify_config(mutate: impl FnOnce(&mut Config) -> SoarResult<()>) -> SoarResult<()> {
+pub(crate) fn modify_config(mutate: impl FnOnce(&mut Config) -> SoarResult<()>) -> SoarResult<()> {
     let mut config = Config::new()?;
     mutate(&mut config)?;
     config.save()?;
@@ -75,6 +76,9 @@ impl SoarContext {
             if let Some(sync_interval) = update.sync_interval {
                 repo.sync_interval = Some(sync_interval);
             }
+            if let Some(min_release_age) = update.min_release_age {
+                repo.min_release_age = Some(min_release_age);
+            }
 
             config.resolve()?;
             Ok(())
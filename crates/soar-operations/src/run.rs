@@ -9,6 +9,7 @@ use soar_core::{
 };
 use soar_db::repository::metadata::MetadataRepository;
 use soar_dl::{download::Download, oci::OciDownload, types::OverwriteMode};
+use soar_package::formats::common::PortableDirs;
 use soar_utils::{
     hash::{calculate_checksum, hash_string},
     version::compare_versions,
@@ -23,13 +24,16 @@ use crate::{
 /// Resolve a package and download it to the cache if needed.
 ///
 /// Returns [`PrepareRunResult::Ready`] with the path to the cached binary,
-/// or [`PrepareRunResult::Ambiguous`] if multiple candidates match.
+/// or [`PrepareRunResult::Ambiguous`] if multiple candidates match. With
+/// `portable` set, the result also carries the directories to run it in,
+/// resolved as an install with `--portable` would resolve them.
 pub async fn prepare_run(
     ctx: &SoarContext,
     package_name: &str,
     repo_name: Option<&str>,
     pkg_id: Option<&str>,
     no_verify: bool,
+    portable: Option<&str>,
 ) -> SoarResult<PrepareRunResult> {
     debug!(package_name = package_name, "preparing run");
     let config = ctx.config();
@@ -128,6 +132,7 @@ pub async fn prepare_run(
     }
 
     let package = packages.into_iter().next().unwrap().resolve(version);
+    let portable = PortableDirs::resolve(&package, portable, None, None, None, None)?;
 
     // Named like an install. A package that published a checksum is keyed by
     // it, so identical content is shared and different content never is;
@@ -200,6 +205,7 @@ pub async fn prepare_run(
             return Ok(PrepareRunResult::Ready {
                 path: binary.clone(),
                 downloaded: false,
+                portable,
             });
         }
     }
@@ -214,6 +220,7 @@ pub async fn prepare_run(
                     return Ok(PrepareRunResult::Ready {
                         path: output_path,
                         downloaded: false,
+                        portable,
                     });
                 }
                 debug!(
@@ -226,6 +233,7 @@ pub async fn prepare_run(
                 return Ok(PrepareRunResult::Ready {
                     path: output_path,
                     downloaded: false,
+                    portable,
                 })
             }
         }
@@ -259,6 +267,7 @@ pub async fn prepare_run(
                 return Ok(PrepareRunResult::Ready {
                     path: binary,
                     downloaded: true,
+                    portable,
                 });
             }
         }
@@ -267,15 +276,25 @@ pub async fn prepare_run(
     Ok(PrepareRunResult::Ready {
         path: output_path,
         downloaded: true,
+        portable,
     })
 }
 
 /// Execute a binary with the given arguments.
-pub fn execute_binary(path: &Path, args: &[String]) -> SoarResult<RunResult> {
+///
+/// A run is never installed, so there is no link to put a launcher behind;
+/// the portable directories are handed to the process directly instead.
+pub fn execute_binary(
+    path: &Path,
+    args: &[String],
+    portable: &PortableDirs,
+) -> SoarResult<RunResult> {
     debug!(path = %path.display(), args = ?args, "executing binary");
 
+    portable.create()?;
     let status = Command::new(path)
         .args(args)
+        .envs(portable.env())
         .status()
         .with_context(|| format!("executing command {}", path.display()))?;
 
@@ -6,19 +6,24 @@ use nucleo_matcher::{
 };
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use soar_config::config::get_config;
-use soar_core::{database::models::Package, package::query::PackageQuery, SoarResult};
+use soar_core::{
+    database::{connection::MetadataManager, models::Package},
+    package::query::PackageQuery,
+    SoarResult,
+};
 use soar_db::{
     models::metadata::FuzzyCandidate,
     repository::{
         core::{CoreRepository, SortDirection},
         metadata::MetadataRepository,
+        unified::UnifiedRepository,
     },
 };
 use soar_utils::version::compare_versions;
 use tracing::{debug, trace};
 
 use crate::{
-    utils::{is_installed, InstalledIndex, NameCounts, PackageKey},
+    utils::{is_installed, offered_names, InstalledIndex, PackageKey},
     SearchEntry, SearchResult, SoarContext,
 };
 
@@ -44,18 +49,18 @@ pub async fn search_packages(
     let search_limit = limit.or(get_config().search_limit).unwrap_or(20);
 
     let packages: Vec<Package> = if case_sensitive {
-        let sql_limit = search_limit as i64;
-        metadata_mgr.query_all_flat(|repo_name, conn| {
-            let pkgs = MetadataRepository::search_case_sensitive(conn, query, Some(sql_limit))?;
-            Ok(pkgs
-                .into_iter()
-                .map(|p| {
-                    let mut pkg: Package = p.into();
-                    pkg.repo_name = repo_name.to_string();
-                    pkg
-                })
-                .collect())
-        })?
+        metadata_mgr
+            .query_unified_page(
+                0,
+                search_limit as i64,
+                |conn, offset, limit| {
+                    UnifiedRepository::search_case_sensitive(conn, query, offset, Some(limit))
+                },
+                UnifiedRepository::cmp_rows,
+            )?
+            .into_iter()
+            .map(Package::from)
+            .collect()
     } else {
         fuzzy_search(ctx, query, search_limit).await?
     };
@@ -87,7 +92,7 @@ pub async fn search_packages(
         }
     }
     // ranking order is the point of a search, so it is preserved
-    let packages: Vec<Package> = order
+    let mut packages: Vec<Package> = order
         .into_iter()
         .filter_map(|k| newest.remove(&k))
         .collect();
@@ -122,21 +127,15 @@ pub async fn search_packages(
             acc
         });
 
-    let offered: NameCounts = metadata_mgr
-        .query_all(|_repo_name, conn| MetadataRepository::count_names(conn))?
-        .into_iter()
-        .flat_map(|(repo_name, rows)| {
-            rows.into_iter().map(move |(pkg_name, offered)| {
-                ((repo_name.clone(), pkg_name), offered.max(0) as usize)
-            })
-        })
-        .collect();
+    let offered = offered_names(metadata_mgr)?;
 
     let total_count = packages.len();
 
+    packages.truncate(search_limit);
+    fill_maintainers(metadata_mgr, &mut packages);
+
     let entries: Vec<SearchEntry> = packages
         .into_iter()
-        .take(search_limit)
         .map(|package| {
             let installed = is_installed(
                 &installed_pkgs,
@@ -180,39 +179,20 @@ pub async fn search_packages(
 async fn fuzzy_search(ctx: &SoarContext, query: &str, limit: usize) -> SoarResult<Vec<Package>> {
     let metadata_mgr = ctx.metadata_manager().await?;
 
-    let candidates: Vec<(String, FuzzyCandidate)> =
-        metadata_mgr.query_all_flat(|repo_name, conn| {
-            let items = MetadataRepository::load_fuzzy_candidates(conn)?;
-            Ok(items
-                .into_iter()
-                .map(|c| (repo_name.to_string(), c))
-                .collect())
-        })?;
+    let candidates = metadata_mgr.query_unified(UnifiedRepository::load_fuzzy_candidates)?;
 
     let scored = score_candidates(query, &candidates);
     let top: Vec<_> = scored.into_iter().take(limit).collect();
 
-    let mut repo_ids: HashMap<&str, Vec<i32>> = HashMap::new();
-    for &(_, idx) in &top {
-        let (repo_name, candidate) = &candidates[idx];
-        repo_ids
-            .entry(repo_name.as_str())
-            .or_default()
-            .push(candidate.id);
-    }
+    let wanted: Vec<(&str, i32)> = top
+        .iter()
+        .map(|&(_, idx)| (candidates[idx].0.as_str(), candidates[idx].1.id))
+        .collect();
 
     let mut full_packages: HashMap<(String, i32), Package> = HashMap::new();
-    for (repo_name, ids) in &repo_ids {
-        if let Some(pkgs) =
-            metadata_mgr.query_repo(repo_name, |conn| MetadataRepository::find_by_ids(conn, ids))?
-        {
-            for p in pkgs {
-                let db_id = p.id;
-                let mut pkg: Package = p.into();
-                pkg.repo_name = repo_name.to_string();
-                full_packages.insert((repo_name.to_string(), db_id), pkg);
-            }
-        }
+    for pkg in metadata_mgr.query_unified(|conn| UnifiedRepository::find_by_ids(conn, &wanted))? {
+        let key = (pkg.repo_name.clone(), pkg.package.id);
+        full_packages.insert(key, pkg.into());
     }
 
     let packages: Vec<Package> = top
@@ -234,14 +214,7 @@ pub async fn suggest_similar(
 ) -> SoarResult<Vec<String>> {
     let metadata_mgr = ctx.metadata_manager().await?;
 
-    let candidates: Vec<(String, FuzzyCandidate)> =
-        metadata_mgr.query_all_flat(|repo_name, conn| {
-            let items = MetadataRepository::load_fuzzy_candidates(conn)?;
-            Ok(items
-                .into_iter()
-                .map(|c| (repo_name.to_string(), c))
-                .collect())
-        })?;
+    let candidates = metadata_mgr.query_unified(UnifiedRepository::load_fuzzy_candidates)?;
 
     let scored = score_candidates(query, &candidates);
 
@@ -328,25 +301,21 @@ pub async fn query_package(ctx: &SoarContext, query_str: &str) -> SoarResult<Vec
             })
             .collect()
     } else {
-        metadata_mgr.query_all_flat(|repo_name, conn| {
-            let pkgs = MetadataRepository::find_filtered(
-                conn,
-                query.name.as_deref(),
-                query.pkg_id.as_deref(),
-                query.family.as_deref(),
-                None,
-                None,
-                Some(SortDirection::Asc),
-            )?;
-            Ok(pkgs
-                .into_iter()
-                .map(|p| {
-                    let mut pkg: Package = p.into();
-                    pkg.repo_name = repo_name.to_string();
-                    pkg
-                })
-                .collect())
-        })?
+        metadata_mgr
+            .query_unified_merged(
+                |conn| {
+                    UnifiedRepository::find_filtered(
+                        conn,
+                        query.name.as_deref(),
+                        query.pkg_id.as_deref(),
+                        query.family.as_deref(),
+                    )
+                },
+                UnifiedRepository::cmp_rows,
+            )?
+            .into_iter()
+            .map(Package::from)
+            .collect()
     };
 
     let mut packages: Vec<Package> = if let Some(ref version) = query.version {
@@ -359,8 +328,25 @@ pub async fn query_package(ctx: &SoarContext, query_str: &str) -> SoarResult<Vec
         packages
     };
 
-    // Maintainers live in their own table, so they take a second query.
-    for package in &mut packages {
+    fill_maintainers(metadata_mgr, &mut packages);
+
+    // The query is ordered by name, which says nothing about several versions
+    // of one package. Newest first, so the one that would be installed is on
+    // top.
+    packages.sort_by(|a, b| {
+        a.pkg_name
+            .cmp(&b.pkg_name)
+            .then(a.repo_name.cmp(&b.repo_name))
+            .then(compare_versions(&b.version, &a.version))
+    });
+
+    Ok(packages)
+}
+
+/// Maintainers live in their own table, so they take a query per package.
+/// Callers trim the list to what is shown first.
+fn fill_maintainers(metadata_mgr: &MetadataManager, packages: &mut [Package]) {
+    for package in packages {
         let found = metadata_mgr.query_repo(&package.repo_name, |conn| {
             MetadataRepository::get_maintainers(conn, package.id as i32)
         });
@@ -381,16 +367,55 @@ pub async fn query_package(ctx: &SoarContext, query_str: &str) -> SoarResult<Vec
             }
         }
     }
+}
 
-    // The query is ordered by name, which says nothing about several versions
-    // of one package. Newest first, so the one that would be installed is on
-    // top.
-    packages.sort_by(|a, b| {
-        a.pkg_name
-            .cmp(&b.pkg_name)
-            .then(a.repo_name.cmp(&b.repo_name))
-            .then(compare_versions(&b.version, &a.version))
-    });
+#[cfg(test)]
+mod tests {
+    use soar_db::{connection::DbConnection, migration::DbType, models::metadata::NewPackage};
+
+    use super::*;
+
+    #[test]
+    fn search_results_carry_their_maintainers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metadata.db");
+        let mut db = DbConnection::open(&path, DbType::Metadata).unwrap();
+        let conn = db.conn();
+        let mut ids = Vec::new();
+        for name in ["kept", "orphan"] {
+            let package = NewPackage {
+                pkg_name: name,
+                version: "1.0",
+                download_url: "https://example.com/pkg",
+                ..Default::default()
+            };
+            MetadataRepository::insert(conn, &package).unwrap();
+            ids.push(MetadataRepository::last_insert_id(conn).unwrap());
+        }
+        let maintainer =
+            MetadataRepository::find_or_create_maintainer(conn, "ada@example.com", "Ada").unwrap();
+        MetadataRepository::link_maintainer(conn, ids[0], maintainer).unwrap();
 
-    Ok(packages)
+        let mut mgr = MetadataManager::new();
+        mgr.add_repo("main", &path).unwrap();
+
+        let mut packages: Vec<Package> = mgr
+            .query_unified(|conn| UnifiedRepository::list_paginated(conn, 0, 10))
+            .unwrap()
+            .into_iter()
+            .map(Package::from)
+            .collect();
+        fill_maintainers(&mgr, &mut packages);
+
+        let kept = packages.iter().find(|p| p.pkg_name == "kept").unwrap();
+        let names: Vec<_> = kept
+            .maintainers
+            .iter()
+            .flatten()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(names, ["Ada"]);
+        let orphan = packages.iter().find(|p| p.pkg_name == "orphan").unwrap();
+        assert!(orphan.maintainers.is_none());
+    }
 }
@@ -5,15 +5,24 @@ use soar_core::{
     error::SoarError,
     SoarResult,
 };
-use soar_db::repository::{
-    core::{CoreRepository, SortDirection},
-    metadata::MetadataRepository,
+use soar_db::{
+    models::core::FileKind,
+    repository::{
+        core::{CoreRepository, SortDirection},
+        metadata::MetadataRepository,
+    },
+};
+use soar_package::{
+    formats::common::{setup_portable_dir, PortableDirs},
+    integrate_package,
 };
-use soar_package::{formats::common::setup_portable_dir, integrate_package};
 use tracing::debug;
 
 use crate::{
-    utils::{has_desktop_integration, mangle_package_symlinks},
+    utils::{
+        has_desktop_integration, link_extracted_images, link_portable_launchers,
+        mangle_package_symlinks, placed_files,
+    },
     SoarContext, VariantInfo,
 };
 
@@ -83,6 +92,7 @@ pub async fn switch_variant(
 
     let pkg_name = &selected_package.pkg_name;
     let pkg_id = selected_package.pkg_id.as_deref();
+    let package_id = selected_package.id;
 
     // Atomically unlink other variants and link the selected one so the DB
     // is never left in a state where all variants are unlinked.
@@ -177,5 +187,31 @@ pub async fn switch_variant(
         )?;
     }
 
+    let symlinks = link_extracted_images(&install_dir, symlinks, false)?;
+    let portable_dirs = if has_portable {
+        let portable_dirs = PortableDirs::resolve(
+            &installed_pkg,
+            installed_pkg.portable_path.as_deref(),
+            installed_pkg.portable_home.as_deref(),
+            installed_pkg.portable_config.as_deref(),
+            installed_pkg.portable_share.as_deref(),
+            installed_pkg.portable_cache.as_deref(),
+        )?;
+        link_portable_launchers(&symlinks, &portable_dirs)?;
+        portable_dirs
+    } else {
+        PortableDirs::default()
+    };
+
+    // Switching leaves man pages and completions where they are, so the ones
+    // already recorded are carried over with what was just relinked.
+    let shared = diesel_db
+        .with_conn(|conn| CoreRepository::list_files(conn, package_id))?
+        .into_iter()
+        .filter(|file| file.kind == FileKind::Shared)
+        .map(|file| PathBuf::from(file.path));
+    let files = placed_files(&install_dir, &symlinks, shared, &portable_dirs, config)?;
+    diesel_db.with_conn(|conn| CoreRepository::replace_files(conn, package_id, &files))?;
+
     Ok(())
 }
@@ -1,14 +1,23 @@
 use std::path::PathBuf;
 
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use soar_core::{
     database::models::{InstalledPackage, Package},
-    package::install::InstallTarget,
+    package::{changelog::ReleaseNotes, install::InstallTarget},
 };
+use soar_db::models::core::{FileKind, InstalledFile};
+use soar_dl::checksum::PublishedChecksums;
+use soar_package::formats::common::PortableDirs;
 
 // ---- Install ----
 
 /// Options for an install operation.
-#[derive(Debug, Default)]
+///
+/// Serialized into the operation journal, so `soar resume` finishes a package
+/// the way it was asked for.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct InstallOptions {
     pub force: bool,
     pub portable: Option<String>,
@@ -18,6 +27,8 @@ pub struct InstallOptions {
     pub portable_cache: Option<String>,
     pub binary_only: bool,
     pub no_verify: bool,
+    /// Unpack AppImages and kin to run without FUSE, even where it works.
+    pub extract: bool,
     pub name_override: Option<String>,
     pub version_override: Option<String>,
     pub pkg_type_override: Option<String>,
@@ -72,6 +83,8 @@ pub struct InstalledInfo {
 #[derive(Debug)]
 pub struct FailedInfo {
     pub pkg_name: String,
+    pub repo_name: String,
+    pub version: String,
     pub error: String,
 }
 
@@ -98,6 +111,27 @@ pub struct RemovedInfo {
     pub version: String,
 }
 
+// ---- Resume ----
+
+/// What resuming the operations a dead process left behind did.
+#[derive(Default)]
+pub struct ResumeReport {
+    pub finished: Vec<InstalledInfo>,
+    /// Recorded as installed before the process died, so nothing was left.
+    pub already_complete: Vec<String>,
+    pub rolled_back: Vec<RolledBackInfo>,
+    pub failed: Vec<FailedInfo>,
+}
+
+/// A package put back the way it was before its install started.
+pub struct RolledBackInfo {
+    pub pkg_name: String,
+    pub repo_name: String,
+    pub version: String,
+    /// The step it was interrupted at.
+    pub step: String,
+}
+
 // ---- Update ----
 
 pub struct UpdateInfo {
@@ -107,6 +141,50 @@ pub struct UpdateInfo {
     pub new_version: String,
     pub target: InstallTarget,
     pub update_toml_url: Option<String>,
+    /// Where to look for the checksum upstream publishes for the new
+    /// artifact, when its metadata does not carry one.
+    pub checksum: Option<ChecksumLookup>,
+}
+
+/// Where an update's published checksum is looked for.
+///
+/// Finding one can take a request per checksum file, which is worth paying
+/// for an update being applied but not for one only being reported, so it
+/// waits for [`perform_update`](crate::update::perform_update).
+pub enum ChecksumLookup {
+    /// Among what the new version's release publishes.
+    Release(PublishedChecksums),
+    /// Beside the artifact, which is served on its own.
+    Beside,
+}
+
+/// A newer version held back until it has been out for the minimum release age.
+pub struct DeferredUpdate {
+    pub pkg_name: String,
+    pub repo_name: String,
+    pub current_version: String,
+    pub new_version: String,
+    pub eligible_at: DateTime<Utc>,
+}
+
+/// Release notes gathered for one package.
+pub struct PackageChangelog {
+    pub pkg_name: String,
+    pub repo_name: String,
+    /// The installed version the notes start after, if any.
+    pub from_version: Option<String>,
+    pub to_version: String,
+    /// Newest first; empty when no source had any.
+    pub notes: Vec<ReleaseNotes>,
+}
+
+/// What a check for updates found.
+#[derive(Default)]
+pub struct UpdateCheck {
+    /// Updates ready to be performed.
+    pub updates: Vec<UpdateInfo>,
+    /// Newer versions the minimum release age is still holding back.
+    pub deferred: Vec<DeferredUpdate>,
 }
 
 pub struct UpdateReport {
@@ -173,6 +251,8 @@ pub struct HealthReport {
     pub man_path_configured: bool,
     pub broken_packages: Vec<BrokenPackage>,
     pub broken_symlinks: Vec<PathBuf>,
+    /// Links to AppImages and kin that cannot mount themselves here.
+    pub needs_fuse: Vec<PathBuf>,
 }
 
 pub struct BrokenPackage {
@@ -219,7 +299,12 @@ pub struct ApplyReport {
 // ---- Run ----
 
 pub enum PrepareRunResult {
-    Ready { path: PathBuf, downloaded: bool },
+    Ready {
+        path: PathBuf,
+        downloaded: bool,
+        /// Where the run keeps its state, empty unless asked to be portable.
+        portable: PortableDirs,
+    },
     Ambiguous(AmbiguousPackage),
 }
 
@@ -233,3 +318,39 @@ pub struct VariantInfo {
     pub package: InstalledPackage,
     pub is_active: bool,
 }
+
+// ---- Commands ----
+
+/// A package that provides a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandProvider {
+    pub pkg_name: String,
+    pub pkg_family: Option<String>,
+    pub repo_name: String,
+}
+
+impl CommandProvider {
+    /// The query that names exactly this package, for `soar install`.
+    pub fn query(&self) -> String {
+        match &self.pkg_family {
+            Some(family) => format!("{family}/{}:{}", self.pkg_name, self.repo_name),
+            None => format!("{}:{}", self.pkg_name, self.repo_name),
+        }
+    }
+}
+
+// ---- Files ----
+
+/// An installed package and the files it placed outside its install directory.
+pub struct PackageFiles {
+    pub package: InstalledPackage,
+    pub files: Vec<InstalledFile>,
+}
+
+/// An installed package a path belongs to.
+pub struct FileOwner {
+    pub package: InstalledPackage,
+    /// How the package placed the path, or `None` when the path lies inside
+    /// its install directory.
+    pub kind: Option<FileKind>,
+}
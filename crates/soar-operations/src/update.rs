@@ -1,19 +1,23 @@
 use std::{collections::HashSet, path::Path};
 
+use chrono::{DateTime, Utc};
 use soar_config::packages::{PackagesConfig, ResolvedPackage};
 use soar_core::{
     database::{
         connection::DieselDatabase,
         models::{InstalledPackage, Package},
     },
+    error::SoarError,
     package::{
         install::{InstallTarget, ZsyncSeed},
         local::LocalPackage,
         query::PackageQuery,
-        release_source::{run_version_command, ReleaseSource},
+        release_age::held_until,
+        release_source::ReleaseSource,
         update::remove_old_versions,
         update_info::{self, UpdateInfo as ArtifactUpdateInfo},
         url::UrlPackage,
+        version_source::VersionSource,
     },
     utils::substitute_placeholders,
     SoarResult,
@@ -24,21 +28,29 @@ use soar_db::repository::{
 };
 use soar_dl::zsync;
 use soar_events::{SoarEvent, UpdateCheckStatus, UpdateCleanupStage};
+use soar_utils::version::{is_constraint, is_newer};
 use tracing::{debug, warn};
 
 use crate::{
-    install::perform_installation, progress::next_op_id, utils::installed_from_source,
-    InstallOptions, SoarContext, UpdateInfo, UpdateReport, UrlUpdateInfo,
+    install::perform_installation,
+    progress::next_op_id,
+    utils::{
+        installed_from_source, min_release_age, newest_aged, newest_matching, published_checksum,
+    },
+    ChecksumLookup, DeferredUpdate, InstallOptions, SoarContext, UpdateCheck, UpdateInfo,
+    UpdateReport, UrlUpdateInfo,
 };
 
 /// Check for available updates.
 ///
 /// If `packages` is `Some`, only checks the specified packages.
 /// If `None`, checks all updatable packages.
+/// Newer versions a minimum release age holds back are reported as deferred
+/// rather than offered.
 pub async fn check_updates(
     ctx: &SoarContext,
     packages: Option<&[String]>,
-) -> SoarResult<Vec<UpdateInfo>> {
+) -> SoarResult<UpdateCheck> {
     debug!("checking for updates");
     let metadata_mgr = ctx.metadata_manager().await?;
     let diesel_db = ctx.diesel_core_db()?.clone();
@@ -50,6 +62,7 @@ pub async fn check_updates(
         .unwrap_or_default();
 
     let mut updates = Vec::new();
+    let mut deferred = Vec::new();
 
     if let Some(packages) = packages {
         for package in packages {
@@ -57,7 +70,9 @@ pub async fn check_updates(
             // as by the name derived from it.
             if UrlPackage::is_remote(package) || LocalPackage::is_local(package) {
                 for pkg in installed_from_source(&diesel_db, package)? {
-                    if let Some(update_info) = check_local_update(&pkg, &resolved_packages, ctx)? {
+                    if let Some(update_info) =
+                        check_local_update(&pkg, &resolved_packages, ctx, &mut deferred)?
+                    {
                         updates.push(update_info);
                     }
                 }
@@ -86,13 +101,22 @@ pub async fn check_updates(
 
             for pkg in installed_pkgs {
                 if pkg.repo_name == "local" {
-                    if let Some(update_info) = check_local_update(&pkg, &resolved_packages, ctx)? {
+                    if let Some(update_info) =
+                        check_local_update(&pkg, &resolved_packages, ctx, &mut deferred)?
+                    {
                         updates.push(update_info);
                     }
                     continue;
                 }
 
-                if let Some(update_info) = check_repo_update(&pkg, metadata_mgr, &diesel_db, ctx)? {
+                if let Some(update_info) = check_repo_update(
+                    &pkg,
+                    &resolved_packages,
+                    metadata_mgr,
+                    &diesel_db,
+                    ctx,
+                    &mut deferred,
+                )? {
                     updates.push(update_info);
                 }
             }
@@ -124,7 +148,9 @@ pub async fn check_updates(
             .collect();
 
         for pkg in local_packages {
-            if let Some(update_info) = check_local_update(&pkg, &resolved_packages, ctx)? {
+            if let Some(update_info) =
+                check_local_update(&pkg, &resolved_packages, ctx, &mut deferred)?
+            {
                 updates.push(update_info);
             }
         }
@@ -134,39 +160,179 @@ pub async fn check_updates(
                 continue;
             }
 
-            if let Some(update_info) = check_repo_update(&pkg, metadata_mgr, &diesel_db, ctx)? {
+            if let Some(update_info) = check_repo_update(
+                &pkg,
+                &resolved_packages,
+                metadata_mgr,
+                &diesel_db,
+                ctx,
+                &mut deferred,
+            )? {
                 updates.push(update_info);
             }
         }
     }
 
-    Ok(updates)
+    Ok(UpdateCheck {
+        updates,
+        deferred,
+    })
 }
 
-fn check_repo_update(
+/// The packages.toml declaration that speaks for an installed package, if any.
+///
+/// A declaration narrowed by repository or family only speaks for packages
+/// that match it.
+pub(crate) fn declaration<'a>(
     pkg: &InstalledPackage,
-    metadata_mgr: &soar_core::database::connection::MetadataManager,
-    diesel_db: &DieselDatabase,
+    resolved_packages: &'a [ResolvedPackage],
+) -> Option<&'a ResolvedPackage> {
+    resolved_packages.iter().find(|r| {
+        r.name == pkg.pkg_name
+            && r.repo.as_deref().is_none_or(|repo| repo == pkg.repo_name)
+            && r.family
+                .as_deref()
+                .is_none_or(|f| Some(f) == pkg.pkg_family.as_deref())
+    })
+}
+
+/// Report a newer version the minimum release age is holding back.
+fn defer(
     ctx: &SoarContext,
-) -> SoarResult<Option<UpdateInfo>> {
-    let new_pkg: Option<Package> = metadata_mgr
+    pkg: &InstalledPackage,
+    new_version: &str,
+    eligible_at: DateTime<Utc>,
+    deferred: &mut Vec<DeferredUpdate>,
+) {
+    debug!(
+        pkg_name = pkg.pkg_name,
+        new_version = new_version,
+        "update deferred until {eligible_at}"
+    );
+    ctx.events().emit(SoarEvent::UpdateCheck {
+        pkg_name: pkg.pkg_name.clone(),
+        status: UpdateCheckStatus::Deferred {
+            current_version: pkg.version.clone(),
+            new_version: new_version.to_string(),
+            eligible_at: eligible_at.to_rfc3339(),
+        },
+    });
+    deferred.push(DeferredUpdate {
+        pkg_name: pkg.pkg_name.clone(),
+        repo_name: pkg.repo_name.clone(),
+        current_version: pkg.version.clone(),
+        new_version: new_version.to_string(),
+        eligible_at,
+    });
+}
+
+/// Every version of an installed package its repository carries.
+fn repo_candidates(
+    pkg: &InstalledPackage,
+    metadata_mgr: &soar_core::database::connection::MetadataManager,
+) -> SoarResult<Vec<Package>> {
+    Ok(metadata_mgr
         .query_repo(&pkg.repo_name, |conn| {
-            MetadataRepository::find_newer_version(
+            MetadataRepository::find_filtered(
                 conn,
-                &pkg.pkg_name,
+                Some(&pkg.pkg_name),
                 pkg.pkg_id.as_deref(),
                 pkg.pkg_family.as_deref(),
-                &pkg.version,
-                pkg.checksum.as_deref(),
+                None,
+                None,
+                None,
             )
         })?
-        .flatten()
-        .map(|p| {
-            let package: Package = p.into();
-            let mut package = package.resolve(None);
-            package.repo_name = pkg.repo_name.clone();
-            package
+        .unwrap_or_default()
+        .into_iter()
+        .map(Into::into)
+        .collect())
+}
+
+fn check_repo_update(
+    pkg: &InstalledPackage,
+    resolved_packages: &[ResolvedPackage],
+    metadata_mgr: &soar_core::database::connection::MetadataManager,
+    diesel_db: &DieselDatabase,
+    ctx: &SoarContext,
+    deferred: &mut Vec<DeferredUpdate>,
+) -> SoarResult<Option<UpdateInfo>> {
+    let declared = declaration(pkg, resolved_packages);
+    let policy = declared
+        .and_then(|d| d.version_req())
+        .transpose()
+        .map_err(SoarError::from)
+        .and_then(|req| {
+            Ok((
+                req,
+                min_release_age(ctx.config(), declared, &pkg.repo_name)?,
+            ))
         });
+    let (req, min_age) = match policy {
+        Ok(policy) => policy,
+        Err(e) => {
+            warn!("Not updating {}: {}", pkg.pkg_name, e);
+            ctx.events().emit(SoarEvent::UpdateCheck {
+                pkg_name: pkg.pkg_name.clone(),
+                status: UpdateCheckStatus::Skipped {
+                    reason: e.to_string(),
+                },
+            });
+            return Ok(None);
+        }
+    };
+
+    let new_pkg: Option<Package> = if let Some(ref req) = req {
+        // Only what the constraint accepts is an update, so the newest of
+        // those is the candidate, and it has to be newer than what is here.
+        newest_matching(req, repo_candidates(pkg, metadata_mgr)?)
+            .filter(|p| is_newer(&p.version, &pkg.version))
+    } else {
+        metadata_mgr
+            .query_repo(&pkg.repo_name, |conn| {
+                MetadataRepository::find_newer_version(
+                    conn,
+                    &pkg.pkg_name,
+                    pkg.pkg_id.as_deref(),
+                    pkg.pkg_family.as_deref(),
+                    &pkg.version,
+                    pkg.checksum.as_deref(),
+                )
+            })?
+            .flatten()
+            .map(|p| {
+                let package: Package = p.into();
+                package.resolve(None)
+            })
+    };
+
+    // The newest version being too recent leaves whatever came before it,
+    // if anything between it and the installed one has aged by now.
+    let new_pkg = match new_pkg {
+        Some(package) => {
+            match held_until(package.build_date.as_deref(), min_age, Utc::now()) {
+                None => Some(package),
+                Some(eligible_at) => {
+                    let older = newest_aged(
+                        req.as_ref(),
+                        min_age,
+                        Some(&pkg.version),
+                        repo_candidates(pkg, metadata_mgr)?,
+                    );
+                    if older.is_none() {
+                        defer(ctx, pkg, &package.version, eligible_at, deferred);
+                        return Ok(None);
+                    }
+                    older
+                }
+            }
+        }
+        None => None,
+    }
+    .map(|mut package| {
+        package.repo_name = pkg.repo_name.clone();
+        package
+    });
 
     let Some(package) = new_pkg else {
         ctx.events().emit(SoarEvent::UpdateCheck {
@@ -212,6 +378,7 @@ fn check_repo_update(
             ..Default::default()
         },
         update_toml_url: None,
+        checksum: None,
     }))
 }
 
@@ -219,6 +386,7 @@ fn check_local_update(
     pkg: &InstalledPackage,
     resolved_packages: &[ResolvedPackage],
     ctx: &SoarContext,
+    deferred: &mut Vec<DeferredUpdate>,
 ) -> SoarResult<Option<UpdateInfo>> {
     // A declaration that names a family only speaks for that family, so a
     // package of the same name from another one is not updated by it.
@@ -255,14 +423,20 @@ fn check_local_update(
         return Ok(None);
     }
 
-    let is_github_or_gitlab = resolved.github.is_some() || resolved.gitlab.is_some();
+    let has_release_source = resolved.has_release_source();
 
-    let (version, download_url, size, update_toml_url) =
-        if let Some(ref cmd) = resolved.version_command {
-            let result = match run_version_command(cmd) {
+    let (version, download_url, size, update_toml_url, checksums) =
+        if let Some(source) = VersionSource::from_resolved(resolved) {
+            let req = resolved.version_req().and_then(Result::ok);
+            let result = match source.resolve(req.as_ref()) {
                 Ok(r) => r,
                 Err(e) => {
-                    warn!("Failed to run version_command for {}: {}", pkg.pkg_name, e);
+                    warn!(
+                        "Failed to run {} for {}: {}",
+                        source.field(),
+                        pkg.pkg_name,
+                        e
+                    );
                     return Ok(None);
                 }
             };
@@ -284,10 +458,32 @@ fn check_local_update(
                 return Ok(None);
             }
 
+            // The source settles on one version, which a constraint either
+            // accepts or holds back.
+            if let Some(req) = resolved.version_req() {
+                let skipped = match req.map(|req| (req.matches(&v), req)) {
+                    Ok((Ok(true), _)) => None,
+                    Ok((Ok(false), req)) => Some(format!("{v} is outside '{req}'")),
+                    Ok((Err(e), _)) | Err(e) => {
+                        warn!("Not updating {}: {}", pkg.pkg_name, e);
+                        Some(e.to_string())
+                    }
+                };
+                if let Some(reason) = skipped {
+                    ctx.events().emit(SoarEvent::UpdateCheck {
+                        pkg_name: pkg.pkg_name.clone(),
+                        status: UpdateCheckStatus::Skipped {
+                            reason,
+                        },
+                    });
+                    return Ok(None);
+                }
+            }
+
             let (url, should_update_toml_url) = match result.download_url {
                 Some(url) => (url, true),
                 None => {
-                    match &resolved.url {
+                    match resolved.url_template.as_ref().or(resolved.url.as_ref()) {
                         Some(url) => {
                             (
                                 substitute_placeholders(url, Some(&v), resolved.arch_map.as_ref()),
@@ -296,7 +492,8 @@ fn check_local_update(
                         }
                         None => {
                             warn!(
-                            "version_command returned no URL and no url field configured for {}",
+                            "{} returned no URL and no url_template or url is configured for {}",
+                            source.field(),
                             pkg.pkg_name
                         );
                             return Ok(None);
@@ -305,12 +502,12 @@ fn check_local_update(
                 }
             };
 
-            let toml_url = if is_github_or_gitlab || !should_update_toml_url {
+            let toml_url = if has_release_source || !should_update_toml_url {
                 None
             } else {
                 Some(url.clone())
             };
-            (v, url, result.size, toml_url)
+            (v, url, result.size, toml_url, None)
         } else {
             let release_source = match ReleaseSource::from_resolved(resolved) {
                 Some(s) => s,
@@ -319,7 +516,10 @@ fn check_local_update(
                     return Ok(None);
                 }
             };
-            let release = match release_source.resolve() {
+            // An exact version is where `apply` puts a package, not a limit on
+            // updating it; a constraint is what updates stay within.
+            let constraint = resolved.version.as_deref().filter(|v| is_constraint(v));
+            let release = match release_source.resolve_version(constraint) {
                 Ok(r) => r,
                 Err(e) => {
                     warn!("Failed to check for updates for {}: {}", pkg.pkg_name, e);
@@ -334,7 +534,20 @@ fn check_local_update(
                 .to_string();
 
             let installed_version = pkg.version.strip_prefix('v').unwrap_or(&pkg.version);
-            if v == installed_version {
+            // A release picked in place of a newer, too recent one can be the
+            // installed one or older, which is no update.
+            let held = release
+                .deferred
+                .as_ref()
+                .filter(|_| v == installed_version || !is_newer(&v, installed_version));
+            if let Some(held) = held {
+                let newer = held.version.strip_prefix('v').unwrap_or(&held.version);
+                if newer != installed_version {
+                    defer(ctx, pkg, newer, held.eligible_at, deferred);
+                    return Ok(None);
+                }
+            }
+            if v == installed_version || held.is_some() {
                 ctx.events().emit(SoarEvent::UpdateCheck {
                     pkg_name: pkg.pkg_name.clone(),
                     status: UpdateCheckStatus::UpToDate {
@@ -344,12 +557,18 @@ fn check_local_update(
                 return Ok(None);
             }
 
-            let url = if is_github_or_gitlab {
+            let url = if has_release_source {
                 None
             } else {
                 Some(release.download_url.clone())
             };
-            (v, release.download_url, release.size, url)
+            (
+                v,
+                release.download_url,
+                release.size,
+                url,
+                Some(release.checksums),
+            )
         };
 
     let mut updated_url_pkg = UrlPackage::from_remote(
@@ -360,6 +579,7 @@ fn check_local_update(
         pkg.pkg_id.as_deref(),
     )?;
     updated_url_pkg.size = size;
+    let package = updated_url_pkg.to_package();
 
     ctx.events().emit(SoarEvent::UpdateCheck {
         pkg_name: pkg.pkg_name.clone(),
@@ -370,7 +590,7 @@ fn check_local_update(
     });
 
     let target = InstallTarget {
-        package: updated_url_pkg.to_package(),
+        package,
         existing_install: Some(pkg.clone()),
         pinned: resolved.pinned,
         profile: resolved.profile.clone(),
@@ -388,6 +608,7 @@ fn check_local_update(
         build: resolved.build.clone(),
         sandbox: resolved.sandbox.clone(),
         arch_map: resolved.arch_map.clone(),
+        verify: resolved.verify.clone(),
     };
 
     Ok(Some(UpdateInfo {
@@ -397,6 +618,7 @@ fn check_local_update(
         new_version: version,
         target,
         update_toml_url,
+        checksum: Some(checksums.map_or(ChecksumLookup::Beside, ChecksumLookup::Release)),
     }))
 }
 
@@ -491,6 +713,7 @@ fn check_release(
         pkg.pkg_id.as_deref(),
     )?;
     updated.size = release.size;
+    let package = updated.to_package();
 
     // A release that publishes a feed beside the artifact can be fetched as a
     // delta against the copy already installed.
@@ -508,7 +731,7 @@ fn check_release(
         current_version: pkg.version.clone(),
         new_version,
         target: InstallTarget {
-            package: updated.to_package(),
+            package,
             existing_install: Some(pkg.clone()),
             pinned: pkg.pinned,
             profile: Some(pkg.profile.clone()),
@@ -521,6 +744,7 @@ fn check_release(
             ..Default::default()
         },
         update_toml_url: None,
+        checksum: Some(ChecksumLookup::Release(release.checksums)),
     }))
 }
 
@@ -617,11 +841,14 @@ fn check_update_feed(pkg: &InstalledPackage, ctx: &SoarContext) -> SoarResult<Fe
             ..Default::default()
         },
         update_toml_url: None,
+        checksum: None,
     })))
 }
 
 fn has_update_source(resolved: &ResolvedPackage) -> bool {
-    resolved.version_command.is_some() || resolved.github.is_some() || resolved.gitlab.is_some()
+    resolved.version_command.is_some()
+        || resolved.version_url.is_some()
+        || resolved.has_release_source()
 }
 
 fn get_existing(
@@ -682,7 +909,22 @@ pub async fn perform_update(
         })
         .collect();
 
-    let targets: Vec<InstallTarget> = updates.into_iter().map(|u| u.target).collect();
+    let targets: Vec<InstallTarget> = updates
+        .into_iter()
+        .map(|mut update| {
+            let package = &mut update.target.package;
+            match update.checksum {
+                Some(ChecksumLookup::Release(ref published)) => {
+                    package.bsum = published_checksum(&package.download_url, Some(published));
+                }
+                Some(ChecksumLookup::Beside) => {
+                    package.bsum = published_checksum(&package.download_url, None);
+                }
+                None => {}
+            }
+            update.target
+        })
+        .collect();
 
     let options = InstallOptions {
         no_verify,
@@ -5,25 +5,45 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use chrono::Utc;
 use soar_config::{
     config::Config,
-    packages::{BinaryMapping, PackageHooks, PackagesConfig, SandboxConfig},
+    packages::{BinaryMapping, PackageHooks, PackagesConfig, ResolvedPackage, SandboxConfig},
 };
 use soar_core::{
     database::{
-        connection::DieselDatabase,
+        connection::{DieselDatabase, MetadataManager},
         models::{InstalledPackage, Package},
     },
     error::{ErrorContext, SoarError},
-    package::{local::LocalPackage, url::UrlPackage},
+    package::{local::LocalPackage, release_age::held_until, url::UrlPackage},
     utils::{shared_link_targets, substitute_placeholders},
     SoarResult,
 };
 use soar_db::{
-    models::types::{PackageFile, PackageProvide},
-    repository::core::{CoreRepository, SortDirection},
+    models::{
+        core::FileKind,
+        types::{PackageFile, PackageProvide},
+    },
+    repository::{
+        core::{CoreRepository, SortDirection},
+        unified::UnifiedRepository,
+    },
+};
+use soar_dl::checksum::{self, PublishedChecksums};
+use soar_package::{
+    formats::{
+        appdir::{appdir_path, can_mount, extract_appdir, is_mountable, APP_RUN},
+        common::{handles_portable_dirs, integration_links, PortableDirs},
+    },
+    get_file_type, PackageFormat,
+};
+use soar_utils::{
+    fs::is_elf,
+    path::{in_target, on_host},
+    version::{compare_versions, is_newer, VersionReq},
+    wrapper::{is_env_name, render_wrapper, rewrap, wrap_with_env, write_wrapper},
 };
-use soar_utils::fs::is_elf;
 use tracing::{debug, warn};
 
 /// Check if a package should have desktop integration (desktop files, icons).
@@ -34,6 +54,111 @@ pub fn has_desktop_integration(package: &Package, config: &Config) -> bool {
     }
 }
 
+/// The newest release of `packages` a constraint accepts, resolved to that
+/// version.
+///
+/// Snapshots count as releases too, so a constraint can settle on an older
+/// version a repository still carries. Versions the constraint cannot be
+/// checked against are logged and passed over rather than taken.
+pub fn newest_matching(req: &VersionReq, packages: Vec<Package>) -> Option<Package> {
+    let candidates = packages.into_iter().flat_map(|pkg| {
+        let versions =
+            std::iter::once(pkg.version.clone()).chain(pkg.snapshots.clone().unwrap_or_default());
+        versions.map(move |version| (pkg.clone(), version))
+    });
+    let (newest, unordered) = req.newest(candidates, |(_, version)| version.as_str());
+    if !unordered.is_empty() {
+        warn!(
+            "skipped versions that '{}' cannot be checked against: {}",
+            req,
+            unordered.join(", ")
+        );
+    }
+    newest.map(|(pkg, version)| {
+        if version == pkg.version {
+            pkg.resolve(None)
+        } else {
+            pkg.resolve(Some(&version))
+        }
+    })
+}
+
+/// The minimum release age a package from `repo_name` waits out, in
+/// milliseconds: its own declaration's if it has one, else its repository's.
+pub fn min_release_age(
+    config: &Config,
+    declared: Option<&ResolvedPackage>,
+    repo_name: &str,
+) -> SoarResult<Option<u128>> {
+    if let Some(age) = declared.map(|d| d.min_release_age()).transpose()?.flatten() {
+        return Ok(Some(age));
+    }
+    Ok(config
+        .get_repository(repo_name)
+        .map(|repo| repo.min_release_age())
+        .transpose()?
+        .flatten())
+}
+
+/// The newest version of `packages` past `installed` that has been out for
+/// `min_age`, and that `req` accepts when there is one.
+///
+/// An index dates only the version a package is currently at. Its snapshots
+/// came before that, so they are taken as old enough rather than unknown.
+pub fn newest_aged(
+    req: Option<&VersionReq>,
+    min_age: Option<u128>,
+    installed: Option<&str>,
+    packages: Vec<Package>,
+) -> Option<Package> {
+    let now = Utc::now();
+    let candidates = packages
+        .into_iter()
+        .flat_map(|pkg| {
+            let head = (pkg.clone(), pkg.version.clone(), pkg.build_date.clone());
+            let snapshots = pkg.snapshots.clone().unwrap_or_default();
+            std::iter::once(head).chain(
+                snapshots
+                    .into_iter()
+                    .map(move |version| (pkg.clone(), version, None)),
+            )
+        })
+        .filter(|(_, version, date)| {
+            installed.is_none_or(|current| is_newer(version, current))
+                && held_until(date.as_deref(), min_age, now).is_none()
+        });
+    let newest = match req {
+        Some(req) => req.newest(candidates, |(_, version, _)| version.as_str()).0,
+        None => candidates.max_by(|a, b| compare_versions(&a.1, &b.1)),
+    };
+    newest.map(|(pkg, version, _)| {
+        if version == pkg.version {
+            pkg.resolve(None)
+        } else {
+            pkg.resolve(Some(&version))
+        }
+    })
+}
+
+/// The checksum upstream publishes for the artifact at `url`, as `bsum`
+/// holds it.
+///
+/// A release says what it publishes, and nothing more is looked for when it
+/// publishes nothing. A file served on its own can only have a sum beside it.
+/// Not finding one is not an error: the artifact is then taken as it always
+/// was, unverified.
+pub fn published_checksum(url: &str, release: Option<&PublishedChecksums>) -> Option<String> {
+    if !UrlPackage::is_url(url) {
+        return None;
+    }
+    let sum = match release {
+        Some(release) => release.resolve(),
+        None => checksum::find_beside(url),
+    };
+    debug!(url, checksum = ?sum, "looked for a published checksum");
+    sum.map(|sum| sum.to_string())
+}
+
 /// Look up hooks and sandbox configuration for a package from packages.toml.
 pub fn get_package_hooks(pkg_name: &str) -> (Option<PackageHooks>, Option<SandboxConfig>) {
     let config = match PackagesConfig::load(None) {
@@ -81,7 +206,7 @@ fn create_provide_symlinks(
                 std::fs::remove_file(&target_path)
                     .with_context(|| format!("removing provide {}", target_path.display()))?;
             }
-            unix::fs::symlink(&real_path, &target_path).with_context(|| {
+            unix::fs::symlink(in_target(&real_path), &target_path).with_context(|| {
                 format!(
                     "creating symlink {} -> {}",
                     real_path.display(),
@@ -128,7 +253,7 @@ pub fn link_shared_files(
             }
             match fs::read_link(&link) {
                 // ours, from this package or an older version of it
-                Ok(target) if target.starts_with(packages_root) => {
+                Ok(target) if on_host(&target).starts_with(packages_root) => {
                     fs::remove_file(&link).ok();
                 }
                 Ok(_) | Err(_) if link.exists() || link.is_symlink() => {
@@ -137,7 +262,7 @@ pub fn link_shared_files(
                 }
                 _ => {}
             }
-            if unix::fs::symlink(&source, &link).is_ok() {
+            if unix::fs::symlink(in_target(&source), &link).is_ok() {
                 linked.push((source, link));
             }
         }
@@ -166,6 +291,193 @@ fn walk_files(dir: &Path) -> Vec<PathBuf> {
     out
 }
 
+/// Link a mapped binary through an exec wrapper carrying the mapping's
+/// environment and arguments.
+///
+/// Variables are exported in name order, so reinstalling writes the same
+/// script byte for byte.
+fn write_binary_wrapper(
+    install_dir: &Path,
+    source_path: &Path,
+    link_path: &Path,
+    mapping: &BinaryMapping,
+) -> SoarResult<()> {
+    let mut env: Vec<(&str, &str)> = mapping
+        .env
+        .iter()
+        .flatten()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    env.sort();
+    if let Some((name, _)) = env.iter().find(|(name, _)| !is_env_name(name)) {
+        return Err(SoarError::Custom(format!(
+            "'{name}' is not a valid environment variable name for {}",
+            mapping.source
+        )));
+    }
+    let args: Vec<&str> = mapping.args.iter().flatten().map(String::as_str).collect();
+
+    let script = render_wrapper(source_path, install_dir, &env, &args);
+    write_wrapper(link_path, &script)?;
+    Ok(())
+}
+
+/// The format of the file at `path`, if it can be read.
+fn file_format(path: &Path) -> Option<PackageFormat> {
+    let file = fs::File::open(path).ok()?;
+    get_file_type(&mut std::io::BufReader::new(file)).ok()
+}
+
+/// Run each linked image from an unpacked copy where it cannot mount itself,
+/// or everywhere when `extract` asks for it.
+///
+/// The link becomes a launcher for the image's `AppRun`, with the variables
+/// the image runtime would have set added to whatever it already exported. An image unpacked by an earlier install
+/// stays unpacked, so relinking or updating it keeps the choice. Returns the
+/// links with each extracted source swapped for its `AppRun`, which is what
+/// they now run.
+pub fn link_extracted_images(
+    install_dir: &Path,
+    symlinks: Vec<(PathBuf, PathBuf)>,
+    extract: bool,
+) -> SoarResult<Vec<(PathBuf, PathBuf)>> {
+    let mut linked = Vec::with_capacity(symlinks.len());
+    for (source, link) in symlinks {
+        let Some(format) = file_format(&source).filter(is_mountable) else {
+            linked.push((source, link));
+            continue;
+        };
+
+        let appdir = appdir_path(&source);
+        let app_run = if appdir.join(APP_RUN).symlink_metadata().is_ok() {
+            appdir.join(APP_RUN)
+        } else if extract || !can_mount(&source, &format) {
+            match extract_appdir(&source) {
+                Ok(app_run) => app_run,
+                Err(err) => {
+                    warn!(
+                        image = %source.display(),
+                        "{err}; it is linked as it is and needs FUSE to run"
+                    );
+                    linked.push((source, link));
+                    continue;
+                }
+            }
+        } else {
+            linked.push((source, link));
+            continue;
+        };
+
+        let within = |path: &Path| {
+            path.strip_prefix(install_dir)
+                .map(|rel| format!("$INSTALL_DIR/{}", rel.display()))
+                .unwrap_or_else(|_| path.display().to_string())
+        };
+        let (appdir, image) = (within(&appdir), within(&source));
+        let env = [
+            ("APPDIR", appdir.as_str()),
+            ("APPIMAGE", image.as_str()),
+            ("ARGV0", "$0"),
+            ("OWD", "$PWD"),
+        ];
+        // A wrapper from the package's binary mapping keeps its environment
+        // and arguments; only what it runs moves to `AppRun`.
+        rewrap(&link, &app_run, install_dir, &env)?;
+        debug!(link = %link.display(), app_run = %app_run.display(), "linked extracted image");
+        linked.push((app_run, link));
+    }
+    Ok(linked)
+}
+
+/// Give each linked binary that cannot redirect its own state a launcher
+/// that points `HOME` and the XDG directories at `dirs`.
+///
+/// AppImages and their kin look for sidecar directories next to themselves,
+/// which integration already links. A plain binary only knows where to write
+/// from its environment, so its link becomes a wrapper that sets it.
+pub fn link_portable_launchers(
+    symlinks: &[(PathBuf, PathBuf)],
+    dirs: &PortableDirs,
+) -> SoarResult<()> {
+    if dirs.is_empty() {
+        return Ok(());
+    }
+
+    let env = dirs.env();
+
+    let mut created = false;
+    for (source, link) in symlinks {
+        if file_format(source)
+            .as_ref()
+            .is_some_and(handles_portable_dirs)
+        {
+            continue;
+        }
+        if !created {
+            dirs.create()?;
+            created = true;
+        }
+        wrap_with_env(link, source, &env)?;
+        debug!(link = %link.display(), "linked portable launcher");
+    }
+    Ok(())
+}
+
+/// Everything an install placed outside `install_dir`, for the package's
+/// file record.
+///
+/// `symlinks` are the bin links as linking returned them, and `shared` the
+/// man pages and completions linked. A command can be linked twice under one
+/// name, once as a symlink and again as the launcher that replaced it, and is
+/// recorded once.
+pub fn placed_files(
+    install_dir: &Path,
+    symlinks: &[(PathBuf, PathBuf)],
+    shared: impl IntoIterator<Item = PathBuf>,
+    portable_dirs: &PortableDirs,
+    config: &Config,
+) -> SoarResult<Vec<(PathBuf, FileKind)>> {
+    let integration = integration_links(install_dir, config)?;
+    let portable = [
+        &portable_dirs.home,
+        &portable_dirs.config,
+        &portable_dirs.share,
+        &portable_dirs.cache,
+    ];
+
+    let mut seen = HashSet::new();
+    Ok(symlinks
+        .iter()
+        .map(|(_, link)| (link.clone(), FileKind::Binary))
+        .chain(shared.into_iter().map(|link| (link, FileKind::Shared)))
+        .chain(
+            integration
+                .desktop
+                .into_iter()
+                .map(|path| (path, FileKind::Desktop)),
+        )
+        .chain(
+            integration
+                .icons
+                .into_iter()
+                .map(|path| (path, FileKind::Icon)),
+        )
+        .chain(
+            integration
+                .metainfo
+                .into_iter()
+                .map(|path| (path, FileKind::Metainfo)),
+        )
+        .chain(
+            portable
+                .into_iter()
+                .flatten()
+                .map(|dir| (dir.clone(), FileKind::Portable)),
+        )
+        .filter(|(path, _)| seen.insert(path.clone()))
+        .collect())
+}
+
 /// Creates symlinks from installed package binaries to the bin directory.
 #[allow(clippy::too_many_arguments)]
 pub async fn mangle_package_symlinks(
@@ -227,7 +539,7 @@ pub async fn mangle_package_symlinks(
                         format!("removing existing file/symlink at {}", link_path.display())
                     })?;
                 }
-                unix::fs::symlink(&source_path, &link_path)
+                unix::fs::symlink(in_target(&source_path), &link_path)
                     .with_context(|| format!("creating symlink {}", link_path.display()))?;
                 symlinks.push((source_path, link_path));
             }
@@ -325,13 +637,19 @@ pub async fn mangle_package_symlinks(
 
                     set_executable(&source_path)?;
 
+                    if mapping.wants_wrapper() {
+                        write_binary_wrapper(install_dir, &source_path, &link_path, mapping)?;
+                        symlinks.push((source_path, link_path));
+                        continue;
+                    }
+
                     if link_path.is_symlink() || link_path.is_file() {
                         std::fs::remove_file(&link_path).with_context(|| {
                             format!("removing existing file/symlink at {}", link_path.display())
                         })?;
                     }
 
-                    unix::fs::symlink(&source_path, &link_path).with_context(|| {
+                    unix::fs::symlink(in_target(&source_path), &link_path).with_context(|| {
                         format!(
                             "creating symlink {} -> {}",
                             source_path.display(),
@@ -370,7 +688,7 @@ pub async fn mangle_package_symlinks(
                     )
                 })?;
             }
-            unix::fs::symlink(&executable, &symlink_name).with_context(|| {
+            unix::fs::symlink(in_target(&executable), &symlink_name).with_context(|| {
                 format!(
                     "creating symlink {} -> {}",
                     executable.display(),
@@ -524,6 +842,16 @@ pub type InstalledIndex = HashMap<(String, String), Vec<(Option<String>, bool)>>
 /// How many packages a repository offers under each name.
 pub type NameCounts = HashMap<(String, String), usize>;
 
+/// What every repository offers under each name, counted across them in
+/// SQLite.
+pub fn offered_names(metadata_mgr: &MetadataManager) -> SoarResult<NameCounts> {
+    Ok(metadata_mgr
+        .query_unified(UnifiedRepository::count_names)?
+        .into_iter()
+        .map(|(repo_name, pkg_name, offered)| ((repo_name, pkg_name), offered.max(0) as usize))
+        .collect())
+}
+
 pub fn is_installed(
     map: &InstalledIndex,
     offered: &NameCounts,
@@ -618,13 +946,20 @@ mod tests {
     use std::{
         collections::HashMap,
         fs::{self, File},
+        os::unix,
         path::PathBuf,
     };
 
+    use soar_config::packages::BinaryMapping;
     use soar_db::models::types::PackageProvide;
+    use soar_package::formats::{appdir::appdir_path, APPIMAGE_MAGIC_BYTES};
     use tempfile::{tempdir, TempDir};
 
-    use super::{create_provide_symlinks, is_installed, InstalledIndex, NameCounts};
+    use super::{
+        create_provide_symlinks, is_installed, link_extracted_images, link_portable_launchers,
+        newest_aged, set_executable, write_binary_wrapper, InstalledIndex, NameCounts, Package,
+        PortableDirs,
+    };
 
     /// One installed package of `name`, recorded under `family`.
     fn installed_as(name: &str, family: Option<&str>, installed: bool) -> InstalledIndex {
@@ -821,4 +1156,146 @@ mod tests {
         assert!(created.is_empty());
         assert!(victim.symlink_metadata().unwrap().file_type().is_file());
     }
+
+    fn mapping(env: &[(&str, &str)], args: &[&str]) -> BinaryMapping {
+        BinaryMapping {
+            source: "bin/tool".into(),
+            link_as: None,
+            env: Some(
+                env.iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+            args: Some(args.iter().map(|a| a.to_string()).collect()),
+            wrapper: false,
+        }
+    }
+
+    #[test]
+    fn a_wrapped_binary_runs_with_its_environment_and_arguments() {
+        let (_root, install, bin) = setup();
+        let source = install.join("tool");
+        fs::write(&source, "#!/bin/sh\necho \"$TOOL_HOME|$*\"\n").unwrap();
+        set_executable(&source).unwrap();
+
+        let link = bin.join("tool");
+        let mapping = mapping(&[("TOOL_HOME", "$INSTALL_DIR/share")], &["--config", "a b"]);
+        write_binary_wrapper(&install, &source, &link, &mapping).unwrap();
+
+        let output = std::process::Command::new(&link)
+            .arg("run")
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            format!("{}/share|--config a b run", install.display())
+        );
+    }
+
+    #[test]
+    fn a_wrapper_refuses_a_variable_the_shell_cannot_export() {
+        let (_root, install, bin) = setup();
+        let mapping = mapping(&[("NOT-A-NAME", "1")], &[]);
+        let link = bin.join("tool");
+
+        assert!(write_binary_wrapper(&install, &install.join("tool"), &link, &mapping).is_err());
+        assert!(!link.exists());
+    }
+
+    #[test]
+    fn a_portable_binary_runs_with_its_own_home() {
+        let (root, install, bin) = setup();
+        let source = install.join("tool");
+        fs::write(&source, "#!/bin/sh\necho \"$HOME|$XDG_CONFIG_HOME\"\n").unwrap();
+        set_executable(&source).unwrap();
+        let link = bin.join("tool");
+        unix::fs::symlink(&source, &link).unwrap();
+
+        let dirs = PortableDirs {
+            home: Some(root.path().join("p/tool.home")),
+            config: Some(root.path().join("p/tool.config")),
+            ..Default::default()
+        };
+        link_portable_launchers(&[(source.clone(), link.clone())], &dirs).unwrap();
+
+        assert!(root.path().join("p/tool.home").is_dir());
+        let output = std::process::Command::new(&link).output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            format!(
+                "{}|{}",
+                root.path().join("p/tool.home").display(),
+                root.path().join("p/tool.config").display()
+            )
+        );
+    }
+
+    #[test]
+    fn an_extracted_image_keeps_the_environment_and_arguments_of_its_mapping() {
+        let (_root, install, bin) = setup();
+        let image = install.join("app.AppImage");
+        let mut header = vec![0u8; 64];
+        header[..4].copy_from_slice(b"\x7fELF");
+        header[8..12].copy_from_slice(&APPIMAGE_MAGIC_BYTES);
+        fs::write(&image, header).unwrap();
+        // unpacked by an earlier install, which is kept
+        let app_run = appdir_path(&image).join("AppRun");
+        fs::create_dir_all(app_run.parent().unwrap()).unwrap();
+        fs::write(
+            &app_run,
+            "#!/bin/sh\necho \"$APPIMAGE_EXTRACT_AND_RUN|$APPDIR|$*\"\n",
+        )
+        .unwrap();
+        set_executable(&app_run).unwrap();
+
+        let link = bin.join("app");
+        let mapping = mapping(&[("APPIMAGE_EXTRACT_AND_RUN", "1")], &["--verbose"]);
+        write_binary_wrapper(&install, &image, &link, &mapping).unwrap();
+
+        let linked =
+            link_extracted_images(&install, vec![(image.clone(), link.clone())], true).unwrap();
+        assert_eq!(linked, [(app_run.clone(), link.clone())]);
+
+        let output = std::process::Command::new(&link)
+            .arg("run")
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            format!("1|{}|--verbose run", appdir_path(&image).display())
+        );
+    }
+
+    /// `version` of widget, built `days_ago` days back.
+    fn built(version: &str, days_ago: i64) -> Package {
+        let date = chrono::Utc::now() - chrono::Duration::days(days_ago);
+        Package {
+            pkg_name: "widget".into(),
+            version: version.into(),
+            build_date: Some(date.to_rfc3339()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn only_versions_past_the_minimum_age_are_taken() {
+        const DAY: u128 = 86_400_000;
+        let repo = || vec![built("1.6.0", 1), built("1.5.0", 10), built("1.4.0", 30)];
+
+        let pick =
+            |age, installed| newest_aged(None, Some(age), installed, repo()).map(|p| p.version);
+        assert_eq!(pick(3 * DAY, Some("1.4.0")).as_deref(), Some("1.5.0"));
+        assert_eq!(pick(0, Some("1.4.0")).as_deref(), Some("1.6.0"));
+        assert_eq!(pick(14 * DAY, Some("1.4.0")), None);
+        assert_eq!(pick(14 * DAY, None).as_deref(), Some("1.4.0"));
+    }
+
+    #[test]
+    fn snapshots_predate_the_build_date_and_count_as_aged() {
+        let mut head = built("2.0.0", 0);
+        head.snapshots = Some(vec!["1.9.0".into(), "1.8.0".into()]);
+
+        let picked = newest_aged(None, Some(86_400_000), Some("1.8.0"), vec![head]).unwrap();
+        assert_eq!(picked.version, "1.9.0");
+    }
 }
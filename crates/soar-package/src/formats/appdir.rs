@@ -0,0 +1,387 @@
+//! Running image packages from an extracted AppDir, for hosts without FUSE.
+//!
+//! An AppImage mounts the filesystem appended to its runtime before it can
+//! start anything inside, which takes `/dev/fuse`, `fusermount` and, for the
+//! older runtime, `libfuse.so.2`. Containers and locked-down servers often
+//! lack one of them. Unpacking that filesystem once at install time leaves an
+//! ordinary directory whose `AppRun` starts without mounting anything.
+
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::Read,
+    os::unix::fs::{symlink, PermissionsExt},
+    path::{Component, Path, PathBuf},
+};
+
+use squishy::{
+    appimage::{get_offset, AppImage},
+    dwarfs::{DwarFS, DwarFSEntryKind},
+    EntryKind,
+};
+use tracing::debug;
+
+use super::PackageFormat;
+use crate::error::{ErrorContext, PackageError, Result};
+
+/// The program an extracted image is started through.
+pub const APP_RUN: &str = "AppRun";
+
+/// How much of a runtime is searched for the library it loads. Runtimes are
+/// a few hundred kilobytes; this only bounds a malformed header.
+const RUNTIME_SCAN_LIMIT: u64 = 4 * 1024 * 1024;
+
+/// Whether `format` is an image that carries a filesystem to unpack.
+pub fn is_mountable(format: &PackageFormat) -> bool {
+    matches!(
+        format,
+        PackageFormat::AppImage | PackageFormat::RunImage | PackageFormat::FlatImage
+    )
+}
+
+/// Where the image at `image` is unpacked to, beside it.
+pub fn appdir_path(image: &Path) -> PathBuf {
+    image.with_extension("AppDir")
+}
+
+/// Whether anything in `dir` was installed extracted.
+pub fn has_appdir(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|entries| {
+        entries.flatten().any(|entry| {
+            entry.path().extension().is_some_and(|ext| ext == "AppDir") && entry.path().is_dir()
+        })
+    })
+}
+
+/// Whether `/dev/fuse` can be opened for a mount.
+fn fuse_device_usable() -> bool {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/fuse")
+        .is_ok()
+}
+
+/// Whether a `fusermount` the runtime could call is on the `PATH`.
+fn has_fusermount() -> bool {
+    let path = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&path).any(|dir| {
+        ["fusermount", "fusermount3"].iter().any(|name| {
+            fs::metadata(dir.join(name))
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        })
+    })
+}
+
+/// Whether the runtime in front of the image at `image` loads
+/// `libfuse.so.2` rather than carrying its own FUSE client.
+///
+/// The older runtime opens the library by name, so the name is in it. The
+/// static runtime has nothing to open and no such string.
+fn wants_libfuse2(image: &Path) -> bool {
+    let Ok(offset) = get_offset(image) else {
+        return false;
+    };
+    let mut runtime = Vec::new();
+    let read = File::open(image).and_then(|file| {
+        file.take(offset.min(RUNTIME_SCAN_LIMIT))
+            .read_to_end(&mut runtime)
+    });
+    read.is_ok() && contains(&runtime, b"libfuse.so.2")
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+/// Whether the dynamic loader can find `libfuse.so.2`.
+///
+/// The loader's cache lists every library `ldconfig` knows of by name, so
+/// finding the name in it is enough. A library only reachable through
+/// `LD_LIBRARY_PATH` is not in the cache and is looked for there.
+fn has_libfuse2() -> bool {
+    if fs::read("/etc/ld.so.cache").is_ok_and(|cache| contains(&cache, b"libfuse.so.2")) {
+        return true;
+    }
+    let paths = env::var_os("LD_LIBRARY_PATH").unwrap_or_default();
+    env::split_paths(&paths).any(|dir| dir.join("libfuse.so.2").exists())
+}
+
+/// Whether the image at `image` could mount itself on this host.
+pub fn can_mount(image: &Path, format: &PackageFormat) -> bool {
+    if !fuse_device_usable() || !has_fusermount() {
+        return false;
+    }
+    // RunImage and FlatImage runtimes are static; only the AppImage runtime
+    // has ever come in a flavour that needs the library.
+    !matches!(format, PackageFormat::AppImage) || !wants_libfuse2(image) || has_libfuse2()
+}
+
+/// Where an entry at `path` inside an image lands under `dest`, or `None` if
+/// it would land outside it.
+fn entry_dest(dest: &Path, path: &Path) -> Option<PathBuf> {
+    let mut out = dest.to_path_buf();
+    for component in path.components() {
+        match component {
+            Component::RootDir | Component::CurDir => {}
+            Component::Normal(name) => out.push(name),
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    (out != dest).then_some(out)
+}
+
+/// A symlink's target as the squashfs image recorded it.
+///
+/// squishy reports every target with a `/` put in front, which makes a
+/// relative target look absolute. Exactly that one is taken back off.
+fn squashfs_link_target(target: &Path) -> PathBuf {
+    let target = target.to_string_lossy();
+    PathBuf::from(target.strip_prefix('/').unwrap_or(&target))
+}
+
+/// Unpack the filesystem inside the image at `image` into `dest`.
+///
+/// Symlinks are made last. Were one made first, a file the image lists
+/// beneath it would be written wherever the link points, which an image
+/// crafted for it could aim anywhere.
+pub fn extract_image(image: &Path, dest: &Path) -> Result<()> {
+    debug!(image = %image.display(), dest = %dest.display(), "extracting image");
+    // The squashfs reader reads ahead half as many blocks as rayon has
+    // threads, which on a single CPU is none, and every file then reads back
+    // empty. Those are the small hosts this is for, so it gets two.
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(rayon::current_num_threads().max(2))
+        .build()
+        .map_err(|err| PackageError::Custom(format!("starting extraction threads: {err}")))?
+        .install(|| extract_image_into(image, dest))
+}
+
+fn extract_image_into(image: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest).with_context(|| format!("creating directory {}", dest.display()))?;
+
+    let mut links = Vec::new();
+    match AppImage::new(None, &image, None) {
+        Ok(appimage) => {
+            if let Some(squashfs) = appimage.as_squashfs() {
+                for entry in squashfs.entries() {
+                    let Some(out) = entry_dest(dest, &entry.path) else {
+                        continue;
+                    };
+                    match entry.kind {
+                        EntryKind::Directory => {
+                            fs::create_dir_all(&out)
+                                .with_context(|| format!("creating directory {}", out.display()))?;
+                        }
+                        EntryKind::File(file) => {
+                            squashfs.write_file_with_permissions(file, &out, entry.header)?;
+                        }
+                        EntryKind::Symlink(ref target) => {
+                            links.push((squashfs_link_target(target), out));
+                        }
+                        EntryKind::Unknown => {}
+                    }
+                }
+            } else {
+                drop(appimage);
+                extract_dwarfs(
+                    DwarFS::from_path_with_offset(
+                        image,
+                        get_offset(image)
+                            .with_context(|| format!("reading {}", image.display()))?,
+                    )?,
+                    dest,
+                    &mut links,
+                )?;
+            }
+        }
+        // A FlatImage keeps more after its runtime than the filesystem, so
+        // the filesystem is not where the runtime ends and has to be found.
+        Err(_) => extract_dwarfs(DwarFS::from_path(image)?, dest, &mut links)?,
+    }
+
+    for (target, out) in links {
+        if out.symlink_metadata().is_ok() {
+            continue;
+        }
+        symlink(&target, &out).with_context(|| format!("creating symlink {}", out.display()))?;
+    }
+    Ok(())
+}
+
+fn extract_dwarfs(
+    mut dwarfs: DwarFS,
+    dest: &Path,
+    links: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<()> {
+    let entries: Vec<_> = dwarfs.entries().collect();
+    for entry in entries {
+        let Some(out) = entry_dest(dest, &entry.path) else {
+            continue;
+        };
+        match entry.kind {
+            DwarFSEntryKind::Directory => {
+                fs::create_dir_all(&out)
+                    .with_context(|| format!("creating directory {}", out.display()))?;
+            }
+            DwarFSEntryKind::File => {
+                dwarfs.write_file(&entry, &out)?;
+                fs::set_permissions(&out, fs::Permissions::from_mode(entry.mode & 0o7777))
+                    .with_context(|| format!("setting permissions on {}", out.display()))?;
+            }
+            DwarFSEntryKind::Symlink(ref target) => links.push((target.clone(), out)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Unpack the image at `image` beside it and return its `AppRun`.
+///
+/// The image is unpacked into a scratch directory first, so a failure leaves
+/// nothing half-written where the next install would take it as done. An
+/// image without an `AppRun` has nothing to start without its runtime, and is
+/// an error.
+pub fn extract_appdir(image: &Path) -> Result<PathBuf> {
+    let appdir = appdir_path(image);
+    let scratch = image.with_extension("AppDir.part");
+    if scratch.exists() {
+        fs::remove_dir_all(&scratch).with_context(|| format!("removing {}", scratch.display()))?;
+    }
+
+    let result = extract_image(image, &scratch).and_then(|_| {
+        let app_run = scratch.join(APP_RUN);
+        if app_run.symlink_metadata().is_err() {
+            return Err(PackageError::Custom(format!(
+                "{} has no {APP_RUN} to start it without FUSE",
+                image.display()
+            )));
+        }
+        if appdir.exists() {
+            fs::remove_dir_all(&appdir)
+                .with_context(|| format!("removing {}", appdir.display()))?;
+        }
+        fs::rename(&scratch, &appdir)
+            .with_context(|| format!("renaming {} to {}", scratch.display(), appdir.display()))
+    });
+    if result.is_err() {
+        fs::remove_dir_all(&scratch).ok();
+    }
+    result?;
+    Ok(appdir.join(APP_RUN))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use backhand::{FilesystemWriter, NodeHeader};
+
+    use super::*;
+
+    /// An AppImage of `files` and `links`: an ELF header claiming the
+    /// AppImage magic and a single null section, followed by a squashfs image.
+    fn appimage(files: &[(&str, &[u8], u16)], links: &[(&str, &str)]) -> Vec<u8> {
+        let mut image = vec![0u8; 128];
+        image[..4].copy_from_slice(b"\x7fELF");
+        image[4] = 2; // 64-bit
+        image[5] = 1; // little-endian
+        image[6] = 1;
+        image[8..12].copy_from_slice(&super::super::APPIMAGE_MAGIC_BYTES);
+        image[40..48].copy_from_slice(&64u64.to_le_bytes()); // e_shoff
+        image[52..54].copy_from_slice(&64u16.to_le_bytes()); // e_ehsize
+        image[58..60].copy_from_slice(&64u16.to_le_bytes()); // e_shentsize
+        image[60..62].copy_from_slice(&1u16.to_le_bytes()); // e_shnum
+
+        let mut fs = FilesystemWriter::default();
+        for (path, contents, mode) in files {
+            fs.push_dir_all(
+                Path::new(path).parent().unwrap(),
+                NodeHeader::new(0o755, 0, 0, 0),
+            )
+            .unwrap();
+            fs.push_file(
+                Cursor::new(contents.to_vec()),
+                path,
+                NodeHeader::new(*mode, 0, 0, 0),
+            )
+            .unwrap();
+        }
+        for (path, target) in links {
+            fs.push_symlink(*target, path, NodeHeader::new(0o777, 0, 0, 0))
+                .unwrap();
+        }
+        let mut squashfs = Cursor::new(Vec::new());
+        fs.write(&mut squashfs).unwrap();
+        image.extend(squashfs.into_inner());
+        image
+    }
+
+    #[test]
+    fn an_image_unpacks_with_its_modes_and_relative_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("tool");
+        fs::write(
+            &image,
+            appimage(
+                &[
+                    ("AppRun", b"#!/bin/sh\necho hi\n", 0o755),
+                    ("usr/lib/libtool.so.1", b"lib", 0o644),
+                    ("usr/share/tool/data", &[7u8; 300_000], 0o644),
+                ],
+                &[("usr/lib/libtool.so", "libtool.so.1")],
+            ),
+        )
+        .unwrap();
+
+        let app_run = extract_appdir(&image).unwrap();
+        let appdir = dir.path().join("tool.AppDir");
+        assert_eq!(app_run, appdir.join("AppRun"));
+        assert!(has_appdir(dir.path()));
+        assert_eq!(
+            fs::metadata(&app_run).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+        assert_eq!(
+            fs::read_link(appdir.join("usr/lib/libtool.so")).unwrap(),
+            Path::new("libtool.so.1")
+        );
+        assert_eq!(fs::read(appdir.join("usr/lib/libtool.so")).unwrap(), b"lib");
+        assert_eq!(
+            fs::read(appdir.join("usr/share/tool/data")).unwrap().len(),
+            300_000
+        );
+    }
+
+    #[test]
+    fn an_image_without_app_run_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("tool");
+        fs::write(&image, appimage(&[("usr/bin/tool", b"x", 0o755)], &[])).unwrap();
+
+        assert!(extract_appdir(&image).is_err());
+        assert!(!has_appdir(dir.path()));
+        assert!(!dir.path().join("tool.AppDir.part").exists());
+    }
+
+    #[test]
+    fn nothing_lands_outside_the_appdir() {
+        let dest = Path::new("/pkgs/tool.AppDir");
+        assert_eq!(
+            entry_dest(dest, Path::new("/usr/bin/tool")),
+            Some(dest.join("usr/bin/tool"))
+        );
+        assert_eq!(entry_dest(dest, Path::new("/../../etc/passwd")), None);
+        assert_eq!(entry_dest(dest, Path::new("/")), None);
+        assert_eq!(
+            squashfs_link_target(Path::new("/libtool.so.1")),
+            Path::new("libtool.so.1")
+        );
+        assert_eq!(
+            squashfs_link_target(Path::new("//usr/lib/x")),
+            Path::new("/usr/lib/x")
+        );
+    }
+}
@@ -1,13 +1,17 @@
 //! AppImage format handling.
 
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use soar_utils::fs::read_file_signature;
 use squishy::appimage::{AppImage, AppImageEntryKind};
 
 use super::{
     common::{symlink_desktop_with_config, symlink_icon_with_mode},
-    PNG_MAGIC_BYTES,
+    freedesktop::metainfo_stem,
+    BundledAssets, PNG_MAGIC_BYTES,
 };
 use crate::{
     error::{ErrorContext, Result},
@@ -38,11 +42,13 @@ pub async fn integrate_appimage<P: AsRef<Path>, T: PackageExt>(
     has_desktop: bool,
     config: &soar_config::config::Config,
 ) -> Result<()> {
-    if has_icon && has_desktop {
+    let install_dir = install_dir.as_ref();
+    // The AppStream file is only ever in the image, so it is still worth
+    // opening for one when the package shipped everything else beside it.
+    if has_icon && has_desktop && has_metainfo(install_dir) {
         return Ok(());
     }
 
-    let install_dir = install_dir.as_ref();
     let pkg_name = package.pkg_name();
     let mut appimage = AppImage::new(None, &file_path, None)?;
 
@@ -101,3 +107,50 @@ pub async fn integrate_appimage<P: AsRef<Path>, T: PackageExt>(
     }
     Ok(())
 }
+
+/// Whether an AppStream file already sits at the top of `dir`.
+fn has_metainfo(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|e| metainfo_stem(&e.file_name().to_string_lossy()).is_some())
+    })
+}
+
+/// Writes the icon and desktop file an AppImage carries into `dir`, named
+/// `<stem>.png` (or `.svg`) and `<stem>.desktop`, for publishing beside it.
+pub fn extract_appimage_assets(file_path: &Path, dir: &Path, stem: &str) -> Result<BundledAssets> {
+    let mut appimage = AppImage::new(None, &file_path, None)?;
+    let mut assets = BundledAssets::default();
+
+    if let Some(entry) = appimage.find_icon() {
+        if entry.kind == AppImageEntryKind::File {
+            let dest = dir.join(format!("{stem}.DirIcon"));
+            appimage.write_entry(&entry, &dest)?;
+            let ext = if read_file_signature(&dest, 8)? == PNG_MAGIC_BYTES {
+                "png"
+            } else {
+                "svg"
+            };
+            let final_path = dir.join(format!("{stem}.{ext}"));
+            fs::rename(&dest, &final_path).with_context(|| {
+                format!(
+                    "renaming from {} to {}",
+                    dest.display(),
+                    final_path.display()
+                )
+            })?;
+            assets.icon = Some(final_path);
+        }
+    }
+
+    if let Some(entry) = appimage.find_desktop() {
+        if entry.kind == AppImageEntryKind::File {
+            let dest: PathBuf = dir.join(format!("{stem}.desktop"));
+            appimage.write_entry(&entry, &dest)?;
+            assets.desktop = Some(dest);
+        }
+    }
+
+    Ok(assets)
+}
@@ -17,13 +17,17 @@ use regex::Regex;
 use soar_config::config::get_config;
 use soar_utils::{
     fs::{create_symlink, walk_dir},
-    path::{icons_dir, is_safe_component},
+    path::{icons_dir, in_target, is_safe_component, on_host},
 };
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
 use super::{
-    appimage::integrate_appimage, get_file_type, onelf::integrate_onelf,
-    wrappe::setup_wrappe_portable_dir, PackageFormat,
+    appimage::integrate_appimage,
+    freedesktop::{metainfo_stem, rewrite_launchables, write_icon_cache, write_mime_cache},
+    get_file_type,
+    onelf::integrate_onelf,
+    wrappe::setup_wrappe_portable_dir,
+    PackageFormat,
 };
 use crate::{
     error::{ErrorContext, PackageError, Result},
@@ -114,7 +118,7 @@ pub fn symlink_icon_with_mode<P: AsRef<Path>>(real_path: P, system_mode: bool) -
         (w, h)
     };
 
-    let final_path = icons_dir(system_mode)
+    let final_path = on_host(icons_dir(system_mode))
         .join(format!("{w}x{h}"))
         .join("apps")
         .join(format!(
@@ -197,7 +201,7 @@ pub fn symlink_desktop_with_config<P: AsRef<Path>, T: PackageExt>(
         .with_context(|| format!("reading content of desktop file: {}", real_path.display()))?;
     let file_name = real_path.file_stem().unwrap();
 
-    let bin_path = config.get_bin_path()?;
+    let bin_path = in_target(config.get_bin_path()?);
 
     let final_content = {
         let re = Regex::new(r"(?m)^(Icon|Exec|TryExec)=(.*)").unwrap();
@@ -241,6 +245,183 @@ pub fn symlink_desktop_with_config<P: AsRef<Path>, T: PackageExt>(
     Ok(final_path)
 }
 
+/// Links an AppStream file into the metainfo directory, pointing its desktop
+/// launchables at the entries soar linked.
+///
+/// The file is linked as `{stem}-soar.metainfo.xml` whichever of the two
+/// suffixes it was published with, the way desktop files get their `-soar`
+/// name. `desktop_ids` pairs each of the package's desktop file stems with the
+/// id it was linked as.
+pub fn symlink_metainfo<P: AsRef<Path>>(
+    real_path: P,
+    desktop_ids: &[(String, String)],
+    config: &soar_config::config::Config,
+) -> Result<PathBuf> {
+    let real_path = real_path.as_ref();
+    trace!(path = %real_path.display(), "creating metainfo symlink");
+    let file_name = real_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stem = metainfo_stem(&file_name).ok_or_else(|| {
+        PackageError::Custom(format!("{} is not an AppStream file", real_path.display()))
+    })?;
+
+    let content = fs::read_to_string(real_path)
+        .with_context(|| format!("reading AppStream file {}", real_path.display()))?;
+    let rewritten = rewrite_launchables(&content, desktop_ids);
+    if rewritten != content {
+        fs::write(real_path, rewritten)
+            .with_context(|| format!("writing AppStream file {}", real_path.display()))?;
+    }
+
+    let final_path = config
+        .get_metainfo_path()
+        .join(format!("{stem}-soar.metainfo.xml"));
+    if final_path.is_symlink() {
+        fs::remove_file(&final_path)
+            .with_context(|| format!("removing existing symlink at {}", final_path.display()))?;
+    }
+
+    create_symlink(real_path, &final_path)?;
+    debug!(metainfo = %final_path.display(), "metainfo symlink created");
+    Ok(final_path)
+}
+
+/// Rebuilds the MIME and icon caches over the desktop and icon directories.
+///
+/// Both caches are an index over what is already in place, so failing to
+/// write one leaves handlers and icons unindexed rather than broken. That is
+/// reported, not treated as a failed install or removal.
+pub fn refresh_desktop_caches(config: &soar_config::config::Config) {
+    match config.get_desktop_path() {
+        Ok(dir) if dir.is_dir() => {
+            if let Err(err) = write_mime_cache(&dir) {
+                warn!(
+                    "failed to update the MIME cache in {}: {}",
+                    dir.display(),
+                    err
+                );
+            }
+        }
+        _ => {}
+    }
+
+    let icons = config.get_icons_path();
+    if icons.is_dir() {
+        if let Err(err) = write_icon_cache(&icons) {
+            warn!(
+                "failed to update the icon cache in {}: {}",
+                icons.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Every symlink under `dir` that points into `installed_path`.
+fn links_into(dir: &Path, installed_path: &Path, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_symlink() {
+            if fs::read_link(&path).is_ok_and(|target| on_host(target).starts_with(installed_path))
+            {
+                found.push(path);
+            }
+        } else if path.is_dir() {
+            links_into(&path, installed_path, found);
+        }
+    }
+}
+
+/// Remove every symlink under `dir` that points into `installed_path`.
+fn unlink_into(dir: &Path, installed_path: &Path, removed: &mut Vec<PathBuf>) {
+    let mut found = Vec::new();
+    links_into(dir, installed_path, &mut found);
+    for path in found {
+        if fs::remove_file(&path).is_ok() {
+            trace!("removed {}", path.display());
+            removed.push(path);
+        }
+    }
+}
+
+/// The desktop integration a package has in place.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IntegrationLinks {
+    pub desktop: Vec<PathBuf>,
+    pub icons: Vec<PathBuf>,
+    pub metainfo: Vec<PathBuf>,
+}
+
+/// Finds a package's desktop entries, icons and AppStream files.
+///
+/// An AppImage's resources are placed by code that does not report what it
+/// linked, so they are found the way [`remove_integration`] finds them: by
+/// where they point.
+pub fn integration_links(
+    installed_path: &Path,
+    config: &soar_config::config::Config,
+) -> Result<IntegrationLinks> {
+    let mut links = IntegrationLinks::default();
+    links_into(
+        &config.get_desktop_path()?,
+        installed_path,
+        &mut links.desktop,
+    );
+    links_into(&config.get_icons_path(), installed_path, &mut links.icons);
+    links_into(
+        &config.get_metainfo_path(),
+        installed_path,
+        &mut links.metainfo,
+    );
+    Ok(links)
+}
+
+/// Undoes a package's desktop integration, returning the links removed.
+///
+/// Its desktop entries, icons and AppStream files are found by where they
+/// point, so ones the package placed from a subdirectory are found as well.
+/// The caches are rebuilt afterwards, or they would keep offering a handler
+/// that no longer exists.
+pub fn remove_integration(
+    installed_path: &Path,
+    config: &soar_config::config::Config,
+) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    // A missing directory holds nothing of the package's, which is not a
+    // reason to abandon a removal half-done.
+    for dir in [
+        config.get_desktop_path()?,
+        config.get_icons_path(),
+        config.get_metainfo_path(),
+    ] {
+        unlink_into(&dir, installed_path, &mut removed);
+    }
+
+    if !removed.is_empty() {
+        refresh_desktop_caches(config);
+    }
+    Ok(removed)
+}
+
+/// Where the portable directory of one kind goes under `base`.
+///
+/// A relative base is taken from where soar was run, as it was typed.
+fn portable_dir(base: &Path, pkg_name: &str, extension: &str) -> Result<PathBuf> {
+    let base = if base.is_absolute() {
+        base.to_path_buf()
+    } else {
+        env::current_dir()
+            .map_err(|_| PackageError::Custom("Error retrieving current directory".into()))?
+            .join(base)
+    };
+    Ok(base.join(pkg_name).with_extension(extension))
+}
+
 /// Creates a portable link for package data directories.
 ///
 /// # Arguments
@@ -259,15 +440,7 @@ pub fn create_portable_link<P: AsRef<Path>>(
     pkg_name: &str,
     extension: &str,
 ) -> Result<()> {
-    let base_dir = env::current_dir()
-        .map_err(|_| PackageError::Custom("Error retrieving current directory".into()))?;
-    let portable_path = portable_path.as_ref();
-    let portable_path = if portable_path.is_absolute() {
-        portable_path
-    } else {
-        &base_dir.join(portable_path)
-    };
-    let portable_path = portable_path.join(pkg_name).with_extension(extension);
+    let portable_path = portable_dir(portable_path.as_ref(), pkg_name, extension)?;
 
     fs::create_dir_all(&portable_path)
         .with_context(|| format!("creating directory {}", portable_path.display()))?;
@@ -275,6 +448,134 @@ pub fn create_portable_link<P: AsRef<Path>>(
     Ok(())
 }
 
+/// The directories a portable install keeps its state in.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PortableDirs {
+    pub home: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub share: Option<PathBuf>,
+    pub cache: Option<PathBuf>,
+}
+
+impl PortableDirs {
+    /// Resolve the portable options recorded for `package` to directories.
+    ///
+    /// `portable` stands for all four at once. An empty value means the
+    /// package's own directory under the configured portable root.
+    pub fn resolve<T: PackageExt>(
+        package: &T,
+        portable: Option<&str>,
+        portable_home: Option<&str>,
+        portable_config: Option<&str>,
+        portable_share: Option<&str>,
+        portable_cache: Option<&str>,
+    ) -> Result<Self> {
+        let (home, config, share, cache) = match portable {
+            Some(portable) => {
+                (
+                    Some(portable),
+                    Some(portable),
+                    Some(portable),
+                    Some(portable),
+                )
+            }
+            None => {
+                (
+                    portable_home,
+                    portable_config,
+                    portable_share,
+                    portable_cache,
+                )
+            }
+        };
+        if home.is_none() && config.is_none() && share.is_none() && cache.is_none() {
+            return Ok(Self::default());
+        }
+
+        // Packages that carry an id keep their existing directory name.
+        // Without one the family has to stand in, or two packages sharing a
+        // name would share a portable directory. Neither is trusted to be a
+        // single path component: both come from metadata, and one holding
+        // `..` would put the directory outside the portable root.
+        let family = package.pkg_family().filter(|f| is_safe_component(f));
+        let default_base = get_config().get_portable_dirs()?.join(
+            match (package.pkg_id().filter(|id| is_safe_component(id)), family) {
+                (Some(pkg_id), _) => format!("{}-{}", package.pkg_name(), pkg_id),
+                (None, Some(family)) => format!("{}-{}", package.pkg_name(), family),
+                (None, None) => package.pkg_name().to_string(),
+            },
+        );
+        let resolve = |value: Option<&str>, kind: &str| -> Result<Option<PathBuf>> {
+            value
+                .map(|value| {
+                    let base = if value.is_empty() {
+                        default_base.as_path()
+                    } else {
+                        Path::new(value)
+                    };
+                    portable_dir(base, package.pkg_name(), kind)
+                })
+                .transpose()
+        };
+
+        Ok(Self {
+            home: resolve(home, "home")?,
+            config: resolve(config, "config")?,
+            share: resolve(share, "share")?,
+            cache: resolve(cache, "cache")?,
+        })
+    }
+
+    /// Whether no directory is redirected at all.
+    pub fn is_empty(&self) -> bool {
+        self.home.is_none() && self.config.is_none() && self.share.is_none() && self.cache.is_none()
+    }
+
+    /// Each directory with the sidecar extension and the XDG variable that
+    /// name it.
+    fn kinds(&self) -> [(&Option<PathBuf>, &'static str, &'static str); 4] {
+        [
+            (&self.home, "home", "HOME"),
+            (&self.config, "config", "XDG_CONFIG_HOME"),
+            (&self.share, "share", "XDG_DATA_HOME"),
+            (&self.cache, "cache", "XDG_CACHE_HOME"),
+        ]
+    }
+
+    /// The environment that points a program at these directories.
+    pub fn env(&self) -> Vec<(&'static str, &Path)> {
+        self.kinds()
+            .into_iter()
+            .filter_map(|(dir, _, var)| dir.as_deref().map(|dir| (var, dir)))
+            .collect()
+    }
+
+    /// Create the directories, so a program finds them there on first run.
+    pub fn create(&self) -> Result<()> {
+        for dir in self
+            .kinds()
+            .into_iter()
+            .filter_map(|(dir, ..)| dir.as_ref())
+        {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("creating directory {}", dir.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a package format redirects its own state into sidecar
+/// directories next to the binary, so it needs no launcher to be portable.
+pub fn handles_portable_dirs(format: &PackageFormat) -> bool {
+    matches!(
+        format,
+        PackageFormat::AppImage
+            | PackageFormat::RunImage
+            | PackageFormat::FlatImage
+            | PackageFormat::Wrappe
+    )
+}
+
 /// Sets up portable directories for a package.
 ///
 /// Creates symlinks for home, config, share, and cache directories based
@@ -302,57 +603,20 @@ pub fn setup_portable_dir<P: AsRef<Path>, T: PackageExt>(
     portable_share: Option<&str>,
     portable_cache: Option<&str>,
 ) -> Result<()> {
-    // Packages that carry an id keep their existing directory name. Without
-    // one the family has to stand in, or two packages sharing a name would
-    // share a portable directory. Neither is trusted to be a single path
-    // component: both come from metadata, and one holding `..` would put the
-    // directory outside the portable root.
-    let family = package.pkg_family().filter(|f| is_safe_component(f));
-    let portable_dir_base = get_config().get_portable_dirs()?.join(
-        match (package.pkg_id().filter(|id| is_safe_component(id)), family) {
-            (Some(pkg_id), _) => format!("{}-{}", package.pkg_name(), pkg_id),
-            (None, Some(family)) => format!("{}-{}", package.pkg_name(), family),
-            (None, None) => package.pkg_name().to_string(),
-        },
-    );
-    let bin_path = bin_path.as_ref();
-
-    let pkg_name = package.pkg_name();
-    let pkg_config = bin_path.with_extension("config");
-    let pkg_home = bin_path.with_extension("home");
-    let pkg_share = bin_path.with_extension("share");
-    let pkg_cache = bin_path.with_extension("cache");
-
-    let (portable_home, portable_config, portable_share, portable_cache) =
-        if let Some(portable) = portable {
-            (
-                Some(portable),
-                Some(portable),
-                Some(portable),
-                Some(portable),
-            )
-        } else {
-            (
-                portable_home,
-                portable_config,
-                portable_share,
-                portable_cache,
-            )
-        };
+    let dirs = PortableDirs::resolve(
+        package,
+        portable,
+        portable_home,
+        portable_config,
+        portable_share,
+        portable_cache,
+    )?;
+    dirs.create()?;
 
-    for (opt, target, kind) in [
-        (portable_home, &pkg_home, "home"),
-        (portable_config, &pkg_config, "config"),
-        (portable_share, &pkg_share, "share"),
-        (portable_cache, &pkg_cache, "cache"),
-    ] {
-        if let Some(val) = opt {
-            let base = if val.is_empty() {
-                &portable_dir_base
-            } else {
-                Path::new(val)
-            };
-            create_portable_link(base, target, pkg_name, kind)?;
+    let bin_path = bin_path.as_ref();
+    for (dir, kind, _) in dirs.kinds() {
+        if let Some(dir) = dir {
+            create_symlink(dir, bin_path.with_extension(kind))?;
         }
     }
 
@@ -365,6 +629,8 @@ pub fn setup_portable_dir<P: AsRef<Path>, T: PackageExt>(
 /// - Desktop file symlinking
 /// - Icon symlinking with dimension normalization
 /// - AppImage resource extraction
+/// - AppStream metadata symlinking
+/// - MIME and icon cache updates
 /// - Portable directory setup
 ///
 /// # Arguments
@@ -464,7 +730,7 @@ pub async fn integrate_package<P: AsRef<Path>, T: PackageExt>(
             }
             trace!("setting up portable directories");
             setup_portable_dir(
-                bin_path,
+                &bin_path,
                 package,
                 portable,
                 portable_home,
@@ -504,10 +770,36 @@ pub async fn integrate_package<P: AsRef<Path>, T: PackageExt>(
         _ => {}
     }
 
+    // Format handlers may have extracted desktop entries and AppStream files
+    // of their own, so both are gathered only now.
+    let mut desktop_ids = Vec::new();
+    let mut metainfo_files = Vec::new();
+    walk_dir(install_dir, &mut |path: &Path| -> Result<()> {
+        if path == bin_path.as_path() {
+            return Ok(());
+        }
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        if metainfo_stem(&file_name).is_some() {
+            metainfo_files.push(path.to_path_buf());
+        } else if let Some(stem) = file_name.strip_suffix(".desktop") {
+            desktop_ids.push((stem.to_string(), format!("{stem}-soar.desktop")));
+        }
+        Ok(())
+    })?;
+    for path in &metainfo_files {
+        symlink_metainfo(path, &desktop_ids, config)?;
+    }
+
+    refresh_desktop_caches(config);
+
     debug!(
         pkg_name = pkg_name,
         has_desktop = has_desktop,
         has_icon = has_icon,
+        metainfo = metainfo_files.len(),
         "package integration completed"
     );
     Ok(())
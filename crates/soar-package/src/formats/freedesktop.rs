@@ -0,0 +1,469 @@
+//! The caches and metadata desktop environments read beside desktop entries.
+//!
+//! Placing a `.desktop` file and an icon is enough for a launcher to list an
+//! application, but not for it to be offered as a handler: file managers ask
+//! `mimeinfo.cache` which applications open a type, and toolkits look icons
+//! up through `icon-theme.cache` when a theme directory has one. Software
+//! centres list what they find in `share/metainfo`. These are written here
+//! rather than by running `update-desktop-database` and
+//! `gtk-update-icon-cache`, which are often not installed at all.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::Path,
+};
+
+use regex::Regex;
+
+/// The MIME cache file `update-desktop-database` writes.
+const MIME_CACHE: &str = "mimeinfo.cache";
+
+/// The icon cache file `gtk-update-icon-cache` writes.
+const ICON_CACHE: &str = "icon-theme.cache";
+
+/// The MIME types a desktop entry declares it opens, or `None` if it does not
+/// take part in MIME associations at all.
+fn declared_mime_types(content: &str) -> Option<Vec<String>> {
+    let mut in_entry = false;
+    let mut types = None;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_entry {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key.trim() {
+            "Hidden" if value.trim() == "true" => return None,
+            "MimeType" => {
+                types = Some(
+                    value
+                        .split(';')
+                        .map(str::trim)
+                        .filter(|t| t.contains('/'))
+                        .map(str::to_string)
+                        .collect(),
+                );
+            }
+            _ => {}
+        }
+    }
+    types
+}
+
+/// Gather the MIME types of every desktop entry under `dir`, keyed by type.
+///
+/// An entry in a subdirectory is known by its path with `-` for `/`, which is
+/// how the specification spells a desktop file id.
+fn collect_mime_types(
+    root: &Path,
+    dir: &Path,
+    types: &mut BTreeMap<String, BTreeSet<String>>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_mime_types(root, &path, types)?;
+            continue;
+        }
+        if path.extension().is_none_or(|ext| ext != "desktop") {
+            continue;
+        }
+        // A link left dangling by a package removed by hand opens nothing.
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let Some(mime_types) = declared_mime_types(&content) else {
+            continue;
+        };
+        let Ok(relative) = path.strip_prefix(root) else {
+            continue;
+        };
+        let id = relative.to_string_lossy().replace('/', "-");
+        for mime_type in mime_types {
+            types.entry(mime_type).or_default().insert(id.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Write `content` to `dir/name` through a temporary file, so a reader never
+/// sees half a cache.
+fn replace_file(dir: &Path, name: &str, content: &[u8]) -> io::Result<()> {
+    let temp = dir.join(format!(".{name}.tmp"));
+    fs::write(&temp, content)?;
+    fs::rename(&temp, dir.join(name))
+}
+
+/// Rewrite `mimeinfo.cache` in an applications directory from the desktop
+/// entries it holds, as `update-desktop-database` would.
+///
+/// The whole directory is read, not only soar's entries: the cache is one
+/// file for all of them, and writing part of it would drop everyone else's.
+pub fn write_mime_cache(dir: &Path) -> io::Result<()> {
+    let mut types = BTreeMap::new();
+    collect_mime_types(dir, dir, &mut types)?;
+
+    let mut cache = String::from("[MIME Cache]\n");
+    for (mime_type, ids) in types {
+        cache.push_str(&mime_type);
+        cache.push('=');
+        for id in ids {
+            cache.push_str(&id);
+            cache.push(';');
+        }
+        cache.push('\n');
+    }
+    replace_file(dir, MIME_CACHE, cache.as_bytes())
+}
+
+/// The hash GTK files icon names under, which reads each byte as signed.
+fn icon_name_hash(name: &str) -> u32 {
+    let mut bytes = name.bytes().map(|b| b as i8 as i32 as u32);
+    let Some(mut hash) = bytes.next() else {
+        return 0;
+    };
+    for byte in bytes {
+        hash = (hash << 5).wrapping_sub(hash).wrapping_add(byte);
+    }
+    hash
+}
+
+/// The suffix flag an icon cache records for an image file, if it is one.
+fn image_flag(path: &Path) -> Option<u16> {
+    match path.extension()?.to_str()? {
+        "xpm" => Some(1),
+        "svg" => Some(2),
+        "png" => Some(4),
+        _ => None,
+    }
+}
+
+/// Every icon under a theme directory, by name, with the directories it is
+/// in and the suffixes it has there.
+fn collect_icons(
+    root: &Path,
+    dir: &Path,
+    dirs: &mut Vec<String>,
+    icons: &mut BTreeMap<String, BTreeMap<u16, u16>>,
+) -> io::Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
+
+    let mut index = None;
+    for path in entries {
+        if path.is_dir() {
+            collect_icons(root, &path, dirs, icons)?;
+            continue;
+        }
+        // Files at the top of the theme, such as `index.theme`, are not icons,
+        // and a link whose target is gone is not one either.
+        if dir == root || !path.is_file() {
+            continue;
+        }
+        let (Some(flag), Some(name)) = (image_flag(&path), path.file_stem()) else {
+            continue;
+        };
+        let index = *index.get_or_insert_with(|| {
+            let relative = dir.strip_prefix(root).unwrap_or(dir);
+            dirs.push(relative.to_string_lossy().into_owned());
+            (dirs.len() - 1) as u16
+        });
+        *icons
+            .entry(name.to_string_lossy().into_owned())
+            .or_default()
+            .entry(index)
+            .or_default() |= flag;
+    }
+    Ok(())
+}
+
+/// A big-endian buffer laid out the way an icon cache is.
+struct CacheWriter(Vec<u8>);
+
+impl CacheWriter {
+    fn offset(&self) -> u32 {
+        self.0.len() as u32
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn patch(&mut self, at: u32, value: u32) {
+        let at = at as usize;
+        self.0[at..at + 4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    /// A NUL-terminated string padded to the next four bytes, returning
+    /// where it starts.
+    fn string(&mut self, value: &str) -> u32 {
+        let start = self.offset();
+        self.0.extend_from_slice(value.as_bytes());
+        self.0.push(0);
+        while !self.0.len().is_multiple_of(4) {
+            self.0.push(0);
+        }
+        start
+    }
+}
+
+/// Build the contents of an `icon-theme.cache` for the given icons.
+///
+/// This is version 1.0 of the format GTK reads: a header, a hash table of
+/// icon names whose chains hold each icon's image list, then the directory
+/// list the images refer to by index. No pixel data is embedded; the cache
+/// only saves a toolkit from reading every directory to find an icon.
+fn build_icon_cache(dirs: &[String], icons: &BTreeMap<String, BTreeMap<u16, u16>>) -> Vec<u8> {
+    const NONE: u32 = u32::MAX;
+
+    let mut out = CacheWriter(Vec::new());
+    out.u16(1);
+    out.u16(0);
+    out.u32(0);
+    out.u32(0);
+
+    let n_buckets = icons.len().max(1) as u32;
+    let hash_offset = out.offset();
+    out.patch(4, hash_offset);
+    out.u32(n_buckets);
+    for _ in 0..n_buckets {
+        out.u32(NONE);
+    }
+
+    let mut buckets: Vec<Vec<&String>> = vec![Vec::new(); n_buckets as usize];
+    for name in icons.keys() {
+        buckets[(icon_name_hash(name) % n_buckets) as usize].push(name);
+    }
+
+    for (bucket, names) in buckets.iter().enumerate() {
+        let mut link = hash_offset + 4 + 4 * bucket as u32;
+        for name in names {
+            let icon = out.offset();
+            out.patch(link, icon);
+            link = icon;
+            out.u32(NONE);
+            out.u32(0);
+            out.u32(0);
+
+            let name_offset = out.string(name);
+            out.patch(icon + 4, name_offset);
+
+            let images = &icons[*name];
+            let list_offset = out.offset();
+            out.patch(icon + 8, list_offset);
+            out.u32(images.len() as u32);
+            for (&dir, &flags) in images {
+                out.u16(dir);
+                out.u16(flags);
+                out.u32(0);
+            }
+        }
+    }
+
+    let dir_list = out.offset();
+    out.patch(8, dir_list);
+    out.u32(dirs.len() as u32);
+    for _ in dirs {
+        out.u32(0);
+    }
+    for (i, dir) in dirs.iter().enumerate() {
+        let offset = out.string(dir);
+        out.patch(dir_list + 4 + 4 * i as u32, offset);
+    }
+
+    out.0
+}
+
+/// Rewrite `icon-theme.cache` in an icon theme directory from the icons it
+/// holds, as `gtk-update-icon-cache` would, if the directory has one.
+///
+/// Without a cache, toolkits scan the theme directories and find soar's
+/// icons on their own, so none is created. An existing one is another
+/// matter: a toolkit that takes it as current never sees icons it does not
+/// list, which is why it is rebuilt once soar's icons are in place.
+pub fn write_icon_cache(theme_dir: &Path) -> io::Result<()> {
+    if !theme_dir.join(ICON_CACHE).is_file() {
+        return Ok(());
+    }
+    let mut dirs = Vec::new();
+    let mut icons = BTreeMap::new();
+    collect_icons(theme_dir, theme_dir, &mut dirs, &mut icons)?;
+    replace_file(theme_dir, ICON_CACHE, &build_icon_cache(&dirs, &icons))
+}
+
+/// The name an AppStream file is known by, without its `.metainfo.xml` or
+/// the older `.appdata.xml`.
+pub fn metainfo_stem(file_name: &str) -> Option<&str> {
+    file_name
+        .strip_suffix(".metainfo.xml")
+        .or_else(|| file_name.strip_suffix(".appdata.xml"))
+        .filter(|stem| !stem.is_empty())
+}
+
+/// Point an AppStream file's desktop launchables at the entries soar linked.
+///
+/// Soar links a desktop file under a `-soar` name, so the id the publisher
+/// wrote no longer names anything, and a software centre would list the
+/// application without a way to start it. `desktop_ids` pairs each desktop
+/// file's original stem with the id it was linked as; a launchable naming
+/// none of them is taken to mean the only entry there is.
+pub fn rewrite_launchables(content: &str, desktop_ids: &[(String, String)]) -> String {
+    let re = Regex::new(r#"(<launchable\s+type="desktop-id"\s*>)\s*([^<]*?)\s*(</launchable>)"#)
+        .unwrap();
+    re.replace_all(content, |caps: &regex::Captures| {
+        let named = caps[2].strip_suffix(".desktop").unwrap_or(&caps[2]);
+        let id = desktop_ids
+            .iter()
+            .find(|(stem, _)| stem == named)
+            .or(match desktop_ids {
+                [only] => Some(only),
+                _ => None,
+            })
+            .map(|(_, id)| id.as_str())
+            .unwrap_or(&caps[2]);
+        format!("{}{}{}", &caps[1], id, &caps[3])
+    })
+    .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn read_u32(data: &[u8], at: u32) -> u32 {
+        let at = at as usize;
+        u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    fn read_string(data: &[u8], at: u32) -> &str {
+        let rest = &data[at as usize..];
+        let end = rest.iter().position(|&b| b == 0).unwrap();
+        std::str::from_utf8(&rest[..end]).unwrap()
+    }
+
+    #[test]
+    fn the_mime_cache_lists_every_handler_by_type() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("viewer-soar.desktop"),
+            "[Desktop Entry]\nName=Viewer\nMimeType=image/png;image/jpeg;\n",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("kde")).unwrap();
+        fs::write(
+            dir.path().join("kde/paint.desktop"),
+            "[Desktop Entry]\nMimeType=image/png\n[Desktop Action New]\nMimeType=text/plain\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("gone.desktop"),
+            "[Desktop Entry]\nHidden=true\nMimeType=image/png;\n",
+        )
+        .unwrap();
+
+        write_mime_cache(dir.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join(MIME_CACHE)).unwrap(),
+            "[MIME Cache]\nimage/jpeg=viewer-soar.desktop;\n\
+             image/png=kde-paint.desktop;viewer-soar.desktop;\n"
+        );
+    }
+
+    #[test]
+    fn an_icon_is_found_through_the_cache_the_way_gtk_looks_it_up() {
+        let dir = tempdir().unwrap();
+        let apps = dir.path().join("48x48/apps");
+        fs::create_dir_all(&apps).unwrap();
+        fs::create_dir_all(dir.path().join("scalable/apps")).unwrap();
+        fs::write(apps.join("tool-soar.png"), b"png").unwrap();
+        fs::write(apps.join("other.png"), b"png").unwrap();
+        fs::write(dir.path().join("scalable/apps/tool-soar.svg"), b"svg").unwrap();
+        fs::write(dir.path().join("index.theme"), b"[Icon Theme]").unwrap();
+        fs::write(dir.path().join(ICON_CACHE), b"stale").unwrap();
+
+        write_icon_cache(dir.path()).unwrap();
+        let data = fs::read(dir.path().join(ICON_CACHE)).unwrap();
+
+        assert_eq!(&data[..4], &[0, 1, 0, 0]);
+        let hash = read_u32(&data, 4);
+        let n_buckets = read_u32(&data, hash);
+        let mut icon = read_u32(
+            &data,
+            hash + 4 + 4 * (icon_name_hash("tool-soar") % n_buckets),
+        );
+        while read_string(&data, read_u32(&data, icon + 4)) != "tool-soar" {
+            icon = read_u32(&data, icon);
+            assert_ne!(icon, u32::MAX, "tool-soar is not in its chain");
+        }
+
+        let dir_list = read_u32(&data, 8);
+        let list = read_u32(&data, icon + 8);
+        assert_eq!(read_u32(&data, list), 2);
+        let mut found = Vec::new();
+        for i in 0..2 {
+            let image = list + 4 + 8 * i;
+            let entry = read_u32(&data, image);
+            let (dir, flags) = (entry >> 16, entry & 0xffff);
+            let name = read_string(&data, read_u32(&data, dir_list + 4 + 4 * dir));
+            found.push((name.to_string(), flags));
+        }
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                ("48x48/apps".to_string(), 4),
+                ("scalable/apps".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn a_theme_without_an_icon_cache_is_left_without_one() {
+        let dir = tempdir().unwrap();
+        let apps = dir.path().join("48x48/apps");
+        fs::create_dir_all(&apps).unwrap();
+        fs::write(apps.join("tool-soar.png"), b"png").unwrap();
+
+        write_icon_cache(dir.path()).unwrap();
+
+        assert!(!dir.path().join(ICON_CACHE).exists());
+    }
+
+    #[test]
+    fn launchables_follow_the_desktop_entry_to_its_soar_name() {
+        let metainfo = r#"<component><launchable type="desktop-id">org.example.Tool.desktop</launchable></component>"#;
+
+        let by_stem = [
+            (
+                "org.example.Tool".to_string(),
+                "org.example.Tool-soar.desktop".to_string(),
+            ),
+            ("helper".to_string(), "helper-soar.desktop".to_string()),
+        ];
+        assert!(rewrite_launchables(metainfo, &by_stem)
+            .contains(">org.example.Tool-soar.desktop</launchable>"));
+
+        let only = [("tool".to_string(), "tool-soar.desktop".to_string())];
+        assert!(rewrite_launchables(metainfo, &only).contains(">tool-soar.desktop</launchable>"));
+
+        assert_eq!(rewrite_launchables(metainfo, &[]), metainfo);
+        assert_eq!(metainfo_stem("tool.appdata.xml"), Some("tool"));
+        assert_eq!(metainfo_stem("tool.xml"), None);
+    }
+}
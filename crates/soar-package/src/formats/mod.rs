@@ -3,12 +3,17 @@
 //! This module provides functionality for detecting package formats based on
 //! magic bytes and handling format-specific operations like desktop integration.
 
+pub mod appdir;
 pub mod appimage;
 pub mod common;
+pub mod freedesktop;
 pub mod onelf;
 pub mod wrappe;
 
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::{
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
 
 use onelf_format::{
     END_MAGIC as ONELF_END_MAGIC, FOOTER_SIZE as ONELF_FOOTER_SIZE, MAGIC as ONELF_MAGIC,
@@ -56,6 +61,31 @@ pub enum PackageFormat {
     Unknown,
 }
 
+/// Desktop resources found inside a package.
+#[derive(Debug, Default, Clone)]
+pub struct BundledAssets {
+    pub icon: Option<PathBuf>,
+    pub desktop: Option<PathBuf>,
+}
+
+/// Writes the icon and desktop file a package of `format` carries into `dir`,
+/// named after `stem`.
+///
+/// Only formats that bundle their desktop resources in a known place have
+/// anything to give; for the rest this finds nothing rather than failing.
+pub fn extract_assets(
+    file_path: &Path,
+    format: PackageFormat,
+    dir: &Path,
+    stem: &str,
+) -> Result<BundledAssets> {
+    match format {
+        PackageFormat::AppImage => appimage::extract_appimage_assets(file_path, dir, stem),
+        PackageFormat::Onelf => onelf::extract_onelf_assets(file_path, dir, stem),
+        _ => Ok(BundledAssets::default()),
+    }
+}
+
 /// Detects the package format by reading magic bytes from the file.
 ///
 /// # Arguments
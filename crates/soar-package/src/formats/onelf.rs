@@ -13,7 +13,10 @@ use std::{
 
 use onelf_format::{Entry, EntryKind, Footer, Manifest, FOOTER_SIZE};
 
-use super::common::{symlink_desktop_with_config, symlink_icon_with_mode};
+use super::{
+    common::{symlink_desktop_with_config, symlink_icon_with_mode},
+    BundledAssets,
+};
 use crate::{
     error::{ErrorContext, PackageError, Result},
     traits::PackageExt,
@@ -233,3 +236,36 @@ pub async fn integrate_onelf<P: AsRef<Path>, T: PackageExt>(
 
     Ok(())
 }
+
+/// Writes the icon and desktop file an onelf binary carries into `dir`, named
+/// `<stem>.png` (or `.svg`) and `<stem>.desktop`, for publishing beside it.
+pub fn extract_onelf_assets(file_path: &Path, dir: &Path, stem: &str) -> Result<BundledAssets> {
+    let mut file =
+        File::open(file_path).with_context(|| format!("opening {}", file_path.display()))?;
+    let (footer, manifest) = read_footer_and_manifest(&mut file)?;
+    let dict = read_dict(&mut file, &footer)?;
+    let ep_name = default_entrypoint_name(&manifest);
+    let mut assets = BundledAssets::default();
+
+    if let Some(idx) = resolve_icon(&manifest, &ep_name) {
+        let ext = if manifest.entry_path(idx).ends_with(".svg") {
+            "svg"
+        } else {
+            "png"
+        };
+        let data = decompress_entry(&mut file, &footer, &manifest.entries[idx], dict.as_deref())?;
+        let dest = dir.join(format!("{stem}.{ext}"));
+        fs::write(&dest, &data).with_context(|| format!("writing icon to {}", dest.display()))?;
+        assets.icon = Some(dest);
+    }
+
+    if let Some(idx) = resolve_desktop(&manifest, &ep_name) {
+        let data = decompress_entry(&mut file, &footer, &manifest.entries[idx], dict.as_deref())?;
+        let dest = dir.join(format!("{stem}.desktop"));
+        fs::write(&dest, &data)
+            .with_context(|| format!("writing desktop file to {}", dest.display()))?;
+        assets.desktop = Some(dest);
+    }
+
+    Ok(assets)
+}
@@ -106,6 +106,10 @@ pub enum RegistryError {
     )]
     MetadataTooLarge { limit: u64 },
 
+    #[error("Invalid signing key: {0}")]
+    #[diagnostic(code(soar_registry::signing_key))]
+    SigningKey(String),
+
     #[error("{0}")]
     #[diagnostic(code(soar_registry::custom))]
     Custom(String),
@@ -36,6 +36,7 @@
 pub mod error;
 pub mod metadata;
 pub mod package;
+pub mod sign;
 
 pub use error::{ErrorContext, RegistryError, Result};
 pub use metadata::{
@@ -43,3 +44,4 @@ pub use metadata::{
     SQLITE_MAGIC_BYTES, SUPPORTED_FORMAT, ZST_MAGIC_BYTES,
 };
 pub use package::RemotePackage;
+pub use sign::SigningKey;
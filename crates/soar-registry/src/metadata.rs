@@ -12,9 +12,8 @@ use std::{
 
 use minisign_verify::{PublicKey, Signature};
 use serde::Deserialize;
-use soar_config::repository::Repository;
+use soar_config::repository::{local_source_path, Repository};
 use soar_dl::http_client::SHARED_AGENT;
-use soar_utils::path::resolve_path;
 use tracing::{debug, warn};
 use ureq::http::{
     header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH, PRAGMA},
@@ -208,21 +207,30 @@ pub async fn fetch_metadata(
     Ok(Some((etag, metadata_content)))
 }
 
-/// Resolves a repository URL to a local filesystem path when it is a local
-/// source (a `file://` URL or a filesystem path), or `None` for http(s) URLs.
+/// Names a directory repository's metadata is looked for under, in order.
+pub const INDEX_NAMES: &[&str] = &[
+    "metadata.db.zst",
+    "metadata.sdb.zstd",
+    "metadata.db",
+    "metadata.sdb",
+    "index.json",
+];
+
+/// Resolves a repository URL to the local metadata file it names, or `None`
+/// for http(s) URLs.
+///
+/// A directory stands for the metadata inside it, so a share that a build
+/// drops artifacts and an index onto can be added as it is.
 fn local_metadata_path(url: &str) -> Option<PathBuf> {
-    let trimmed = url.trim();
-    if let Some(rest) = trimmed.strip_prefix("file://") {
-        return resolve_path(rest).ok();
-    }
-    if trimmed.starts_with('/')
-        || trimmed.starts_with('~')
-        || trimmed.starts_with('.')
-        || trimmed.starts_with('$')
-    {
-        return resolve_path(trimmed).ok();
+    let path = local_source_path(url)?;
+    if !path.is_dir() {
+        return Some(path);
     }
-    None
+    INDEX_NAMES
+        .iter()
+        .map(|name| path.join(name))
+        .find(|candidate| candidate.is_file())
+        .or(Some(path))
 }
 
 /// Validates the scheme of a remote metadata URL.
@@ -245,9 +253,11 @@ fn ensure_remote_scheme_allowed(url: &str, scheme: &str, signature_verified: boo
 
 /// Reads and verifies repository metadata from a local file.
 ///
-/// Uses the file modification time as the change-detection token so an unchanged
-/// file returns `Ok(None)` on subsequent syncs, mirroring the ETag behaviour of
-/// the remote path.
+/// In place of an ETag, the change-detection token is the file's modification
+/// time followed by a hash of its content. An unchanged mtime is taken at its
+/// word without reading anything. A changed one is checked against the hash,
+/// since a build that copies the same index onto a share again bumps the mtime
+/// without changing a byte, and that is not worth a re-import.
 fn fetch_local_metadata(
     repo: &Repository,
     path: &Path,
@@ -255,17 +265,26 @@ fn fetch_local_metadata(
     existing_etag: &str,
     force: bool,
 ) -> Result<Option<(String, MetadataContent)>> {
+    if path.is_dir() {
+        return Err(RegistryError::Custom(format!(
+            "{}: no repository metadata found; expected one of {}",
+            path.display(),
+            INDEX_NAMES.join(", ")
+        )));
+    }
+
     let file_info =
         fs::metadata(path).with_context(|| format!("reading metadata file {}", path.display()))?;
 
-    let mtime_tag = file_info
+    let mtime = file_info
         .modified()
         .ok()
         .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
         .map(|d| d.as_millis().to_string())
         .unwrap_or_default();
+    let (known_mtime, known_hash) = existing_etag.split_once(':').unwrap_or((existing_etag, ""));
 
-    if !force && !mtime_tag.is_empty() && existing_etag == mtime_tag {
+    if !force && !mtime.is_empty() && known_mtime == mtime {
         return Ok(None);
     }
 
@@ -279,12 +298,20 @@ fn fetch_local_metadata(
 
     let content =
         fs::read(path).with_context(|| format!("reading metadata file {}", path.display()))?;
+    let hash = blake3::hash(&content).to_hex().to_string();
+    if !force && known_hash == hash {
+        debug!(
+            "{} was touched but not changed; keeping the synced metadata",
+            path.display()
+        );
+        return Ok(None);
+    }
 
     verify_metadata_signature(repo, &content, || read_local_signature(path))?;
 
     let metadata_content = process_metadata_content(content, metadata_db)?;
 
-    Ok(Some((mtime_tag, metadata_content)))
+    Ok(Some((format!("{mtime}:{hash}"), metadata_content)))
 }
 
 /// Reads the detached signature published next to a local metadata file.
@@ -567,6 +594,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn directories_stand_for_the_metadata_inside() {
+        let dir = std::env::temp_dir().join(format!("soar-local-repo-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let url = format!("file://{}", dir.display());
+
+        // Nothing to find leaves the directory, which sync reports on.
+        assert_eq!(local_metadata_path(&url), Some(dir.clone()));
+
+        fs::write(dir.join("index.json"), "{}").unwrap();
+        assert_eq!(local_metadata_path(&url), Some(dir.join("index.json")));
+        fs::write(dir.join("metadata.db.zst"), "").unwrap();
+        assert_eq!(
+            local_metadata_path(&dir.display().to_string()),
+            Some(dir.join("metadata.db.zst"))
+        );
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
     #[test]
     fn https_is_always_allowed() {
         assert!(ensure_remote_scheme_allowed("https://x/m.sdb", "https", false).is_ok());
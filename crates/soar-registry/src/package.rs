@@ -244,6 +244,14 @@ pub struct RemotePackage {
     /// artifact is the package, which is how the older format always behaved.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub files: Option<Vec<RemoteFile>>,
+    /// Where the package's release notes are published, as a changelog
+    /// document with a heading per version.
+    #[serde(
+        default,
+        deserialize_with = "empty_is_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub changelog_url: Option<String>,
 }
 
 #[cfg(test)]
@@ -0,0 +1,288 @@
+//! Minisign signatures for publishing a repository.
+//!
+//! The counterpart to the verification `fetch_metadata` and the installer
+//! already perform: signatures written here are the prehashed kind minisign
+//! itself produces by default, which both the metadata check and the streaming
+//! check on installed files accept.
+//!
+//! Only unencrypted secret keys are read. An encrypted one needs scrypt to
+//! open, and a key a build job signs with unattended is kept unencrypted
+//! anyway, as `minisign -G -W` writes it.
+
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use blake2::{Blake2b256, Blake2b512, Digest};
+use ring::{
+    rand::{SecureRandom as _, SystemRandom},
+    signature::{Ed25519KeyPair, KeyPair as _},
+};
+
+use crate::error::{ErrorContext, RegistryError, Result};
+
+const SIG_ALG: &[u8; 2] = b"Ed";
+const PREHASHED_SIG_ALG: &[u8; 2] = b"ED";
+const KDF_NONE: [u8; 2] = [0, 0];
+const CHK_ALG: &[u8; 2] = b"B2";
+/// Algorithm ids, salt and limits, key id, secret key, checksum.
+const SECRET_KEY_LEN: usize = 2 + 2 + 2 + 32 + 8 + 8 + 8 + 64 + 32;
+
+/// A minisign secret key, able to sign what a repository publishes.
+pub struct SigningKey {
+    key_id: [u8; 8],
+    /// Kept alongside the key pair, which does not give it back, so the key
+    /// can be written out again.
+    seed: [u8; 32],
+    keypair: Ed25519KeyPair,
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("key_id", &self.key_id_hex())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigningKey {
+    /// A fresh key with a random key id.
+    pub fn generate() -> Result<Self> {
+        let rng = SystemRandom::new();
+        let mut seed = [0u8; 32];
+        let mut key_id = [0u8; 8];
+        rng.fill(&mut seed)
+            .and_then(|_| rng.fill(&mut key_id))
+            .map_err(|_| RegistryError::SigningKey("no randomness available".into()))?;
+        let keypair = Ed25519KeyPair::from_seed_unchecked(&seed)
+            .map_err(|err| RegistryError::SigningKey(err.to_string()))?;
+
+        Ok(Self {
+            key_id,
+            seed,
+            keypair,
+        })
+    }
+
+    /// Read a secret key file as minisign writes it.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading secret key {}", path.display()))?;
+        Self::decode(&text)
+    }
+
+    /// Parse the text of a secret key file.
+    pub fn decode(text: &str) -> Result<Self> {
+        let invalid = |reason: &str| RegistryError::SigningKey(reason.to_string());
+
+        let encoded = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+            .ok_or_else(|| invalid("no key in the file"))?;
+        let bytes = STANDARD
+            .decode(encoded)
+            .map_err(|_| invalid("the key is not valid base64"))?;
+        if bytes.len() != SECRET_KEY_LEN || &bytes[..2] != SIG_ALG || &bytes[4..6] != CHK_ALG {
+            return Err(invalid("not a minisign secret key"));
+        }
+        if bytes[2..4] != KDF_NONE {
+            return Err(invalid(
+                "the key is encrypted; write an unencrypted one with `soar repo keygen` \
+                 or `minisign -G -W`",
+            ));
+        }
+
+        let key_id: [u8; 8] = bytes[54..62].try_into().unwrap();
+        let secret = &bytes[62..126];
+        if checksum(&key_id, secret) != bytes[126..] {
+            return Err(invalid("the key's checksum does not match"));
+        }
+        let keypair = Ed25519KeyPair::from_seed_and_public_key(&secret[..32], &secret[32..])
+            .map_err(|err| invalid(&err.to_string()))?;
+
+        Ok(Self {
+            key_id,
+            seed: secret[..32].try_into().unwrap(),
+            keypair,
+        })
+    }
+
+    /// The text of an unencrypted secret key file for this key.
+    pub fn encode(&self) -> String {
+        let secret = self.secret();
+        let mut bytes = Vec::with_capacity(SECRET_KEY_LEN);
+        bytes.extend_from_slice(SIG_ALG);
+        bytes.extend_from_slice(&KDF_NONE);
+        bytes.extend_from_slice(CHK_ALG);
+        // Salt and scrypt limits, unused without encryption.
+        bytes.extend_from_slice(&[0u8; 32 + 8 + 8]);
+        bytes.extend_from_slice(&self.key_id);
+        bytes.extend_from_slice(&secret);
+        bytes.extend_from_slice(&checksum(&self.key_id, &secret));
+
+        format!(
+            "untrusted comment: minisign secret key {}\n{}\n",
+            self.key_id_hex(),
+            STANDARD.encode(bytes)
+        )
+    }
+
+    /// The public key in the base64 form a repository's `pubkey` takes.
+    pub fn public_key(&self) -> String {
+        let mut bytes = Vec::with_capacity(42);
+        bytes.extend_from_slice(SIG_ALG);
+        bytes.extend_from_slice(&self.key_id);
+        bytes.extend_from_slice(self.keypair.public_key().as_ref());
+        STANDARD.encode(bytes)
+    }
+
+    /// The text of a public key file for this key.
+    pub fn public_key_file(&self) -> String {
+        format!(
+            "untrusted comment: minisign public key {}\n{}\n",
+            self.key_id_hex(),
+            self.public_key()
+        )
+    }
+
+    /// The text of a detached signature over `content`.
+    pub fn sign(&self, content: &[u8], trusted_comment: &str) -> String {
+        let mut hasher = Blake2b512::new();
+        hasher.update(content);
+        self.sign_prehashed(&hasher.finalize(), trusted_comment)
+    }
+
+    /// Sign the file at `path`, writing the signature beside it as
+    /// `<path>.sig`, and return where it went.
+    pub fn sign_file(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
+        let path = path.as_ref();
+        let mut file =
+            File::open(path).with_context(|| format!("opening {} to sign", path.display()))?;
+        let mut hasher = Blake2b512::new();
+        let mut buffer = [0u8; 8192];
+        loop {
+            match file
+                .read(&mut buffer)
+                .with_context(|| format!("reading {} to sign", path.display()))?
+            {
+                0 => break,
+                n => hasher.update(&buffer[..n]),
+            }
+        }
+
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let signature = self.sign_prehashed(
+            &hasher.finalize(),
+            &format!("timestamp:{timestamp}\tfile:{name}\thashed"),
+        );
+
+        let mut sig_path = path.as_os_str().to_os_string();
+        sig_path.push(".sig");
+        let sig_path = PathBuf::from(sig_path);
+        std::fs::write(&sig_path, signature)
+            .with_context(|| format!("writing signature {}", sig_path.display()))?;
+        Ok(sig_path)
+    }
+
+    fn sign_prehashed(&self, digest: &[u8], trusted_comment: &str) -> String {
+        let trusted_comment = trusted_comment.replace(['\r', '\n'], " ");
+        let signature = self.keypair.sign(digest);
+
+        let mut sig_bytes = Vec::with_capacity(74);
+        sig_bytes.extend_from_slice(PREHASHED_SIG_ALG);
+        sig_bytes.extend_from_slice(&self.key_id);
+        sig_bytes.extend_from_slice(signature.as_ref());
+
+        // The global signature binds the trusted comment to the signature, so
+        // the comment cannot be swapped out.
+        let mut global = signature.as_ref().to_vec();
+        global.extend_from_slice(trusted_comment.as_bytes());
+        let global = self.keypair.sign(&global);
+
+        format!(
+            "untrusted comment: signature from soar secret key\n{}\ntrusted comment: {}\n{}\n",
+            STANDARD.encode(sig_bytes),
+            trusted_comment,
+            STANDARD.encode(global.as_ref())
+        )
+    }
+
+    /// The seed followed by the public key, as minisign stores a secret key.
+    fn secret(&self) -> [u8; 64] {
+        let mut secret = [0u8; 64];
+        secret[..32].copy_from_slice(&self.seed);
+        secret[32..].copy_from_slice(self.keypair.public_key().as_ref());
+        secret
+    }
+
+    fn key_id_hex(&self) -> String {
+        format!("{:016X}", u64::from_le_bytes(self.key_id))
+    }
+}
+
+fn checksum(key_id: &[u8; 8], secret: &[u8]) -> Vec<u8> {
+    let mut hasher = Blake2b256::new();
+    hasher.update(SIG_ALG);
+    hasher.update(key_id);
+    hasher.update(secret);
+    hasher.finalize().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use minisign_verify::{PublicKey, Signature};
+
+    use super::*;
+
+    #[test]
+    fn signatures_verify_whole_and_streamed() {
+        let key = SigningKey::generate().unwrap();
+        let public = PublicKey::from_base64(&key.public_key()).unwrap();
+        // Several blocks, ending exactly on a block boundary.
+        let content = vec![7u8; 128 * 5];
+
+        let signature = Signature::decode(&key.sign(&content, "file:index.json")).unwrap();
+        assert_eq!(signature.trusted_comment(), "file:index.json");
+        public.verify(&content, &signature, false).unwrap();
+
+        let mut stream = public.verify_stream(&signature).unwrap();
+        for chunk in content.chunks(100) {
+            stream.update(chunk);
+        }
+        stream.finalize().unwrap();
+
+        assert!(public.verify(b"tampered", &signature, true).is_err());
+    }
+
+    #[test]
+    fn secret_keys_survive_a_round_trip() {
+        let key = SigningKey::generate().unwrap();
+        let decoded = SigningKey::decode(&key.encode()).unwrap();
+        assert_eq!(decoded.public_key(), key.public_key());
+        assert!(key
+            .public_key_file()
+            .ends_with(&format!("{}\n", key.public_key())));
+
+        let mut encrypted = STANDARD
+            .decode(key.encode().lines().nth(1).unwrap())
+            .unwrap();
+        encrypted[2..4].copy_from_slice(b"Sc");
+        assert!(SigningKey::decode(&STANDARD.encode(&encrypted)).is_err());
+
+        let mut corrupted = STANDARD
+            .decode(key.encode().lines().nth(1).unwrap())
+            .unwrap();
+        corrupted[70] ^= 1;
+        assert!(SigningKey::decode(&STANDARD.encode(&corrupted)).is_err());
+    }
+}
@@ -109,6 +109,45 @@ pub fn parse_bytes(s: &str) -> BytesResult<u64> {
         })
 }
 
+/// Parses a transfer rate the way `curl --limit-rate` does.
+///
+/// A bare number is bytes, and a `K`, `M` or `G` suffix multiplies it by a power of
+/// 1024. Anything [`parse_bytes`] accepts, such as `5MB` or `5MiB`, is accepted too.
+///
+/// # Errors
+///
+/// * [`BytesError::ParseFailed`] if the string is not a non-negative rate.
+///
+/// # Example
+///
+/// ```
+/// use soar_utils::bytes::parse_rate;
+///
+/// assert_eq!(parse_rate("5M").unwrap(), 5 * 1024_u64.pow(2));
+/// ```
+pub fn parse_rate(s: &str) -> BytesResult<u64> {
+    let rate = s.trim();
+    let shift = match rate.chars().last() {
+        Some('k' | 'K') => 10,
+        Some('m' | 'M') => 20,
+        Some('g' | 'G') => 30,
+        _ => return parse_bytes(rate),
+    };
+
+    rate[..rate.len() - 1]
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n >= 0.0)
+        .map(|n| (n * (1_u64 << shift) as f64).round() as u64)
+        .ok_or_else(|| {
+            BytesError::ParseFailed {
+                input: s.to_string(),
+                reason: "Unrecognized rate format".into(),
+            }
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -193,6 +232,21 @@ mod tests {
         assert_eq!(parse_bytes("1.50EB").unwrap(), 3 * 1000_u64.pow(6) / 2);
     }
 
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("1024").unwrap(), 1024);
+        assert_eq!(parse_rate("5M").unwrap(), 5 * 1024_u64.pow(2));
+        assert_eq!(parse_rate("1.5k").unwrap(), 1536);
+        assert_eq!(parse_rate("2G").unwrap(), 2 * 1024_u64.pow(3));
+        assert_eq!(parse_rate("5MiB").unwrap(), 5 * 1024_u64.pow(2));
+        assert_eq!(parse_rate("5MB").unwrap(), 5 * 1000_u64.pow(2));
+
+        assert!(parse_rate("-1M").is_err());
+        assert!(parse_rate("M").is_err());
+        assert!(parse_rate("").is_err());
+        assert!(parse_rate("fast").is_err());
+    }
+
     #[test]
     fn test_fail_parse_bytes() {
         assert!(parse_bytes("1.xE").is_err());
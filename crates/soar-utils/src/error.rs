@@ -31,6 +31,24 @@ pub enum HashError {
     },
 }
 
+/// Error type for version constraints.
+#[derive(Error, Diagnostic, Debug)]
+pub enum VersionError {
+    #[error("Invalid version constraint '{input}': {reason}")]
+    #[diagnostic(
+        code(soar_utils::version::constraint),
+        help("Use a constraint like '^1.4', '~0.9' or '>=2.0, <3'")
+    )]
+    InvalidConstraint { input: String, reason: String },
+
+    #[error("'{0}' has no order a version constraint can be checked against")]
+    #[diagnostic(
+        code(soar_utils::version::unordered),
+        help("Only versions that start with a number can be matched against a constraint")
+    )]
+    Unordered(String),
+}
+
 /// Errors that can occur when working with locks.
 #[derive(Debug, Diagnostic, Error)]
 pub enum LockError {
@@ -393,6 +411,10 @@ pub enum UtilsError {
     #[error(transparent)]
     #[diagnostic(transparent)]
     Hash(#[from] HashError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Version(#[from] VersionError),
 }
 
 pub type BytesResult<T> = std::result::Result<T, BytesError>;
@@ -400,6 +422,7 @@ pub type FileSystemResult<T> = std::result::Result<T, FileSystemError>;
 pub type HashResult<T> = std::result::Result<T, HashError>;
 pub type LockResult<T> = std::result::Result<T, LockError>;
 pub type PathResult<T> = std::result::Result<T, PathError>;
+pub type VersionResult<T> = std::result::Result<T, VersionError>;
 pub type UtilsResult<T> = std::result::Result<T, UtilsError>;
 
 #[cfg(test)]
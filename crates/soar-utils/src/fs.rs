@@ -5,7 +5,10 @@ use std::{
     path::Path,
 };
 
-use crate::error::{FileSystemError, FileSystemResult, IoOperation, IoResultExt};
+use crate::{
+    error::{FileSystemError, FileSystemResult, IoOperation, IoResultExt},
+    path::in_target,
+};
 
 /// Removes the specified file or directory safely.
 ///
@@ -133,7 +136,7 @@ pub fn create_symlink<P: AsRef<Path>, Q: AsRef<Path>>(
         fs::remove_file(target).with_path(target, IoOperation::RemoveFile)?;
     }
 
-    os::unix::fs::symlink(source, target).with_path(
+    os::unix::fs::symlink(in_target(source), target).with_path(
         source,
         IoOperation::CreateSymlink {
             target: target.into(),
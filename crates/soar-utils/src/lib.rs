@@ -9,3 +9,4 @@ pub mod pattern;
 pub mod system;
 pub mod time;
 pub mod version;
+pub mod wrapper;
@@ -1,6 +1,7 @@
 use std::{
     env,
     path::{Component, Path, PathBuf},
+    sync::OnceLock,
 };
 
 use crate::{
@@ -179,6 +180,70 @@ pub fn icons_dir(system: bool) -> PathBuf {
     }
 }
 
+/// Returns the AppStream metadata directory
+pub fn metainfo_dir(system: bool) -> PathBuf {
+    if system {
+        PathBuf::from("/usr/local/share/metainfo")
+    } else {
+        xdg_data_home().join("metainfo")
+    }
+}
+
+static INSTALL_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Install into the filesystem mounted at `root` rather than the running one.
+///
+/// Every path soar works with still means what it would on the target
+/// system; [`on_host`] finds where that is from here, and [`in_target`]
+/// turns a path back into what the target will see, which is what links and
+/// the database record. The root is set once, before any path is resolved,
+/// and later calls are ignored.
+pub fn set_install_root<P: Into<PathBuf>>(root: P) {
+    let _ = INSTALL_ROOT.set(root.into());
+}
+
+/// The alternate root installs go into, if one was set.
+pub fn install_root() -> Option<&'static Path> {
+    INSTALL_ROOT.get().map(PathBuf::as_path)
+}
+
+/// Where `path`, as the target system names it, is reachable from the host.
+///
+/// Paths already under the root are returned as they are, so resolving one
+/// twice is harmless. Without a root this is the identity.
+pub fn on_host<P: AsRef<Path>>(path: P) -> PathBuf {
+    match install_root() {
+        Some(root) => join_root(root, path.as_ref()),
+        None => path.as_ref().to_path_buf(),
+    }
+}
+
+/// What the target system will call `path`, a path on the host.
+///
+/// This is what belongs in a symlink, a wrapper or the database, which are
+/// read once the target is booted or chrooted into. Without a root this is
+/// the identity.
+pub fn in_target<P: AsRef<Path>>(path: P) -> PathBuf {
+    match install_root() {
+        Some(root) => strip_root(root, path.as_ref()),
+        None => path.as_ref().to_path_buf(),
+    }
+}
+
+fn join_root(root: &Path, path: &Path) -> PathBuf {
+    if !path.is_absolute() || path.starts_with(root) {
+        return path.to_path_buf();
+    }
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+fn strip_root(root: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(root) {
+        Ok(rest) => Path::new("/").join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
 fn expand_variables(path: &str) -> PathResult<String> {
     let mut result = String::with_capacity(path.len());
     let mut chars = path.chars().peekable();
@@ -517,4 +582,32 @@ mod tests {
         let icons = icons_dir(true);
         assert_eq!(icons, PathBuf::from("/usr/local/share/icons/hicolor"));
     }
+
+    #[test]
+    fn paths_move_between_the_host_and_the_target_root() {
+        let root = Path::new("/mnt/target");
+
+        assert_eq!(
+            join_root(root, Path::new("/opt/soar/bin")),
+            PathBuf::from("/mnt/target/opt/soar/bin")
+        );
+        assert_eq!(
+            join_root(root, Path::new("/mnt/target/opt/soar")),
+            PathBuf::from("/mnt/target/opt/soar")
+        );
+        assert_eq!(join_root(root, Path::new("tool")), PathBuf::from("tool"));
+
+        assert_eq!(
+            strip_root(root, Path::new("/mnt/target/opt/soar/bin/tool")),
+            PathBuf::from("/opt/soar/bin/tool")
+        );
+        assert_eq!(
+            strip_root(root, Path::new("/mnt/target")),
+            PathBuf::from("/")
+        );
+        assert_eq!(
+            strip_root(root, Path::new("/mnt/targets/x")),
+            PathBuf::from("/mnt/targets/x")
+        );
+    }
 }
@@ -14,8 +14,14 @@
 //! time. Those compare equal to each other and below any ordinary version, so a
 //! repository that wants upgrades between snapshots has to publish something
 //! ordered, such as a date.
+//!
+//! The same ordering answers constraints like `^1.4` or `>=2.0, <3`, see
+//! [`VersionReq`], so a constraint accepts exactly the upgrades comparison
+//! would call upgrades.
+
+use std::{cmp::Ordering, fmt};
 
-use std::cmp::Ordering;
+use crate::error::{VersionError, VersionResult};
 
 /// Compare two version strings.
 ///
@@ -167,6 +173,222 @@ fn segments(version: &str) -> impl Iterator<Item = &str> {
     })
 }
 
+/// Whether a declared version is a constraint rather than an exact version.
+///
+/// A bare version keeps meaning exactly that version, so only an operator or a
+/// list of bounds makes it a constraint.
+pub fn is_constraint(spec: &str) -> bool {
+    let spec = spec.trim_start();
+    spec.starts_with(['^', '~', '<', '>', '=']) || spec.contains(',')
+}
+
+/// A constraint on versions: `^1.4`, `~0.9`, `>=2.0, <3`.
+///
+/// Each comma-separated part is one bound and a version has to satisfy them
+/// all. `^` accepts anything up to the next change of the leftmost non-zero
+/// part (`^1.4` is `>=1.4, <2`, `^0.9` is `>=0.9, <0.10`), and `~` up to the
+/// next minor (`~1.4.2` is `>=1.4.2, <1.5`). Bounds are checked with
+/// [`compare_versions`], so a rebuild like `1.4.0-2` stays inside `^1.4`
+/// while a prerelease like `2.0-rc1` stays outside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    raw: String,
+    bounds: Vec<(Op, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// What one part of a constraint says before its version is expanded.
+enum Bound {
+    Cmp(Op),
+    Caret,
+    Tilde,
+}
+
+/// Split one part of a constraint into its operator and version.
+fn parse_bound(part: &str) -> (Bound, &str) {
+    const OPERATORS: [(&str, Bound); 7] = [
+        (">=", Bound::Cmp(Op::Ge)),
+        ("<=", Bound::Cmp(Op::Le)),
+        (">", Bound::Cmp(Op::Gt)),
+        ("<", Bound::Cmp(Op::Lt)),
+        ("=", Bound::Cmp(Op::Eq)),
+        ("^", Bound::Caret),
+        ("~", Bound::Tilde),
+    ];
+    for (prefix, bound) in OPERATORS {
+        if let Some(rest) = part.strip_prefix(prefix) {
+            return (bound, rest);
+        }
+    }
+    (Bound::Cmp(Op::Eq), part)
+}
+
+impl Op {
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+        }
+    }
+}
+
+impl VersionReq {
+    /// Parse a constraint.
+    ///
+    /// A bare version is accepted as an exact one, the same as `=`.
+    pub fn parse(input: &str) -> VersionResult<Self> {
+        let invalid = |reason: &str| {
+            VersionError::InvalidConstraint {
+                input: input.to_string(),
+                reason: reason.to_string(),
+            }
+        };
+
+        let mut bounds = Vec::new();
+        for part in input.split(',') {
+            let part = part.trim();
+            if part.is_empty() {
+                return Err(invalid("empty bound"));
+            }
+            let (kind, base) = parse_bound(part);
+            let base = base.trim();
+            if base.split('.').any(|p| matches!(p, "*" | "x" | "X")) {
+                return Err(invalid("wildcards are not supported, use ~ or ^"));
+            }
+            if !untagged(base).starts_with(|c: char| c.is_ascii_digit())
+                || base.contains(char::is_whitespace)
+            {
+                return Err(invalid(&format!("'{base}' is not a version")));
+            }
+
+            if let Bound::Cmp(op) = kind {
+                bounds.push((op, base.to_string()));
+                continue;
+            }
+
+            let too_large = || invalid(&format!("'{base}' has a part too large to bump"));
+            let parts = numeric_prefix(base)
+                .filter(|parts| !parts.is_empty())
+                .ok_or_else(too_large)?;
+            // The part that changes is the leftmost non-zero one for `^`, and
+            // the minor for `~`, or the major when that is all there is.
+            let bump = if matches!(kind, Bound::Caret) {
+                parts
+                    .iter()
+                    .position(|&p| p != 0)
+                    .unwrap_or(parts.len() - 1)
+            } else {
+                parts.len().min(2) - 1
+            };
+            let bumped = parts[bump].checked_add(1).ok_or_else(too_large)?;
+            let upper = parts[..bump]
+                .iter()
+                .copied()
+                .chain(std::iter::once(bumped))
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(".");
+            bounds.push((Op::Ge, base.to_string()));
+            bounds.push((Op::Lt, upper));
+        }
+
+        Ok(Self {
+            raw: input.trim().to_string(),
+            bounds,
+        })
+    }
+
+    /// Whether `version` satisfies every bound.
+    ///
+    /// A version that carries no order, a commit hash or a tag like `nightly`,
+    /// is an error rather than a mismatch: it is not known to be outside the
+    /// constraint, only impossible to check, and the caller should say so.
+    pub fn matches(&self, version: &str) -> VersionResult<bool> {
+        if !is_ordered(version) || !untagged(version).starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(VersionError::Unordered(version.to_string()));
+        }
+        Ok(self
+            .bounds
+            .iter()
+            .all(|(op, bound)| op.accepts(compare_versions(version, bound))))
+    }
+
+    /// The newest of `items` the constraint accepts, with the versions of
+    /// those that could not be checked at all.
+    pub fn newest<T>(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        version: impl Fn(&T) -> &str,
+    ) -> (Option<T>, Vec<String>) {
+        let mut newest: Option<T> = None;
+        let mut unordered = Vec::new();
+        for item in items {
+            match self.matches(version(&item)) {
+                Ok(true) => {
+                    let newer = newest.as_ref().is_none_or(|kept| {
+                        compare_versions(version(&item), version(kept)) == Ordering::Greater
+                    });
+                    if newer {
+                        newest = Some(item);
+                    }
+                }
+                Ok(false) => {}
+                Err(_) => unordered.push(version(&item).to_string()),
+            }
+        }
+        (newest, unordered)
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl std::str::FromStr for VersionReq {
+    type Err = VersionError;
+
+    fn from_str(s: &str) -> VersionResult<Self> {
+        Self::parse(s)
+    }
+}
+
+/// The leading numeric parts of a version: `[1, 4, 2]` for `v1.4.2-rc1`.
+///
+/// `None` when one of them is too large to count in, since the parts after it
+/// cannot be told apart from the ones before. Empty only for a version that
+/// does not start with a digit.
+fn numeric_prefix(version: &str) -> Option<Vec<u64>> {
+    let mut parts = Vec::new();
+    for part in untagged(version).split('.') {
+        let digits = part
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(part, |end| &part[..end]);
+        if digits.is_empty() {
+            break;
+        }
+        parts.push(digits.parse().ok()?);
+        // A part with a suffix, like the `2-rc1` in `1.2-rc1`, ends the
+        // numeric run after itself.
+        if digits.len() != part.len() {
+            break;
+        }
+    }
+    Some(parts)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -308,4 +530,121 @@ mod tests {
             Ordering::Greater
         );
     }
+
+    #[test]
+    fn a_bare_version_is_not_a_constraint() {
+        assert!(!is_constraint("1.4.2"));
+        assert!(!is_constraint("v1.4.2"));
+        assert!(is_constraint("^1.4"));
+        assert!(is_constraint("~0.9"));
+        assert!(is_constraint(">=2.0, <3"));
+        assert!(is_constraint("1.0, 2.0"));
+    }
+
+    #[test]
+    fn caret_holds_the_leftmost_nonzero_part() {
+        let req = VersionReq::parse("^1.4").unwrap();
+        assert!(req.matches("1.4").unwrap());
+        assert!(req.matches("1.4.7").unwrap());
+        assert!(req.matches("v1.9.0").unwrap());
+        assert!(!req.matches("1.3.9").unwrap());
+        assert!(!req.matches("2.0.0").unwrap());
+
+        let req = VersionReq::parse("^0.9").unwrap();
+        assert!(req.matches("0.9.3").unwrap());
+        assert!(!req.matches("0.10.0").unwrap());
+
+        let req = VersionReq::parse("^0.0.3").unwrap();
+        assert!(req.matches("0.0.3").unwrap());
+        assert!(!req.matches("0.0.4").unwrap());
+    }
+
+    #[test]
+    fn tilde_holds_the_minor() {
+        let req = VersionReq::parse("~0.9").unwrap();
+        assert!(req.matches("0.9.12").unwrap());
+        assert!(!req.matches("0.10.0").unwrap());
+
+        let req = VersionReq::parse("~1.4.2").unwrap();
+        assert!(req.matches("1.4.5").unwrap());
+        assert!(!req.matches("1.4.1").unwrap());
+        assert!(!req.matches("1.5.0").unwrap());
+
+        let req = VersionReq::parse("~1").unwrap();
+        assert!(req.matches("1.8").unwrap());
+        assert!(!req.matches("2.0").unwrap());
+    }
+
+    #[test]
+    fn ranges_need_every_bound() {
+        let req = VersionReq::parse(">=2.0, <3").unwrap();
+        assert!(req.matches("2.0").unwrap());
+        assert!(req.matches("2.17.1").unwrap());
+        assert!(!req.matches("1.9").unwrap());
+        assert!(!req.matches("3.0.0").unwrap());
+        assert_eq!(req.to_string(), ">=2.0, <3");
+    }
+
+    #[test]
+    fn rebuilds_stay_inside_and_prereleases_of_the_next_major_outside() {
+        let req = VersionReq::parse("^1.4").unwrap();
+        assert!(req.matches("1.4.0-2").unwrap());
+        assert!(req.matches("1.4.0-r6").unwrap());
+        assert!(!req.matches("2.0.0-rc1").unwrap());
+        assert!(!req.matches("1.4-rc1").unwrap());
+    }
+
+    #[test]
+    fn unordered_versions_are_reported_not_matched() {
+        let req = VersionReq::parse("^1.4").unwrap();
+        assert!(matches!(
+            req.matches("nightly"),
+            Err(VersionError::Unordered(_))
+        ));
+        assert!(matches!(
+            req.matches("89c99d2a9"),
+            Err(VersionError::Unordered(_))
+        ));
+
+        let (newest, unordered) =
+            req.newest(["1.4.1", "nightly", "1.6.0", "2.0.0", "1.5.3"], |v| v);
+        assert_eq!(newest, Some("1.6.0"));
+        assert_eq!(unordered, vec!["nightly".to_string()]);
+    }
+
+    #[test]
+    fn malformed_constraints_are_rejected() {
+        for input in ["^", ">= ", "^1.x", "1.4.*", ">=1.0,", "^latest", ">= 1 .0"] {
+            assert!(
+                VersionReq::parse(input).is_err(),
+                "{input} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn the_numeric_prefix_stops_at_the_first_suffix() {
+        assert_eq!(numeric_prefix("v1.4.2"), Some(vec![1, 4, 2]));
+        assert_eq!(numeric_prefix("1.2-rc1.5"), Some(vec![1, 2]));
+        assert_eq!(numeric_prefix("3"), Some(vec![3]));
+    }
+
+    #[test]
+    fn parts_too_large_to_bump_are_rejected_not_panicked_on() {
+        for input in [
+            "^99999999999999999999",
+            "~1.99999999999999999999",
+            "^18446744073709551615",
+            "~0.18446744073709551615.3",
+        ] {
+            assert!(
+                matches!(
+                    VersionReq::parse(input),
+                    Err(VersionError::InvalidConstraint { .. })
+                ),
+                "{input} should not parse"
+            );
+        }
+        assert!(VersionReq::parse("^18446744073709551614").is_ok());
+    }
 }
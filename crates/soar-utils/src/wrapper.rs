@@ -0,0 +1,367 @@
+//! Exec wrappers standing in for binary symlinks.
+//!
+//! A symlink cannot carry an environment variable or a default flag, so a
+//! binary that needs either is given a short shell script in its place. The
+//! script names its target on a marker line, which is how everything that
+//! follows links back to their package (removal, health checks, moving a
+//! profile) recognises one without knowing how it was written.
+
+use std::{
+    fs::{self, File},
+    io::Read,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{FileSystemResult, IoOperation, IoResultExt},
+    path::{in_target, on_host},
+};
+
+const SHEBANG: &str = "#!/bin/sh\n";
+
+/// The line that records a wrapper's target, right below the shebang.
+pub const WRAPPER_MARKER: &str = "# soar-wrapper: ";
+
+/// Whether `name` can be exported as an environment variable by `sh`.
+pub fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Escape `text` for use inside double quotes, keeping `$` live so the
+/// value is expanded when the wrapper runs.
+fn escape_expanding(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '"' | '\\' | '`') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape `text` for use inside double quotes as a literal.
+fn escape_literal(text: &str) -> String {
+    escape_expanding(text).replace('$', "\\$")
+}
+
+/// Escape a configured value and put the install directory in place of
+/// `$INSTALL_DIR`, the name hooks know it by.
+///
+/// The directory is substituted after escaping, as a literal, so a path that
+/// happens to contain a `$` is not expanded along with the rest.
+fn expand_value(value: &str, install_dir: &Path) -> String {
+    let install_dir = escape_literal(&in_target(install_dir).to_string_lossy());
+    escape_expanding(value)
+        .replace("${INSTALL_DIR}", &install_dir)
+        .replace("$INSTALL_DIR", &install_dir)
+}
+
+/// The script that runs `target` with `env` exported and `args` ahead of
+/// whatever it is called with.
+///
+/// Values are double-quoted, so `$HOME` or `${LD_LIBRARY_PATH}` in them is
+/// expanded each time the command runs rather than frozen at install time.
+pub fn render_wrapper(
+    target: &Path,
+    install_dir: &Path,
+    env: &[(&str, &str)],
+    args: &[&str],
+) -> String {
+    let target = in_target(target);
+    let mut script = String::from(SHEBANG);
+    script.push_str(WRAPPER_MARKER);
+    script.push_str(&target.to_string_lossy());
+    script.push('\n');
+    for (name, value) in env {
+        script.push_str(&format!(
+            "export {name}=\"{}\"\n",
+            expand_value(value, install_dir)
+        ));
+    }
+    script.push_str(&format!(
+        "exec \"{}\"",
+        escape_literal(&target.to_string_lossy())
+    ));
+    for arg in args {
+        script.push_str(&format!(" \"{}\"", expand_value(arg, install_dir)));
+    }
+    script.push_str(" \"$@\"\n");
+    script
+}
+
+/// Write a wrapper script to `path`, replacing whatever is there.
+pub fn write_wrapper<P: AsRef<Path>>(path: P, script: &str) -> FileSystemResult<()> {
+    let path = path.as_ref();
+    if path.is_symlink() || path.is_file() {
+        fs::remove_file(path).with_path(path, IoOperation::RemoveFile)?;
+    }
+    fs::write(path, script).with_path(path, IoOperation::WriteFile)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .with_path(path, IoOperation::WriteFile)
+}
+
+/// Export the directories in `env` ahead of the link at `link`.
+///
+/// The directories are host paths, written as the target system names them
+/// and taken literally. A symlink becomes a wrapper running `target`. A
+/// wrapper keeps what it had, and its own exports stay after these, so a
+/// value configured for the binary itself still wins. Exports already
+/// present are not repeated, which keeps doing this twice the same as doing
+/// it once.
+pub fn wrap_with_env<P: AsRef<Path>>(
+    link: P,
+    target: &Path,
+    env: &[(&str, &Path)],
+) -> FileSystemResult<()> {
+    let link = link.as_ref();
+    let script = if wrapper_target(link).is_some() {
+        fs::read_to_string(link).with_path(link, IoOperation::ReadFile)?
+    } else {
+        render_wrapper(target, Path::new(""), &[], &[])
+    };
+
+    let exports: Vec<String> = env
+        .iter()
+        .map(|(name, dir)| {
+            let dir = in_target(dir);
+            format!(
+                "export {name}=\"{}\"",
+                escape_literal(&dir.to_string_lossy())
+            )
+        })
+        .collect();
+    write_wrapper(link, &with_exports(&script, &exports))
+}
+
+/// `script` with `exports` below its shebang and marker, ahead of its own,
+/// and without any earlier copy of them.
+fn with_exports(script: &str, exports: &[String]) -> String {
+    let mut lines: Vec<&str> = script.lines().collect();
+    lines.retain(|line| !exports.iter().any(|export| export == line));
+    // Below the shebang and the marker.
+    let at = lines.len().min(2);
+    lines.splice(at..at, exports.iter().map(String::as_str));
+
+    let mut script = lines.join("\n");
+    script.push('\n');
+    script
+}
+
+/// Make the link at `link` run `target` instead, with `env` exported ahead
+/// of it.
+///
+/// Values are expanded as [`render_wrapper`] expands them. A wrapper keeps
+/// its own exports, after these, and the arguments it passes; only the
+/// command it runs changes. Anything else at `link` gives way to a new
+/// wrapper.
+pub fn rewrap<P: AsRef<Path>>(
+    link: P,
+    target: &Path,
+    install_dir: &Path,
+    env: &[(&str, &str)],
+) -> FileSystemResult<()> {
+    let link = link.as_ref();
+    let Some(previous) = wrapper_target(link) else {
+        return write_wrapper(link, &render_wrapper(target, install_dir, env, &[]));
+    };
+    let script = fs::read_to_string(link).with_path(link, IoOperation::ReadFile)?;
+
+    let (from, to) = (in_target(&previous), in_target(target));
+    let (from, to) = (from.to_string_lossy(), to.to_string_lossy());
+    let marker = format!("{WRAPPER_MARKER}{from}");
+    let exec = format!("exec \"{}\"", escape_literal(&from));
+    let script: String = script
+        .lines()
+        .map(|line| {
+            if line == marker {
+                format!("{WRAPPER_MARKER}{to}\n")
+            } else if let Some(args) = line.strip_prefix(&exec) {
+                format!("exec \"{}\"{args}\n", escape_literal(&to))
+            } else {
+                format!("{line}\n")
+            }
+        })
+        .collect();
+
+    let exports: Vec<String> = env
+        .iter()
+        .map(|(name, value)| format!("export {name}=\"{}\"", expand_value(value, install_dir)))
+        .collect();
+    write_wrapper(link, &with_exports(&script, &exports))
+}
+
+/// The binary a wrapper at `path` runs, if `path` is one soar wrote.
+///
+/// Only the first few hundred bytes are read, so asking this of every file
+/// in a directory costs an open apiece rather than reading binaries whole.
+pub fn wrapper_target<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    let mut head = [0u8; 512];
+    let mut file = File::open(path).ok()?;
+    let len = file.read(&mut head).ok()?;
+    let head = std::str::from_utf8(&head[..len]).ok()?;
+    let target = head
+        .strip_prefix(SHEBANG)?
+        .strip_prefix(WRAPPER_MARKER)?
+        .split('\n')
+        .next()?;
+    Some(on_host(target))
+}
+
+/// Where a link soar made at `path` leads, be it a symlink or a wrapper.
+pub fn link_target<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    let path = path.as_ref();
+    if path.is_symlink() {
+        return fs::read_link(path).ok().map(on_host);
+    }
+    if path.is_file() {
+        return wrapper_target(path);
+    }
+    None
+}
+
+/// Whether `path` is a symlink or wrapper whose target is gone.
+pub fn is_broken_link<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    if path.is_symlink() {
+        // The link may name a target only valid inside the install root, so
+        // it is resolved by hand rather than followed.
+        let Ok(target) = fs::read_link(path) else {
+            return false;
+        };
+        let base = path.parent().unwrap_or(Path::new("/"));
+        return !base.join(on_host(target)).exists();
+    }
+    wrapper_target(path).is_some_and(|target| !target.exists())
+}
+
+/// Point the wrapper at `path` from under `from` to the same place under
+/// `to`, returning whether it was one to change.
+///
+/// The install directory appears wherever the configuration used it, not
+/// only as the target, so every occurrence moves with it.
+pub fn retarget_wrapper<P: AsRef<Path>>(path: P, from: &Path, to: &Path) -> FileSystemResult<bool> {
+    let path = path.as_ref();
+    if !wrapper_target(path).is_some_and(|target| target.starts_with(from)) {
+        return Ok(false);
+    }
+    let script = fs::read_to_string(path).with_path(path, IoOperation::ReadFile)?;
+    let script = retarget_script(&script, &in_target(from), &in_target(to));
+    write_wrapper(path, &script)?;
+    Ok(true)
+}
+
+/// `script` with every mention of `from` turned into `to`, whether written
+/// as it is or escaped the way a wrapper quotes it.
+pub fn retarget_script(script: &str, from: &Path, to: &Path) -> String {
+    let (from, to) = (from.to_string_lossy(), to.to_string_lossy());
+    let mut script = script.replace(&*from, &to);
+    let (escaped_from, escaped_to) = (escape_literal(&from), escape_literal(&to));
+    if escaped_from != from {
+        script = script.replace(&escaped_from, &escaped_to);
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_wrapper_exports_its_environment_and_leads_with_its_arguments() {
+        let script = render_wrapper(
+            Path::new("/pkgs/tool/bin/tool"),
+            Path::new("/pkgs/tool"),
+            &[("LD_LIBRARY_PATH", "$INSTALL_DIR/lib:${LD_LIBRARY_PATH}")],
+            &["--config", "$HOME/.config/tool \"main\".toml"],
+        );
+        assert_eq!(
+            script,
+            "#!/bin/sh\n\
+             # soar-wrapper: /pkgs/tool/bin/tool\n\
+             export LD_LIBRARY_PATH=\"/pkgs/tool/lib:${LD_LIBRARY_PATH}\"\n\
+             exec \"/pkgs/tool/bin/tool\" \"--config\" \"$HOME/.config/tool \\\"main\\\".toml\" \"$@\"\n"
+        );
+    }
+
+    #[test]
+    fn a_wrapper_is_followed_like_a_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("old");
+        let to = dir.path().join("new");
+        let wrapper = dir.path().join("tool");
+        let script = render_wrapper(&from.join("tool"), &from, &[("A", "$INSTALL_DIR")], &[]);
+        write_wrapper(&wrapper, &script).unwrap();
+
+        assert_eq!(link_target(&wrapper), Some(from.join("tool")));
+        assert!(is_broken_link(&wrapper));
+
+        assert!(retarget_wrapper(&wrapper, &from, &to).unwrap());
+        assert_eq!(link_target(&wrapper), Some(to.join("tool")));
+        let script = fs::read_to_string(&wrapper).unwrap();
+        assert!(!script.contains(&*from.to_string_lossy()));
+
+        fs::write(dir.path().join("plain"), "#!/bin/sh\necho hi\n").unwrap();
+        assert_eq!(link_target(dir.path().join("plain")), None);
+    }
+
+    #[test]
+    fn exports_go_ahead_of_the_ones_a_wrapper_had() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("tool");
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        wrap_with_env(&link, &target, &[("HOME", Path::new("/p/tool.home"))]).unwrap();
+        assert_eq!(link_target(&link), Some(target.clone()));
+
+        let script = render_wrapper(&target, dir.path(), &[("HOME", "/mine")], &["-v"]);
+        write_wrapper(&link, &script).unwrap();
+        wrap_with_env(&link, &target, &[("HOME", Path::new("/p/tool.home"))]).unwrap();
+        wrap_with_env(&link, &target, &[("HOME", Path::new("/p/tool.home"))]).unwrap();
+        let script = fs::read_to_string(&link).unwrap();
+        let exports: Vec<&str> = script.lines().filter(|l| l.starts_with("export")).collect();
+        assert_eq!(
+            exports,
+            ["export HOME=\"/p/tool.home\"", "export HOME=\"/mine\""]
+        );
+    }
+
+    #[test]
+    fn a_rewrapped_link_keeps_its_own_exports_and_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("app.AppImage");
+        let app_run = dir.path().join("app.AppDir/AppRun");
+        let link = dir.path().join("app");
+        let script = render_wrapper(&image, dir.path(), &[("MODE", "1")], &["--verbose"]);
+        write_wrapper(&link, &script).unwrap();
+
+        for _ in 0..2 {
+            rewrap(&link, &app_run, dir.path(), &[("ARGV0", "$0")]).unwrap();
+        }
+        assert_eq!(link_target(&link), Some(app_run.clone()));
+        let script = fs::read_to_string(&link).unwrap();
+        let lines: Vec<&str> = script.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            [
+                "export ARGV0=\"$0\"".to_string(),
+                "export MODE=\"1\"".to_string(),
+                format!("exec \"{}\" \"--verbose\" \"$@\"", app_run.display()),
+            ]
+        );
+
+        let plain = dir.path().join("plain");
+        std::os::unix::fs::symlink(&image, &plain).unwrap();
+        rewrap(&plain, &app_run, dir.path(), &[("ARGV0", "$0")]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&plain).unwrap(),
+            render_wrapper(&app_run, dir.path(), &[("ARGV0", "$0")], &[])
+        );
+    }
+}
@@ -0,0 +1,35 @@
+//! Wrappers written while installing into another root.
+//!
+//! The root is set once for the whole process, so this lives apart from the
+//! unit tests, which all expect host paths to be left alone.
+
+use std::{fs, os::unix::fs::symlink};
+
+use soar_utils::{
+    path::set_install_root,
+    wrapper::{link_target, wrap_with_env},
+};
+
+#[test]
+fn a_portable_launcher_under_a_root_exports_what_the_target_will_see() {
+    let root = tempfile::tempdir().unwrap();
+    set_install_root(root.path());
+
+    let on_host = |path: &str| root.path().join(path.trim_start_matches('/'));
+    let target = on_host("/opt/soar/packages/tool/tool");
+    let home = on_host("/opt/soar/portable-dirs/tool/home");
+    let link = on_host("/opt/soar/bin/tool");
+    fs::create_dir_all(link.parent().unwrap()).unwrap();
+    symlink("/opt/soar/packages/tool/tool", &link).unwrap();
+
+    wrap_with_env(&link, &target, &[("HOME", &home)]).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&link).unwrap(),
+        "#!/bin/sh\n\
+         # soar-wrapper: /opt/soar/packages/tool/tool\n\
+         export HOME=\"/opt/soar/portable-dirs/tool/home\"\n\
+         exec \"/opt/soar/packages/tool/tool\" \"$@\"\n"
+    );
+    assert_eq!(link_target(&link).as_deref(), Some(target.as_path()));
+}